@@ -0,0 +1,103 @@
+//! Benchmarks the candidate selection on large tag lists, as fetched for
+//! popular images with the default tag search limit.
+//!
+//! The crate is a binary only, so the modules are included directly.
+#![allow(dead_code, unused_imports, clippy::unwrap_used)]
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+
+#[path = "../src/backup.rs"]
+mod backup;
+#[path = "../src/cache.rs"]
+mod cache;
+#[path = "../src/cli.rs"]
+mod cli;
+#[path = "../src/config.rs"]
+mod config;
+#[path = "../src/consistency.rs"]
+mod consistency;
+#[path = "../src/container_image.rs"]
+mod container_image;
+#[path = "../src/daemon.rs"]
+mod daemon;
+#[path = "../src/digest.rs"]
+mod digest;
+#[path = "../src/eol.rs"]
+mod eol;
+#[path = "../src/hook.rs"]
+mod hook;
+#[path = "../src/marker.rs"]
+mod marker;
+#[path = "../src/metrics.rs"]
+mod metrics;
+#[path = "../src/patch.rs"]
+mod patch;
+#[path = "../src/paths.rs"]
+mod paths;
+#[path = "../src/pin.rs"]
+mod pin;
+#[path = "../src/registries/mod.rs"]
+mod registries;
+#[path = "../src/report.rs"]
+mod report;
+#[path = "../src/risk.rs"]
+mod risk;
+#[path = "../src/scanners.rs"]
+mod scanners;
+#[path = "../src/serve.rs"]
+mod serve;
+#[path = "../src/skipped.rs"]
+mod skipped;
+#[path = "../src/stats.rs"]
+mod stats;
+#[path = "../src/tag/mod.rs"]
+mod tag;
+#[path = "../src/utils.rs"]
+mod utils;
+#[path = "../src/yaml_path.rs"]
+mod yaml_path;
+
+use crate::tag::{CandidateOptions, Tag};
+use crate::utils::Strategy;
+
+/// Builds a sorted list of roughly 2000 tags with a few variants, similar to
+/// the tags of `node`.
+fn tag_list() -> Vec<Tag> {
+    let mut tags = Vec::new();
+    for major in 10..30 {
+        for minor in 0..10 {
+            for patch in 0..3 {
+                for variant in ["", "-alpine", "-bookworm", "-bookworm-slim"] {
+                    tags.push(format!("{major}.{minor}.{patch}{variant}").parse().unwrap());
+                }
+            }
+        }
+    }
+    tags.sort();
+    tags
+}
+
+fn candidate_selection(c: &mut Criterion) {
+    let tags = tag_list();
+    let current: Tag = "18.4.1-alpine".parse().unwrap();
+
+    for strategy in [Strategy::Latest, Strategy::NextMinor, Strategy::LatestPatch] {
+        c.bench_function(&format!("find candidate ({strategy})"), |b| {
+            b.iter(|| black_box(&current).find_candidate_tag(black_box(&tags), &strategy, false, None));
+        });
+    }
+
+    let options = CandidateOptions {
+        prefer_suffix: Some("slim".to_owned()),
+        ..CandidateOptions::from(Strategy::Latest)
+    };
+    let current: Tag = "18.4.1-bookworm".parse().unwrap();
+    c.bench_function("select candidate (prefer suffix)", |b| {
+        b.iter(|| black_box(&current).select_candidate(black_box(&tags), &options));
+    });
+}
+
+criterion_group!(benches, candidate_selection);
+criterion_main!(benches);
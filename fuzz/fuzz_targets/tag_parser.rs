@@ -0,0 +1,77 @@
+//! Fuzzes the tag parser: every parsed tag has to be written and parsed
+//! again to the same tag, and the order of tags has to be total.
+//!
+//! Run with `cargo fuzz run tag_parser` in the `fuzz` folder. The crate is a
+//! binary only, so the modules are included directly.
+#![no_main]
+#![allow(dead_code, unused_imports, clippy::unwrap_used)]
+
+use std::cmp::Ordering;
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../src/backup.rs"]
+mod backup;
+#[path = "../../src/cache.rs"]
+mod cache;
+#[path = "../../src/cli.rs"]
+mod cli;
+#[path = "../../src/config.rs"]
+mod config;
+#[path = "../../src/consistency.rs"]
+mod consistency;
+#[path = "../../src/container_image.rs"]
+mod container_image;
+#[path = "../../src/daemon.rs"]
+mod daemon;
+#[path = "../../src/digest.rs"]
+mod digest;
+#[path = "../../src/eol.rs"]
+mod eol;
+#[path = "../../src/hook.rs"]
+mod hook;
+#[path = "../../src/marker.rs"]
+mod marker;
+#[path = "../../src/metrics.rs"]
+mod metrics;
+#[path = "../../src/patch.rs"]
+mod patch;
+#[path = "../../src/paths.rs"]
+mod paths;
+#[path = "../../src/pin.rs"]
+mod pin;
+#[path = "../../src/registries/mod.rs"]
+mod registries;
+#[path = "../../src/report.rs"]
+mod report;
+#[path = "../../src/risk.rs"]
+mod risk;
+#[path = "../../src/scanners.rs"]
+mod scanners;
+#[path = "../../src/serve.rs"]
+mod serve;
+#[path = "../../src/skipped.rs"]
+mod skipped;
+#[path = "../../src/stats.rs"]
+mod stats;
+#[path = "../../src/tag/mod.rs"]
+mod tag;
+#[path = "../../src/utils.rs"]
+mod utils;
+#[path = "../../src/yaml_path.rs"]
+mod yaml_path;
+
+use crate::tag::Tag;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    let Ok(tag) = text.parse::<Tag>() else {
+        return;
+    };
+    let written = tag.to_string();
+    let reparsed: Tag = written.parse().expect("A written tag can be parsed.");
+    assert_eq!(reparsed.to_string(), written);
+    assert_eq!(tag.cmp(&reparsed) == Ordering::Equal, tag == reparsed);
+});
@@ -0,0 +1,127 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::{LazyLock, RwLock};
+use std::{fs, io};
+
+use tracing::info;
+
+/// The suffix of the copies, unless another one is given.
+pub const DEFAULT_SUFFIX: &str = ".orig";
+
+/// Where the copies of modified files are written, see `set_backup`.
+static BACKUP: LazyLock<RwLock<Option<Backup>>> = LazyLock::new(|| RwLock::new(None));
+
+/// Copies files before they are modified, so that they can be restored
+/// without version control.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    /// Appended to the file name, e.g. `Dockerfile.orig`.
+    pub suffix: String,
+    /// The copies are placed in this folder with the relative path of the
+    /// file, instead of next to the file.
+    pub dir:    Option<PathBuf>,
+}
+
+impl Backup {
+    /// Returns the path of the copy of the file, e.g. `api/Dockerfile.orig` or
+    /// `backups/api/Dockerfile.orig` with a folder.
+    pub(crate) fn path_of(&self, file: &Path) -> PathBuf {
+        let mut name = file.file_name().unwrap_or_default().to_owned();
+        name.push(&self.suffix);
+        match &self.dir {
+            Some(dir) => {
+                let relative: PathBuf = file.components().filter(|component| matches!(component, Component::Normal(_))).collect();
+                dir.join(relative).with_file_name(name)
+            }
+            None => file.with_file_name(name),
+        }
+    }
+
+    /// Copies the file, if the content that is about to be written differs
+    /// from it. Returns the path of the copy, if one was written.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the copy can not be written.
+    pub(crate) fn save(&self, file: &Path, content: &str) -> io::Result<Option<PathBuf>> {
+        let Ok(current) = fs::read_to_string(file) else {
+            return Ok(None);
+        };
+        if current == content {
+            return Ok(None);
+        }
+        let path = self.path_of(file);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, current)?;
+        info!("Saved a copy of `{}` to `{}`.", file.display(), path.display());
+        Ok(Some(path))
+    }
+}
+
+/// Sets where modified files are copied to, `None` to not copy them.
+pub fn set_backup(backup: Option<Backup>) {
+    *BACKUP.write().expect("Backup can be written.") = backup;
+}
+
+/// Copies the file before the content is written to it, if backups are
+/// enabled.
+///
+/// # Errors
+///
+/// This function will return an error if the copy can not be written.
+pub fn save(file: &Path, content: &str) -> io::Result<()> {
+    if let Some(backup) = BACKUP.read().expect("Backup can be read.").as_ref() {
+        backup.save(file, content)?;
+    }
+    Ok(())
+}
+
+/// Checks if the file is a copy of the enabled backups, e.g.
+/// `Dockerfile.orig`, so that it is not searched for images.
+pub fn is_copy(file_name: &str) -> bool {
+    BACKUP
+        .read()
+        .expect("Backup can be read.")
+        .as_ref()
+        .is_some_and(|backup| file_name.ends_with(&backup.suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use std::path::{Path, PathBuf};
+
+    use pretty_assertions::assert_eq;
+
+    use crate::backup::{Backup, DEFAULT_SUFFIX};
+    use crate::paths;
+
+    #[test]
+    fn backups() {
+        let next_to = Backup {
+            suffix: DEFAULT_SUFFIX.to_owned(),
+            dir:    None,
+        };
+        assert_eq!(next_to.path_of(Path::new("api/Dockerfile")), PathBuf::from("api/Dockerfile.orig"));
+        let in_dir = Backup {
+            suffix: ".bak".to_owned(),
+            dir:    Some(PathBuf::from("backups")),
+        };
+        assert_eq!(in_dir.path_of(Path::new("./api/Dockerfile")), PathBuf::from("backups/api/Dockerfile.bak"));
+        assert_eq!(
+            in_dir.path_of(Path::new("/srv/api/Dockerfile")),
+            PathBuf::from("backups/srv/api/Dockerfile.bak")
+        );
+
+        let root = paths::temp_path("backup");
+        std::fs::create_dir_all(&root).unwrap();
+        let file = root.join("Dockerfile");
+        std::fs::write(&file, "FROM node:20.11\n").unwrap();
+        assert_eq!(next_to.save(&file, "FROM node:20.11\n").unwrap(), None);
+        assert_eq!(next_to.save(&file, "FROM node:22.1\n").unwrap(), Some(root.join("Dockerfile.orig")));
+        assert_eq!(std::fs::read_to_string(root.join("Dockerfile.orig")).unwrap(), "FROM node:20.11\n");
+        assert_eq!(next_to.save(&root.join("Dockerfile.missing"), "FROM node:22.1\n").unwrap(), None);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+use crate::registries::DURATION_HOUR_AS_SECS;
+use crate::tag::Tag;
+use crate::{paths, stats};
+
+/// File name of the cache store inside the cache directory.
+const CACHE_FILE_NAME: &str = "tags-cache.json";
+
+/// A cache for quicker lookups for repeated usage of already fetched tags
+/// during a single run.
+pub static TAGS_CACHE: LazyLock<TagCache> = LazyLock::new(TagCache::default);
+
+/// Guards the read-modify-write of the cache store, since images are resolved
+/// in parallel.
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Cache store related errors.
+#[derive(Debug, thiserror::Error)]
+pub enum CacheError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse cache store `{0}`: {1}")]
+    Parse(PathBuf, String),
+}
+
+/// The slot of a single image, it is locked while its tags are fetched.
+type TagSlot = Arc<Mutex<Option<Vec<Tag>>>>;
+
+/// Thread safe in memory cache of the tags per image. Fetches for the same
+/// image are done only once, concurrent callers wait for the first fetch.
+#[derive(Debug, Default)]
+pub struct TagCache {
+    slots: Mutex<HashMap<String, TagSlot>>,
+}
+
+impl TagCache {
+    fn slot(&self, key: &str) -> TagSlot {
+        let mut slots = self.slots.lock().expect("Tags cache lock is not poisoned.");
+        Arc::clone(slots.entry(key.to_owned()).or_default())
+    }
+
+    /// Returns the tags of the image, if they were fetched already.
+    pub(crate) fn get(&self, key: &str) -> Option<Vec<Tag>> {
+        let slot = self.slots.lock().expect("Tags cache lock is not poisoned.").get(key).cloned()?;
+        slot.lock().expect("Tags cache slot is not poisoned.").clone()
+    }
+
+    /// Returns the tags of the image, fetching them if they are not cached yet.
+    /// Only one fetch per image is running at a time and failed fetches are
+    /// not cached.
+    ///
+    /// # Errors
+    ///
+    /// This function will return the error of the fetch.
+    pub(crate) fn get_or_fetch<F, E>(&self, key: &str, fetch: F) -> Result<Vec<Tag>, E>
+    where
+        F: FnOnce() -> Result<Vec<Tag>, E>,
+    {
+        let slot = self.slot(key);
+        // The slot stays locked during the fetch, so that concurrent callers wait for
+        // it.
+        let mut tags = slot.lock().expect("Tags cache slot is not poisoned.");
+        if let Some(tags) = tags.as_ref() {
+            debug!("Found tags in application cache.");
+            return Ok(tags.clone());
+        }
+        let fetched = fetch()?;
+        debug!("Inserted {} tags for `{key}` into the application cache.", fetched.len());
+        *tags = Some(fetched.clone());
+        drop(tags);
+        Ok(fetched)
+    }
+}
+
+/// The fetched tags of a single image, with the time they were fetched at.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CacheEntry {
+    /// Seconds since the unix epoch.
+    pub fetched_at: u64,
+    pub tags:       Vec<Tag>,
+}
+
+impl CacheEntry {
+    /// Entries are valid for `DURATION_HOUR_AS_SECS` seconds.
+    pub(crate) const fn is_expired(&self, now: u64) -> bool {
+        now.saturating_sub(self.fetched_at) >= DURATION_HOUR_AS_SECS
+    }
+}
+
+/// A single store for the tags of all images, keyed by registry and image,
+/// e.g. `hub.docker.com/library/node`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CacheStore {
+    entries: BTreeMap<String, CacheEntry>,
+}
+
+/// Summary of the cache store, see `cache stats`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub images:  usize,
+    pub expired: usize,
+    pub tags:    usize,
+}
+
+impl CacheStore {
+    /// Reads the store from the given file. A missing file is an empty store.
+    pub(crate) fn load(path: &Path) -> Result<Self, CacheError> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(|e| CacheError::Parse(path.to_path_buf(), e.to_string()))
+    }
+
+    /// Writes the store to the given file, creating the folder if needed.
+    pub(crate) fn save(&self, path: &Path) -> Result<(), CacheError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string(self).map_err(|e| CacheError::Parse(path.to_path_buf(), e.to_string()))?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Returns the tags of the image, if they have not expired yet.
+    pub(crate) fn get(&self, key: &str, now: u64) -> Option<&Vec<Tag>> {
+        self.entries.get(key).filter(|entry| !entry.is_expired(now)).map(|entry| &entry.tags)
+    }
+
+    pub(crate) fn insert(&mut self, key: &str, tags: Vec<Tag>, now: u64) {
+        self.entries.insert(key.to_owned(), CacheEntry { fetched_at: now, tags });
+    }
+
+    /// Removes all expired entries, or all entries if `all` is set. Returns the
+    /// amount of removed entries.
+    pub(crate) fn prune(&mut self, now: u64, all: bool) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, entry| !all && !entry.is_expired(now));
+        before - self.entries.len()
+    }
+
+    pub(crate) fn stats(&self, now: u64) -> CacheStats {
+        CacheStats {
+            images:  self.entries.len(),
+            expired: self.entries.values().filter(|entry| entry.is_expired(now)).count(),
+            tags:    self.entries.values().map(|entry| entry.tags.len()).sum(),
+        }
+    }
+}
+
+/// Returns the seconds since the unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs())
+}
+
+/// Returns the path of the cache store, inside the users cache directory, e.g.
+/// `~/.cache/dockerimage-updater/tags-cache.json`, see `paths::cache_dir`.
+pub fn store_path() -> PathBuf {
+    paths::cache_dir().join(CACHE_FILE_NAME)
+}
+
+/// Returns the cached tags of the image, if they have not expired yet.
+pub fn read_tags(key: &str) -> Option<Vec<Tag>> {
+    let _guard = STORE_LOCK.lock().expect("Cache store lock is not poisoned.");
+    match CacheStore::load(&store_path()) {
+        Ok(store) => {
+            let tags = store.get(key, now()).cloned();
+            stats::cache_lookup(tags.is_some());
+            debug!("Cache store contains {} tags for `{key}`.", tags.as_ref().map_or(0, Vec::len));
+            tags
+        }
+        Err(e) => {
+            error!("Could not read cache store: {e}");
+            stats::cache_lookup(false);
+            None
+        }
+    }
+}
+
+/// Saves the tags of the image in the cache store.
+pub fn write_tags(key: &str, tags: &[Tag]) {
+    let _guard = STORE_LOCK.lock().expect("Cache store lock is not poisoned.");
+    let path = store_path();
+    let mut store = CacheStore::load(&path).unwrap_or_default();
+    store.insert(key, tags.to_vec(), now());
+    if let Err(e) = store.save(&path) {
+        error!("Could not write cache store `{}`: {e}", path.display());
+    }
+}
+
+/// Removes expired entries (or all entries) from the cache store. Returns the
+/// amount of removed entries.
+pub fn prune(all: bool) -> Result<usize, CacheError> {
+    let _guard = STORE_LOCK.lock().expect("Cache store lock is not poisoned.");
+    let path = store_path();
+    let mut store = CacheStore::load(&path)?;
+    let removed = store.prune(now(), all);
+    store.save(&path)?;
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::cache::{CacheStats, CacheStore, TagCache};
+    use crate::paths;
+    use crate::registries::DURATION_HOUR_AS_SECS;
+    use crate::tag::Tag;
+
+    #[test]
+    fn single_flight() {
+        let cache = TagCache::default();
+        let fetches = AtomicUsize::new(0);
+        let fetch = || {
+            fetches.fetch_add(1, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(20));
+            Ok::<_, String>(vec!["1.0.0".parse::<Tag>().unwrap()])
+        };
+
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| assert_eq!(cache.get_or_fetch("mock/image", fetch).unwrap().len(), 1));
+            }
+        });
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.get("mock/image").map(|tags| tags.len()), Some(1));
+
+        // Errors are not cached.
+        assert_eq!(cache.get_or_fetch("mock/broken", || Err("failed".to_owned())), Err("failed".to_owned()));
+        assert_eq!(cache.get("mock/broken"), None);
+        assert_eq!(cache.get_or_fetch("mock/broken", fetch).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn store() {
+        let path = paths::temp_path("cache").join("tags-cache.json");
+        let mut store = CacheStore::load(&path).unwrap();
+        assert_eq!(store, CacheStore::default());
+
+        let tags = vec!["1.0.0".parse().unwrap(), "1.1.0".parse().unwrap()];
+        store.insert("hub.docker.com/library/node", tags.clone(), 1000);
+        store.insert("mcr.microsoft.com/dotnet/aspnet", vec!["9.0.0".parse().unwrap()], 1000 + DURATION_HOUR_AS_SECS);
+        store.save(&path).unwrap();
+
+        let mut store = CacheStore::load(&path).unwrap();
+        let now = 1000 + DURATION_HOUR_AS_SECS;
+        assert_eq!(store.get("hub.docker.com/library/node", 1000), Some(&tags));
+        assert_eq!(store.get("hub.docker.com/library/node", now), None);
+        assert_eq!(
+            store.stats(now),
+            CacheStats {
+                images:  2,
+                expired: 1,
+                tags:    3,
+            }
+        );
+
+        assert_eq!(store.prune(now, false), 1);
+        assert_eq!(store.stats(now).images, 1);
+        assert_eq!(store.prune(now, true), 1);
+        assert_eq!(store, CacheStore::default());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
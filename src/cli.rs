@@ -1,12 +1,27 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
-use clap::{Args, Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand};
+use tracing_subscriber::EnvFilter;
 
-use crate::utils::Strategy;
+use crate::backup::{Backup, DEFAULT_SUFFIX};
+use crate::container_image::StageFilter;
+use crate::daemon::Runtime;
+use crate::pin::Pin;
+use crate::registries::FetchOptions;
+use crate::report::{ReportFormat, Severity};
+use crate::scanners::{FileKind, HIDDEN_CI_CONFIGS, YamlScanners};
+use crate::tag::CandidateOptions;
+use crate::tag::variant::VariantPin;
+use crate::utils::{OutputFormat, Strategy};
+use crate::yaml_path;
 
 #[derive(Parser, Debug)]
 #[command(version)]
 #[command(long_about = None)]
+#[command(
+    after_help = "All flags can be set via `DIU_*` environment variables as well, e.g. `DIU_ARCH=arm64` for `--arch arm64`. Flags given on the command line take precedence over the environment."
+)]
 pub struct Cli {
     #[command(subcommand)]
     pub(crate) mode: Mode,
@@ -34,8 +49,112 @@ pub enum Mode {
     #[command(alias = "m")]
     Multi(MultiFileArguments),
 
+    /// Batch mode: Choose a file with one image per line (or json lines with a
+    /// strategy and architecture per image) and receive the candidate of each.
+    #[command(alias = "b")]
+    Batch(BatchArguments),
+
+    /// Local mode: Lists the images of the local docker or podman daemon (or
+    /// of containerd via nerdctl) and reports which of them have newer tags.
+    Local(LocalArguments),
+
+    /// Outdated mode: Lists the images of a dockerfile or a folder with their
+    /// latest patch, minor and major version as a table, without modifying any
+    /// file.
+    Outdated(OutdatedArguments),
+
+    /// Exists mode: Checks if an image exists in its registry, the exit code is
+    /// non-zero if it does not.
+    Exists(ExistsArguments),
+
+    /// Compare mode: Compares two tags of an image and reports which one is
+    /// newer and what kind of update it is, without querying the registry.
+    Compare(CompareArguments),
+
+    /// Consistency mode: Reports images that are used with different tags in
+    /// the dockerfiles of a folder and optionally aligns them to a single tag.
+    Consistency(ConsistencyArguments),
+
+    /// Digest mode: Refreshes the digests of images pinned by digest with their
+    /// tag in a comment, e.g. `FROM node@sha256:... # 20.11.1`.
+    Digest(DigestArguments),
+
+    /// Strategies mode: Lists all strategies with a description and an example.
+    Strategies(StrategiesArguments),
+
+    /// Serve mode: Answers JSON-RPC requests for candidates on stdin, one per
+    /// line, e.g. for editors and long running CI agents that keep the fetched
+    /// tags cached.
+    Serve(ServeArguments),
+
     /// Will download the latest binary and place it next to the current one.
     SelfUpdate,
+
+    /// Cache mode: Inspect or clean up the cache store of fetched tags.
+    Cache(CacheArguments),
+}
+
+impl Mode {
+    /// Returns the common options of the mode, if it has any.
+    pub(crate) const fn common(&self) -> Option<&CommonOptions> {
+        match self {
+            Self::Input(InputArguments { common, .. })
+            | Self::Overview(OverviewArguments { common, .. })
+            | Self::File(SingleFileArguments { common, .. })
+            | Self::Multi(MultiFileArguments { common, .. })
+            | Self::Batch(BatchArguments { common, .. })
+            | Self::Local(LocalArguments { common, .. })
+            | Self::Outdated(OutdatedArguments { common, .. })
+            | Self::Exists(ExistsArguments { common, .. })
+            | Self::Digest(DigestArguments { common, .. })
+            | Self::Serve(ServeArguments { common, .. }) => Some(common),
+            Self::Compare(_) | Self::Consistency(_) | Self::Strategies(_) | Self::SelfUpdate | Self::Cache(_) => None,
+        }
+    }
+
+    /// Returns `true` if the mode prints a document for other tools, so that no
+    /// log lines may be mixed into it.
+    pub(crate) fn is_machine_readable(&self) -> bool {
+        match self {
+            Self::File(SingleFileArguments { output, .. }) | Self::Multi(MultiFileArguments { output, .. }) => *output != ReportFormat::Text,
+            // Standard output only carries the responses.
+            Self::Serve(_) | Self::Input(InputArguments { json: true, .. }) => true,
+            mode => mode.output().is_some_and(|output| *output != OutputFormat::Text),
+        }
+    }
+
+    /// Returns the output format of the mode, if it can be chosen.
+    pub(crate) const fn output(&self) -> Option<&OutputFormat> {
+        match self {
+            Self::Batch(BatchArguments { output, .. })
+            | Self::Local(LocalArguments { output, .. })
+            | Self::Outdated(OutdatedArguments { output, .. })
+            | Self::Exists(ExistsArguments { output, .. })
+            | Self::Compare(CompareArguments { output, .. })
+            | Self::Overview(OverviewArguments { output, .. })
+            | Self::Consistency(ConsistencyArguments { output, .. })
+            | Self::Strategies(StrategiesArguments { output }) => Some(output),
+            Self::Input(_) | Self::File(_) | Self::Multi(_) | Self::Digest(_) | Self::Serve(_) | Self::SelfUpdate | Self::Cache(_) => None,
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CacheArguments {
+    #[command(subcommand)]
+    pub(crate) command: CacheCommand,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum CacheCommand {
+    /// Shows the location of the cache store and the amount of cached images.
+    Stats,
+
+    /// Removes expired entries from the cache store.
+    Prune {
+        #[arg(long, env = "DIU_PRUNE_ALL", help = "Removes all entries, not only the expired ones.")]
+        all: bool,
+    },
 }
 
 #[derive(Args, Debug, Clone)]
@@ -44,12 +163,92 @@ pub struct SingleFileArguments {
     #[arg(value_name = "FILE", help = "Path to the file.")]
     pub(crate) file: PathBuf,
 
-    #[arg(long, help = "Which strategy should be used.", default_value = Strategy::Latest)]
-    pub(crate) strat: Strategy,
+    /// Repeating the flag (e.g. `--strat next-minor --strat latest-major`) uses
+    /// the first strategy that yields a candidate.
+    #[arg(
+        long,
+        env = "DIU_STRAT",
+        value_delimiter = ',',
+        help = "Which strategy should be used, further strategies are tried if it finds no candidate.",
+        default_value = Strategy::Latest
+    )]
+    pub(crate) strat: Vec<Strategy>,
 
-    #[arg(long, short = 'n', help = "If set will output the new file contents for inspection.")]
+    #[arg(long, short = 'n', env = "DIU_DRY_RUN", help = "If set will output the new file contents for inspection.")]
     pub(crate) dry_run: bool,
 
+    /// Nothing is modified, the patch can be applied selectively, e.g. with
+    /// `git apply`.
+    #[arg(
+        long,
+        env = "DIU_WRITE_PATCH",
+        value_name = "FILE",
+        conflicts_with_all = ["dry_run", "verify_build", "verify_cmd", "post_update_cmd"],
+        help = "Writes the proposed changes of all files as unified diff to the file, instead of modifying them."
+    )]
+    pub(crate) write_patch: Option<PathBuf>,
+
+    /// The other updates are reported, but not written. Rebuilds count as
+    /// patch updates.
+    #[arg(
+        long,
+        env = "DIU_APPLY",
+        value_enum,
+        value_delimiter = ',',
+        help = "Only writes the updates of the given severities, e.g. patch,minor. All updates are written by default."
+    )]
+    pub(crate) apply: Vec<Severity>,
+
+    /// Once the date has passed the image is updated again and a warning asks
+    /// to remove the pin.
+    #[arg(
+        long,
+        env = "DIU_PIN",
+        value_delimiter = ',',
+        value_name = "IMAGE=until:DATE",
+        help = "Ignores the image until the date has passed, e.g. node:18=until:2025-12-31."
+    )]
+    pub(crate) pin: Vec<Pin>,
+
+    /// Pinned images are refreshed by the digest mode, the images that are not
+    /// updated keep their tag.
+    #[arg(
+        long,
+        env = "DIU_PIN_DIGEST",
+        requires = "arch",
+        help = "Pins the updated images to the digest of their --arch image, keeping the tag in front of it."
+    )]
+    pub(crate) pin_digest: bool,
+
+    /// The images of the other stages are kept, e.g. when the build and the
+    /// runtime base are updated on different cadences.
+    #[arg(
+        long,
+        env = "DIU_STAGE",
+        value_name = "NAME",
+        conflicts_with_all = ["only_final_stage", "only_first_stage"],
+        help = "Only updates the image of the named stage, e.g. `runtime`."
+    )]
+    pub(crate) stage: Option<String>,
+
+    #[command(flatten)]
+    pub(crate) stages: StageOptions,
+
+    #[command(flatten)]
+    pub(crate) hooks: UpdateHooks,
+
+    #[command(flatten)]
+    pub(crate) backup: BackupOptions,
+
+    /// The images of included files are updated in the included files.
+    #[arg(long, env = "DIU_RESOLVE_INCLUDES", help = "Resolves `#include <path>` directives before the images are read.")]
+    pub(crate) resolve_includes: bool,
+
+    /// Other formats print a single document on standard output, the log lines
+    /// are not printed then.
+    #[arg(long, env = "DIU_OUTPUT", value_enum, default_value_t, help = "The format of the report of all base images.")]
+    pub(crate) output: ReportFormat,
+
     #[command(flatten)]
     pub(crate) common: CommonOptions,
 }
@@ -60,8 +259,37 @@ pub struct InputArguments {
     #[arg(value_name = "IMAGE", help = "The full docker image including the tag, that shall be updated.")]
     pub(crate) input: String,
 
-    #[arg(long, help = "Which strategy should be used.", default_value = Strategy::Latest)]
-    pub(crate) strat: Strategy,
+    /// Repeating the flag (e.g. `--strat next-minor --strat latest-major`) uses
+    /// the first strategy that yields a candidate.
+    #[arg(
+        long,
+        env = "DIU_STRAT",
+        value_delimiter = ',',
+        help = "Which strategy should be used, further strategies are tried if it finds no candidate.",
+        default_value = Strategy::Latest
+    )]
+    pub(crate) strat: Vec<Strategy>,
+
+    /// Lists the matching tags without verifying them or assessing their risk,
+    /// e.g. `--candidates 3` with `latest-minor` shows the three latest minors.
+    #[arg(
+        long,
+        env = "DIU_CANDIDATES",
+        value_name = "N",
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Show the top N candidates of the strategy, instead of a single one."
+    )]
+    pub(crate) candidates: Option<u16>,
+
+    /// The query has the fields of the `candidate` method of the serve mode,
+    /// e.g. `{"image": "node:20.11", "strategy": "latest-minor", "arch":
+    /// "arm64"}`, and the result is printed like a line of the batch mode.
+    #[arg(
+        long,
+        env = "DIU_JSON",
+        help = "Read the image as json query instead, `-` reads it from standard input, and print the result as json."
+    )]
+    pub(crate) json: bool,
 
     #[command(flatten)]
     pub(crate) common: CommonOptions,
@@ -73,30 +301,452 @@ pub struct OverviewArguments {
     #[arg(value_name = "IMAGE", help = "The full docker image including the tag, that shall be updated.")]
     pub(crate) input: String,
 
+    #[arg(long, env = "DIU_OUTPUT", value_enum, default_value_t, help = "The format of the result.")]
+    pub(crate) output: OutputFormat,
+
+    /// Lists the matching tags without verifying them or assessing their risk,
+    /// e.g. `--candidates 3` with `latest-minor` shows the three latest minors.
+    #[arg(
+        long,
+        env = "DIU_CANDIDATES",
+        value_name = "N",
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Show the top N candidates of the strategy, instead of a single one."
+    )]
+    pub(crate) candidates: Option<u16>,
+
+    #[command(flatten)]
+    pub(crate) common: CommonOptions,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct BatchArguments {
+    // Using positional argument instead of named argument
+    #[arg(value_name = "FILE", help = "Path to the file with one image per line.")]
+    pub(crate) file: PathBuf,
+
+    /// Used for all images that do not specify their own strategy. Repeating
+    /// the flag uses the first strategy that yields a candidate.
+    #[arg(
+        long,
+        env = "DIU_STRAT",
+        value_delimiter = ',',
+        help = "Which strategy should be used, further strategies are tried if it finds no candidate.",
+        default_value = Strategy::Latest
+    )]
+    pub(crate) strat: Vec<Strategy>,
+
+    #[arg(
+        long,
+        env = "DIU_OUTPUT",
+        value_enum,
+        default_value_t,
+        help = "The format of the result, json prints one line per image."
+    )]
+    pub(crate) output: OutputFormat,
+
+    #[command(flatten)]
+    pub(crate) common: CommonOptions,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct LocalArguments {
+    /// Defaults to `DOCKER_HOST` if it is a unix socket, the docker socket or
+    /// the rootless podman socket.
+    #[arg(long, env = "DIU_SOCKET", value_name = "PATH", help = "The socket of the docker or podman daemon.")]
+    pub(crate) socket: Option<PathBuf>,
+
+    #[arg(long, env = "DIU_RUNTIME", value_enum, default_value_t, help = "Where the local images are listed from.")]
+    pub(crate) runtime: Runtime,
+
+    /// Only used with nerdctl, kubernetes pulls its images into the `k8s.io`
+    /// namespace.
+    #[arg(
+        long,
+        env = "DIU_NAMESPACE",
+        help = "The containerd namespace of the images, nerdctl uses `default` if it is not set."
+    )]
+    pub(crate) namespace: Option<String>,
+
+    #[arg(long, env = "DIU_STRAT", value_delimiter = ',', help = "Which strategies should be reported, defaults to all.")]
+    pub(crate) strat: Vec<Strategy>,
+
+    #[arg(long, env = "DIU_OUTPUT", value_enum, default_value_t, help = "The format of the result.")]
+    pub(crate) output: OutputFormat,
+
+    #[command(flatten)]
+    pub(crate) common: CommonOptions,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct OutdatedArguments {
+    #[arg(
+        long,
+        env = "DIU_FILE",
+        value_name = "FILE",
+        conflicts_with = "folder",
+        required_unless_present = "folder",
+        help = "Path to the dockerfile."
+    )]
+    pub(crate) file: Option<PathBuf>,
+
+    #[arg(long, env = "DIU_FOLDER", value_name = "FOLDER", help = "Path to a folder, all of its dockerfiles are listed.")]
+    pub(crate) folder: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub(crate) discovery: DiscoveryOptions,
+
+    #[arg(long, env = "DIU_OUTPUT", value_enum, default_value_t, help = "The format of the result.")]
+    pub(crate) output: OutputFormat,
+
+    #[command(flatten)]
+    pub(crate) common: CommonOptions,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ExistsArguments {
+    // Using positional argument instead of named argument
+    #[arg(value_name = "IMAGE", help = "The full docker image including the tag, that shall be checked.")]
+    pub(crate) input: String,
+
+    #[arg(long, env = "DIU_OUTPUT", value_enum, default_value_t, help = "The format of the result.")]
+    pub(crate) output: OutputFormat,
+
     #[command(flatten)]
     pub(crate) common: CommonOptions,
 }
 
+#[derive(Args, Debug, Clone)]
+pub struct CompareArguments {
+    #[arg(value_name = "IMAGE", help = "The docker image without a tag, e.g. node.")]
+    pub(crate) image: String,
+
+    #[arg(value_name = "TAG_A", help = "The first tag.")]
+    pub(crate) tag_a: String,
+
+    #[arg(value_name = "TAG_B", help = "The second tag.")]
+    pub(crate) tag_b: String,
+
+    #[arg(long, env = "DIU_OUTPUT", value_enum, default_value_t, help = "The format of the result.")]
+    pub(crate) output: OutputFormat,
+
+    #[arg(long, short, env = "DIU_QUIET", help = "Will print out only the newer tag, or nothing if both are equal.")]
+    pub(crate) quiet: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ConsistencyArguments {
+    // Using positional argument instead of named argument
+    #[arg(value_name = "FOLDER", help = "Path to the folder.")]
+    pub(crate) folder: PathBuf,
+
+    /// The newest of the used tags is chosen, unless a tag is given with
+    /// `--align-to`.
+    #[arg(long, env = "DIU_ALIGN", help = "Rewrites every inconsistent image to a single tag.")]
+    pub(crate) align: bool,
+
+    /// Implies `--align` for the given images, e.g. `--align-to node:20.10`.
+    #[arg(
+        long,
+        env = "DIU_ALIGN_TO",
+        value_name = "IMAGE",
+        value_delimiter = ',',
+        help = "The tag the image is aligned to, e.g. node:20.10."
+    )]
+    pub(crate) align_to: Vec<String>,
+
+    #[arg(long, short = 'n', env = "DIU_DRY_RUN", help = "If set will output the aligned file contents for inspection.")]
+    pub(crate) dry_run: bool,
+
+    #[arg(long, short, env = "DIU_EXCLUDE_FILE", value_delimiter = ',', help = "The list of files to exclude")]
+    pub(crate) exclude_file: Vec<String>,
+
+    #[command(flatten)]
+    pub(crate) discovery: DiscoveryOptions,
+
+    #[arg(long, env = "DIU_OUTPUT", value_enum, default_value_t, help = "The format of the result.")]
+    pub(crate) output: OutputFormat,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DigestArguments {
+    // Using positional argument instead of named argument
+    #[arg(value_name = "PATH", help = "Path to a dockerfile or to a folder with dockerfiles.")]
+    pub(crate) path: PathBuf,
+
+    /// Without the flag the digest of the tag in the comment is refreshed, the
+    /// tag itself is kept.
+    #[arg(
+        long,
+        env = "DIU_STRAT",
+        value_delimiter = ',',
+        help = "Updates the tag in the comment with the given strategies before its digest is resolved."
+    )]
+    pub(crate) strat: Vec<Strategy>,
+
+    #[arg(long, short = 'n', env = "DIU_DRY_RUN", help = "If set will output the new file contents for inspection.")]
+    pub(crate) dry_run: bool,
+
+    #[command(flatten)]
+    pub(crate) discovery: DiscoveryOptions,
+
+    #[command(flatten)]
+    pub(crate) common: CommonOptions,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ServeArguments {
+    /// Used for all requests that do not specify their own strategy.
+    #[arg(
+        long,
+        env = "DIU_STRAT",
+        value_delimiter = ',',
+        help = "Which strategy should be used, further strategies are tried if it finds no candidate.",
+        default_value = Strategy::Latest
+    )]
+    pub(crate) strat: Vec<Strategy>,
+
+    /// The gauges cover the images requested so far, e.g. for alerting on
+    /// outdated base images.
+    #[arg(
+        long,
+        env = "DIU_METRICS_ADDR",
+        value_name = "ADDR",
+        help = "Serves Prometheus metrics on `/metrics` at the given address, e.g. `0.0.0.0:9090`."
+    )]
+    pub(crate) metrics_addr: Option<String>,
+
+    #[command(flatten)]
+    pub(crate) common: CommonOptions,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct StrategiesArguments {
+    #[arg(long, env = "DIU_OUTPUT", value_enum, default_value_t, help = "The format of the result.")]
+    pub(crate) output: OutputFormat,
+}
+
+// Cli flags are bools by nature.
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Args, Debug, Clone)]
 pub struct CommonOptions {
-    #[arg(long, short, help = "Will filter out tags only for the given architecture.")]
+    #[arg(long, short, env = "DIU_ARCH", help = "Will filter out tags only for the given architecture.")]
     pub(crate) arch: Option<String>,
 
-    #[arg(long, help = "Limit the amount of tags to be searched on Docker Hub.")]
+    #[arg(long, env = "DIU_TAG_SEARCH_LIMIT", help = "Limit the amount of tags to be searched on Docker Hub and the MCR.")]
     pub(crate) tag_search_limit: Option<u16>,
 
-    #[arg(long, short, help = "Activates debug logging.")]
-    pub(crate) debug: bool,
+    /// Switches the variant of an image to one with the given suffix, e.g.
+    /// `node:24.6.0-bookworm` to `node:24.7.0-bookworm-slim`, if the same
+    /// version is published with it.
+    #[arg(
+        long,
+        env = "DIU_PREFER_SUFFIX",
+        value_name = "SUFFIX",
+        help = "Prefer variants with the given suffix (e.g. slim), if the same version exists with it."
+    )]
+    pub(crate) prefer_suffix: Option<String>,
 
-    #[arg(long, short, help = "Activates color output.", default_value_t = false)]
+    /// Candidates with another version of the variant are skipped until the pin
+    /// is bumped, e.g. `1.27.4-alpine3.21` with `alpine=3.20`. Tags with a
+    /// variant without a version (`-alpine`) keep updating.
+    #[arg(
+        long,
+        env = "DIU_VARIANT_PIN",
+        value_delimiter = ',',
+        value_name = "VARIANT=VERSION",
+        help = "Only propose candidates with the given version of the variant, e.g. alpine=3.20."
+    )]
+    pub(crate) variant_pin: Vec<VariantPin>,
+
+    /// The end of life of alpine, debian and ubuntu releases is warned about
+    /// regardless, e.g. for `3.11.4-slim-buster`.
+    #[arg(
+        long,
+        env = "DIU_UPDATE_EOL_VARIANTS",
+        help = "Move tags whose variant is based on an OS release past its end of life to the latest supported release."
+    )]
+    pub(crate) update_eol_variants: bool,
+
+    /// Keeps bare major and major.minor pins in their form, e.g. `postgres:16`
+    /// to `postgres:17` and `python:3.12` to `python:3.13`.
+    #[arg(long, env = "DIU_SAME_PRECISION", help = "Only consider tags with as many version numbers as the current tag.")]
+    pub(crate) same_precision: bool,
+
+    /// By default the candidate is written with the same specificity as the
+    /// current tag, e.g. `1.29` is updated to `1.30` instead of `1.30.2`.
+    #[arg(
+        long,
+        env = "DIU_FULL_PRECISION",
+        help = "Write the candidate with all its version numbers instead of matching the current tag."
+    )]
+    pub(crate) full_precision: bool,
+
+    /// Released tags are only updated to other releases, e.g. `9.0.8` is not
+    /// updated to `10.0.0-preview.7`. Pre-release tags always move on to later
+    /// pre-releases and to the release of their version.
+    #[arg(long, env = "DIU_ALLOW_PRERELEASE", help = "Propose pre-releases (preview, rc) as candidates for released tags.")]
+    pub(crate) allow_prerelease: bool,
+
+    /// Mutable tags (`latest`, `stable`, `lts`, `edge`) are skipped by default,
+    /// since they do not pin a version. With the flag the latest strategy
+    /// proposes the first published one for tags without a variant.
+    #[arg(
+        long,
+        env = "DIU_INCLUDE_LATEST",
+        help = "Propose mutable tags like latest or stable with the latest strategy, instead of skipping them."
+    )]
+    pub(crate) include_latest: bool,
+
+    /// Issues a manifest request for the final tag, which catches tags that are
+    /// listed but can not be pulled (for the requested architecture).
+    #[arg(
+        long,
+        env = "DIU_VERIFY",
+        help = "Only apply an update if the manifest of the new tag can be fetched from the registry."
+    )]
+    pub(crate) verify: bool,
+
+    /// The score adds up major (3), variant (2), pre-release (2), eol boundary
+    /// (2) and fresh (1) updates, e.g. `--max-risk 2` skips major updates.
+    #[arg(
+        long,
+        env = "DIU_MAX_RISK",
+        value_name = "SCORE",
+        help = "Only apply an update if its risk score does not exceed the given one."
+    )]
+    pub(crate) max_risk: Option<u8>,
+
+    /// For organizations that restrict their base images to the trusted
+    /// content of Docker Hub. The images of the MCR are allowed, they are
+    /// published by Microsoft.
+    #[arg(
+        long,
+        env = "DIU_OFFICIAL_ONLY",
+        help = "Fail to resolve images that are no Docker Official Image and not of a Verified Publisher."
+    )]
+    pub(crate) official_only: bool,
+
+    /// Only checked with `--max-risk`, since the push date needs another
+    /// request per candidate.
+    #[arg(
+        long,
+        env = "DIU_MIN_AGE",
+        value_name = "DAYS",
+        default_value_t = 7,
+        help = "Tags pushed within fewer days count as fresh, which adds to the risk score."
+    )]
+    pub(crate) min_age: u64,
+
+    /// Requests beyond the budget fail like unreachable registries, so the
+    /// images are reported as unresolved and the others are updated. Docker
+    /// Hub requests also stop once its rate limit reports no requests left.
+    #[arg(
+        long,
+        env = "DIU_MAX_REQUESTS",
+        value_name = "N",
+        help = "The maximum of requests sent to the registries during the run."
+    )]
+    pub(crate) max_requests: Option<u64>,
+
+    /// Dockerhub lists the most recently pushed tags first, so the remaining
+    /// pages are skipped once they only contain versions lower than the
+    /// current tag. This may miss updates of old release lines.
+    #[arg(long, env = "DIU_FAST", help = "Stop fetching tags from Docker Hub once only older versions are returned.")]
+    pub(crate) fast: bool,
+
+    /// Without the flag only the log lines of this tool up to info are shown,
+    /// `-v` adds the ones of the libraries, `-vv` debug and `-vvv` trace
+    /// logging, e.g. of every http request.
+    #[arg(long, short, env = "DIU_VERBOSE", action = ArgAction::Count, help = "Increases the verbosity of the logging (info, debug, trace).")]
+    pub(crate) verbose: u8,
+
+    /// Takes precedence over `--verbose` and `RUST_LOG`.
+    #[arg(
+        long,
+        env = "DIU_LOG_FILTER",
+        value_name = "FILTER",
+        value_parser = parse_log_filter,
+        help = "Filters the log lines per module, e.g. dockerimage_updater::registries=trace,ureq=debug."
+    )]
+    pub(crate) log_filter: Option<String>,
+
+    /// Logs the method, url, status, duration and size of every registry
+    /// request at trace level, without the noise of the other trace logging.
+    #[arg(long, env = "DIU_TRACE_HTTP", help = "Logs a summary of every request to the registries.")]
+    pub(crate) trace_http: bool,
+
+    #[arg(long, short, env = "DIU_COLOR", help = "Activates color output.", default_value_t = false)]
     pub(crate) color: bool,
 
     #[arg(
         long,
+        env = "DIU_QUIET",
         short,
         help = "Will print out only the result or an empty string if no match was found when used in input mode."
     )]
     pub(crate) quiet: bool,
+
+    /// Saves every raw registry response into the given folder, so that a run
+    /// can be reproduced later on with `--replay`.
+    #[arg(
+        long,
+        env = "DIU_RECORD",
+        value_name = "DIR",
+        conflicts_with = "replay",
+        help = "Records all raw registry responses into the given folder."
+    )]
+    pub(crate) record: Option<PathBuf>,
+
+    /// Reads the registry responses from a folder created with `--record`
+    /// instead of querying the registries.
+    #[arg(
+        long,
+        env = "DIU_REPLAY",
+        value_name = "DIR",
+        help = "Replays previously recorded registry responses from the given folder."
+    )]
+    pub(crate) replay: Option<PathBuf>,
+
+    /// The program gets the image, the current tag, the strategies, the
+    /// built-in suggestion and all fetched tags as json on stdin and prints the
+    /// chosen tag, or nothing to skip the image.
+    #[arg(
+        long,
+        env = "DIU_CANDIDATE_CMD",
+        value_name = "EXE",
+        help = "Lets the given program choose the candidate from the fetched tags."
+    )]
+    pub(crate) candidate_cmd: Option<PathBuf>,
+
+    /// Without this flag `dockerimage-updater.toml` of the working directory is
+    /// used, if it exists.
+    #[arg(
+        long,
+        env = "DIU_CONFIG",
+        value_name = "FILE",
+        help = "Reads per image settings like custom tag schemes from the given file."
+    )]
+    pub(crate) config: Option<PathBuf>,
+
+    /// Json output modes print the statistics as json object on standard
+    /// error, so that their document stays intact.
+    #[arg(
+        long,
+        env = "DIU_STATS",
+        help = "Prints statistics like files scanned, cache hit rate, http requests and the time per phase at the end of the run."
+    )]
+    pub(crate) stats: bool,
+
+    /// Tags read from the cache only show the variant and strategy steps, the
+    /// others are recorded while fetching. Json output modes print the report
+    /// as json object on standard error.
+    #[arg(
+        long,
+        env = "DIU_SHOW_SKIPPED",
+        help = "Prints which fetched tags were skipped at which filter step (arch, parse, variant, strategy) at the end of the run."
+    )]
+    pub(crate) show_skipped: bool,
 }
 
 #[derive(Args, Debug, Clone)]
@@ -105,23 +755,468 @@ pub struct MultiFileArguments {
     #[arg(value_name = "FOLDER", help = "Path to the folder.")]
     pub(crate) folder: PathBuf,
 
-    #[arg(long, help = "Which strategy should be used.", default_value = Strategy::Latest)]
-    pub(crate) strat: Strategy,
+    /// Repeating the flag (e.g. `--strat next-minor --strat latest-major`) uses
+    /// the first strategy that yields a candidate. Without the flag the
+    /// strategies of the config files are used, `latest` otherwise.
+    #[arg(
+        long,
+        env = "DIU_STRAT",
+        value_delimiter = ',',
+        help = "Which strategy should be used, further strategies are tried if it finds no candidate. Defaults to the config files or latest."
+    )]
+    pub(crate) strat: Vec<Strategy>,
 
-    #[arg(long, short = 'n', help = "If set will output the new file contents for inspection.")]
+    #[arg(long, short = 'n', env = "DIU_DRY_RUN", help = "If set will output the new file contents for inspection.")]
     pub(crate) dry_run: bool,
 
+    /// Nothing is modified, the patch can be applied selectively, e.g. with
+    /// `git apply`.
+    #[arg(
+        long,
+        env = "DIU_WRITE_PATCH",
+        value_name = "FILE",
+        conflicts_with_all = ["dry_run", "verify_build", "verify_cmd", "post_update_cmd"],
+        help = "Writes the proposed changes of all files as unified diff to the file, instead of modifying them."
+    )]
+    pub(crate) write_patch: Option<PathBuf>,
+
+    /// The other updates are reported, but not written. Rebuilds count as
+    /// patch updates.
+    #[arg(
+        long,
+        env = "DIU_APPLY",
+        value_enum,
+        value_delimiter = ',',
+        help = "Only writes the updates of the given severities, e.g. patch,minor. All updates are written by default."
+    )]
+    pub(crate) apply: Vec<Severity>,
+
+    /// Pinned images are refreshed by the digest mode, the images that are not
+    /// updated keep their tag.
+    #[arg(
+        long,
+        env = "DIU_PIN_DIGEST",
+        requires = "arch",
+        help = "Pins the updated images to the digest of their --arch image, keeping the tag in front of it."
+    )]
+    pub(crate) pin_digest: bool,
+
+    #[command(flatten)]
+    pub(crate) stages: StageOptions,
+
+    #[command(flatten)]
+    pub(crate) hooks: UpdateHooks,
+
+    #[command(flatten)]
+    pub(crate) backup: BackupOptions,
+
+    /// The images of included files are updated in the included files.
+    #[arg(long, env = "DIU_RESOLVE_INCLUDES", help = "Resolves `#include <path>` directives before the images are read.")]
+    pub(crate) resolve_includes: bool,
+
+    /// Other formats print a single document on standard output, the log lines
+    /// are not printed then.
+    #[arg(long, env = "DIU_OUTPUT", value_enum, default_value_t, help = "The format of the report of all base images.")]
+    pub(crate) output: ReportFormat,
+
+    #[command(flatten)]
+    pub(crate) discovery: DiscoveryOptions,
+
     /// Allows the user to exclude certain files in the folder and its
     /// subfolders.
-    #[arg(long, short, help = "The list of files to exclude", required = false, num_args = 0..)]
+    #[arg(long, short, env = "DIU_EXCLUDE_FILE", value_delimiter = ',', help = "The list of files to exclude", required = false, num_args = 0..)]
     pub(crate) exclude_file: Vec<String>,
 
     /// Allows to ignore certain versions to not be updated, in case of needed
     /// legacy compatibility. This ignore applies globally for all found
-    /// files that will be processed.
-    #[arg(long, short, help = "The list of versions to ignore (they will not be updated), e.g.: alpine:3.12", required = false, num_args = 0..)]
+    /// files that will be processed, in addition to the ones of the config
+    /// files. `node:18.1-alpine` ignores this very tag, `node:18` every tag
+    /// with the major 18 and `node` the image altogether, the most specific
+    /// match is reported.
+    #[arg(long, short, env = "DIU_IGNORE_VERSIONS", value_delimiter = ',', help = "The list of versions to ignore (they will not be updated), e.g.: alpine:3.12, node:18 (the whole major) or node (every tag)", required = false, num_args = 0..)]
     pub(crate) ignore_versions: Vec<String>,
 
+    /// Once the date has passed the image is updated again and a warning asks
+    /// to remove the pin. Combined with the pins of the config files.
+    #[arg(
+        long,
+        env = "DIU_PIN",
+        value_delimiter = ',',
+        value_name = "IMAGE=until:DATE",
+        help = "Ignores the image until the date has passed, e.g. node:18=until:2025-12-31."
+    )]
+    pub(crate) pin: Vec<Pin>,
+
+    /// The files are parsed and their images are resolved in parallel. Defaults
+    /// to the number of available cpus.
+    #[arg(long, short, env = "DIU_JOBS", help = "How many files and images are processed in parallel.")]
+    pub(crate) jobs: Option<usize>,
+
     #[command(flatten)]
     pub(crate) common: CommonOptions,
 }
+
+/// Decides how the folder is traversed when searching for dockerfiles.
+// Cli flags are bools by nature.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Args, Debug, Clone, Default)]
+pub struct DiscoveryOptions {
+    /// Symlinked folders are not entered by default, which also avoids loops.
+    #[arg(long, env = "DIU_FOLLOW_SYMLINKS", help = "Follows symbolic links while searching for dockerfiles.")]
+    pub(crate) follow_symlinks: bool,
+
+    /// Hidden files and folders (e.g. `.git`) are skipped by default.
+    #[arg(long, env = "DIU_INCLUDE_HIDDEN", help = "Searches hidden files and folders for dockerfiles as well.")]
+    pub(crate) include_hidden: bool,
+
+    /// A depth of `1` only searches the folder itself.
+    #[arg(long, env = "DIU_MAX_DEPTH", value_name = "N", help = "How many folders deep dockerfiles are searched.")]
+    pub(crate) max_depth: Option<usize>,
+
+    /// Only the base image (`from`) of the jib plugin is updated, not the
+    /// image that is built (`to`).
+    #[arg(long, env = "DIU_JIB", help = "Searches `pom.xml` and `build.gradle(.kts)` files for jib base images as well.")]
+    pub(crate) jib: bool,
+
+    /// Only string defaults of variables and locals marked with a
+    /// `# dockerimage-updater` comment are updated, in the line above or at the
+    /// end of the line.
+    #[arg(long, env = "DIU_TERRAFORM", help = "Searches `.tf` files for marked image variables and locals as well.")]
+    pub(crate) terraform: bool,
+
+    /// The `image` options of the `docker_container` and
+    /// `docker_swarm_service` tasks (including `community.docker.*`) are
+    /// updated, other YAML files stay as they are.
+    #[arg(
+        long,
+        env = "DIU_ANSIBLE",
+        help = "Searches `.yml` and `.yaml` files for images of ansible container tasks as well."
+    )]
+    pub(crate) ansible: bool,
+
+    /// Only values marked with `# diu:` (optionally with `image=<name>` and
+    /// `strategy=<strategy>`) or a Flux `$imagepolicy` comment are updated.
+    #[arg(
+        long,
+        env = "DIU_MARKERS",
+        help = "Searches `.yml` and `.yaml` files for values marked with `# diu:` or a Flux image policy as well."
+    )]
+    pub(crate) markers: bool,
+
+    /// The `newTag` (and `digest`) of the `images` transformer are updated,
+    /// the manifests it applies to stay as they are.
+    #[arg(
+        long,
+        env = "DIU_KUSTOMIZE",
+        help = "Searches `kustomization.yaml` files for the images of their transformer as well."
+    )]
+    pub(crate) kustomize: bool,
+
+    /// The images of the services and of the `x-` extension fields are
+    /// updated, aliases like `image: *base-image` at their anchor definition.
+    #[arg(
+        long,
+        env = "DIU_COMPOSE",
+        help = "Searches compose files (e.g. `compose.yaml`, `docker-compose.yml`) for the images of their services as well."
+    )]
+    pub(crate) compose: bool,
+
+    /// The `tag` (and `digest`) of multi-line `oci.pull` and `container_pull`
+    /// calls are updated, pulls pinned only by digest stay as they are.
+    #[arg(
+        long,
+        env = "DIU_BAZEL",
+        help = "Searches `MODULE.bazel` and `WORKSPACE` files for the image pulls of rules_oci and rules_docker as well."
+    )]
+    pub(crate) bazel: bool,
+
+    /// Only the `image` of multi-line `source "docker"` blocks is updated, the
+    /// other builders stay as they are.
+    #[arg(
+        long,
+        env = "DIU_PACKER",
+        help = "Searches `.pkr.hcl` packer templates for the images of their docker builders as well."
+    )]
+    pub(crate) packer: bool,
+
+    /// The `.gitlab-ci.yml`, `.drone.yml` and `.woodpecker.yml` files and the
+    /// `.circleci` and `.woodpecker` folders are searched, even though hidden
+    /// files are skipped otherwise.
+    #[arg(
+        long,
+        env = "DIU_CI",
+        help = "Searches the CI configs of GitLab, CircleCI, Drone and Woodpecker for the images of their jobs and services as well."
+    )]
+    pub(crate) ci: bool,
+}
+
+impl DiscoveryOptions {
+    /// Returns whether files of the kind are searched, dockerfiles always are.
+    /// YAML files are searched as well if YAML paths are configured, but only
+    /// for these paths, see `yaml_scanners`.
+    pub(crate) fn searches(&self, kind: FileKind) -> bool {
+        match kind {
+            FileKind::Dockerfile => true,
+            FileKind::Maven | FileKind::Gradle => self.jib,
+            FileKind::Terraform => self.terraform,
+            FileKind::Ansible => self.ansible || self.markers || !yaml_path::yaml_paths().is_empty(),
+            FileKind::Kustomize => self.kustomize,
+            FileKind::Compose => self.compose || self.markers || !yaml_path::yaml_paths().is_empty(),
+            FileKind::Bazel => self.bazel,
+            FileKind::Packer => self.packer,
+            FileKind::GitlabCi | FileKind::CircleCi | FileKind::Drone => self.ci || self.markers || !yaml_path::yaml_paths().is_empty(),
+        }
+    }
+
+    /// Returns whether the hidden file or folder is searched, even though
+    /// hidden files are not included, e.g. `.gitlab-ci.yml` with `--ci`.
+    pub(crate) fn searches_hidden(&self, name: &str) -> bool {
+        self.ci && HIDDEN_CI_CONFIGS.contains(&name)
+    }
+
+    /// Returns the scanners that search the YAML files of the folder, e.g.
+    /// ansible tasks only with `--ansible` and the service images of compose
+    /// files only with `--compose`.
+    pub(crate) const fn yaml_scanners(&self) -> YamlScanners {
+        YamlScanners {
+            ansible: self.ansible,
+            compose: self.compose,
+            ci:      self.ci,
+        }
+    }
+}
+
+/// Selects the stage of the dockerfiles whose image is updated, the images of
+/// the other stages are kept.
+#[derive(Args, Debug, Clone, Default)]
+pub struct StageOptions {
+    /// Usually the runtime base, the stages before build the application.
+    #[arg(
+        long,
+        env = "DIU_ONLY_FINAL_STAGE",
+        conflicts_with = "only_first_stage",
+        help = "Only updates the image of the final stage of every dockerfile."
+    )]
+    pub(crate) only_final_stage: bool,
+
+    #[arg(long, env = "DIU_ONLY_FIRST_STAGE", help = "Only updates the image of the first stage of every dockerfile.")]
+    pub(crate) only_first_stage: bool,
+}
+
+impl StageOptions {
+    /// Returns the selected stage, `None` if all stages are updated.
+    pub(crate) const fn filter(&self) -> Option<StageFilter> {
+        if self.only_final_stage {
+            Some(StageFilter::Final)
+        } else if self.only_first_stage {
+            Some(StageFilter::First)
+        } else {
+            None
+        }
+    }
+}
+
+/// Commands that are run for every file that was modified.
+#[derive(Args, Debug, Clone, Default)]
+pub struct UpdateHooks {
+    /// Builds the file with `docker build` in its folder, unless a command is
+    /// given with `--verify-cmd`.
+    #[arg(long, env = "DIU_VERIFY_BUILD", help = "Builds every modified file and reverts the update if the build fails.")]
+    pub(crate) verify_build: bool,
+
+    /// The program gets the file via `DIU_UPDATED_FILE`, a non-zero exit code
+    /// reverts the update.
+    #[arg(
+        long,
+        env = "DIU_VERIFY_CMD",
+        value_name = "EXE",
+        help = "Verifies every modified file with the given program instead of docker build."
+    )]
+    pub(crate) verify_cmd: Option<PathBuf>,
+
+    /// See `hook::run_post_update_cmd` for the environment of the program.
+    #[arg(
+        long,
+        env = "DIU_POST_UPDATE_CMD",
+        value_name = "EXE",
+        help = "Runs the given program for every modified file, with the file and the old and new images in DIU_UPDATED_FILE, DIU_OLD_IMAGES and DIU_NEW_IMAGES."
+    )]
+    pub(crate) post_update_cmd: Option<PathBuf>,
+}
+
+/// Keeps a copy of every file before it is modified.
+#[derive(Args, Debug, Clone, Default)]
+pub struct BackupOptions {
+    /// Files that are not modified are not copied. Existing copies are
+    /// overwritten.
+    #[arg(long, env = "DIU_BACKUP", help = "Copies every modified file to `<name>.orig` before it is written.")]
+    pub(crate) backup: bool,
+
+    /// An empty suffix would copy the files onto themselves, so it is only
+    /// accepted with `--backup-dir`.
+    #[arg(
+        long,
+        env = "DIU_BACKUP_SUFFIX",
+        value_name = "SUFFIX",
+        requires = "backup",
+        help = "The suffix of the copies, defaults to `.orig`."
+    )]
+    pub(crate) backup_suffix: Option<String>,
+
+    #[arg(
+        long,
+        env = "DIU_BACKUP_DIR",
+        value_name = "DIR",
+        requires = "backup",
+        required_if_eq("backup_suffix", ""),
+        help = "Places the copies in the given folder with the paths of the files, instead of next to them."
+    )]
+    pub(crate) backup_dir: Option<PathBuf>,
+}
+
+impl BackupOptions {
+    /// Returns where the modified files are copied to, `None` without
+    /// `--backup`.
+    pub(crate) fn backup(&self) -> Option<Backup> {
+        self.backup.then(|| Backup {
+            suffix: self.backup_suffix.clone().unwrap_or_else(|| DEFAULT_SUFFIX.to_owned()),
+            dir:    self.backup_dir.clone(),
+        })
+    }
+}
+
+/// Checks that the filter can be used as `EnvFilter`.
+fn parse_log_filter(filter: &str) -> Result<String, String> {
+    EnvFilter::try_new(filter).map(|_| filter.to_owned()).map_err(|e| e.to_string())
+}
+
+/// Returns the log filter for the given amount of `-v` flags.
+pub const fn verbosity_filter(verbose: u8) -> &'static str {
+    match verbose {
+        0 => "warn,dockerimage_updater=info",
+        1 => "info",
+        2 => "debug",
+        _ => "trace",
+    }
+}
+
+impl CommonOptions {
+    /// Returns the candidate options for the given strategies, the first one is
+    /// used unless it yields no candidate.
+    pub(crate) fn candidate_options(&self, strategies: &[Strategy]) -> CandidateOptions {
+        let (strategy, fallbacks) = strategies
+            .split_first()
+            .map_or_else(Default::default, |(strategy, fallbacks)| (strategy.clone(), fallbacks.to_vec()));
+        CandidateOptions {
+            strategy,
+            fallbacks,
+            prefer_suffix: self.prefer_suffix.clone(),
+            same_precision: self.same_precision,
+            full_precision: self.full_precision,
+            verify: self.verify,
+            candidate_cmd: self.candidate_cmd.clone(),
+            allow_prerelease: self.allow_prerelease,
+            include_latest: self.include_latest,
+            lts_majors: None,
+            max_risk: self.max_risk,
+            min_age: self.min_age,
+            variant_pins: self.variant_pin.clone(),
+            update_eol_variants: self.update_eol_variants,
+            official_only: self.official_only,
+            image_strategies: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the fetch options. The query is only narrowed down for a single
+    /// strategy, since fallbacks and candidate commands may need tags outside
+    /// of its name filter.
+    pub(crate) fn fetch_options(&self, strategies: &[Strategy]) -> FetchOptions {
+        FetchOptions {
+            limit:    self.tag_search_limit,
+            arch:     self.arch.clone(),
+            fast:     self.fast,
+            strategy: match strategies {
+                [strategy] if self.candidate_cmd.is_none() => Some(strategy.clone()),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    use clap::{CommandFactory, Parser};
+    use pretty_assertions::assert_eq;
+
+    use crate::cli::{Cli, Mode, verbosity_filter};
+
+    /// Returns the flags of the command and all of its subcommands, that can
+    /// not be set via the environment.
+    fn flags_without_env(command: &clap::Command) -> Vec<String> {
+        let mut missing: Vec<String> = command
+            .get_arguments()
+            .filter(|arg| !arg.is_positional() && !matches!(arg.get_id().as_str(), "help" | "version"))
+            .filter(|arg| !arg.get_env().is_some_and(|env| env.to_string_lossy().starts_with("DIU_")))
+            .map(|arg| format!("{} --{}", command.get_name(), arg.get_id()))
+            .collect();
+        missing.extend(command.get_subcommands().flat_map(flags_without_env));
+        missing
+    }
+
+    #[test]
+    fn every_flag_has_env() {
+        assert_eq!(flags_without_env(&Cli::command()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn verbosity() {
+        let cli = Cli::try_parse_from(["diu", "input", "node:22", "-vv", "--log-filter", "ureq=trace"]).unwrap();
+        let common = cli.mode.common().unwrap();
+        assert_eq!((common.verbose, common.log_filter.as_deref()), (2, Some("ureq=trace")));
+        assert_eq!(verbosity_filter(common.verbose), "debug");
+        assert_eq!(verbosity_filter(7), "trace");
+        assert!(Cli::try_parse_from(["diu", "input", "node:22", "--log-filter", "ureq=[unclosed"]).is_err());
+    }
+
+    #[test]
+    fn empty_backup_suffix() {
+        assert!(Cli::try_parse_from(["diu", "multi", "./tests", "--backup", "--backup-suffix", ""]).is_err());
+        let cli = Cli::try_parse_from(["diu", "multi", "./tests", "--backup", "--backup-suffix", "", "--backup-dir", "backups"]).unwrap();
+        let Mode::Multi(multi_mode) = cli.mode else {
+            panic!("Multi mode was parsed.");
+        };
+        assert_eq!(
+            multi_mode.backup.backup().unwrap().path_of(Path::new("api/Dockerfile")),
+            PathBuf::from("backups/api/Dockerfile")
+        );
+    }
+
+    #[test]
+    fn env_precedence() {
+        // The variables are only set for a child process that runs this test again,
+        // the environment of the other tests is left untouched.
+        if std::env::var_os("DIU_TEST_ENV_PRECEDENCE").is_none() {
+            let status = Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "cli::tests::env_precedence", "--quiet"])
+                .env("DIU_TEST_ENV_PRECEDENCE", "1")
+                .env("DIU_ARCH", "arm64")
+                .env("DIU_FAST", "true")
+                .env("DIU_IGNORE_VERSIONS", "alpine:3.12,node:8.0")
+                .status()
+                .unwrap();
+            assert!(status.success());
+            return;
+        }
+        let cli = Cli::try_parse_from(["diu", "multi", "./tests", "--arch", "amd64"]).unwrap();
+        let Mode::Multi(multi_mode) = cli.mode else {
+            panic!("Multi mode was parsed.");
+        };
+        assert_eq!(multi_mode.common.arch.as_deref(), Some("amd64"));
+        assert!(multi_mode.common.fast);
+        assert_eq!(multi_mode.ignore_versions, ["alpine:3.12", "node:8.0"]);
+    }
+}
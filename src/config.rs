@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::pin::Pin;
+use crate::tag::scheme::{SchemeError, TagScheme};
+use crate::utils::Strategy;
+use crate::yaml_path::YamlPath;
+
+/// Name of the config file, that is read from the working directory if no
+/// other file is given. In multi mode the files of the folder and its
+/// subfolders are merged as well.
+pub const CONFIG_FILE_NAME: &str = "dockerimage-updater.toml";
+/// The suffix of the file next to a dockerfile that holds the settings of
+/// this file only, e.g. `Dockerfile.diu.toml`.
+pub const FILE_CONFIG_SUFFIX: &str = ".diu.toml";
+
+/// Config file related errors.
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Could not read config file `{0}`: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("Could not parse config file `{0}`: {1}")]
+    Parse(PathBuf, String),
+    #[error(transparent)]
+    Scheme(#[from] SchemeError),
+}
+
+/// Settings that are read from the config file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// The strategies of the dockerfiles in the folder of the file, unless
+    /// given on the command line.
+    #[serde(default)]
+    pub strat:           Vec<Strategy>,
+    /// Images that are not updated in the folder of the file, in addition to
+    /// the ones given on the command line, e.g. `node:18`.
+    #[serde(default)]
+    pub ignore_versions: Vec<String>,
+    /// Images that are not updated in the folder of the file until a date has
+    /// passed, e.g. `node:18=until:2025-12-31`.
+    #[serde(default)]
+    pub pins:            Vec<Pin>,
+    /// Settings per image, the keys are the names of the images, e.g.
+    /// `node` or `internal/app`.
+    #[serde(default)]
+    pub images:          HashMap<String, ImageConfig>,
+    /// Settings per registry, the keys are hosts, e.g. `docker.io` (which
+    /// includes its subdomains) or `mcr.microsoft.com`.
+    #[serde(default)]
+    pub registries:      HashMap<String, RegistryConfig>,
+    /// The values that are updated in any YAML file, e.g.
+    /// `spec.values.image` of a Flux `HelmRelease`.
+    #[serde(default)]
+    pub yaml_paths:      Vec<YamlPath>,
+}
+
+/// Settings of a single image.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ImageConfig {
+    /// A regex with the named capture groups `major`, `minor`, `patch`,
+    /// `variant` and `build`, that is used instead of the built-in tag parser.
+    pub tag_scheme: Option<String>,
+    /// The majors that are LTS lines, e.g. `[11, 17, 21, 25]` for Temurin,
+    /// used by the `lts-only` strategy instead of the even majors.
+    pub lts_majors: Option<Vec<u64>>,
+}
+
+/// Settings of a single dockerfile in multi mode, read from the file next to
+/// it, see `FILE_CONFIG_SUFFIX`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FileConfig {
+    /// The strategies of the dockerfile, they replace the ones of the command
+    /// line and the config files.
+    #[serde(default)]
+    pub strat: Vec<Strategy>,
+}
+
+/// Settings of a single registry.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RegistryConfig {
+    /// The maximum of requests sent to the registry at the same time.
+    pub concurrency: Option<usize>,
+}
+
+impl Config {
+    /// Merges the config of a subfolder into this one: its strategies replace
+    /// the ones of the parent folder, its ignored and pinned images are added.
+    pub(crate) fn merge(&mut self, child: Self) {
+        if !child.strat.is_empty() {
+            self.strat = child.strat;
+        }
+        for image in child.ignore_versions {
+            if !self.ignore_versions.contains(&image) {
+                self.ignore_versions.push(image);
+            }
+        }
+        for pin in child.pins {
+            if !self.pins.contains(&pin) {
+                self.pins.push(pin);
+            }
+        }
+        self.images.extend(child.images);
+        self.registries.extend(child.registries);
+        for path in child.yaml_paths {
+            if !self.yaml_paths.contains(&path) {
+                self.yaml_paths.push(path);
+            }
+        }
+    }
+
+    /// Compiles the custom tag schemes of all images.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a scheme is invalid.
+    pub(crate) fn tag_schemes(&self) -> Result<HashMap<String, TagScheme>, ConfigError> {
+        self.images
+            .iter()
+            .filter_map(|(image, config)| config.tag_scheme.as_ref().map(|pattern| (image, pattern)))
+            .map(|(image, pattern)| Ok((image.clone(), TagScheme::new(image, pattern)?)))
+            .collect()
+    }
+
+    /// Returns the configured concurrency caps of all registries.
+    pub(crate) fn concurrency(&self) -> HashMap<String, usize> {
+        self.registries
+            .iter()
+            .filter_map(|(registry, config)| config.concurrency.map(|concurrency| (registry.clone(), concurrency)))
+            .collect()
+    }
+
+    /// Returns the configured LTS majors of all images.
+    pub(crate) fn lts_majors(&self) -> HashMap<String, Vec<u64>> {
+        self.images
+            .iter()
+            .filter_map(|(image, config)| config.lts_majors.clone().map(|majors| (image.clone(), majors)))
+            .collect()
+    }
+}
+
+/// Parses the content of a config file.
+///
+/// # Errors
+///
+/// This function will return an error if the content is no valid config.
+pub fn parse_config(path: &Path, content: &str) -> Result<Config, ConfigError> {
+    toml::from_str(content).map_err(|e| ConfigError::Parse(path.to_path_buf(), e.to_string()))
+}
+
+/// Loads the given config file or, if none is given, the config file of the
+/// working directory if it exists.
+///
+/// # Errors
+///
+/// This function will return an error if the file can not be read or parsed.
+pub fn load_config(path: Option<&Path>) -> Result<Config, ConfigError> {
+    let path = if let Some(path) = path {
+        path.to_path_buf()
+    } else {
+        let default = PathBuf::from(CONFIG_FILE_NAME);
+        if !default.is_file() {
+            return Ok(Config::default());
+        }
+        default
+    };
+    read_config(&path)
+}
+
+/// Reads and parses the config file.
+fn read_config(path: &Path) -> Result<Config, ConfigError> {
+    debug!("Reading config file `{}`.", path.display());
+    let content = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+    parse_config(path, &content)
+}
+
+/// Returns the path of the config file of the dockerfile, e.g.
+/// `api/Dockerfile.diu.toml` for `api/Dockerfile`.
+pub fn file_config_path(dockerfile: &Path) -> PathBuf {
+    let mut path = dockerfile.as_os_str().to_owned();
+    path.push(FILE_CONFIG_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// Reads the config file next to the dockerfile, if there is one.
+///
+/// # Errors
+///
+/// This function will return an error if the file can not be read or parsed.
+pub fn load_file_config(dockerfile: &Path) -> Result<Option<FileConfig>, ConfigError> {
+    let path = file_config_path(dockerfile);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    debug!("Reading config file `{}`.", path.display());
+    let content = fs::read_to_string(&path).map_err(|e| ConfigError::Io(path.clone(), e))?;
+    toml::from_str(&content).map(Some).map_err(|e| ConfigError::Parse(path, e.to_string()))
+}
+
+/// Merges the config files of the root folder and every folder below it down
+/// to the given folder, so that subfolders can override the settings of the
+/// root.
+///
+/// # Errors
+///
+/// This function will return an error if one of the files can not be read or
+/// parsed.
+pub fn load_folder_config(root: &Path, folder: &Path) -> Result<Config, ConfigError> {
+    let mut config = Config::default();
+    let mut current = root.to_path_buf();
+    let subfolders = folder.strip_prefix(root).map(Path::components).into_iter().flatten();
+    for subfolder in std::iter::once(None).chain(subfolders.map(Some)) {
+        if let Some(subfolder) = subfolder {
+            current.push(subfolder);
+        }
+        let path = current.join(CONFIG_FILE_NAME);
+        if path.is_file() {
+            config.merge(read_config(&path)?);
+        }
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use std::path::Path;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::config::{CONFIG_FILE_NAME, ConfigError, load_folder_config, parse_config};
+    use crate::paths;
+    use crate::utils::Strategy;
+
+    #[test]
+    fn tag_schemes() {
+        let content = r#"
+            [images."internal/app"]
+            tag-scheme = '^release-(?<major>\d+)\.(?<minor>\d+)-build(?<build>\d+)$'
+
+            [images.temurin]
+            lts-majors = [17, 21, 25]
+        "#;
+        let config = parse_config(Path::new("test.toml"), content).unwrap();
+        assert_eq!(config.images.len(), 2);
+        let schemes = config.tag_schemes().unwrap();
+        assert_eq!(schemes.len(), 1);
+        assert_eq!(schemes["internal/app"].parse("release-2024.3-build77").unwrap().major, Some(2024));
+        assert_eq!(config.lts_majors()["temurin"], [17, 21, 25]);
+
+        let content = "[registries.\"docker.io\"]\nconcurrency = 3\n[registries.\"mcr.microsoft.com\"]";
+        let config = parse_config(Path::new("test.toml"), content).unwrap();
+        assert_eq!(config.concurrency(), [("docker.io".to_owned(), 3)].into());
+
+        let invalid = parse_config(Path::new("test.toml"), "[images.app]\ntag-scheme = '(?<minor>\\d+)'").unwrap();
+        assert!(matches!(invalid.tag_schemes(), Err(ConfigError::Scheme(_))));
+        assert!(matches!(parse_config(Path::new("test.toml"), "unknown = 1"), Err(ConfigError::Parse(..))));
+        let config = parse_config(Path::new("test.toml"), "yaml-paths = [\"spec.values.image\"]").unwrap();
+        assert_eq!(config.yaml_paths, ["spec.values.image".parse().unwrap()]);
+        assert!(matches!(
+            parse_config(Path::new("test.toml"), "yaml-paths = [\"spec..image\"]"),
+            Err(ConfigError::Parse(..))
+        ));
+    }
+
+    #[test]
+    fn nested_configs() {
+        let root = paths::temp_path("config");
+        let service = root.join("services").join("api");
+        std::fs::create_dir_all(&service).unwrap();
+        std::fs::write(root.join(CONFIG_FILE_NAME), "strat = [\"next-minor\"]\nignore-versions = [\"node:18\"]").unwrap();
+        std::fs::write(
+            service.join(CONFIG_FILE_NAME),
+            "strat = [\"next-patch\"]\nignore-versions = [\"alpine:3.12\"]\npins = [\"node:20=until:2026-06-30\"]",
+        )
+        .unwrap();
+
+        let config = load_folder_config(&root, &root.join("services")).unwrap();
+        assert_eq!(config.strat, [Strategy::NextMinor]);
+        assert_eq!(config.ignore_versions, ["node:18"]);
+
+        let config = load_folder_config(&root, &service).unwrap();
+        assert_eq!(config.strat, [Strategy::NextPatch]);
+        assert_eq!(config.ignore_versions, ["node:18", "alpine:3.12"]);
+        assert_eq!(config.pins, ["node:20=until:2026-06-30".parse().unwrap()]);
+        std::fs::write(service.join(CONFIG_FILE_NAME), "pins = [\"node:20\"]").unwrap();
+        assert!(matches!(load_folder_config(&root, &service), Err(ConfigError::Parse(..))));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
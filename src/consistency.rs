@@ -0,0 +1,183 @@
+use serde::Serialize;
+
+use crate::container_image::{ContainerImage, Dockerfile, ImageLocation};
+use crate::report::{self, Report};
+use crate::tag::Tag;
+use crate::utils::ImageCandidates;
+
+/// An image that is used with different tags across files or stages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Inconsistency {
+    /// The name of the image, e.g. `node` or `mcr.microsoft.com/dotnet/aspnet`.
+    pub image:      String,
+    /// The different tags in the order of their first use.
+    pub usages:     Vec<TagUsage>,
+    /// The tag all usages were aligned to, `None` if they were only reported.
+    pub aligned_to: Option<String>,
+}
+
+/// A tag of an inconsistent image and where it is used.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TagUsage {
+    pub tag:       String,
+    /// The files and lines of the `FROM` instructions, e.g. `app/Dockerfile:3`.
+    pub locations: Vec<String>,
+    #[serde(skip)]
+    parsed:        Tag,
+    /// The images as written, e.g. `node:18` and `library/node:18`.
+    #[serde(skip)]
+    references:    Vec<String>,
+}
+
+impl Inconsistency {
+    /// Returns the newest of the used tags.
+    pub(crate) fn newest_tag(&self) -> &Tag {
+        self.usages
+            .iter()
+            .map(|usage| &usage.parsed)
+            .max_by(|a, b| a.cmp_version(b))
+            .expect("An inconsistency has at least two usages.")
+    }
+
+    /// Adds the candidates that replace every other tag of the image with the
+    /// given one, so that they can be applied with
+    /// `Dockerfile::generate_image_updates`.
+    pub(crate) fn align_to(&mut self, tag: &Tag, candidates: &mut ImageCandidates) {
+        for usage in &self.usages {
+            if usage.tag != tag.to_string() {
+                for reference in &usage.references {
+                    candidates.insert(reference.clone(), tag.clone());
+                }
+            }
+        }
+        self.aligned_to = Some(tag.to_string());
+    }
+}
+
+impl Report for Vec<Inconsistency> {
+    fn text(&self) -> Vec<String> {
+        self.iter()
+            .flat_map(|inconsistency| {
+                std::iter::once(format!("`{}` is used with {} different tags:", inconsistency.image, inconsistency.usages.len()))
+                    .chain(
+                        inconsistency
+                            .usages
+                            .iter()
+                            .map(|usage| format!("===> {}:\t{}", usage.tag, usage.locations.join(", "))),
+                    )
+                    .chain(
+                        inconsistency
+                            .aligned_to
+                            .iter()
+                            .map(|tag| format!("===> Aligned to `{}:{tag}`.", inconsistency.image)),
+                    )
+            })
+            .chain(std::iter::once(format!("{} images are used with different tags.", self.len())))
+            .collect()
+    }
+
+    fn markdown(&self) -> Vec<String> {
+        let rows = self.iter().flat_map(|inconsistency| {
+            inconsistency.usages.iter().map(|usage| {
+                [
+                    format!("`{}`", inconsistency.image),
+                    format!("`{}`", usage.tag),
+                    usage.locations.join(", "),
+                    inconsistency.aligned_to.clone().unwrap_or_else(|| "-".to_owned()),
+                ]
+            })
+        });
+        report::markdown_table(["Image", "Tag", "Locations", "Aligned to"], rows)
+    }
+}
+
+/// Returns the name images are grouped by, so that e.g. `node` and
+/// `library/node` are treated as the same image.
+pub fn image_key(image: &ContainerImage) -> String {
+    let name = image.get_dockerimage_name();
+    name.strip_prefix("library/").map(str::to_owned).unwrap_or(name)
+}
+
+/// Finds all images that are used with more than one tag in the given
+/// dockerfiles, sorted by their name.
+pub fn find_inconsistencies(dockerfiles: &[Dockerfile]) -> Vec<Inconsistency> {
+    let mut images: Vec<Inconsistency> = Vec::new();
+    for dockerfile in dockerfiles {
+        for ImageLocation { file, line, image, .. } in dockerfile.get_base_image_lines() {
+            if image.is_empty() {
+                continue;
+            }
+            let key = image_key(image);
+            let location = format!("{}:{line}", file.map(|file| file.display().to_string()).unwrap_or_default());
+            let index = images.iter().position(|entry| entry.image == key).unwrap_or_else(|| {
+                images.push(Inconsistency {
+                    image:      key,
+                    usages:     Vec::new(),
+                    aligned_to: None,
+                });
+                images.len() - 1
+            });
+            let tag = image.get_tag().to_string();
+            let usages = &mut images[index].usages;
+            let reference = image.to_string();
+            if let Some(usage) = usages.iter_mut().find(|usage| usage.tag == tag) {
+                usage.locations.push(location);
+                if !usage.references.contains(&reference) {
+                    usage.references.push(reference);
+                }
+            } else {
+                usages.push(TagUsage {
+                    tag,
+                    locations: vec![location],
+                    parsed: image.get_tag().clone(),
+                    references: vec![reference],
+                });
+            }
+        }
+    }
+    images.retain(|entry| entry.usages.len() > 1);
+    images.sort_by(|a, b| a.image.cmp(&b.image));
+    images
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use pretty_assertions::assert_eq;
+
+    use crate::consistency::find_inconsistencies;
+    use crate::container_image::Dockerfile;
+    use crate::utils::ImageCandidates;
+
+    #[test]
+    fn mismatched_tags() {
+        let mut api = Dockerfile::parse("FROM node:20.10 AS build\nFROM library/node:18\nFROM alpine:3.20\n").unwrap();
+        api.set_path("api/Dockerfile");
+        let mut web = Dockerfile::parse("FROM node:18\nFROM alpine:3.20\nFROM build\n").unwrap();
+        web.set_path("web/Dockerfile");
+
+        let mut inconsistencies = find_inconsistencies(&[api.clone(), web.clone()]);
+        assert_eq!(inconsistencies.len(), 1);
+        let node = &mut inconsistencies[0];
+        assert_eq!(node.image, "node");
+        let usages: Vec<(&str, Vec<&str>)> = node
+            .usages
+            .iter()
+            .map(|usage| (usage.tag.as_str(), usage.locations.iter().map(String::as_str).collect()))
+            .collect();
+        assert_eq!(
+            usages,
+            [("20.10", vec!["api/Dockerfile:1"]), ("18", vec!["api/Dockerfile:2", "web/Dockerfile:1"])]
+        );
+
+        let newest = node.newest_tag().clone();
+        assert_eq!(newest.to_string(), "20.10");
+        let mut candidates = ImageCandidates::new();
+        node.align_to(&newest, &mut candidates);
+        assert_eq!(node.aligned_to.as_deref(), Some("20.10"));
+        let api = api.generate_image_updates(&candidates, &[]).apply();
+        let web = web.generate_image_updates(&candidates, &[]).apply();
+        assert_eq!(web.to_string(), "FROM node:20.10\nFROM alpine:3.20\nFROM build\n");
+        assert!(find_inconsistencies(&[api, web]).is_empty());
+    }
+}
@@ -1,19 +1,53 @@
+use std::collections::BTreeMap;
 use std::fmt::{Display, Formatter};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::time::Duration;
-
-use tracing::{debug, error, info};
-use ureq::Agent;
-
-use crate::registries::dockerhub::DockerHubResponse;
-use crate::registries::mcr::McrResponseEntry;
-use crate::registries::{self, RegistryResponse, TAG_RESULT_LIMIT, TAGS_CACHE};
-use crate::tag::Tag;
-use crate::utils::{DockerfileUpdate, Strategy, extract_cache_from_file};
+use std::sync::LazyLock;
+
+use regex::Regex;
+use tracing::{debug, error, info, warn};
+
+use crate::cache::{self, TAGS_CACHE};
+use crate::hook::{self, CandidateRequest};
+use crate::marker::Marker;
+use crate::pin::Date;
+use crate::registries::credentials::{self, DOCKERHUB_SERVER};
+use crate::registries::dockerhub::{DockerHubRepository, DockerHubResponse, DockerHubResult, PublisherKind};
+use crate::registries::http::{self, HttpClient, HttpError};
+use crate::registries::manifest::{self, ManifestError};
+use crate::registries::mcr::{McrPage, McrResponseEntry};
+use crate::registries::{self, FAST_STOP_PAGES, FetchOptions, NAME_FILTER_MIN_RESULTS, PageError, PublishedTag, RegistryResponse};
+use crate::report::Severity;
+use crate::risk::Risk;
+use crate::scanners::{FileKind, LineScanner, ReferencePart, YamlScanners};
+use crate::tag::scheme::normalize;
+use crate::tag::{CandidateOptions, Tag, channel, scheme};
+use crate::utils::{DockerfileUpdate, ImageCandidates, Strategy};
+use crate::{backup, digest, eol, marker, patch, skipped, stats};
 
 const MCR_PREFIX: &str = "mcr.microsoft.com/";
+/// Hosts of docker hub that may be written in front of an image, e.g.
+/// `docker.io/library/node:22`. They are kept as written.
+const DOCKERHUB_HOSTS: [&str; 2] = ["docker.io/", "index.docker.io/"];
+/// Directive of include preprocessors, e.g. `#include <base.dockerfile>`.
+const INCLUDE_DIRECTIVE: &str = "#include";
+/// Includes nested deeper than this are not resolved, which stops cycles.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// The empty image, it has no tags.
+const SCRATCH: &str = "scratch";
+
+/// A `FROM` instruction, with the flags (e.g. `--platform=$BUILDPLATFORM`)
+/// separated from the image and stage name.
+static FROM_LINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(?i:FROM)\s+(?<flags>(?:--[^\s=]+=\S+\s+)*)(?<rest>.*)$").expect("From regex is valid."));
+/// A `COPY` instruction that copies from a stage by its index, e.g. `COPY
+/// --from=0 /app /app`.
+static COPY_FROM_INDEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)^\s*COPY\s.*--from=(?<index>\d+)(?:\s|$)").expect("Copy from regex is valid."));
+/// An image that is the default of a build argument, e.g.
+/// `${BASE_IMAGE:-node:20.11} AS build`.
+static ARG_DEFAULT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\$\{(?<arg>[A-Za-z_][A-Za-z0-9_]*:?-)(?<image>[^}\s]+)\}(?<rest>.*)$").expect("Argument default regex is valid."));
 
 /// The dockerfile related errors, that may occur during parsing.
 #[derive(Debug, thiserror::Error, PartialEq, Eq)]
@@ -35,6 +69,40 @@ pub enum ParseError {
     EmptyFile,
     #[error("Could not parse dockerhub response.")]
     InvalidDockerhubResponse,
+    #[error("Could not parse line {0} of the batch file: {1}")]
+    InvalidBatchLine(usize, String),
+    #[error("Could not parse line {0}: {1}")]
+    InvalidLine(usize, String),
+}
+
+/// A structural issue of a dockerfile found while parsing. The file is updated
+/// regardless, but the result may not be what was intended.
+#[derive(Debug, Clone, thiserror::Error, PartialEq, Eq)]
+pub enum StructureWarning {
+    #[error("The stage `{name}` is already defined in line {first}.")]
+    DuplicateName { line: usize, name: String, first: usize },
+    #[error("`{name}` refers to the stage defined in line {defined}, which comes later, so the image `{name}` is pulled instead.")]
+    ForwardReference { line: usize, name: String, defined: usize },
+    #[error("`--from={index}` refers to no earlier stage.")]
+    UnknownCopySource { line: usize, index: usize },
+}
+
+impl StructureWarning {
+    /// Returns the line the issue is found in, starting at 1.
+    pub(crate) const fn line(&self) -> usize {
+        match self {
+            Self::DuplicateName { line, .. } | Self::ForwardReference { line, .. } | Self::UnknownCopySource { line, .. } => *line,
+        }
+    }
+}
+
+/// An image whose tags could not be fetched, the other images are resolved
+/// regardless.
+#[derive(Debug, Clone, thiserror::Error, PartialEq, Eq)]
+#[error("Could not resolve `{image}`: {reason}")]
+pub struct ResolveError {
+    pub image:  String,
+    pub reason: String,
 }
 
 /// A dockerfile consists of a set of instructions and an optional path, in case
@@ -42,8 +110,14 @@ pub enum ParseError {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Dockerfile {
     instructions: Vec<DockerInstruction>,
+    /// The lines as they were read, with their line endings, and the
+    /// instructions parsed from them. Instructions that did not change are
+    /// written as these lines.
+    source:       Vec<(String, DockerInstruction)>,
     /// Original path of the file, in case it shall be written again.
     path:         Option<PathBuf>,
+    /// The structural issues found while parsing, see `check_structure`.
+    warnings:     Vec<StructureWarning>,
 }
 
 impl Dockerfile {
@@ -58,15 +132,88 @@ impl Dockerfile {
     /// This function will return an error if the file cannot be read
     /// parsing may return an empty object if the file is empty or invalid.
     pub(crate) fn read<P>(path: &P) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        P: AsRef<Path>,
+    {
+        Self::read_with(path, YamlScanners::ALL)
+    }
+
+    /// Reads the file like `read`, YAML files are only searched by the given
+    /// scanners, e.g. the ones enabled for the discovered files of a folder.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be read or
+    /// parsed.
+    pub(crate) fn read_with<P>(path: &P, scanners: YamlScanners) -> Result<Self, Box<dyn std::error::Error>>
     where
         P: AsRef<Path>,
     {
         let content = fs::read_to_string(path)?;
-        let mut dockerfile = Self::parse(&content)?;
+        stats::file_scanned();
+        let mut dockerfile = Self::parse_with(&content, FileKind::of(path.as_ref()), scanners)?;
         dockerfile.set_path(path);
+        for warning in &dockerfile.warnings {
+            warn!("{}:{}: {warning}", path.as_ref().display(), warning.line());
+        }
+        Ok(dockerfile)
+    }
+
+    /// Reads the dockerfile like `read` and resolves `#include <path>`
+    /// directives, the paths are relative to the including file. The images of
+    /// included files are updated in the included files, the directives stay
+    /// in place.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file or an included file
+    /// cannot be read or parsed.
+    pub(crate) fn read_with_includes<P>(path: &P, scanners: YamlScanners) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let mut dockerfile = Self::read_with(path, scanners)?;
+        dockerfile.resolve_includes(0)?;
         Ok(dockerfile)
     }
 
+    /// Replaces the include directives with the parsed included files.
+    fn resolve_includes(&mut self, depth: usize) -> Result<(), Box<dyn std::error::Error>> {
+        let folder = self.path.as_ref().and_then(|path| path.parent()).map(Path::to_path_buf).unwrap_or_default();
+        for instruction in &mut self.instructions {
+            let DockerInstruction::Raw(line) = instruction else {
+                continue;
+            };
+            let Some(include) = parse_include(line) else {
+                continue;
+            };
+            if depth >= MAX_INCLUDE_DEPTH {
+                return Err(format!("Includes of `{include}` are nested too deep.").into());
+            }
+            debug!("Resolving include `{include}`.");
+            let mut included = Self::read(&folder.join(include))?;
+            included.resolve_includes(depth + 1)?;
+            *instruction = DockerInstruction::Include(line.clone(), Box::new(included));
+        }
+        Ok(())
+    }
+
+    /// Writes the included files, see `read_with_includes`. Copies are only
+    /// saved with `backup`, see `revert`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an included file cannot be
+    /// written.
+    pub(crate) fn write_includes(&self, backup: bool) -> Result<(), Box<dyn std::error::Error>> {
+        for instruction in &self.instructions {
+            if let DockerInstruction::Include(_, included) = instruction {
+                included.write_as(backup)?;
+            }
+        }
+        Ok(())
+    }
+
     /// # Returns
     ///
     /// This function will return an `Option<Pathbuf>`. This will contain the
@@ -85,9 +232,7 @@ impl Dockerfile {
         })
     }
 
-    #[allow(unused)]
-    /// For testing purposes only
-    fn set_path<P>(&mut self, path: P)
+    pub(crate) fn set_path<P>(&mut self, path: P)
     where
         P: AsRef<Path>,
     {
@@ -124,15 +269,201 @@ impl Dockerfile {
     pub(crate) fn get_base_images_mut(&mut self) -> Vec<&mut Box<ContainerImage>> {
         self.get_instructions_mut()
             .iter_mut()
-            .filter_map(|instruction| instruction.get_image_mut())
+            .flat_map(|instruction| match instruction {
+                DockerInstruction::Include(_, included) => included.get_base_images_mut(),
+                instruction => instruction.get_image_mut().into_iter().collect(),
+            })
             .collect::<Vec<&mut Box<ContainerImage>>>()
     }
 
+    /// Returns the instructions of the dockerfile and its included files, that
+    /// start a stage.
+    fn get_stage_instructions_mut(&mut self) -> Vec<&mut DockerInstruction> {
+        self.get_instructions_mut()
+            .iter_mut()
+            .flat_map(|instruction| match instruction {
+                DockerInstruction::Include(_, included) => included.get_stage_instructions_mut(),
+                instruction => instruction.starts_stage().then_some(instruction).into_iter().collect(),
+            })
+            .collect()
+    }
+
+    /// Returns the strategy of a marker in the first line of the file, e.g. `#
+    /// diu:strategy=next-minor`, which is used instead of the strategy of the
+    /// run for the images of the file.
+    pub(crate) fn marked_strategy(&self) -> Option<Strategy> {
+        let DockerInstruction::Raw(line) = self.get_instructions().first()? else {
+            return None;
+        };
+        match marker::parse(line)? {
+            Marker::Image { strategy } => strategy,
+            Marker::Tag { .. } | Marker::PolicyTag(_) | Marker::PolicyName(_) => None,
+        }
+    }
+
+    /// Returns the strategies that markers give to the images of the file and
+    /// its included files, e.g. `next-patch` for `redis` of `tag: 7.4.0 # diu:
+    /// image=redis strategy=next-patch`. They are used instead of the
+    /// strategy of the run for these images, see `CandidateOptions`.
+    pub(crate) fn marked_image_strategies(&self) -> BTreeMap<String, Strategy> {
+        self.get_instructions()
+            .iter()
+            .flat_map(|instruction| match instruction {
+                DockerInstruction::Reference { image, suffix, .. } => match marker::parse(suffix) {
+                    Some(Marker::Image { strategy: Some(strategy) } | Marker::Tag { strategy: Some(strategy), .. }) => {
+                        BTreeMap::from([(normalize(&image.get_full_name()).to_owned(), strategy)])
+                    }
+                    _ => BTreeMap::new(),
+                },
+                DockerInstruction::Include(_, included) => included.marked_image_strategies(),
+                DockerInstruction::From { .. } | DockerInstruction::Raw(_) => BTreeMap::new(),
+            })
+            .collect()
+    }
+
+    /// Returns the build stages of the dockerfile, derived from its
+    /// instructions. Every stage starts with a `FROM` instruction and ends
+    /// before the next one, instructions before the first `FROM` belong to no
+    /// stage. Included files are not expanded, their directive is part of the
+    /// stage it is written in.
+    pub(crate) fn stages(&self) -> Vec<Stage<'_>> {
+        let starts: Vec<usize> = self
+            .instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(index, instruction)| instruction.starts_stage().then_some(index))
+            .collect();
+        starts
+            .iter()
+            .enumerate()
+            .map(|(index, &from_index)| {
+                let end = starts.get(index + 1).copied().unwrap_or(self.instructions.len());
+                let from = &self.instructions[from_index];
+                Stage {
+                    index,
+                    name: from.get_stage_name(),
+                    from_index,
+                    image: match from {
+                        DockerInstruction::From { image, .. } => Some(image.as_ref()),
+                        _ => None,
+                    },
+                    instructions: &self.instructions[from_index..end],
+                }
+            })
+            .collect()
+    }
+
+    /// Keeps only the image of the selected stage updatable, the `FROM`
+    /// instructions of the other stages are written as they are. Stage names
+    /// are compared case-insensitively, like docker does. Returns `false` if
+    /// the dockerfile has no such stage.
+    pub(crate) fn retain_stage(&mut self, filter: &StageFilter) -> bool {
+        let mut stages = self.get_stage_instructions_mut();
+        let selected = match filter {
+            StageFilter::Named(name) => stages
+                .iter()
+                .position(|instruction| instruction.get_stage_name().is_some_and(|stage| stage.eq_ignore_ascii_case(name))),
+            StageFilter::First => (!stages.is_empty()).then_some(0),
+            StageFilter::Final => stages.len().checked_sub(1),
+        };
+        for (index, instruction) in stages.iter_mut().enumerate() {
+            if Some(index) != selected && matches!(instruction, DockerInstruction::From { .. }) {
+                **instruction = DockerInstruction::Raw(instruction.to_string().trim_end_matches('\n').to_owned());
+            }
+        }
+        selected.is_some()
+    }
+
     /// This function will parse a Dockerfile, an empty dockerfile will result
     /// in an error.
+    #[cfg(test)]
     pub(crate) fn parse(content: &str) -> Result<Self, Error> {
-        let instructions = DockerInstruction::parse_file_content(content)?;
-        Ok(Self { instructions, path: None })
+        Self::parse_as(content, FileKind::Dockerfile)
+    }
+
+    /// Parses a file of the given kind, the lines of other build files are kept
+    /// as they are, apart from their image references.
+    #[cfg(test)]
+    pub(crate) fn parse_as(content: &str, kind: FileKind) -> Result<Self, Error> {
+        Self::parse_with(content, kind, YamlScanners::ALL)
+    }
+
+    /// Parses a file of the given kind like `parse_as`, YAML files are only
+    /// searched by the given scanners.
+    pub(crate) fn parse_with(content: &str, kind: FileKind, scanners: YamlScanners) -> Result<Self, Error> {
+        let instructions = DockerInstruction::parse_file_content(content, kind, scanners)?;
+        let source = content.split_inclusive('\n').map(str::to_owned).zip(instructions.iter().cloned()).collect();
+        let mut dockerfile = Self {
+            instructions,
+            source,
+            path: None,
+            warnings: Vec::new(),
+        };
+        if kind == FileKind::Dockerfile {
+            dockerfile.warnings = dockerfile.check_structure();
+        }
+        Ok(dockerfile)
+    }
+
+    /// Returns the structural issues of the stages, e.g. duplicate stage names
+    /// or references to stages that are not defined yet.
+    fn check_structure(&self) -> Vec<StructureWarning> {
+        let stages = self.stages();
+        let mut warnings = Vec::new();
+        for stage in &stages {
+            let line = stage.from_index + 1;
+            let defined = |name: &str| {
+                stages
+                    .iter()
+                    .find(|other| other.name.as_ref().is_some_and(|other| other.eq_ignore_ascii_case(name)))
+                    .map(|other| (other.index, other.from_index + 1))
+            };
+            if let Some(name) = &stage.name
+                && let Some((index, first)) = defined(name)
+                && index < stage.index
+            {
+                warnings.push(StructureWarning::DuplicateName {
+                    line,
+                    name: name.clone(),
+                    first,
+                });
+            }
+            // Docker only resolves stages defined before, otherwise the image is pulled.
+            if let Some(image) = stage.image.filter(|image| image.get_tag().allowed_missing)
+                && let Some((index, defined)) = defined(image.get_name())
+                && index >= stage.index
+            {
+                warnings.push(StructureWarning::ForwardReference {
+                    line,
+                    name: image.get_name().clone(),
+                    defined,
+                });
+            }
+            for (offset, instruction) in stage.instructions.iter().enumerate() {
+                let DockerInstruction::Raw(raw) = instruction else {
+                    continue;
+                };
+                if let Some(index) = COPY_FROM_INDEX.captures(raw).and_then(|captures| captures["index"].parse().ok())
+                    && index >= stage.index
+                {
+                    warnings.push(StructureWarning::UnknownCopySource { line: line + offset, index });
+                }
+            }
+        }
+        warnings
+    }
+
+    /// Returns the structural issues of the dockerfile and its included files,
+    /// with the file they are found in.
+    pub(crate) fn get_warnings(&self) -> Vec<(Option<&PathBuf>, &StructureWarning)> {
+        self.warnings
+            .iter()
+            .map(|warning| (self.get_path(), warning))
+            .chain(self.get_instructions().iter().flat_map(|instruction| match instruction {
+                DockerInstruction::Include(_, included) => included.get_warnings(),
+                _ => Vec::new(),
+            }))
+            .collect()
     }
 
     /// Writes the dockerfile to the disk, with the given path. It ignores the
@@ -161,8 +492,8 @@ impl Dockerfile {
         }
     }
 
-    /// Writes the dockerfile to the disk, with the given path. Will use the
-    /// path given in the data. # Returns
+    /// Writes the dockerfile and its included files to the disk, with the
+    /// given path. Will use the path given in the data. # Returns
     ///
     /// * `Ok()` - If the file can be successfully written.
     /// * `Err(Box<dyn std::error::Error>)` - An error if writing the file
@@ -173,12 +504,35 @@ impl Dockerfile {
     /// This function will return an error if the file cannot be written or if
     /// no path was set.
     pub(crate) fn write(&self) -> Result<(), Box<dyn std::error::Error>> {
-        if self.path.is_some() {
+        self.write_as(true)
+    }
+
+    /// Writes the original file back, e.g. after a failed verification. No
+    /// copies are saved, so that they keep the content before the update.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be written or if
+    /// no path was set.
+    pub(crate) fn revert(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.write_as(false)
+    }
+
+    fn write_as(&self, backup: bool) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(path) = &self.path {
             let content = format!("{self}"); // since display is implemented.
-            match fs::write(self.path.clone().expect("Path is set."), content) {
+            if patch::record(path, &content) {
+                debug!("Added the changes of `{}` to the patch.", path.display());
+                return self.write_includes(backup);
+            }
+            if backup && let Err(e) = backup::save(path, &content) {
+                error!("Could not save a copy of: {}, the file is not written, reason: {e}", path.display());
+                return Err(e.into());
+            }
+            match fs::write(path, content) {
                 Ok(()) => {
                     info!("Successfully written new dockerfile to: {}", self.path.clone().expect("Path is set").display());
-                    return Ok(());
+                    return self.write_includes(backup);
                 }
                 Err(e) => {
                     error!("Could not write file: {}, reason: {e}", self.path.clone().expect("Path is set").display());
@@ -190,9 +544,47 @@ impl Dockerfile {
         Err(Box::new(Error::MissingPath))
     }
 
-    /// Updates the images in a the dockerfile with the given strategy. If the
-    /// changes shall not be applied, it will print out a preview.
-    pub(crate) fn update_images(&mut self, apply_to_file: bool, strategy: &Strategy, limit: Option<u16>, arch: Option<&String>) {
+    /// Returns the images of all stages, that can be updated.
+    pub(crate) fn get_base_images(&self) -> Vec<&ContainerImage> {
+        self.get_base_image_lines().into_iter().map(|location| location.image).collect()
+    }
+
+    /// Returns the images of all stages, that can be updated, with the file,
+    /// the line of their `FROM` instruction and the column of the image.
+    /// Images of included files are returned with the included file.
+    pub(crate) fn get_base_image_lines(&self) -> Vec<ImageLocation<'_>> {
+        self.get_instructions()
+            .iter()
+            .enumerate()
+            .flat_map(|(index, instruction)| match instruction {
+                DockerInstruction::Include(_, included) => included.get_base_image_lines(),
+                // Every line is parsed as a single instruction.
+                DockerInstruction::From { image, prefix, .. } | DockerInstruction::Reference { image, prefix, .. } if instruction.has_valid_image() => {
+                    vec![ImageLocation {
+                        file:   self.get_path(),
+                        line:   index + 1,
+                        column: prefix.chars().count() + 1,
+                        image:  image.as_ref(),
+                    }]
+                }
+                DockerInstruction::From { .. } | DockerInstruction::Reference { .. } | DockerInstruction::Raw(_) => vec![],
+            })
+            .collect()
+    }
+
+    /// Updates the images in a the dockerfile with the given candidate options.
+    /// If the changes shall not be applied, it will print out a preview.
+    /// Images that can not be resolved are skipped, the others are updated
+    /// regardless. Updates of other severities than the given ones are only
+    /// logged. Ignored images are not looked up. The updated images are pinned
+    /// by digest, see `pin_digests`, if requested and an architecture is given.
+    /// Returns `true` if the file was written and the skipped images.
+    pub(crate) fn update_images(
+        &mut self, apply_to_file: bool, apply: &[Severity], pin_digest: bool, ignore_versions: &[ContainerImage], options: &CandidateOptions,
+        fetch: &FetchOptions,
+    ) -> (bool, Vec<ResolveError>) {
+        let original = pin_digest.then(|| self.clone());
+        let mut failures = Vec::new();
         for image in self.get_base_images_mut() {
             if image.is_empty() {
                 // If this happens, we can not fetch any data. This can be cause by comments
@@ -200,42 +592,127 @@ impl Dockerfile {
                 // an empty image. This can be caused by referencing previous stages.
                 continue;
             }
-            let mut docker_image_tags = image.get_remote_tags(limit, arch).expect("Tags could be found.");
-            docker_image_tags.sort();
-
-            if let Some(found_tag) = image.get_tag().find_candidate_tag(&docker_image_tags, strategy) {
-                debug!("Found tag: {found_tag:?}");
-                image.set_tag(&found_tag.clone());
+            if let Some(ignored) = image.find_ignored(ignore_versions) {
+                debug!("Not updating `{image}`, it is ignored by `{ignored}`.");
+                continue;
+            }
+            match image.resolve_update(options, fetch) {
+                Ok(Some(found_tag)) if Severity::between(image.get_tag(), &found_tag).is_applied(apply) => image.set_tag(&found_tag),
+                Ok(Some(found_tag)) => info!(
+                    "Not applying the {} update of `{image}` to `{found_tag}`.",
+                    Severity::between(image.get_tag(), &found_tag)
+                ),
+                Ok(None) => {}
+                Err(e) => {
+                    error!("{e}");
+                    failures.push(e);
+                }
             }
         }
+        self.refresh_digests(fetch.arch.as_deref());
+        if let (Some(original), Some(arch)) = (original, fetch.arch.as_deref()) {
+            self.pin_digests(http::client().as_ref(), &original, arch);
+        }
 
-        if apply_to_file && self.get_path().is_some() {
-            let _ = self.write();
+        let written = if apply_to_file && self.get_path().is_some() {
+            self.write().is_ok()
         } else {
             info!("Resulting dockerfile:\n{}", self);
+            false
+        };
+        (written, failures)
+    }
+
+    /// Pins the images of the `FROM` instructions that differ from the original
+    /// file to the digest of their image of the architecture, with the tag in
+    /// front of the digest, e.g. `FROM node:22.2.0@sha256:... AS build`. The
+    /// digest mode refreshes such pins. Images whose digest can not be looked
+    /// up keep their tag only.
+    pub(crate) fn pin_digests(&mut self, client: &dyn HttpClient, original: &Self, arch: &str) {
+        for (instruction, original) in self.instructions.iter_mut().zip(&original.instructions) {
+            if let (DockerInstruction::Include(_, included), DockerInstruction::Include(_, original)) = (&mut *instruction, original) {
+                included.pin_digests(client, original, arch);
+                continue;
+            }
+            let (DockerInstruction::From { image, prefix, suffix, .. }, DockerInstruction::From { image: original_image, .. }) = (&*instruction, original)
+            else {
+                continue;
+            };
+            if image == original_image {
+                continue;
+            }
+            let pin = match image.lookup_tag(client, Some(arch)) {
+                // Docker only treats `#` at the start of a line as comment, so
+                // the tag is kept in front of the digest.
+                Ok(Some(PublishedTag { digest: Some(arch_digest), .. })) => format!("{prefix}{image}@{arch_digest}{suffix}"),
+                Ok(_) => {
+                    warn!("No digest of `{image}` is published for `{arch}`, the tag is kept.");
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Could not look up the digest of `{image}`, the tag is kept: {e}");
+                    continue;
+                }
+            };
+            if digest::is_pinned(&pin) {
+                *instruction = DockerInstruction::Raw(pin);
+            } else {
+                warn!("Can not pin `{image}` in `{}`, the tag is kept.", instruction.to_string().trim_end());
+            }
+        }
+    }
+
+    /// Looks up the digests that belong to an updated tag again, e.g. the
+    /// `digest` of a kustomize image whose `newTag` changed. Digests that can
+    /// not be looked up are kept as they are.
+    pub(crate) fn refresh_digests(&mut self, arch: Option<&str>) {
+        self.refresh_digests_with(http::client().as_ref(), arch);
+    }
+
+    /// Looks up the digests like `refresh_digests` with the given client.
+    pub(crate) fn refresh_digests_with(&mut self, client: &dyn HttpClient, arch: Option<&str>) {
+        let instructions = self.get_instructions_mut();
+        for index in 0..instructions.len() {
+            let tagged = paired_tag(instructions, index);
+            match &mut instructions[index] {
+                DockerInstruction::Include(_, included) => included.refresh_digests_with(client, arch),
+                DockerInstruction::Reference {
+                    image,
+                    part: ReferencePart::Digest(digest),
+                    ..
+                } => {
+                    let Some(tagged) = tagged.filter(|tagged| tagged.get_tag() != image.get_tag()) else {
+                        continue;
+                    };
+                    match tagged.lookup_tag(client, arch) {
+                        Ok(Some(PublishedTag { digest: Some(new_digest), .. })) => {
+                            *digest = new_digest;
+                            **image = tagged;
+                        }
+                        Ok(_) => warn!("Could not find the digest of `{tagged}`, the old digest is kept."),
+                        Err(e) => warn!("Could not look up the digest of `{tagged}`, the old digest is kept: {e}"),
+                    }
+                }
+                DockerInstruction::From { .. } | DockerInstruction::Reference { .. } | DockerInstruction::Raw(_) => {}
+            }
         }
     }
 
-    /// Generates a list of updates that should be applied to a file, since we
-    /// want to preview the changes differently for multi file updates.
-    pub(crate) fn generate_image_updates(
-        &self, strategy: &Strategy, limit: Option<u16>, arch: Option<&String>, ignore_versions: &[ContainerImage],
-    ) -> DockerfileUpdate {
+    /// Generates a list of updates that should be applied to a file from the
+    /// candidates resolved for all files, since we want to preview the changes
+    /// differently for multi file updates.
+    pub(crate) fn generate_image_updates(&self, candidates: &ImageCandidates, ignore_versions: &[ContainerImage]) -> DockerfileUpdate {
         let mut result = DockerfileUpdate {
             dockerfile: self.clone(),
             updates:    Vec::new(),
         };
-        for (index, image) in result.dockerfile.get_base_images_mut().iter().enumerate() {
-            if image.get_tag().allowed_missing {
+        for (index, image) in self.get_base_images().into_iter().enumerate() {
+            if let Some(ignored) = image.find_ignored(ignore_versions) {
+                debug!("Not updating `{image}`, it is ignored by `{ignored}`.");
                 continue;
             }
-            let mut docker_image_tags = image.get_remote_tags(limit, arch).expect("Tags could be found.");
-            docker_image_tags.sort();
-            if let Some(found_tag) = image.get_tag().find_candidate_tag(&docker_image_tags, strategy) {
-                debug!("Found tag: {found_tag:?}");
-                if !ignore_versions.contains(image) {
-                    result.updates.push((index, found_tag.clone()));
-                }
+            if let Some(found_tag) = candidates.get(&image.to_string()) {
+                result.updates.push((index, found_tag.clone()));
             }
         }
         result
@@ -243,45 +720,224 @@ impl Dockerfile {
 }
 
 impl Display for Dockerfile {
+    /// Unchanged instructions are written as they were read, changed ones
+    /// keep the line ending of their line, e.g. `\r\n`.
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        for instructions in self.get_instructions() {
-            write!(f, "{instructions}")?;
+        for (index, instruction) in self.get_instructions().iter().enumerate() {
+            match self.source.get(index) {
+                Some((line, original)) if original == instruction => write!(f, "{line}")?,
+                Some((line, _)) => {
+                    let content = line.trim_end_matches(['\r', '\n']);
+                    write!(f, "{}{}", instruction.to_string().trim_end_matches('\n'), &line[content.len()..])?;
+                }
+                None => write!(f, "{instruction}")?,
+            }
         }
         write!(f, "")
     }
 }
 
+/// An updatable image of a dockerfile and where it is written, see
+/// `Dockerfile::get_base_image_lines`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageLocation<'a> {
+    /// The file the image is written in, `None` for standard input.
+    pub file:   Option<&'a PathBuf>,
+    /// The line of the instruction, starting at 1.
+    pub line:   usize,
+    /// The column the image (or its tag) starts at, starting at 1.
+    pub column: usize,
+    pub image:  &'a ContainerImage,
+}
+
+/// A build stage of a dockerfile, see `Dockerfile::stages`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stage<'a> {
+    /// The position of the stage, starting at 0, as used by `COPY --from=0`.
+    pub index:        usize,
+    /// The name of the stage, e.g. `build` of `FROM node:22 AS build`.
+    pub name:         Option<String>,
+    /// The index of the `FROM` instruction of the stage in the instructions of
+    /// the dockerfile.
+    pub from_index:   usize,
+    /// The base image of the stage, `None` if it is written as it is, e.g.
+    /// `FROM scratch`.
+    pub image:        Option<&'a ContainerImage>,
+    /// The instructions of the stage, starting with its `FROM` instruction.
+    pub instructions: &'a [DockerInstruction],
+}
+
+impl Display for Stage<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{name}"),
+            None => write!(f, "{}", self.index),
+        }
+    }
+}
+
+/// Selects the stage of a dockerfile whose image is updated, see
+/// `Dockerfile::retain_stage`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StageFilter {
+    /// The stage with the name, e.g. `runtime` of `FROM node:22 AS runtime`.
+    Named(String),
+    First,
+    /// Usually the runtime base, the stages before build the application.
+    Final,
+}
+
+impl Display for StageFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Named(name) => write!(f, "stage `{name}`"),
+            Self::First => write!(f, "first stage"),
+            Self::Final => write!(f, "final stage"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum DockerInstruction {
-    From(Box<ContainerImage>, Option<String>),
+    From {
+        image:      Box<ContainerImage>,
+        stage_name: Option<String>,
+        /// The text before the image as it was written, e.g. `FROM
+        /// --platform=$BUILDPLATFORM ` or `from ${BASE_IMAGE:-`.
+        prefix:     String,
+        /// The text after the image as it was written, e.g. ` AS build` or `}
+        /// as build`.
+        suffix:     String,
+    },
+    /// An image referenced by another kind of build file, with the text around
+    /// it, e.g. `<image>` and `</image>` in a `pom.xml`.
+    Reference {
+        prefix: String,
+        image:  Box<ContainerImage>,
+        suffix: String,
+        /// The part of the image that is written, e.g. only the tag for
+        /// `newTag: 1.27.0`.
+        part:   ReferencePart,
+    },
     Raw(String),
+    /// An include directive and the included file, see
+    /// `Dockerfile::read_with_includes`.
+    Include(String, Box<Dockerfile>),
+}
+
+/// Returns the headers of a Docker Hub API request, see
+/// `credentials::hub_authorization`.
+fn authorization_headers(authorization: Option<&str>) -> Vec<(&'static str, &str)> {
+    authorization.map(|authorization| ("Authorization", authorization)).into_iter().collect()
+}
+
+/// Returns the tag reference that belongs to the digest reference at the index,
+/// i.e. the reference of the same image right before or after it, e.g. the
+/// `tag` of a bazel pull whose `digest` comes first.
+fn paired_tag(instructions: &[DockerInstruction], index: usize) -> Option<ContainerImage> {
+    let DockerInstruction::Reference {
+        image: digested,
+        part: ReferencePart::Digest(_),
+        ..
+    } = &instructions[index]
+    else {
+        return None;
+    };
+    let is_reference = |i: &usize| matches!(instructions[*i], DockerInstruction::Reference { .. });
+    [(0..index).rev().find(is_reference), (index + 1..instructions.len()).find(is_reference)]
+        .into_iter()
+        .flatten()
+        .find_map(|i| match &instructions[i] {
+            DockerInstruction::Reference {
+                image,
+                part: ReferencePart::Tag,
+                ..
+            } if image.get_full_name() == digested.get_full_name() => Some(image.as_ref().clone()),
+            _ => None,
+        })
+}
+
+/// Returns the path of an include directive, e.g. `base.dockerfile` for
+/// `#include <base.dockerfile>` or `#include "base.dockerfile"`.
+fn parse_include(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix(INCLUDE_DIRECTIVE)?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let rest = rest.trim();
+    let path = rest
+        .strip_prefix('<')
+        .and_then(|path| path.strip_suffix('>'))
+        .or_else(|| rest.strip_prefix('"').and_then(|path| path.strip_suffix('"')))
+        .unwrap_or(rest);
+    (!path.is_empty()).then_some(path)
 }
 
 impl DockerInstruction {
     /// On successful parsing will return a vector of docker instructions.
-    fn parse_file_content(content: &str) -> Result<Vec<Self>, Error> {
+    fn parse_file_content(content: &str, kind: FileKind, scanners: YamlScanners) -> Result<Vec<Self>, Error> {
         if content.is_empty() {
             return Err(Error::Parse(ParseError::EmptyFile));
         }
 
         let mut instructions = Vec::new();
-        for line in content.lines() {
-            instructions.push(Self::from_str(line)?);
+        let mut scanner = LineScanner::new(kind, scanners).with_image_aliases(content).with_bazel_pulls(content);
+        for (index, line) in content.lines().enumerate() {
+            instructions.push(match kind {
+                FileKind::Dockerfile => Self::from_str(line).map_err(|e| ParseError::InvalidLine(index + 1, e.to_string()))?,
+                FileKind::Maven
+                | FileKind::Gradle
+                | FileKind::Terraform
+                | FileKind::Ansible
+                | FileKind::Kustomize
+                | FileKind::Compose
+                | FileKind::Bazel
+                | FileKind::Packer
+                | FileKind::GitlabCi
+                | FileKind::CircleCi
+                | FileKind::Drone => Self::from_reference_line(&mut scanner, line),
+            });
         }
         Ok(instructions)
     }
 
+    /// Parses a line of another build file, lines without an image reference
+    /// or with an invalid one are kept as they are.
+    fn from_reference_line(scanner: &mut LineScanner, line: &str) -> Self {
+        let Some(reference) = scanner.scan(line) else {
+            return Self::Raw(line.to_owned());
+        };
+        match reference.image.parse() {
+            Ok(image) => Self::Reference {
+                prefix: reference.prefix.to_owned(),
+                image:  Box::new(image),
+                suffix: reference.suffix.to_owned(),
+                part:   reference.part,
+            },
+            Err(e) => {
+                debug!("Not updating `{}`: {e}", reference.image);
+                Self::Raw(line.to_owned())
+            }
+        }
+    }
+
     const fn has_valid_image(&self) -> bool {
         match self {
-            Self::From(container_image, _) => !container_image.get_tag().allowed_missing,
-            Self::Raw(_) => false,
+            // A digest follows the tag it belongs to, it is not updated on its own.
+            Self::Reference {
+                part: ReferencePart::Digest(_),
+                ..
+            }
+            | Self::Raw(_)
+            | Self::Include(..) => false,
+            Self::From { image, .. } | Self::Reference { image, .. } => !image.get_tag().allowed_missing,
         }
     }
 
     const fn get_image_mut(&mut self) -> Option<&mut Box<ContainerImage>> {
         if !self.has_valid_image() {
             None
-        } else if let Self::From(image, _) = self {
+        } else if let Self::From { image, .. } | Self::Reference { image, .. } = self {
             Some(image)
         } else {
             None
@@ -292,8 +948,8 @@ impl DockerInstruction {
     #[cfg(test)]
     pub(crate) fn get_full_image_name(&self) -> Option<String> {
         match self {
-            Self::From(container_image, _) => Some(container_image.to_string()),
-            Self::Raw(_) => None,
+            Self::From { image, .. } | Self::Reference { image, .. } => Some(image.to_string()),
+            Self::Raw(_) | Self::Include(..) => None,
         }
     }
 
@@ -301,8 +957,8 @@ impl DockerInstruction {
     #[cfg(test)]
     pub(crate) fn get_only_image_name(&self) -> Option<String> {
         match self {
-            Self::From(container_image, _) => Some(container_image.get_tagged_name()),
-            Self::Raw(_) => None,
+            Self::From { image, .. } | Self::Reference { image, .. } => Some(image.get_tagged_name()),
+            Self::Raw(_) | Self::Include(..) => None,
         }
     }
 
@@ -310,17 +966,30 @@ impl DockerInstruction {
     #[cfg(test)]
     pub(crate) const fn get_image_tag(&self) -> Option<&Tag> {
         match self {
-            Self::From(container_image, _) => Some(container_image.get_tag()),
-            Self::Raw(_) => None,
+            Self::From { image, .. } | Self::Reference { image, .. } => Some(image.get_tag()),
+            Self::Raw(_) | Self::Include(..) => None,
         }
     }
 
-    // Used for testing
-    #[cfg(test)]
+    /// Checks if the instruction starts a stage. `FROM` lines whose image is
+    /// not updated (e.g. `FROM scratch AS runtime`) start a stage as well.
+    fn starts_stage(&self) -> bool {
+        match self {
+            Self::From { .. } => true,
+            Self::Raw(line) => FROM_LINE.is_match(line),
+            Self::Reference { .. } | Self::Include(..) => false,
+        }
+    }
+
+    /// Returns the name of the stage the instruction starts, if it has one.
     pub(crate) fn get_stage_name(&self) -> Option<String> {
         match self {
-            Self::From(_, stage_name) => stage_name.clone(),
-            Self::Raw(_) => None,
+            Self::From { stage_name, .. } => stage_name.clone(),
+            Self::Raw(line) => FROM_LINE.captures(line).and_then(|captures| {
+                let mut words = captures["rest"].split_whitespace();
+                words.find(|word| word.eq_ignore_ascii_case("as")).and_then(|_| words.next()).map(str::to_owned)
+            }),
+            Self::Reference { .. } | Self::Include(..) => None,
         }
     }
 }
@@ -328,15 +997,13 @@ impl DockerInstruction {
 impl Display for DockerInstruction {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::From(image, stage_name) => match stage_name {
-                Some(stage_name) => {
-                    writeln!(f, "FROM {image} AS {stage_name}")
-                }
-                None => {
-                    writeln!(f, "FROM {image}")
-                }
+            Self::From { image, prefix, suffix, .. } => writeln!(f, "{prefix}{image}{suffix}"),
+            Self::Reference { prefix, image, suffix, part } => match part {
+                ReferencePart::Image => writeln!(f, "{prefix}{image}{suffix}"),
+                ReferencePart::Tag => writeln!(f, "{prefix}{}{suffix}", image.get_tag()),
+                ReferencePart::Digest(digest) => writeln!(f, "{prefix}{digest}{suffix}"),
             },
-            Self::Raw(s) => writeln!(f, "{s}"),
+            Self::Raw(s) | Self::Include(s, _) => writeln!(f, "{s}"),
         }
     }
 }
@@ -345,16 +1012,44 @@ impl FromStr for DockerInstruction {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.trim_start().to_uppercase().starts_with("FROM ") {
-            let (image, stage_name) = ContainerImage::parse_from_line(s)?;
-            return Ok(Self::From(Box::new(image), stage_name));
+        // Images pinned by digest are refreshed by the digest mode only.
+        if digest::is_pinned(s) {
+            return Ok(Self::Raw(s.to_string()));
         }
-        Ok(Self::Raw(s.to_string()))
+        let Some(captures) = FROM_LINE.captures(s) else {
+            return Ok(Self::Raw(s.to_string()));
+        };
+        // The default is updated, the build argument is kept around it.
+        let rest = captures.name("rest").expect("Rest is always captured.");
+        let (start, rest) = ARG_DEFAULT.captures(rest.as_str()).map_or_else(
+            || (rest.start(), rest.as_str().to_owned()),
+            |default| {
+                let image = default.name("image").expect("Image is always captured.");
+                (rest.start() + image.start(), format!("{}{}", image.as_str(), &default["rest"]))
+            },
+        );
+        let image = rest.split_whitespace().next().unwrap_or_default();
+        if image.eq_ignore_ascii_case(SCRATCH) || image.contains('$') {
+            debug!("Not updating `{}`, it is empty or set by a build argument.", s.trim());
+            return Ok(Self::Raw(s.to_string()));
+        }
+        // Everything around the image is written as it was, e.g. the case of `as`.
+        let end = start + image.len();
+        let (image, stage_name) = ContainerImage::parse_from_line(&format!("FROM {rest}"))?;
+        Ok(Self::From {
+            image: Box::new(image),
+            stage_name,
+            prefix: s[..start].to_owned(),
+            suffix: s[end..].to_owned(),
+        })
     }
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct ImageMetadata {
+    /// The docker hub host as written in front of the image, e.g.
+    /// `docker.io/`, see `DOCKERHUB_HOSTS`.
+    host:  Option<String>,
     group: Option<String>,
     name:  String,
     tag:   Tag,
@@ -388,29 +1083,36 @@ impl FromStr for ImageMetadata {
         if let Some((group, name)) = cleaned_slice.split_once('/') {
             if let Some((name, tag)) = name.split_once(':') {
                 return Ok(Self {
+                    host:  None,
                     group: Some(group.to_owned()),
                     name:  name.to_owned(),
-                    tag:   tag.parse()?,
+                    tag:   scheme::parse_tag(&format!("{group}/{name}"), tag)?,
                 });
             }
         } else if let Some((name, tag)) = cleaned_slice.split_once(':') {
             return Ok(Self {
+                host:  None,
                 group: None,
                 name:  name.to_owned(),
-                tag:   tag.parse()?,
+                tag:   scheme::parse_tag(name, tag)?,
             });
         }
         //This happens if we reference another image that did not have a :<tag>
         Ok(Self {
+            host:  None,
             group: None,
             name:  cleaned_slice.to_owned(),
             tag:   Tag {
                 major:           None,
                 minor:           None,
                 patch:           None,
+                build:           None,
                 variant:         None,
+                revision:        None,
+                digits:          [0; 3],
                 allowed_missing: true,
                 latest:          false,
+                template:        None,
             },
         })
     }
@@ -499,6 +1201,31 @@ impl ContainerImage {
         }
     }
 
+    /// Returns the most specific ignored image that matches this image, if
+    /// any. `node:18.1-alpine` only matches this very tag, `node:18` matches
+    /// every tag with the major 18 and `node` every tag of the image. An exact
+    /// tag takes precedence over the major, which takes precedence over the
+    /// name.
+    pub(crate) fn find_ignored<'a>(&self, ignore_versions: &'a [Self]) -> Option<&'a Self> {
+        let name = self.get_dockerimage_name();
+        let same_image: Vec<&Self> = ignore_versions
+            .iter()
+            .filter(|ignored| ignored.is_mcr() == self.is_mcr() && normalize(&ignored.get_dockerimage_name()) == normalize(&name))
+            .collect();
+        let tag = self.get_tag();
+        same_image
+            .iter()
+            .find(|ignored| ignored.get_tag() == tag)
+            .or_else(|| {
+                same_image.iter().find(|ignored| {
+                    let ignored = ignored.get_tag();
+                    ignored.precision() == 1 && ignored.variant.is_none() && ignored.build.is_none() && ignored.major == tag.major
+                })
+            })
+            .or_else(|| same_image.iter().find(|ignored| ignored.get_tag().allowed_missing))
+            .copied()
+    }
+
     /// Returns the full name for a  given image, e.g. node:<tag>,
     /// library/python:<tag>, dotnet/aspnet:<tag>
     pub(crate) fn get_full_tagged_name(&self) -> String {
@@ -523,12 +1250,30 @@ impl ContainerImage {
         }
     }
 
+    /// Returns the package url of the image with the given tag, e.g.
+    /// `pkg:docker/node@22.1.0` or
+    /// `pkg:docker/dotnet/aspnet@9.0.0?repository_url=mcr.microsoft.com`.
+    pub(crate) fn get_purl(&self, tag: &Tag) -> String {
+        match self {
+            Self::Dockerhub(_) => format!("pkg:docker/{}@{tag}", self.get_dockerimage_name()),
+            Self::Mcr(_) => format!("pkg:docker/{}@{tag}?repository_url={}", self.get_full_name(), MCR_PREFIX.trim_end_matches('/')),
+        }
+    }
+
     pub const fn get_tag(&self) -> &Tag {
         match self {
             Self::Dockerhub(metadata) | Self::Mcr(metadata) => &metadata.tag,
         }
     }
 
+    /// Returns the docker hub host as written in front of the image, e.g.
+    /// `docker.io/`, or an empty string.
+    pub(crate) fn get_host(&self) -> &str {
+        match self {
+            Self::Dockerhub(metadata) | Self::Mcr(metadata) => metadata.host.as_deref().unwrap_or_default(),
+        }
+    }
+
     fn set_tag(&mut self, tag: &Tag) {
         match self {
             Self::Dockerhub(metadata) | Self::Mcr(metadata) => metadata.tag = tag.clone(),
@@ -555,17 +1300,22 @@ impl ContainerImage {
         }
     }
 
-    fn is_empty(&self) -> bool {
+    pub(crate) fn is_empty(&self) -> bool {
         match self {
             Self::Dockerhub(image_metadata) | Self::Mcr(image_metadata) => *image_metadata == ImageMetadata::default(),
         }
     }
 
-    fn get_query_url(&self) -> String {
+    /// Returns the url to query the tags, narrowed down to tags containing the
+    /// name filter, if any. Only dockerhub supports filtering.
+    fn get_query_url(&self, name_filter: Option<&str>) -> String {
         match self {
             Self::Dockerhub(_) => {
                 let full_name = self.get_full_name();
-                format!("https://hub.docker.com/v2/repositories/{full_name}/tags?page_size=100")
+                name_filter.map_or_else(
+                    || format!("https://hub.docker.com/v2/repositories/{full_name}/tags?page_size=100"),
+                    |name| format!("https://hub.docker.com/v2/repositories/{full_name}/tags?page_size=100&name={name}"),
+                )
             }
             Self::Mcr(_) => {
                 let full_name = self.get_full_name();
@@ -574,143 +1324,432 @@ impl ContainerImage {
         }
     }
 
-    /// Handles the data fetching for dockerhub, since dockerhub only returns a
-    /// limited amount of versions, but will return the next query link.
-    fn request_dockerhub(&self, limit: Option<u16>) -> Result<DockerHubResponse, Box<dyn std::error::Error>> {
-        // build agent with global timeout
-        let config = Agent::config_builder().timeout_global(Some(Duration::from_secs(10))).build();
-        let agent: Agent = config.into();
+    /// Returns the url of the manifest of the image with the given tag.
+    fn get_manifest_url(&self, tag: &Tag) -> String {
+        let full_name = self.get_full_name();
+        match self {
+            Self::Dockerhub(_) => format!("https://registry-1.docker.io/v2/{full_name}/manifests/{tag}"),
+            Self::Mcr(_) => format!("https://mcr.microsoft.com/v2/{full_name}/manifests/{tag}"),
+        }
+    }
 
-        let mut request_url = Some(self.get_query_url());
-        let mut parsed_response = DockerHubResponse::default();
+    /// Checks if the manifest of the image with the given tag can be fetched,
+    /// for the given architecture if any. Docker hub requires a token even for
+    /// public images, the MCR does not. The token is requested with the
+    /// credentials of the docker config, if there are any.
+    pub(crate) fn verify_tag(&self, client: &dyn HttpClient, tag: &Tag, arch: Option<&String>) -> Result<(), ManifestError> {
+        let token = match self {
+            Self::Dockerhub(_) => {
+                let token_url = format!(
+                    "https://auth.docker.io/token?service=registry.docker.io&scope=repository:{}:pull",
+                    self.get_full_name()
+                );
+                let credentials = client.sends_credentials().then(|| credentials::lookup(DOCKERHUB_SERVER)).flatten();
+                Some(manifest::request_token(client, &token_url, credentials.as_ref())?)
+            }
+            Self::Mcr(_) => None,
+        };
+        manifest::verify_manifest(client, &self.get_manifest_url(tag), token.as_deref(), arch.map(String::as_str))
+    }
 
-        while let Some(ref inner_url) = request_url {
-            let mut response = match agent.get(inner_url).call() {
-                Ok(resp) => {
-                    debug!("Received response: {:?}", resp);
-                    resp
+    /// Selects the candidate from the given tags and verifies it against the
+    /// registry, if requested.
+    pub(crate) fn find_update(&self, tags: &[Tag], options: &CandidateOptions, arch: Option<&String>) -> Option<Tag> {
+        stats::image_resolved();
+        eol::warn_past_eol(&self.get_dockerimage_name(), self.get_tag());
+        let options = &self.image_options(options);
+        if skipped::is_enabled() {
+            let current = self.get_tag();
+            for tag in tags.iter().filter(|tag| *tag != current) {
+                if let Some(stage) = options.skip_reason(current, tag) {
+                    skipped::record(&self.get_full_name(), stage, tag.to_string());
                 }
-                Err(e) => {
-                    error!("Failed to send request to DockerHub: {e}");
-                    return Err(Box::new(Error::ImageNotFound(self.get_full_name())));
-                }
-            };
+            }
+        }
+        let found_tag = match &options.candidate_cmd {
+            Some(cmd) => self.select_with_command(cmd, tags, options)?,
+            None => self.get_tag().select_candidate(tags, options)?,
+        };
+        if options.verify {
+            if let Err(e) = self.verify_tag(http::client().as_ref(), &found_tag, arch) {
+                warn!(
+                    "Skipping update of `{}` to `{found_tag}`, since it could not be verified: {e}",
+                    self.get_full_tagged_name()
+                );
+                return None;
+            }
+            debug!("Verified tag: {found_tag}");
+        }
+        if let Some(max_risk) = options.max_risk {
+            let risk = Risk::assess(self, &found_tag, self.is_fresh(&found_tag, options.min_age, arch));
+            if risk.score > max_risk {
+                warn!(
+                    "Skipping update of `{}` to `{found_tag}`, its risk {risk} exceeds the maximum of {max_risk}.",
+                    self.get_full_tagged_name()
+                );
+                return None;
+            }
+        }
+        Some(found_tag)
+    }
 
-            let json: DockerHubResponse = match response.body_mut().read_json() {
-                Ok(json) => {
-                    debug!("Parsed JSON response successfully.");
-                    json
-                }
-                Err(e) => {
-                    error!("Failed to parse JSON response: {e}. Exiting tag retrieval.");
-                    if parsed_response.results.is_empty() {
-                        // If the error happens on the first iteration
-                        return Err(Box::new(Error::Parse(ParseError::InvalidDockerhubResponse)));
-                    }
-                    break;
-                }
-            };
+    /// Returns up to `count` candidates from the given tags, the best one
+    /// first, see `Tag::select_candidates`. They are neither verified nor
+    /// assessed.
+    pub(crate) fn find_candidates(&self, tags: &[Tag], options: &CandidateOptions, count: usize) -> Vec<Tag> {
+        self.get_tag().select_candidates(tags, &self.image_options(options), count)
+    }
+
+    /// Returns the options with the settings of the image applied.
+    fn image_options(&self, options: &CandidateOptions) -> CandidateOptions {
+        let mut options = options.clone();
+        // The configured LTS majors of the image are only known here.
+        if let Some(lts_majors) = channel::lts_majors_for(&self.get_full_name()) {
+            options.lts_majors = Some(lts_majors);
+        }
+        // A marker of the image overrides the strategy of the run.
+        if let Some(strategy) = options.image_strategies.get(normalize(&self.get_full_name())) {
+            options.strategy = strategy.clone();
+        }
+        options
+    }
+
+    /// Checks if the candidate was pushed within fewer than the given days, the
+    /// push date is looked up in the registry. Unknown dates are not fresh.
+    fn is_fresh(&self, candidate: &Tag, min_age: u64, arch: Option<&String>) -> bool {
+        let mut image = self.clone();
+        image.set_tag(candidate);
+        let pushed = image
+            .lookup_tag(http::client().as_ref(), arch.map(String::as_str))
+            .ok()
+            .flatten()
+            .and_then(|published| published.pushed)
+            .and_then(|pushed| pushed.get(..10)?.parse::<Date>().ok());
+        pushed.is_some_and(|pushed| Date::today().to_days().saturating_sub(pushed.to_days()) < min_age)
+    }
 
-            request_url.clone_from(&json.next);
-            let mut results = json.results.clone();
-            if results.is_empty() {
-                info!("Fetching tags done!");
-                break;
+    /// Lets the candidate command choose from the tags. The built-in candidate
+    /// is passed as suggestion, tags that are not in the list are rejected.
+    fn select_with_command(&self, cmd: &Path, tags: &[Tag], options: &CandidateOptions) -> Option<Tag> {
+        let current = self.get_tag();
+        let request = CandidateRequest {
+            image:      self.get_full_name(),
+            current:    current.to_string(),
+            strategy:   options.strategy.clone(),
+            fallbacks:  options.fallbacks.clone(),
+            suggestion: current.select_candidate(tags, options).map(|tag| tag.to_string()),
+            tags:       tags.iter().map(ToString::to_string).collect(),
+        };
+        let chosen = match hook::run_candidate_cmd(cmd, &request) {
+            Ok(chosen) => chosen?,
+            Err(e) => {
+                warn!("Skipping `{}`: {e}", self.get_full_tagged_name());
+                return None;
             }
+        };
+        let found_tag = tags.iter().find(|tag| tag.to_string() == chosen).cloned();
+        if found_tag.is_none() {
+            warn!(
+                "Candidate command chose `{chosen}` for `{}`, which is not a known tag.",
+                self.get_full_tagged_name()
+            );
+        }
+        found_tag
+    }
+
+    /// Fetches the tags of the image and selects the candidate for it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the tags can not be fetched.
+    pub(crate) fn resolve_update(&self, options: &CandidateOptions, fetch: &FetchOptions) -> Result<Option<Tag>, ResolveError> {
+        self.check_publisher(http::client().as_ref(), options)?;
+        // The query is narrowed down to the tags of the strategy of a marker.
+        let marked = options.image_strategies.get(normalize(&self.get_full_name())).map(|strategy| FetchOptions {
+            strategy: Some(strategy.clone()),
+            ..fetch.clone()
+        });
+        let fetch = marked.as_ref().unwrap_or(fetch);
+        let docker_image_tags = self.get_remote_tags(fetch).map_err(|e| ResolveError {
+            image:  self.to_string(),
+            reason: e.to_string(),
+        })?;
+        let Some(found_tag) = self.find_update(&docker_image_tags, options, fetch.arch.as_ref()) else {
+            return Ok(None);
+        };
+        debug!("Found tag: {found_tag:?}");
+        if self.get_tag().is_rebuild(&found_tag) {
+            info!("Update for `{}` is a rebuild only: {found_tag}", self.get_full_tagged_name());
+        }
+        Ok(Some(found_tag))
+    }
 
-            parsed_response.results.append(&mut results);
-            debug!("Parsed results length: {}", parsed_response.results.len());
+    /// Returns `true` if the page only contains versions lower than the
+    /// current tag, pages without any version are not considered.
+    fn is_older_page(&self, names: &[&str]) -> bool {
+        let current = self.get_tag();
+        let full_name = self.get_full_name();
+        let versions: Vec<Tag> = names
+            .iter()
+            .filter_map(|name| scheme::parse_tag(&full_name, name).ok())
+            .filter(|tag| tag.major.is_some())
+            .collect();
+        !versions.is_empty()
+            && versions
+                .iter()
+                .all(|tag| (tag.major, tag.minor, tag.patch) < (current.major, current.minor, current.patch))
+    }
 
-            let limit = limit.unwrap_or_else(|| u16::try_from(TAG_RESULT_LIMIT).expect("Tag result limit is <= 65535"));
-            info!("Fetched {}/{}.", parsed_response.results.len(), limit);
+    /// Checks that the image may be updated with `--official-only`, i.e. that
+    /// it is a Docker Official Image or of a Verified Publisher. Every image
+    /// passes without the flag.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the publisher is not trusted or
+    /// can not be looked up.
+    pub(crate) fn check_publisher(&self, client: &dyn HttpClient, options: &CandidateOptions) -> Result<(), ResolveError> {
+        if !options.official_only || self.get_tag().allowed_missing {
+            return Ok(());
+        }
+        let publisher = self.fetch_publisher(client).map_err(|e| ResolveError {
+            image:  self.to_string(),
+            reason: format!("Could not look up the publisher: {e}"),
+        })?;
+        if publisher.is_trusted() {
+            Ok(())
+        } else {
+            Err(ResolveError {
+                image:  self.to_string(),
+                reason: format!("The image is no Docker Official Image and not of a Verified Publisher ({publisher})."),
+            })
+        }
+    }
 
-            if parsed_response.results.len() >= usize::from(limit) {
-                info!("Fetching tags done!");
-                break;
+    /// Returns who publishes the image. The images of the `library` namespace
+    /// are the official ones and the MCR is Microsoft's, so only the other
+    /// Dockerhub images are looked up.
+    pub(crate) fn fetch_publisher(&self, client: &dyn HttpClient) -> Result<PublisherKind, Box<dyn std::error::Error>> {
+        match self {
+            Self::Mcr(_) => Ok(PublisherKind::VerifiedPublisher),
+            Self::Dockerhub(_) if self.get_group().is_none_or(|group| group.eq_ignore_ascii_case("library")) => Ok(PublisherKind::Official),
+            Self::Dockerhub(_) => {
+                let url = format!("https://hub.docker.com/v2/repositories/{}/", self.get_full_name());
+                let authorization = credentials::hub_authorization(client);
+                let body = client.get_with_headers(&url, &authorization_headers(authorization.as_deref()))?;
+                Ok(PublisherKind::of(&serde_json::from_str::<DockerHubRepository>(&body)?))
             }
         }
-        {
-            let names: Vec<&String> = parsed_response.results.iter().map(|r| &r.name).collect();
-            debug!("Found raw tags: {names:?}");
+    }
+
+    /// Looks up the tag of the image in the registry, without consulting any
+    /// cache. Returns `None` if the tag does not exist or is not published for
+    /// the given architecture.
+    pub(crate) fn lookup_tag(&self, client: &dyn HttpClient, arch: Option<&str>) -> Result<Option<PublishedTag>, Box<dyn std::error::Error>> {
+        let tag = self.get_tag().to_string();
+        let published = match self {
+            Self::Dockerhub(_) => {
+                let url = format!("https://hub.docker.com/v2/repositories/{}/tags/{tag}", self.get_full_name());
+                let authorization = credentials::hub_authorization(client);
+                match client.get_with_headers(&url, &authorization_headers(authorization.as_deref())) {
+                    Ok(body) => Some(PublishedTag::from_dockerhub(&serde_json::from_str::<DockerHubResult>(&body)?, arch)),
+                    Err(HttpError::Status(_, 404)) => None,
+                    Err(e) => return Err(Box::new(e)),
+                }
+            }
+            Self::Mcr(_) => {
+                // The tag may be on any page.
+                let all_pages = FetchOptions {
+                    limit: Some(u16::MAX),
+                    ..FetchOptions::default()
+                };
+                let entries = self.request_mcr(client, &all_pages)?;
+                let entries: Vec<&McrResponseEntry> = entries.iter().filter(|entry| entry.name == tag).collect();
+                (!entries.is_empty()).then(|| PublishedTag::from_mcr(&tag, &entries, arch))
+            }
+        };
+        Ok(published.filter(|published| published.supports(arch)))
+    }
+
+    /// Returns the key the tags of the image are cached with, e.g.
+    /// `hub.docker.com/library/node`,
+    /// `hub.docker.com/library/node?name=1.29` for a name filtered query or
+    /// `hub.docker.com/library/node#arch=arm64` for the tags of an
+    /// architecture.
+    fn get_cache_key(&self, fetch: &FetchOptions) -> String {
+        let registry = match self {
+            Self::Dockerhub(_) => "hub.docker.com",
+            Self::Mcr(_) => "mcr.microsoft.com",
+        };
+        let full_name = self.get_full_name();
+        let key = self.get_name_filter(fetch).map_or_else(
+            || format!("{registry}/{full_name}"),
+            |name_filter| format!("{registry}/{full_name}?name={name_filter}"),
+        );
+        // Tags filtered by architecture are cached separately.
+        let key = match fetch.arch.as_deref() {
+            Some(arch) => format!("{key}#arch={arch}"),
+            None => key,
+        };
+        // Tags parsed with a custom scheme are cached separately.
+        match scheme::scheme_for(&full_name) {
+            Some(scheme) => format!("{key}#scheme={}", scheme.as_str()),
+            None => key,
         }
+    }
 
-        Ok(parsed_response)
+    /// Returns the name filter for the query of the tags, see
+    /// `Tag::name_filter`. Only dockerhub supports filtering.
+    fn get_name_filter(&self, fetch: &FetchOptions) -> Option<String> {
+        match self {
+            Self::Dockerhub(_) => fetch.strategy.as_ref().and_then(|strategy| self.get_tag().name_filter(strategy)),
+            Self::Mcr(_) => None,
+        }
     }
 
-    fn request_mcr(&self) -> Result<Vec<McrResponseEntry>, Box<dyn std::error::Error>> {
-        // build agent with global timeout
-        let config = Agent::config_builder().timeout_global(Some(Duration::from_secs(10))).build();
-        let agent: Agent = config.into();
+    /// Handles the data fetching for dockerhub. The query is narrowed down by
+    /// the name filter of the strategy, if that returns too few tags all tags
+    /// are fetched instead.
+    fn request_dockerhub(&self, client: &dyn HttpClient, fetch: &FetchOptions) -> Result<DockerHubResponse, Box<dyn std::error::Error>> {
+        if let Some(name_filter) = self.get_name_filter(fetch) {
+            let response = self.request_dockerhub_pages(client, Some(&name_filter), fetch)?;
+            if response.results.len() >= NAME_FILTER_MIN_RESULTS {
+                return Ok(response);
+            }
+            info!("Only {} tags contain `{name_filter}`, fetching all tags instead.", response.results.len());
+        }
+        self.request_dockerhub_pages(client, None, fetch)
+    }
 
-        let url = self.get_query_url();
-        let mut response = match agent.get(&url).call() {
-            Ok(resp) => {
-                debug!("Received response: {:?}", resp);
-                resp
+    /// Fetches the pages of dockerhub, see `registries::fetch_pages`.
+    ///
+    /// Dockerhub returns the most recently pushed tags first, so in fast mode
+    /// the fetching stops after `FAST_STOP_PAGES` consecutive pages with
+    /// versions lower than the current tag only.
+    fn request_dockerhub_pages(
+        &self, client: &dyn HttpClient, name_filter: Option<&str>, fetch: &FetchOptions,
+    ) -> Result<DockerHubResponse, Box<dyn std::error::Error>> {
+        let mut older_pages = 0;
+        let authorization = credentials::hub_authorization(client);
+        let headers = authorization_headers(authorization.as_deref());
+        let results = registries::fetch_pages::<DockerHubResponse>(client, self.get_query_url(name_filter), &headers, fetch, |results| {
+            if !fetch.fast || self.get_tag().latest {
+                return false;
             }
-            Err(e) => {
-                error!("Failed to send request to DockerHub: {e}");
-                return Err(Box::new(Error::ImageNotFound(self.get_full_name())));
+            let names: Vec<&str> = results.iter().map(|result| result.name.as_str()).collect();
+            older_pages = if self.is_older_page(&names) { older_pages + 1 } else { 0 };
+            let only_older = older_pages >= FAST_STOP_PAGES;
+            if only_older {
+                info!("Only older versions are left!");
             }
-        };
+            only_older
+        })
+        .map_err(|e| -> Box<dyn std::error::Error> {
+            error!("Failed to fetch the tags from DockerHub: {e}");
+            match e {
+                PageError::Request(_) => Box::new(Error::ImageNotFound(self.get_full_name())),
+                PageError::Parse(_) => Box::new(Error::Parse(ParseError::InvalidDockerhubResponse)),
+            }
+        })?;
+        {
+            let names: Vec<&String> = results.iter().map(|r| &r.name).collect();
+            debug!("Found raw tags: {names:?}");
+        }
+        Ok(DockerHubResponse::with_results(results))
+    }
 
-        match response.body_mut().read_json::<Vec<McrResponseEntry>>() {
-            Ok(json) => Ok(json),
-            Err(e) => {
-                error!("Failed to parse JSON response: {e}");
-                Err(Box::new(Error::ImageNotFound(self.get_full_name())))
+    /// Requests the tags from the MCR, following the links to the next pages
+    /// until the limit of the fetch options is reached.
+    fn request_mcr(&self, client: &dyn HttpClient, fetch: &FetchOptions) -> Result<Vec<McrResponseEntry>, Box<dyn std::error::Error>> {
+        registries::fetch_pages::<McrPage>(client, self.get_query_url(None), &[], fetch, |_| false).map_err(|e| {
+            error!("Failed to fetch the tags from the MCR: {e}");
+            Error::ImageNotFound(self.get_full_name()).into()
+        })
+    }
+
+    /// Requests the tags from the registry of the image.
+    fn request_registry(&self, client: &dyn HttpClient, fetch: &FetchOptions) -> Result<RegistryResponse, Box<dyn std::error::Error>> {
+        Ok(match &self {
+            Self::Dockerhub(_) => registries::RegistryResponse::DockerHub(self.request_dockerhub(client, fetch)?),
+            Self::Mcr(_) => registries::RegistryResponse::MicrosoftContainerRegistry(self.request_mcr(client, fetch)?),
+        })
+    }
+
+    /// Returns the tags of the response, sorted and without duplicates.
+    fn parse_tags(&self, response: &RegistryResponse, fetch: &FetchOptions) -> Vec<Tag> {
+        let scheme = scheme::scheme_for(&self.get_full_name());
+        if skipped::is_enabled() {
+            for (stage, name) in response.skipped_tags(fetch.arch.as_deref(), scheme.as_ref()) {
+                skipped::record(&self.get_full_name(), stage, name);
             }
         }
+        let mut tags = response.get_tags(fetch.arch.as_deref(), scheme.as_ref());
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
+    /// Fetches the tags from the registry, without consulting any cache. The
+    /// result is sorted and does not contain duplicates.
+    fn fetch_remote_tags(&self, client: &dyn HttpClient, fetch: &FetchOptions) -> Result<Vec<Tag>, Box<dyn std::error::Error>> {
+        Ok(self.parse_tags(&self.request_registry(client, fetch)?, fetch))
     }
 
-    pub(crate) fn get_remote_tags(&self, limit: Option<u16>, arch: Option<&String>) -> Result<Vec<Tag>, Box<dyn std::error::Error>> {
+    /// Fetches the tags from the registry together with their metadata, e.g.
+    /// the architectures and push dates. The cache only holds the tags, so it
+    /// is not consulted.
+    pub(crate) fn fetch_published_tags(
+        &self, client: &dyn HttpClient, fetch: &FetchOptions,
+    ) -> Result<(Vec<Tag>, Vec<PublishedTag>), Box<dyn std::error::Error>> {
+        let response = self.request_registry(client, fetch)?;
+        Ok((self.parse_tags(&response, fetch), response.published_tags()))
+    }
+
+    /// Returns the tags of the image, using the client given by the global http
+    /// mode.
+    pub(crate) fn get_remote_tags(&self, fetch: &FetchOptions) -> Result<Vec<Tag>, Box<dyn std::error::Error>> {
+        self.get_remote_tags_with(http::client().as_ref(), fetch)
+    }
+
+    /// Returns the tags of the image from the application cache, the cache
+    /// files or the registry, in this order. The tags are sorted.
+    ///
+    /// Tags fetched in fast mode may be incomplete, so they are not cached.
+    /// Tags fetched with a name filter are cached separately.
+    pub(crate) fn get_remote_tags_with(&self, client: &dyn HttpClient, fetch: &FetchOptions) -> Result<Vec<Tag>, Box<dyn std::error::Error>> {
         if self.get_tag().clone().allowed_missing {
             // This happens if we reference a previous stage, so we just return
             return Ok(Vec::new());
         }
-        let full_name = &self.get_full_name();
-        let mut tags = Vec::<Tag>::new();
-        if full_name.is_empty() || full_name == "/" || (self.get_group().is_none() && self.get_name().is_empty()) {
-            return Ok(tags);
+        if self.get_full_name().is_empty() || self.get_full_name() == "/" || (self.get_group().is_none() && self.get_name().is_empty()) {
+            return Ok(Vec::new());
         }
-        let mut cache_file_name = full_name.replace('/', "-");
-        cache_file_name.push_str(".json");
-        extract_cache_from_file(full_name, &mut tags, &cache_file_name)?;
-
+        let full_name = &self.get_cache_key(fetch);
+        let use_cache_store = client.allows_file_cache();
         debug!("Searching for all tags for image: {full_name}");
-        let cache = TAGS_CACHE.read().expect("Tags cache can be read.");
-        if cache.contains_key(full_name) {
-            debug!("Found tags in application cache.");
-            tags.clone_from(cache.get(full_name).expect("Version exists in cache."));
-            Ok(tags)
-        } else {
-            drop(cache); // explicit drop, since the cache would still be locked for reading otherwise.
 
-            let registry_response: RegistryResponse = match &self {
-                Self::Dockerhub(image_metadata) => registries::RegistryResponse::DockerHub(self.request_dockerhub(limit)?),
-                Self::Mcr(image_metadata) => registries::RegistryResponse::MicrosoftContainerRegistry(self.request_mcr()?),
-            };
+        if fetch.fast {
+            // Possibly incomplete, so only previously cached tags are used, but nothing is
+            // cached.
+            if let Some(tags) = TAGS_CACHE
+                .get(full_name)
+                .or_else(|| use_cache_store.then(|| cache::read_tags(full_name)).flatten())
+            {
+                return Ok(tags);
+            }
+            return self.fetch_remote_tags(client, fetch);
+        }
 
-            let mut tags = registry_response.get_tags(arch.map(std::string::String::as_str));
-            tags.sort();
-            tags.dedup();
-            let tags = tags;
-
-            // Inserting found tags into cache
-            let mut cache = TAGS_CACHE.write().expect("Cache can be written.");
-            if cache.insert(full_name.clone(), tags.clone()).is_none() {
-                debug!(
-                    "Inserted tags into cache successfully. Cache contains {} tags for {full_name}",
-                    cache.get(full_name).expect("Version exists in cache.").len()
-                );
+        TAGS_CACHE.get_or_fetch(full_name, || {
+            if use_cache_store && let Some(tags) = cache::read_tags(full_name) {
+                return Ok(tags);
             }
-            drop(cache); // drop since we no longer need to keep the lock after the insertion
-            {
-                let tags_content = serde_json::to_string_pretty(&tags);
-                let _ = fs::write(cache_file_name, tags_content.expect("Tags can be turned into json string."));
+            let tags = self.fetch_remote_tags(client, fetch)?;
+            if use_cache_store {
+                cache::write_tags(full_name, &tags);
             }
             Ok(tags)
-        }
+        })
     }
 
     pub(crate) fn parse_from_line(line: &str) -> Result<(Self, Option<String>), Error> {
@@ -737,8 +1776,15 @@ impl FromStr for ContainerImage {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(if s.to_ascii_lowercase().starts_with(MCR_PREFIX) {
+        let lowercase = s.to_ascii_lowercase();
+        Ok(if lowercase.starts_with(MCR_PREFIX) {
             Self::Mcr(s.strip_prefix(MCR_PREFIX).expect("Prefix exists.").parse()?)
+        } else if let Some(host) = DOCKERHUB_HOSTS.iter().find(|host| lowercase.starts_with(*host)) {
+            let (host, image) = s.split_at(host.len());
+            Self::Dockerhub(ImageMetadata {
+                host: Some(host.to_owned()),
+                ..image.parse()?
+            })
         } else {
             Self::Dockerhub(s.parse()?)
         })
@@ -752,6 +1798,9 @@ impl Display for ContainerImage {
                 if self.is_mcr() {
                     write!(f, "mcr.microsoft.com/")?;
                 }
+                if let Some(host) = &metadata.host {
+                    write!(f, "{host}")?;
+                }
                 if metadata.group.is_some() {
                     write!(f, "{}/{}", metadata.group.clone().expect("Group was set"), metadata.name)?;
                 } else {
@@ -773,12 +1822,20 @@ mod tests {
     #![allow(clippy::unwrap_used)]
     use std::fs::{File, remove_file};
     use std::io::Write;
+    use std::path::PathBuf;
 
     use pretty_assertions::assert_eq;
     use rand::RngExt;
-
-    use crate::container_image::{ContainerImage, DockerInstruction, Dockerfile};
+    use regex::Regex;
+
+    use crate::container_image::{ContainerImage, DockerInstruction, Dockerfile, Error, ParseError, StageFilter, StructureWarning};
+    use crate::paths;
+    use crate::registries::FetchOptions;
+    use crate::registries::dockerhub::PublisherKind;
+    use crate::registries::http::{MockClient, replay_fixtures};
+    use crate::scanners::{FileKind, YamlScanners};
     use crate::tag::Tag;
+    use crate::utils::{DockerfileUpdate, ImageCandidates, Strategy};
 
     const CONTENT: &str = r#"# Comment 1
 # Comment 2
@@ -838,6 +1895,15 @@ RUN echo && \
     ( echo "meow" ) | piped -a "hello"
 "#;
 
+    /// Builds a page of a dockerhub tag listing.
+    fn hub_page(names: &[&str], next: Option<&str>) -> String {
+        let results: Vec<serde_json::Value> = names
+            .iter()
+            .map(|name| serde_json::json!({ "name": name, "images": [{ "architecture": "amd64" }] }))
+            .collect();
+        serde_json::json!({ "count": names.len(), "next": next, "previous": null, "results": results }).to_string()
+    }
+
     // rand will be a dev dependency
     fn random_string(length: usize) -> String {
         const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
@@ -879,12 +1945,259 @@ RUN echo && \
         assert_eq!(CONTENT, dockerfile.to_string());
     }
 
+    #[test]
+    fn shared_candidates() {
+        let first = Dockerfile::parse("FROM node:8.0-alpine AS build\nFROM python:3.12.3-alpine\n").unwrap();
+        let second = Dockerfile::parse("FROM node:8.0-alpine\n").unwrap();
+        let candidates = ImageCandidates::from([
+            ("node:8.0-alpine".to_owned(), "8.1-alpine".parse::<Tag>().unwrap()),
+            ("python:3.12.3-alpine".to_owned(), "3.12.4-alpine".parse::<Tag>().unwrap()),
+        ]);
+
+        assert_eq!(
+            first.generate_image_updates(&candidates, &[]).apply().to_string(),
+            "FROM node:8.1-alpine AS build\nFROM python:3.12.4-alpine\n"
+        );
+        assert_eq!(second.generate_image_updates(&candidates, &[]).apply().to_string(), "FROM node:8.1-alpine\n");
+
+        let ignored = vec!["python:3.12.3-alpine".parse::<ContainerImage>().unwrap()];
+        assert_eq!(
+            first.generate_image_updates(&candidates, &ignored).apply().to_string(),
+            "FROM node:8.1-alpine AS build\nFROM python:3.12.3-alpine\n"
+        );
+    }
+
+    #[test]
+    fn ignore_forms() {
+        let ignored: Vec<ContainerImage> = ["node:18", "node:18.20-alpine", "library/python", "mcr.microsoft.com/dotnet/aspnet:8"]
+            .iter()
+            .map(|image| image.parse().unwrap())
+            .collect();
+        let find = |image: &str| image.parse::<ContainerImage>().unwrap().find_ignored(&ignored).map(ToString::to_string);
+        assert_eq!(find("node:18.20-alpine").as_deref(), Some("node:18.20-alpine"));
+        assert_eq!(find("node:18.19.1").as_deref(), Some("node:18"));
+        assert_eq!(find("node:20.1.0"), None);
+        assert_eq!(find("python:3.12.3-slim").as_deref(), Some("library/python"));
+        assert_eq!(
+            find("mcr.microsoft.com/dotnet/aspnet:8.0.1").as_deref(),
+            Some("mcr.microsoft.com/dotnet/aspnet:8")
+        );
+        assert_eq!(find("dotnet/aspnet:8.0.1"), None);
+        assert_eq!(find("redis:7.4.0"), None);
+    }
+
+    #[test]
+    fn stage_selection() {
+        let content = "FROM node:20.11 AS builder\nRUN npm ci\nFROM scratch AS assets\nFROM node:20.11-alpine as Runtime\n";
+        let mut dockerfile = Dockerfile::parse(content).unwrap();
+        assert!(dockerfile.retain_stage(&StageFilter::Named("runtime".to_owned())));
+        let images: Vec<String> = dockerfile.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["node:20.11-alpine"]);
+        assert_eq!(dockerfile.to_string(), content);
+
+        let mut dockerfile = Dockerfile::parse(content).unwrap();
+        assert!(dockerfile.retain_stage(&StageFilter::Named("assets".to_owned())));
+        assert!(dockerfile.get_base_images().is_empty());
+        assert!(!Dockerfile::parse(content).unwrap().retain_stage(&StageFilter::Named("test".to_owned())));
+
+        let mut dockerfile = Dockerfile::parse(content).unwrap();
+        assert!(dockerfile.retain_stage(&StageFilter::First));
+        let images: Vec<String> = dockerfile.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["node:20.11"]);
+        let mut dockerfile = Dockerfile::parse(content).unwrap();
+        assert!(dockerfile.retain_stage(&StageFilter::Final));
+        let images: Vec<String> = dockerfile.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["node:20.11-alpine"]);
+        assert!(
+            !Dockerfile::parse("services:\n  web:\n    image: nginx:1.27.0\n")
+                .unwrap()
+                .retain_stage(&StageFilter::Final)
+        );
+    }
+
+    #[test]
+    fn stages() {
+        let content = "ARG BASE=node\nFROM node:20.11 AS builder\nRUN npm ci\nFROM scratch AS assets\nCOPY --from=builder /app /app\nFROM \
+                       node:20.11-alpine\n";
+        let dockerfile = Dockerfile::parse(content).unwrap();
+        let stages = dockerfile.stages();
+        let summary: Vec<_> = stages
+            .iter()
+            .map(|stage| {
+                (
+                    stage.index,
+                    stage.name.as_deref(),
+                    stage.from_index,
+                    stage.image.map(ToString::to_string),
+                    stage.instructions.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (0, Some("builder"), 1, Some("node:20.11".to_owned()), 2),
+                (1, Some("assets"), 3, None, 2),
+                (2, None, 5, Some("node:20.11-alpine".to_owned()), 1)
+            ]
+        );
+        assert_eq!(stages.iter().map(ToString::to_string).collect::<Vec<_>>(), ["builder", "assets", "2"]);
+        assert!(Dockerfile::parse("services:\n  web:\n    image: nginx:1.27.0\n").unwrap().stages().is_empty());
+    }
+
+    #[test]
+    fn structure_warnings() {
+        let content = "FROM node:20.11 AS build\nRUN npm ci\nFROM base\nCOPY --from=0 /app /app\nCOPY --from=2 /app /app\nFROM \
+                       node:20.11-alpine AS BUILD\nFROM node:20.11-slim AS base\n";
+        let dockerfile = Dockerfile::parse(content).unwrap();
+        assert_eq!(
+            dockerfile.get_warnings().into_iter().map(|(_, warning)| warning.clone()).collect::<Vec<_>>(),
+            [
+                StructureWarning::ForwardReference {
+                    line:    3,
+                    name:    "base".to_owned(),
+                    defined: 7,
+                },
+                StructureWarning::UnknownCopySource { line: 5, index: 2 },
+                StructureWarning::DuplicateName {
+                    line:  6,
+                    name:  "BUILD".to_owned(),
+                    first: 1,
+                },
+            ]
+        );
+        assert_eq!(dockerfile.get_warnings()[2].1.to_string(), "The stage `BUILD` is already defined in line 1.");
+        assert!(Dockerfile::parse("services:\n  web:\n    image: base\n").unwrap().get_warnings().is_empty());
+    }
+
+    #[test]
+    fn image_locations() {
+        let dockerfile = Dockerfile::parse("# syntax=docker/dockerfile:1\nFROM --platform=$BUILDPLATFORM node:22.1.0 AS build\n  from redis:7.4.0\n").unwrap();
+        let locations: Vec<(usize, usize, String)> = dockerfile
+            .get_base_image_lines()
+            .into_iter()
+            .map(|location| (location.line, location.column, location.image.to_string()))
+            .collect();
+        assert_eq!(locations, [(2, 32, "node:22.1.0".to_owned()), (3, 8, "redis:7.4.0".to_owned())]);
+        assert_eq!(
+            Dockerfile::parse("FROM node:22.1.0\nFROM :\n"),
+            Err(Error::Parse(ParseError::InvalidLine(2, "Image name is empty.".to_owned())))
+        );
+    }
+
+    #[test]
+    fn formatting_round_trip() {
+        let content = "from node:20.11   as build\nFROM  --platform=$BUILDPLATFORM  python:3.12.3-slim AS  Runtime  \n  From alpine:3.20\t\n";
+        let dockerfile = Dockerfile::parse(content).unwrap();
+        assert_eq!(dockerfile.to_string(), content);
+        let stages: Vec<Option<String>> = dockerfile.get_instructions().iter().map(DockerInstruction::get_stage_name).collect();
+        assert_eq!(stages, [Some("build".to_owned()), Some("Runtime".to_owned()), None]);
+
+        let candidates = ImageCandidates::from([
+            ("node:20.11".to_owned(), "22.1".parse::<Tag>().unwrap()),
+            ("python:3.12.3-slim".to_owned(), "3.12.4-slim".parse::<Tag>().unwrap()),
+            ("alpine:3.20".to_owned(), "3.21".parse::<Tag>().unwrap()),
+        ]);
+        assert_eq!(
+            dockerfile.generate_image_updates(&candidates, &[]).apply().to_string(),
+            "from node:22.1   as build\nFROM  --platform=$BUILDPLATFORM  python:3.12.4-slim AS  Runtime  \n  From alpine:3.21\t\n"
+        );
+    }
+
+    #[test]
+    fn changed_lines_only() {
+        let content = "FROM node:20.11 AS build\r\nRUN npm ci\r\n\r\nFROM python:3.12.3-slim\r\nFROM alpine:3.20";
+        let dockerfile = Dockerfile::parse(content).unwrap();
+        assert_eq!(dockerfile.to_string(), content);
+
+        let candidates = ImageCandidates::from([
+            ("python:3.12.3-slim".to_owned(), "3.12.4-slim".parse::<Tag>().unwrap()),
+            ("alpine:3.20".to_owned(), "3.21".parse::<Tag>().unwrap()),
+        ]);
+        assert_eq!(
+            dockerfile.generate_image_updates(&candidates, &[]).apply().to_string(),
+            "FROM node:20.11 AS build\r\nRUN npm ci\r\n\r\nFROM python:3.12.4-slim\r\nFROM alpine:3.21"
+        );
+    }
+
+    #[test]
+    fn argument_defaults() {
+        let dockerfile =
+            Dockerfile::parse("ARG BASE_IMAGE\nFROM ${BASE_IMAGE:-node:20.11} AS build\nfrom ${RUNTIME-mcr.microsoft.com/dotnet/aspnet:9.0.0}\n").unwrap();
+        let images: Vec<String> = dockerfile.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["node:20.11", "mcr.microsoft.com/dotnet/aspnet:9.0.0"]);
+        assert_eq!(dockerfile.get_instructions()[1].get_stage_name().as_deref(), Some("build"));
+
+        let candidates = ImageCandidates::from([
+            ("node:20.11".to_owned(), "22.1".parse::<Tag>().unwrap()),
+            ("mcr.microsoft.com/dotnet/aspnet:9.0.0".to_owned(), "9.0.1".parse::<Tag>().unwrap()),
+        ]);
+        assert_eq!(
+            dockerfile.generate_image_updates(&candidates, &[]).apply().to_string(),
+            "ARG BASE_IMAGE\nFROM ${BASE_IMAGE:-node:22.1} AS build\nfrom ${RUNTIME-mcr.microsoft.com/dotnet/aspnet:9.0.1}\n"
+        );
+    }
+
+    #[test]
+    fn pseudo_images() {
+        let content = "ARG BASE\nFROM scratch\nFROM --platform=$BUILDPLATFORM node:20.11 AS build\nFROM ${BASE}\nFROM $BASE AS runtime\nFROM node:${NODE_VERSION}\nFROM build\n";
+        let dockerfile = Dockerfile::parse(content).unwrap();
+        let images: Vec<String> = dockerfile.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["node:20.11"]);
+        assert_eq!(dockerfile.to_string(), content);
+
+        let candidates = ImageCandidates::from([("node:20.11".to_owned(), "22.1".parse::<Tag>().unwrap())]);
+        assert_eq!(
+            dockerfile.generate_image_updates(&candidates, &[]).apply().to_string(),
+            content.replace("node:20.11", "node:22.1")
+        );
+    }
+
+    #[test]
+    fn includes() {
+        let folder = paths::temp_path(&random_string(15));
+        std::fs::create_dir_all(folder.join("common")).unwrap();
+        std::fs::write(folder.join("Dockerfile"), "#include <common/base.dockerfile>\nFROM python:3.12.3-alpine\n").unwrap();
+        std::fs::write(folder.join("common/base.dockerfile"), "# shared build stage\nFROM node:8.0-alpine AS build\n").unwrap();
+
+        let dockerfile = Dockerfile::read_with_includes(&folder.join("Dockerfile"), YamlScanners::ALL).unwrap();
+        let locations: Vec<(PathBuf, usize, String)> = dockerfile
+            .get_base_image_lines()
+            .into_iter()
+            .map(|location| (location.file.unwrap().clone(), location.line, location.image.to_string()))
+            .collect();
+        assert_eq!(
+            locations,
+            [
+                (folder.join("common/base.dockerfile"), 2, "node:8.0-alpine".to_owned()),
+                (folder.join("Dockerfile"), 2, "python:3.12.3-alpine".to_owned()),
+            ]
+        );
+
+        let candidates = ImageCandidates::from([
+            ("node:8.0-alpine".to_owned(), "8.1-alpine".parse::<Tag>().unwrap()),
+            ("python:3.12.3-alpine".to_owned(), "3.12.4-alpine".parse::<Tag>().unwrap()),
+        ]);
+        dockerfile.generate_image_updates(&candidates, &[]).apply().write().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(folder.join("Dockerfile")).unwrap(),
+            "#include <common/base.dockerfile>\nFROM python:3.12.4-alpine\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(folder.join("common/base.dockerfile")).unwrap(),
+            "# shared build stage\nFROM node:8.1-alpine AS build\n"
+        );
+
+        // Without resolving, the directive is a plain comment.
+        assert_eq!(Dockerfile::read(&folder.join("Dockerfile")).unwrap().get_base_images().len(), 1);
+        std::fs::write(folder.join("common/base.dockerfile"), "#include \"base.dockerfile\"\n").unwrap();
+        assert!(Dockerfile::read_with_includes(&folder.join("Dockerfile"), YamlScanners::ALL).is_err());
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
     #[test]
     fn file_handling() {
-        #[cfg(target_os = "linux")]
-        let filename = format!("/tmp/{}", random_string(15));
-        #[cfg(target_os = "windows")]
-        let filename = format!("C:\\Windows\\Temp\\{}", random_string(15));
+        let filename = paths::temp_path(&random_string(15)).display().to_string();
 
         let mut file = File::create(&filename).expect("File can be created.");
         assert!(file.write_all(CONTENT.as_bytes()).is_ok());
@@ -898,8 +2211,33 @@ RUN echo && \
         assert!(remove_file(&filename).is_ok());
     }
 
+    #[test]
+    fn dockerhub_hosts() {
+        let image: ContainerImage = "docker.io/library/node:20.11-alpine".parse().unwrap();
+        assert!(image.is_dockerhub());
+        assert_eq!(image.get_host(), "docker.io/");
+        assert_eq!(image.get_full_name(), "library/node");
+        assert_eq!(image.get_tag(), "20.11-alpine".parse::<Tag>().unwrap().as_ref());
+        assert_eq!(image.to_string(), "docker.io/library/node:20.11-alpine");
+
+        let image: ContainerImage = "Index.Docker.io/node:20.11".parse().unwrap();
+        assert_eq!(image.get_full_name(), "library/node");
+        assert_eq!(image.get_dockerimage_name(), "node");
+        assert_eq!(image.to_string(), "Index.Docker.io/node:20.11");
+
+        let image: ContainerImage = "docker.io/bitnami/redis:7.2.4".parse().unwrap();
+        assert_eq!(image.get_full_name(), "bitnami/redis");
+        let dockerfile = Dockerfile::parse("FROM docker.io/library/node:20.11 AS build\n").unwrap();
+        let candidates = ImageCandidates::from([("docker.io/library/node:20.11".to_owned(), "22.1".parse::<Tag>().unwrap())]);
+        assert_eq!(
+            dockerfile.generate_image_updates(&candidates, &[]).apply().to_string(),
+            "FROM docker.io/library/node:22.1 AS build\n"
+        );
+    }
+
     #[test]
     fn parse_registry_image_dockerhub() {
+        replay_fixtures();
         // parsing library dockerhub image
         let image = "node:8.0.0-alpine3.10";
         let registry_image: ContainerImage = image.parse().unwrap();
@@ -908,7 +2246,7 @@ RUN echo && \
         assert!(registry_image.get_group().is_none());
         assert_eq!(registry_image.get_tag(), "8.0.0-alpine3.10".parse::<Tag>().unwrap().as_ref());
         assert_eq!(registry_image.get_name(), "node");
-        let tags = registry_image.get_remote_tags(None, None);
+        let tags = registry_image.get_remote_tags(&FetchOptions::default());
         assert!(tags.is_ok());
         assert!(!tags.unwrap().is_empty());
 
@@ -928,13 +2266,17 @@ RUN echo && \
         assert_eq!(registry_image.get_group(), Some(&String::from("guacamole")));
         assert_eq!(registry_image.get_name(), "guacamole");
         assert_eq!(image, &registry_image.to_string());
-        let tags = registry_image.get_remote_tags(None, Some(&String::from("amd64")));
+        let tags = registry_image.get_remote_tags(&FetchOptions {
+            arch: Some(String::from("amd64")),
+            ..FetchOptions::default()
+        });
         assert!(tags.is_ok());
         assert!(!tags.unwrap().is_empty());
     }
 
     #[test]
     fn parse_registry_image_mcr() {
+        replay_fixtures();
         let image = "mcr.microsoft.com/dotnet/aspnet:9.0.0";
         let registry_image: ContainerImage = image.parse().unwrap();
         assert!(!registry_image.is_latest());
@@ -944,8 +2286,368 @@ RUN echo && \
         assert_eq!(registry_image.get_tag(), "9.0.0".parse::<Tag>().unwrap().as_ref());
         assert_eq!(registry_image.get_name(), "aspnet");
         assert_eq!(image, &registry_image.to_string());
-        let tags = registry_image.get_remote_tags(None, None);
+        let tags = registry_image.get_remote_tags(&FetchOptions::default());
         assert!(tags.is_ok());
         assert!(!tags.unwrap().is_empty());
     }
+
+    #[test]
+    fn dockerhub_pagination() {
+        let first = "https://hub.docker.com/v2/repositories/mock/paged/tags?page_size=100";
+        let second = "https://hub.docker.com/v2/repositories/mock/paged/tags?page=2&page_size=100";
+        let client = MockClient::default()
+            .with_response(first, &hub_page(&["1.2.0", "1.1.0", "latest"], Some(second)))
+            .with_response(second, &hub_page(&["1.1.0", "1.0.0"], None));
+        let image: ContainerImage = "mock/paged:1.0.0".parse().unwrap();
+
+        let tags = image.fetch_remote_tags(&client, &FetchOptions::default()).unwrap();
+        assert_eq!(client.requests(), [first, second]);
+        let tags: Vec<String> = tags.iter().map(ToString::to_string).collect();
+        // Mutable tags are kept, the candidate selection skips them.
+        assert_eq!(tags, ["latest", "1.0.0", "1.1.0", "1.2.0"]);
+
+        // The limit stops the pagination early.
+        let client = MockClient::default()
+            .with_response(first, &hub_page(&["1.2.0", "1.1.0"], Some(second)))
+            .with_response(second, &hub_page(&["1.0.0"], None));
+        let tags = image
+            .fetch_remote_tags(
+                &client,
+                &FetchOptions {
+                    limit: Some(2),
+                    ..FetchOptions::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(client.requests(), [first]);
+        assert_eq!(tags.len(), 2);
+    }
+
+    #[test]
+    fn digest_pins() {
+        let tag = r#"{"name": "22.2.0", "digest": "sha256:0f", "images": [{"architecture": "amd64", "digest": "sha256:a86"}, {"architecture": "arm64", "digest": "sha256:a64"}]}"#;
+        let client = MockClient::default().with_response("https://hub.docker.com/v2/repositories/library/node/tags/22.2.0", tag);
+        let original = Dockerfile::parse("FROM --platform=$BUILDPLATFORM node:22.1.0 AS build\nFROM redis:7.4.0\nFROM mock/gone:1.0\n").unwrap();
+        let mut updated = Dockerfile::parse("FROM --platform=$BUILDPLATFORM node:22.2.0 AS build\nFROM redis:7.4.0\nFROM mock/gone:1.1\n").unwrap();
+        updated.pin_digests(&client, &original, "arm64");
+        // Unchanged images are not looked up, missing digests keep the tag.
+        assert_eq!(
+            client.requests(),
+            [
+                "https://hub.docker.com/v2/repositories/library/node/tags/22.2.0",
+                "https://hub.docker.com/v2/repositories/mock/gone/tags/1.1"
+            ]
+        );
+        assert_eq!(
+            updated.to_string(),
+            "FROM --platform=$BUILDPLATFORM node:22.2.0@sha256:a64 AS build\nFROM redis:7.4.0\nFROM mock/gone:1.1\n"
+        );
+        // A valid `FROM` line has no inline comment, only flags, the image and the
+        // stage.
+        let line = updated.to_string().lines().next().unwrap().to_owned();
+        let valid_from = Regex::new(r"^FROM (?:--\S+ )*[^\s#]+(?: AS [^\s#]+)?$").unwrap();
+        assert!(valid_from.is_match(&line), "{line}");
+        let pin = crate::digest::DigestPin::parse(&line).unwrap();
+        assert_eq!((pin.image.as_str(), pin.digest.as_str(), pin.tag.as_str()), ("node", "sha256:a64", "22.2.0"));
+    }
+
+    #[test]
+    fn dockerhub_fast_pagination() {
+        let pages: Vec<String> = (1..=4)
+            .map(|page| format!("https://hub.docker.com/v2/repositories/mock/fast/tags?page={page}&page_size=100"))
+            .collect();
+        let first = "https://hub.docker.com/v2/repositories/mock/fast/tags?page_size=100";
+        let client = MockClient::default()
+            .with_response(first, &hub_page(&["2.1.0", "latest"], Some(&pages[1])))
+            .with_response(&pages[1], &hub_page(&["1.9.1", "1.8.4"], Some(&pages[2])))
+            .with_response(&pages[2], &hub_page(&["latest-alpine"], Some(&pages[3])))
+            .with_response(&pages[3], &hub_page(&["1.8.3"], None));
+        let image: ContainerImage = "mock/fast:2.0.0".parse().unwrap();
+
+        // Pages without versions do not count as older pages.
+        let tags = image
+            .fetch_remote_tags(
+                &client,
+                &FetchOptions {
+                    fast: true,
+                    ..FetchOptions::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(client.requests(), [first, &pages[1], &pages[2], &pages[3]]);
+        assert_eq!(tags.len(), 6);
+
+        let client = MockClient::default()
+            .with_response(first, &hub_page(&["2.1.0", "1.9.2"], Some(&pages[1])))
+            .with_response(&pages[1], &hub_page(&["1.9.1", "1.8.4"], Some(&pages[2])))
+            .with_response(&pages[2], &hub_page(&["1.8.3"], Some(&pages[3])))
+            .with_response(&pages[3], &hub_page(&["1.8.2"], None));
+        let tags = image
+            .fetch_remote_tags(
+                &client,
+                &FetchOptions {
+                    fast: true,
+                    ..FetchOptions::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(client.requests(), [first, &pages[1], &pages[2]]);
+        assert_eq!(tags.len(), 5);
+    }
+
+    #[test]
+    fn dockerhub_name_filter() {
+        let filtered = "https://hub.docker.com/v2/repositories/mock/filtered/tags?page_size=100&name=1.29";
+        let all = "https://hub.docker.com/v2/repositories/mock/filtered/tags?page_size=100";
+        let image: ContainerImage = "mock/filtered:1.29.1".parse().unwrap();
+        let fetch = FetchOptions {
+            strategy: Some(Strategy::LatestPatch),
+            ..FetchOptions::default()
+        };
+
+        let client = MockClient::default().with_response(filtered, &hub_page(&["1.29.2", "1.29.1"], None));
+        let tags = image.fetch_remote_tags(&client, &fetch).unwrap();
+        assert_eq!(client.requests(), [filtered]);
+        assert_eq!(tags.len(), 2);
+
+        // Too few results fall back to fetching all tags.
+        let client = MockClient::default()
+            .with_response(filtered, &hub_page(&[], None))
+            .with_response(all, &hub_page(&["1.30.0", "1.29.2", "1.29.1"], None));
+        let tags = image.fetch_remote_tags(&client, &fetch).unwrap();
+        assert_eq!(client.requests(), [filtered, all]);
+        assert_eq!(tags.len(), 3);
+    }
+
+    #[test]
+    fn dockerhub_errors() {
+        let first = "https://hub.docker.com/v2/repositories/mock/broken/tags?page_size=100";
+        let second = "https://hub.docker.com/v2/repositories/mock/broken/tags?page=2&page_size=100";
+        let image: ContainerImage = "mock/broken:1.0.0".parse().unwrap();
+
+        let error = image.fetch_remote_tags(&MockClient::default(), &FetchOptions::default()).unwrap_err();
+        assert_eq!(error.downcast_ref::<Error>(), Some(&Error::ImageNotFound("mock/broken".to_owned())));
+
+        let client = MockClient::default().with_response(first, "<html>rate limited</html>");
+        let error = image.fetch_remote_tags(&client, &FetchOptions::default()).unwrap_err();
+        assert_eq!(error.downcast_ref::<Error>(), Some(&Error::Parse(ParseError::InvalidDockerhubResponse)));
+
+        // A broken page after the first one keeps the tags fetched so far.
+        let client = MockClient::default()
+            .with_response(first, &hub_page(&["1.1.0"], Some(second)))
+            .with_response(second, "{");
+        let tags = image.fetch_remote_tags(&client, &FetchOptions::default()).unwrap();
+        assert_eq!(tags, vec!["1.1.0".parse::<Tag>().unwrap()]);
+    }
+
+    #[test]
+    fn mcr_response() {
+        let url = "https://mcr.microsoft.com/api/v1/catalog/mock/runtime/tags?reg=mar";
+        let body = r#"[
+            {"name": "8.0.1", "architecture": "amd64"},
+            {"name": "8.0.1", "architecture": "arm64"},
+            {"name": "8.0.2", "architecture": "arm64"},
+            {"name": "latest"}
+        ]"#;
+        let client = MockClient::default().with_response(url, body);
+        let image: ContainerImage = "mcr.microsoft.com/mock/runtime:8.0.0".parse().unwrap();
+
+        let tags = image.fetch_remote_tags(&client, &FetchOptions::default()).unwrap();
+        assert_eq!(tags, ["latest", "8.0.1", "8.0.2"].map(|tag| tag.parse::<Tag>().unwrap()));
+        let tags = image
+            .fetch_remote_tags(
+                &client,
+                &FetchOptions {
+                    arch: Some("amd64".to_owned()),
+                    ..FetchOptions::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(tags, vec!["8.0.1".parse::<Tag>().unwrap()]);
+
+        let error = image
+            .fetch_remote_tags(&MockClient::default().with_response(url, "{}"), &FetchOptions::default())
+            .unwrap_err();
+        assert_eq!(error.downcast_ref::<Error>(), Some(&Error::ImageNotFound("mock/runtime".to_owned())));
+    }
+
+    #[test]
+    fn mcr_pagination() {
+        let first = "https://mcr.microsoft.com/api/v1/catalog/mock/paged/tags?reg=mar";
+        let second = "https://mcr.microsoft.com/api/v1/catalog/mock/paged/tags?reg=mar&continuationToken=2";
+        let third = "https://mcr.microsoft.com/api/v1/catalog/mock/paged/tags?reg=mar&continuationToken=3";
+        let client = MockClient::default()
+            .with_response(
+                first,
+                r#"{"value": [{"name": "8.0.1"}, {"name": "8.0.2"}], "nextLink": "/api/v1/catalog/mock/paged/tags?reg=mar&continuationToken=2"}"#,
+            )
+            .with_response(second, &format!(r#"{{"value": [{{"name": "9.0.0"}}], "nextLink": "{third}"}}"#))
+            .with_response(third, r#"{"value": [{"name": "9.0.1"}]}"#);
+        let image: ContainerImage = "mcr.microsoft.com/mock/paged:8.0.1".parse().unwrap();
+
+        let tags = image.fetch_remote_tags(&client, &FetchOptions::default()).unwrap();
+        assert_eq!(client.requests(), [first, second, third]);
+        assert_eq!(tags, ["8.0.1", "8.0.2", "9.0.0", "9.0.1"].map(|tag| tag.parse::<Tag>().unwrap()));
+
+        // The limit stops the pagination early, like for Docker Hub.
+        let client = MockClient::default()
+            .with_response(
+                first,
+                &format!(r#"{{"value": [{{"name": "8.0.1"}}, {{"name": "8.0.2"}}], "nextLink": "{second}"}}"#),
+            )
+            .with_response(second, "{");
+        let fetch = FetchOptions {
+            limit: Some(2),
+            ..FetchOptions::default()
+        };
+        assert_eq!(image.fetch_remote_tags(&client, &fetch).unwrap().len(), 2);
+        assert_eq!(client.requests(), [first]);
+        // A broken page after the first one keeps the tags fetched so far.
+        assert_eq!(image.fetch_remote_tags(&client, &FetchOptions::default()).unwrap().len(), 2);
+
+        // The limit counts tags, not their entries per architecture.
+        let client = MockClient::default()
+            .with_response(
+                first,
+                &format!(
+                    r#"{{"value": [{{"name": "8.0.1", "architecture": "amd64"}}, {{"name": "8.0.1", "architecture": "arm64"}}], "nextLink": "{second}"}}"#
+                ),
+            )
+            .with_response(second, r#"{"value": [{"name": "8.0.2", "architecture": "amd64"}]}"#);
+        assert_eq!(image.fetch_remote_tags(&client, &fetch).unwrap().len(), 2);
+        assert_eq!(client.requests(), [first, second]);
+    }
+
+    #[test]
+    fn bazel_digest_refresh() {
+        let content = "oci.pull(\n    name = \"postgres\",\n    digest = \"sha256:old\",\n    image = \"mock/postgres\",\n    tag = \"16.3\",\n)\n";
+        let client = MockClient::default().with_response(
+            "https://hub.docker.com/v2/repositories/mock/postgres/tags/16.4",
+            r#"{"name": "16.4", "digest": "sha256:new", "images": []}"#,
+        );
+        let module = Dockerfile::parse_as(content, FileKind::Bazel).unwrap();
+        let update = DockerfileUpdate {
+            dockerfile: module,
+            updates:    vec![(0, "16.4".parse::<Tag>().unwrap())],
+        };
+        let mut updated = update.apply();
+        updated.refresh_digests_with(&client, None);
+        assert_eq!(
+            updated.to_string(),
+            "oci.pull(\n    name = \"postgres\",\n    digest = \"sha256:new\",\n    image = \"mock/postgres\",\n    tag = \"16.4\",\n)\n"
+        );
+    }
+
+    #[test]
+    fn kustomize_digest_refresh() {
+        let content = "images:\n  - name: app\n    newName: mock/redis\n    newTag: 7.4.0\n    digest: sha256:old\n";
+        let client = MockClient::default().with_response(
+            "https://hub.docker.com/v2/repositories/mock/redis/tags/7.4.2",
+            r#"{"name": "7.4.2", "digest": "sha256:new", "images": []}"#,
+        );
+        let kustomization = Dockerfile::parse_as(content, FileKind::Kustomize).unwrap();
+        let update = DockerfileUpdate {
+            dockerfile: kustomization,
+            updates:    vec![(0, "7.4.2".parse::<Tag>().unwrap())],
+        };
+        let mut updated = update.apply();
+        updated.refresh_digests_with(&client, None);
+        assert_eq!(
+            updated.to_string(),
+            "images:\n  - name: app\n    newName: mock/redis\n    newTag: 7.4.2\n    digest: sha256:new\n"
+        );
+        assert_eq!(client.requests(), ["https://hub.docker.com/v2/repositories/mock/redis/tags/7.4.2"]);
+    }
+
+    #[test]
+    fn tag_lookup() {
+        let url = "https://hub.docker.com/v2/repositories/mock/existing/tags/1.0.0";
+        let body = r#"{
+            "name": "1.0.0",
+            "digest": "sha256:index",
+            "images": [
+                {"architecture": "amd64", "digest": "sha256:amd64"},
+                {"architecture": "arm64", "digest": "sha256:arm64"}
+            ]
+        }"#;
+        let client = MockClient::default().with_response(url, body);
+        let image: ContainerImage = "mock/existing:1.0.0".parse().unwrap();
+
+        let published = image.lookup_tag(&client, None).unwrap().unwrap();
+        assert_eq!(published.digest.as_deref(), Some("sha256:index"));
+        assert_eq!(published.architectures, ["amd64", "arm64"]);
+        let published = image.lookup_tag(&client, Some("arm64")).unwrap().unwrap();
+        assert_eq!(published.digest.as_deref(), Some("sha256:arm64"));
+        assert_eq!(image.lookup_tag(&client, Some("s390x")).unwrap(), None);
+
+        let image: ContainerImage = "mock/existing:2.0.0".parse().unwrap();
+        assert_eq!(image.lookup_tag(&client, None).unwrap(), None);
+
+        let url = "https://mcr.microsoft.com/api/v1/catalog/mock/runtime/tags?reg=mar";
+        let body = r#"[
+            {"name": "8.0.1", "architecture": "amd64", "digest": "sha256:amd64"},
+            {"name": "8.0.1", "architecture": "arm64", "digest": "sha256:arm64"}
+        ]"#;
+        let client = MockClient::default().with_response(url, body);
+        let image: ContainerImage = "mcr.microsoft.com/mock/runtime:8.0.1".parse().unwrap();
+        let published = image.lookup_tag(&client, Some("amd64")).unwrap().unwrap();
+        assert_eq!(published.digest.as_deref(), Some("sha256:amd64"));
+        assert_eq!(image.lookup_tag(&client, None).unwrap().unwrap().digest, None);
+        let image: ContainerImage = "mcr.microsoft.com/mock/runtime:8.0.2".parse().unwrap();
+        assert_eq!(image.lookup_tag(&client, None).unwrap(), None);
+    }
+
+    #[test]
+    fn tag_verification() {
+        let token_url = "https://auth.docker.io/token?service=registry.docker.io&scope=repository:mock/verified:pull";
+        let client = MockClient::default()
+            .with_response(token_url, r#"{"token":"secret"}"#)
+            .with_response("https://registry-1.docker.io/v2/mock/verified/manifests/1.1.0", "")
+            .with_response("https://mcr.microsoft.com/v2/mock/runtime/manifests/8.0.2", "");
+        let image: ContainerImage = "mock/verified:1.0.0".parse().unwrap();
+
+        assert!(image.verify_tag(&client, &"1.1.0".parse().unwrap(), None).is_ok());
+        assert!(image.verify_tag(&client, &"1.2.0".parse().unwrap(), None).is_err());
+        assert_eq!(client.requests()[0], token_url);
+
+        let image: ContainerImage = "mcr.microsoft.com/mock/runtime:8.0.0".parse().unwrap();
+        assert!(image.verify_tag(&client, &"8.0.2".parse().unwrap(), None).is_ok());
+    }
+
+    #[test]
+    fn publishers() {
+        let client = MockClient::default()
+            .with_response(
+                "https://hub.docker.com/v2/repositories/bitnami/redis/",
+                r#"{"name":"redis","badge":"verified_publisher"}"#,
+            )
+            .with_response("https://hub.docker.com/v2/repositories/mock/app/", r#"{"name":"app"}"#);
+        let publisher = |image: &str| image.parse::<ContainerImage>().unwrap().fetch_publisher(&client).unwrap();
+
+        assert_eq!(publisher("node:22.1.0"), PublisherKind::Official);
+        assert_eq!(publisher("docker.io/library/node:22.1.0"), PublisherKind::Official);
+        assert_eq!(publisher("mcr.microsoft.com/dotnet/aspnet:9.0.0"), PublisherKind::VerifiedPublisher);
+        assert!(client.requests().is_empty());
+        assert_eq!(publisher("bitnami/redis:7.2.4"), PublisherKind::VerifiedPublisher);
+        assert_eq!(publisher("mock/app:1.0.0"), PublisherKind::Community);
+        assert!(!PublisherKind::Community.is_trusted());
+        assert!("mock/missing:1.0.0".parse::<ContainerImage>().unwrap().fetch_publisher(&client).is_err());
+    }
+
+    #[test]
+    fn remote_tags_are_cached() {
+        let url = "https://hub.docker.com/v2/repositories/mock/cached/tags?page_size=100";
+        let client = MockClient::default().with_response(url, &hub_page(&["2.0.0", "1.0.0"], None));
+        let image: ContainerImage = "mock/cached:1.0.0".parse().unwrap();
+
+        let first = image.get_remote_tags_with(&client, &FetchOptions::default()).unwrap();
+        let second = image.get_remote_tags_with(&client, &FetchOptions::default()).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(client.requests().len(), 1);
+
+        // Stage references never query the registry.
+        let stage: ContainerImage = "base".parse().unwrap();
+        assert!(stage.get_remote_tags_with(&client, &FetchOptions::default()).unwrap().is_empty());
+        assert_eq!(client.requests().len(), 1);
+    }
 }
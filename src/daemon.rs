@@ -0,0 +1,263 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::Deserialize;
+use tracing::debug;
+
+/// Default location of the docker socket.
+const DOCKER_SOCKET: &str = "/var/run/docker.sock";
+/// Location of the rootless podman socket, relative to `XDG_RUNTIME_DIR`.
+const PODMAN_SOCKET: &str = "podman/podman.sock";
+/// Registry prefix docker and podman may use for images of Docker Hub.
+const DOCKERHUB_PREFIXES: [&str; 2] = ["docker.io/", "index.docker.io/"];
+const MCR_HOST: &str = "mcr.microsoft.com";
+/// The cli of containerd, it lists the images in the format of docker.
+const NERDCTL: &str = "nerdctl";
+/// Used by nerdctl for the parts of untagged images.
+const NONE: &str = "<none>";
+
+/// Where the local images are listed from.
+#[derive(Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+#[clap(rename_all = "kebab-case")]
+pub enum Runtime {
+    /// The docker or podman daemon, via its socket.
+    #[default]
+    Docker,
+    /// containerd via `nerdctl`, e.g. on k3s or kubernetes nodes.
+    Nerdctl,
+}
+
+/// Docker daemon related errors.
+#[derive(Debug, thiserror::Error)]
+pub enum DaemonError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("The daemon responded with `{0}`.")]
+    Status(String),
+    #[error("Could not parse the response of the daemon: {0}")]
+    Parse(String),
+    #[error("`nerdctl images` failed: {0}")]
+    Nerdctl(String),
+    #[cfg(not(unix))]
+    #[error("Talking to the daemon is only supported via unix sockets.")]
+    Unsupported,
+}
+
+/// A single image of the `/images/json` endpoint, only the needed fields are
+/// deserialized.
+#[derive(Debug, Clone, Default, Deserialize)]
+struct DaemonImage {
+    #[serde(rename = "RepoTags", default)]
+    repo_tags: Option<Vec<String>>,
+}
+
+/// A single image of `nerdctl images --format json`, only the needed fields are
+/// deserialized.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct NerdctlImage {
+    repository: String,
+    tag:        String,
+}
+
+/// Returns the socket of the daemon: `DOCKER_HOST` if it points to a unix
+/// socket, the docker socket if it exists and the rootless podman socket
+/// otherwise.
+pub fn socket_path() -> PathBuf {
+    if let Some(path) = std::env::var("DOCKER_HOST")
+        .ok()
+        .and_then(|host| host.strip_prefix("unix://").map(PathBuf::from))
+    {
+        return path;
+    }
+    let docker = PathBuf::from(DOCKER_SOCKET);
+    if docker.exists() {
+        return docker;
+    }
+    std::env::var_os("XDG_RUNTIME_DIR").map_or(docker, |runtime_dir| PathBuf::from(runtime_dir).join(PODMAN_SOCKET))
+}
+
+/// Returns all tagged images known to the daemon, e.g. `node:22.1.0`.
+///
+/// # Errors
+///
+/// This function will return an error if the daemon can not be reached or its
+/// response can not be parsed.
+pub fn list_images(socket: &Path) -> Result<Vec<String>, DaemonError> {
+    debug!("Listing images of the daemon at `{}`.", socket.display());
+    let response = request(socket, "/images/json")?;
+    parse_images(&parse_response(&response)?)
+}
+
+/// Returns all tagged images of the containerd namespace, e.g.
+/// `docker.io/library/node:22`. Without a namespace the default namespace of
+/// nerdctl is used, images pulled by kubernetes are in `k8s.io`.
+///
+/// # Errors
+///
+/// This function will return an error if nerdctl can not be run, fails or
+/// prints no valid images.
+pub fn list_nerdctl_images(namespace: Option<&str>) -> Result<Vec<String>, DaemonError> {
+    let mut command = Command::new(NERDCTL);
+    if let Some(namespace) = namespace {
+        command.args(["--namespace", namespace]);
+    }
+    debug!("Listing images via `nerdctl` in namespace `{}`.", namespace.unwrap_or("default"));
+    let output = command.args(["images", "--format", "json"]).output()?;
+    if !output.status.success() {
+        return Err(DaemonError::Nerdctl(String::from_utf8_lossy(&output.stderr).trim().to_owned()));
+    }
+    parse_nerdctl_images(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Sends a get request over the unix socket. HTTP/1.0 is used, so that the
+/// daemon closes the connection and does not send a chunked body.
+#[cfg(unix)]
+fn request(socket: &Path, path: &str) -> Result<String, DaemonError> {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(socket)?;
+    write!(stream, "GET {path} HTTP/1.0\r\nHost: localhost\r\n\r\n")?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
+
+#[cfg(not(unix))]
+fn request(_socket: &Path, _path: &str) -> Result<String, DaemonError> {
+    Err(DaemonError::Unsupported)
+}
+
+/// Returns the body of a raw http response, if its status is `200`.
+pub fn parse_response(response: &str) -> Result<String, DaemonError> {
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| DaemonError::Parse("Missing end of headers.".to_owned()))?;
+    let status_line = head.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some("200") => Ok(body.to_owned()),
+        _ => Err(DaemonError::Status(status_line.to_owned())),
+    }
+}
+
+/// Returns all tags of the `/images/json` response, untagged images are
+/// skipped.
+fn parse_images(body: &str) -> Result<Vec<String>, DaemonError> {
+    let images: Vec<DaemonImage> = serde_json::from_str(body).map_err(|e| DaemonError::Parse(e.to_string()))?;
+    let mut repo_tags: Vec<String> = images
+        .into_iter()
+        .flat_map(|image| image.repo_tags.unwrap_or_default())
+        .filter(|repo_tag| repo_tag != "<none>:<none>")
+        .collect();
+    repo_tags.sort();
+    repo_tags.dedup();
+    Ok(repo_tags)
+}
+
+/// Returns all tags of the json lines printed by nerdctl, untagged images are
+/// skipped.
+fn parse_nerdctl_images(output: &str) -> Result<Vec<String>, DaemonError> {
+    let mut repo_tags = Vec::new();
+    for line in output.lines().filter(|line| !line.trim().is_empty()) {
+        let image: NerdctlImage = serde_json::from_str(line).map_err(|e| DaemonError::Parse(e.to_string()))?;
+        if image.repository != NONE && image.tag != NONE {
+            repo_tags.push(format!("{}:{}", image.repository, image.tag));
+        }
+    }
+    repo_tags.sort();
+    repo_tags.dedup();
+    Ok(repo_tags)
+}
+
+/// Turns a tag of the daemon into a reference the registries can be queried
+/// with, e.g. `docker.io/library/node:22` into `library/node:22`. Returns
+/// `None` for images of other registries.
+pub fn registry_reference(repo_tag: &str) -> Option<String> {
+    let reference = DOCKERHUB_PREFIXES.iter().find_map(|prefix| repo_tag.strip_prefix(prefix)).unwrap_or(repo_tag);
+    match reference.split_once('/') {
+        // The first part is a registry, if it contains a dot or a port.
+        Some((host, _)) if host == MCR_HOST => Some(reference.to_owned()),
+        Some((host, _)) if host.contains(['.', ':']) || host == "localhost" => None,
+        _ => Some(reference.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use pretty_assertions::assert_eq;
+
+    use crate::daemon::{DaemonError, list_images, parse_images, parse_nerdctl_images, parse_response, registry_reference};
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::unix::net::UnixListener;
+
+        let socket = crate::paths::temp_path("daemon").with_extension("sock");
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request_line = String::new();
+            BufReader::new(&stream).read_line(&mut request_line).unwrap();
+            write!(stream, "HTTP/1.0 200 OK\r\n\r\n[{{\"RepoTags\": [\"node:22.1.0\"]}}]").unwrap();
+            request_line
+        });
+
+        assert_eq!(list_images(&socket).unwrap(), ["node:22.1.0"]);
+        assert_eq!(server.join().unwrap(), "GET /images/json HTTP/1.0\r\n");
+        std::fs::remove_file(&socket).unwrap();
+    }
+
+    #[test]
+    fn images_response() {
+        let body = r#"[
+            {"Id": "sha256:1", "RepoTags": ["node:22.1.0", "node:22"]},
+            {"Id": "sha256:2", "RepoTags": ["<none>:<none>"]},
+            {"Id": "sha256:3", "RepoTags": null},
+            {"Id": "sha256:4", "RepoTags": ["docker.io/library/alpine:3.20", "node:22"]}
+        ]"#;
+        let response = format!("HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n{body}");
+        let images = parse_images(&parse_response(&response).unwrap()).unwrap();
+        assert_eq!(images, ["docker.io/library/alpine:3.20", "node:22", "node:22.1.0"]);
+
+        let error = parse_response("HTTP/1.0 404 Not Found\r\n\r\n{}").unwrap_err();
+        assert!(matches!(error, DaemonError::Status(status) if status == "HTTP/1.0 404 Not Found"));
+        assert!(matches!(parse_images("{}"), Err(DaemonError::Parse(_))));
+    }
+
+    #[test]
+    fn nerdctl_images() {
+        let output = concat!(
+            r#"{"CreatedAt":"2025-01-01 10:00:00 +0000 UTC","ID":"1","Repository":"docker.io/library/node","Tag":"22.1.0","Size":"1GB"}"#,
+            "\n",
+            r#"{"ID":"2","Repository":"<none>","Tag":"<none>"}"#,
+            "\n\n",
+            r#"{"ID":"3","Repository":"registry.k8s.io/pause","Tag":"3.9"}"#,
+            "\n",
+        );
+        assert_eq!(
+            parse_nerdctl_images(output).unwrap(),
+            ["docker.io/library/node:22.1.0", "registry.k8s.io/pause:3.9"]
+        );
+        assert!(parse_nerdctl_images("").unwrap().is_empty());
+        assert!(matches!(parse_nerdctl_images("[]"), Err(DaemonError::Parse(_))));
+    }
+
+    #[test]
+    fn references() {
+        assert_eq!(registry_reference("node:22.1.0").as_deref(), Some("node:22.1.0"));
+        assert_eq!(registry_reference("docker.io/library/node:22").as_deref(), Some("library/node:22"));
+        assert_eq!(registry_reference("clamav/clamav:1.5.1").as_deref(), Some("clamav/clamav:1.5.1"));
+        assert_eq!(
+            registry_reference("mcr.microsoft.com/dotnet/aspnet:9.0.0").as_deref(),
+            Some("mcr.microsoft.com/dotnet/aspnet:9.0.0")
+        );
+        assert_eq!(registry_reference("ghcr.io/owner/image:1.0.0"), None);
+        assert_eq!(registry_reference("localhost:5000/image:1.0.0"), None);
+        assert_eq!(registry_reference("localhost/image:1.0.0"), None);
+    }
+}
@@ -0,0 +1,163 @@
+use std::ops::Range;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+/// A `FROM` line pinned by digest that keeps its tag, e.g.
+/// `FROM node:20.11.1@sha256:...`.
+static TAGGED_PIN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^\s*FROM\s+(?:--platform=\S+\s+)?(?<image>[^\s@]+):(?<tag>[^\s@:/]+)@(?<digest>sha256:[0-9a-f]+)(?:\s|$)")
+        .expect("Tagged pin regex is valid.")
+});
+/// A `FROM` line pinned by digest, with the human readable tag in a comment.
+static PIN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^\s*FROM\s+(?:--platform=\S+\s+)?(?<image>[^\s@]+)@(?<digest>sha256:[0-9a-f]+)(?:\s.*)?#\s*(?<tag>[^\s#]+)\s*$")
+        .expect("Pin regex is valid.")
+});
+/// Any `FROM` line that references its image by digest.
+static DIGEST_REFERENCE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)^\s*FROM\s+(?:--platform=\S+\s+)?\S+@sha256:").expect("Digest regex is valid."));
+
+/// An image pinned by digest with its tag, e.g.
+/// `FROM node:20.11.1@sha256:...` or `FROM node@sha256:... # 20.11.1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigestPin {
+    /// The image without tag, e.g. `node`.
+    pub image:    String,
+    pub digest:   String,
+    /// The tag in front of the digest or of the comment, e.g. `20.11.1`.
+    pub tag:      String,
+    digest_range: Range<usize>,
+    tag_range:    Range<usize>,
+}
+
+/// A pin that was changed by `refresh_pins`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinUpdate {
+    /// The line of the `FROM` instruction, starting at 1.
+    pub line:        usize,
+    pub image:       String,
+    pub from_tag:    String,
+    pub to_tag:      String,
+    pub from_digest: String,
+    pub to_digest:   String,
+}
+
+impl DigestPin {
+    /// Parses a pinned `FROM` line, returns `None` if the line is no pin or
+    /// has neither a tag nor a tag comment.
+    pub(crate) fn parse(line: &str) -> Option<Self> {
+        let captures = TAGGED_PIN.captures(line).or_else(|| PIN.captures(line))?;
+        let (image, digest, tag) = (captures.name("image")?, captures.name("digest")?, captures.name("tag")?);
+        Some(Self {
+            image:        image.as_str().to_owned(),
+            digest:       digest.as_str().to_owned(),
+            tag:          tag.as_str().to_owned(),
+            digest_range: digest.range(),
+            tag_range:    tag.range(),
+        })
+    }
+
+    /// Returns the line the pin was parsed from with the given digest and
+    /// tag, everything else is kept as written.
+    pub(crate) fn render(&self, line: &str, digest: &str, tag: &str) -> String {
+        let mut rendered = line.to_owned();
+        // The later range is replaced first, so that the other stays valid.
+        if self.tag_range.start > self.digest_range.start {
+            rendered.replace_range(self.tag_range.clone(), tag);
+            rendered.replace_range(self.digest_range.clone(), digest);
+        } else {
+            rendered.replace_range(self.digest_range.clone(), digest);
+            rendered.replace_range(self.tag_range.clone(), tag);
+        }
+        rendered
+    }
+}
+
+/// Returns `true` if the line is a `FROM` instruction referencing its image by
+/// digest. Such lines are not updated by tag.
+pub fn is_pinned(line: &str) -> bool {
+    DIGEST_REFERENCE.is_match(line)
+}
+
+/// Refreshes every pin of the content. The resolver gets the pin and returns
+/// the new tag and its digest, `None` keeps the line as it is. Returns the new
+/// content and the changed pins.
+pub fn refresh_pins<F>(content: &str, mut resolve: F) -> (String, Vec<PinUpdate>)
+where
+    F: FnMut(&DigestPin) -> Option<(String, String)>,
+{
+    let mut refreshed = String::with_capacity(content.len());
+    let mut updates = Vec::new();
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        let Some((pin, (tag, digest))) = DigestPin::parse(line).and_then(|pin| resolve(&pin).map(|resolved| (pin, resolved))) else {
+            refreshed.push_str(line);
+            continue;
+        };
+        if pin.tag == tag && pin.digest == digest {
+            refreshed.push_str(line);
+            continue;
+        }
+        refreshed.push_str(&pin.render(line, &digest, &tag));
+        updates.push(PinUpdate {
+            line:        index + 1,
+            image:       pin.image,
+            from_tag:    pin.tag,
+            to_tag:      tag,
+            from_digest: pin.digest,
+            to_digest:   digest,
+        });
+    }
+    (refreshed, updates)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use pretty_assertions::assert_eq;
+
+    use crate::digest::{DigestPin, is_pinned, refresh_pins};
+
+    #[test]
+    fn pinned_lines() {
+        let pin = DigestPin::parse("FROM --platform=$BUILDPLATFORM node@sha256:0a1b AS build # 20.11.1\n").unwrap();
+        assert_eq!((pin.image.as_str(), pin.digest.as_str(), pin.tag.as_str()), ("node", "sha256:0a1b", "20.11.1"));
+        assert_eq!(
+            pin.render("FROM --platform=$BUILDPLATFORM node@sha256:0a1b AS build # 20.11.1\n", "sha256:ff", "20.12.0"),
+            "FROM --platform=$BUILDPLATFORM node@sha256:ff AS build # 20.12.0\n"
+        );
+        assert_eq!(DigestPin::parse("FROM node@sha256:0a1b"), None);
+        assert_eq!(DigestPin::parse("FROM node:20.11.1 # 20.11.1"), None);
+        let line = "FROM localhost:5000/node:20.11.1@sha256:0a1b AS build\n";
+        let pin = DigestPin::parse(line).unwrap();
+        assert_eq!(
+            (pin.image.as_str(), pin.digest.as_str(), pin.tag.as_str()),
+            ("localhost:5000/node", "sha256:0a1b", "20.11.1")
+        );
+        assert_eq!(
+            pin.render(line, "sha256:ff", "20.12.0"),
+            "FROM localhost:5000/node:20.12.0@sha256:ff AS build\n"
+        );
+        assert!(is_pinned("from node@sha256:0a1b"));
+        assert!(!is_pinned("FROM node:20.11.1"));
+    }
+
+    #[test]
+    fn refreshed_pins() {
+        let content = "FROM node@sha256:0a1b # 20.11.1\r\nRUN npm ci\nFROM alpine@sha256:2c3d #3.20\nFROM python@sha256:4e5f # 3.12\n";
+        let (refreshed, updates) = refresh_pins(content, |pin| match pin.image.as_str() {
+            "node" => Some(("20.12.0".to_owned(), "sha256:9999".to_owned())),
+            "alpine" => Some(("3.20".to_owned(), "sha256:8888".to_owned())),
+            _ => Some((pin.tag.clone(), pin.digest.clone())),
+        });
+        assert_eq!(
+            refreshed,
+            "FROM node@sha256:9999 # 20.12.0\r\nRUN npm ci\nFROM alpine@sha256:8888 #3.20\nFROM python@sha256:4e5f # 3.12\n"
+        );
+        let lines: Vec<(usize, &str, &str)> = updates
+            .iter()
+            .map(|update| (update.line, update.to_tag.as_str(), update.from_tag.as_str()))
+            .collect();
+        assert_eq!(lines, [(1, "20.12.0", "20.11.1"), (3, "3.20", "3.20")]);
+        assert_eq!(refresh_pins(content, |_| None).0, content);
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashSet;
+use std::sync::{LazyLock, Mutex};
+
+use tracing::warn;
+
+use crate::pin::Date;
+use crate::tag::Tag;
+use crate::tag::scheme::normalize;
+
+/// Images that were already warned about, so that every image is only reported
+/// once per run.
+static WARNED: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// A release cycle of an image and the day its support ends, taken from
+/// <https://endoflife.date>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub image:    &'static str,
+    /// The major, or major and minor version of the cycle, e.g. `18` or
+    /// `3.18`.
+    pub version:  &'static str,
+    /// The release name some images are tagged with as well, e.g. `bookworm`.
+    pub codename: Option<&'static str>,
+    pub eol:      Date,
+}
+
+const fn cycle(image: &'static str, version: &'static str, codename: Option<&'static str>, eol: Date) -> Cycle {
+    Cycle { image, version, codename, eol }
+}
+
+/// The images whose releases variants are based on, e.g. `alpine3.21` or
+/// `bookworm`.
+const VARIANT_IMAGES: [&str; 3] = ["alpine", "debian", "ubuntu"];
+
+/// The release cycles of popular base images, newer cycles are added with
+/// their releases.
+const CYCLES: [Cycle; 57] = [
+    cycle("debian", "8", Some("jessie"), Date::new(2018, 6, 17)),
+    cycle("debian", "9", Some("stretch"), Date::new(2020, 7, 6)),
+    cycle("debian", "10", Some("buster"), Date::new(2022, 9, 10)),
+    cycle("debian", "11", Some("bullseye"), Date::new(2024, 8, 14)),
+    cycle("debian", "12", Some("bookworm"), Date::new(2026, 6, 10)),
+    cycle("debian", "13", Some("trixie"), Date::new(2028, 8, 9)),
+    cycle("ubuntu", "16.04", Some("xenial"), Date::new(2021, 4, 30)),
+    cycle("ubuntu", "18.04", Some("bionic"), Date::new(2023, 5, 31)),
+    cycle("ubuntu", "20.04", Some("focal"), Date::new(2025, 5, 31)),
+    cycle("ubuntu", "22.04", Some("jammy"), Date::new(2027, 6, 1)),
+    cycle("ubuntu", "23.04", Some("lunar"), Date::new(2024, 1, 25)),
+    cycle("ubuntu", "23.10", Some("mantic"), Date::new(2024, 7, 11)),
+    cycle("ubuntu", "24.04", Some("noble"), Date::new(2029, 5, 31)),
+    cycle("ubuntu", "24.10", Some("oracular"), Date::new(2025, 7, 10)),
+    cycle("ubuntu", "25.04", Some("plucky"), Date::new(2026, 1, 15)),
+    cycle("alpine", "3.12", None, Date::new(2022, 5, 1)),
+    cycle("alpine", "3.13", None, Date::new(2022, 11, 1)),
+    cycle("alpine", "3.14", None, Date::new(2023, 5, 1)),
+    cycle("alpine", "3.15", None, Date::new(2023, 11, 1)),
+    cycle("alpine", "3.16", None, Date::new(2024, 5, 23)),
+    cycle("alpine", "3.17", None, Date::new(2024, 11, 22)),
+    cycle("alpine", "3.18", None, Date::new(2025, 5, 9)),
+    cycle("alpine", "3.19", None, Date::new(2025, 11, 1)),
+    cycle("alpine", "3.20", None, Date::new(2026, 4, 1)),
+    cycle("alpine", "3.21", None, Date::new(2026, 11, 1)),
+    cycle("alpine", "3.22", None, Date::new(2027, 5, 1)),
+    cycle("node", "12", None, Date::new(2022, 4, 30)),
+    cycle("node", "14", None, Date::new(2023, 4, 30)),
+    cycle("node", "15", None, Date::new(2021, 6, 1)),
+    cycle("node", "16", None, Date::new(2023, 9, 11)),
+    cycle("node", "17", None, Date::new(2022, 6, 1)),
+    cycle("node", "18", None, Date::new(2025, 4, 30)),
+    cycle("node", "19", None, Date::new(2023, 6, 1)),
+    cycle("node", "20", None, Date::new(2026, 4, 30)),
+    cycle("node", "21", None, Date::new(2024, 6, 1)),
+    cycle("node", "22", None, Date::new(2027, 4, 30)),
+    cycle("node", "23", None, Date::new(2025, 6, 1)),
+    cycle("node", "24", None, Date::new(2028, 4, 30)),
+    cycle("python", "3.6", None, Date::new(2021, 12, 23)),
+    cycle("python", "3.7", None, Date::new(2023, 6, 27)),
+    cycle("python", "3.8", None, Date::new(2024, 10, 7)),
+    cycle("python", "3.9", None, Date::new(2025, 10, 31)),
+    cycle("python", "3.10", None, Date::new(2026, 10, 31)),
+    cycle("python", "3.11", None, Date::new(2027, 10, 31)),
+    cycle("python", "3.12", None, Date::new(2028, 10, 31)),
+    cycle("python", "3.13", None, Date::new(2029, 10, 31)),
+    cycle("python", "3.14", None, Date::new(2030, 10, 31)),
+    cycle("postgres", "9.6", None, Date::new(2021, 11, 11)),
+    cycle("postgres", "10", None, Date::new(2022, 11, 10)),
+    cycle("postgres", "11", None, Date::new(2023, 11, 9)),
+    cycle("postgres", "12", None, Date::new(2024, 11, 21)),
+    cycle("postgres", "13", None, Date::new(2025, 11, 13)),
+    cycle("postgres", "14", None, Date::new(2026, 11, 12)),
+    cycle("postgres", "15", None, Date::new(2027, 11, 11)),
+    cycle("postgres", "16", None, Date::new(2028, 11, 9)),
+    cycle("postgres", "17", None, Date::new(2029, 11, 8)),
+    cycle("postgres", "18", None, Date::new(2030, 11, 14)),
+];
+
+impl Cycle {
+    /// Checks if the tag belongs to the cycle, either by its version, e.g.
+    /// `3.18.4-slim` to `3.18`, or by the codename, e.g. `bookworm-slim`.
+    fn contains(&self, tag: &Tag) -> bool {
+        let mut parts = self.version.split('.').map(str::parse::<u64>);
+        let major = parts.next().and_then(Result::ok);
+        let minor = parts.next().and_then(Result::ok);
+        let by_version = tag.major.is_some() && tag.major == major && minor.is_none_or(|minor| tag.minor == Some(minor));
+        let by_codename = self.codename.is_some_and(|codename| {
+            let tag = tag.to_string();
+            tag == codename || tag.starts_with(&format!("{codename}-"))
+        });
+        by_version || by_codename
+    }
+}
+
+/// Returns the release cycle the tag of the image belongs to, if it is known.
+pub fn find_cycle(image: &str, tag: &Tag) -> Option<&'static Cycle> {
+    let image = normalize(image);
+    CYCLES.iter().find(|cycle| cycle.image == image && cycle.contains(tag))
+}
+
+/// Returns the day the support of the tag ended, if it did so before the given
+/// day.
+pub fn past_eol(image: &str, tag: &Tag, today: Date) -> Option<Date> {
+    find_cycle(image, tag).map(|cycle| cycle.eol).filter(|eol| *eol < today)
+}
+
+/// Returns the release of the operating system the variant of the tag is
+/// based on, e.g. alpine 3.17 for `1.2.3-alpine3.17` or debian 10 for
+/// `3.11.4-slim-buster`, and the variant with the release replaced by `{os}`.
+fn variant_release(tag: &Tag) -> Option<(&'static Cycle, String)> {
+    let variant = tag.variant.as_ref()?.to_string();
+    variant.split(['-', '_']).find_map(|token| {
+        CYCLES
+            .iter()
+            .filter(|cycle| VARIANT_IMAGES.contains(&cycle.image))
+            .find(|cycle| {
+                cycle.codename == Some(token)
+                    || token
+                        .strip_prefix(cycle.image)
+                        .is_some_and(|version| version == cycle.version || version.starts_with(&format!("{}.", cycle.version)))
+            })
+            .map(|cycle| (cycle, variant.replacen(token, "{os}", 1)))
+    })
+}
+
+/// Returns the release cycle of the operating system the variant of the tag is
+/// based on, if its support ended before the given day.
+pub fn past_variant_eol(tag: &Tag, today: Date) -> Option<&'static Cycle> {
+    variant_release(tag).map(|(cycle, _)| cycle).filter(|cycle| cycle.eol < today)
+}
+
+/// Returns the tag of the same version, whose variant is based on the latest
+/// supported release of the same operating system, e.g. `3.11.4-slim-bookworm`
+/// for `3.11.4-slim-buster`. Returns `None` if the variant of the tag is still
+/// supported.
+pub fn find_supported_variant<'a, I>(current: &Tag, tags: I, today: Date) -> Option<&'a Tag>
+where
+    I: IntoIterator<Item = &'a Tag>,
+{
+    let (cycle, variant) = variant_release(current).filter(|(cycle, _)| cycle.eol < today)?;
+    tags.into_iter()
+        .filter(|tag| (tag.major, tag.minor, tag.patch) == (current.major, current.minor, current.patch))
+        .filter_map(|tag| variant_release(tag).map(|release| (tag, release)))
+        .filter(|(_, (release, other))| release.image == cycle.image && release.eol >= today && *other == variant)
+        .max_by_key(|(_, (release, _))| release.eol)
+        .map(|(tag, _)| tag)
+}
+
+/// Warns about the image, if its tag or the operating system of its variant is
+/// past its end of life. This is independent of the candidate, e.g. a strategy
+/// that stays within the major version may find none.
+pub fn warn_past_eol(image: &str, tag: &Tag) {
+    let today = Date::today();
+    let reference = format!("{image}:{tag}");
+    let message = if let Some(eol) = past_eol(image, tag, today) {
+        format!("`{reference}` reached its end of life on {eol}, it no longer receives security updates.")
+    } else if let Some(cycle) = past_variant_eol(tag, today) {
+        format!(
+            "`{reference}` is based on {} {}, which reached its end of life on {}, `--update-eol-variants` moves it to a supported release.",
+            cycle.image, cycle.version, cycle.eol
+        )
+    } else {
+        return;
+    };
+    if WARNED.lock().expect("Warned images can be locked.").insert(reference) {
+        warn!("{message}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use pretty_assertions::assert_eq;
+
+    use crate::eol::{find_cycle, find_supported_variant, past_eol, past_variant_eol};
+    use crate::pin::Date;
+    use crate::tag::Tag;
+
+    #[test]
+    fn end_of_life() {
+        let cycle = |image: &str, tag: &str| find_cycle(image, &tag.parse::<Tag>().unwrap()).map(|cycle| cycle.version);
+        assert_eq!(cycle("node", "18.20.1-alpine"), Some("18"));
+        assert_eq!(cycle("library/alpine", "3.18.4"), Some("3.18"));
+        assert_eq!(cycle("alpine", "3"), None);
+        assert_eq!(cycle("debian", "bullseye-slim"), Some("11"));
+        assert_eq!(cycle("debian", "12.5"), Some("12"));
+        assert_eq!(cycle("ubuntu", "jammy"), Some("22.04"));
+        assert_eq!(cycle("node", "18-bullseye"), Some("18"));
+        assert_eq!(cycle("redis", "6.2"), None);
+        assert_eq!(cycle("debian", "trixie-slim"), Some("13"));
+        assert_eq!(cycle("ubuntu", "noble"), Some("24.04"));
+        assert_eq!(cycle("python", "3.14.0-alpine3.22"), Some("3.14"));
+
+        let today = Date::new(2025, 6, 1);
+        let tag: Tag = "16.20.2".parse().unwrap();
+        assert_eq!(past_eol("node", &tag, today), Some(Date::new(2023, 9, 11)));
+        assert_eq!(past_eol("node", &"22.1.0".parse().unwrap(), today), None);
+        assert_eq!(past_eol("ubuntu", &"20.04".parse().unwrap(), today), Some(Date::new(2025, 5, 31)));
+    }
+
+    #[test]
+    fn variant_end_of_life() {
+        let today = Date::new(2025, 6, 1);
+        let version = |tag: &str| past_variant_eol(&tag.parse().unwrap(), today).map(|cycle| cycle.version);
+        assert_eq!(version("20.11.1-alpine3.17"), Some("3.17"));
+        assert_eq!(version("3.11.4-slim-buster"), Some("10"));
+        assert_eq!(version("17.0.9_9-jre-focal"), Some("20.04"));
+        assert_eq!(version("20.11.1-alpine3.21"), None);
+        assert_eq!(version("20.11.1-bookworm"), None);
+        assert_eq!(version("20.11.1-alpine"), None);
+
+        let tags: Vec<Tag> = [
+            "3.11.4-slim-buster",
+            "3.11.4-slim-bullseye",
+            "3.11.4-slim-bookworm",
+            "3.11.4-bookworm",
+            "3.11.5-slim-bookworm",
+        ]
+        .iter()
+        .map(|tag| tag.parse().unwrap())
+        .collect();
+        let supported = |tag: &str| find_supported_variant(&tag.parse().unwrap(), &tags, today).map(ToString::to_string);
+        assert_eq!(supported("3.11.4-slim-buster").as_deref(), Some("3.11.4-slim-bookworm"));
+        assert_eq!(supported("3.11.4-slim-bookworm"), None);
+        assert_eq!(supported("3.11.3-slim-buster"), None);
+    }
+}
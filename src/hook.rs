@@ -0,0 +1,202 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use serde::Serialize;
+use tracing::{debug, info};
+
+use crate::utils::Strategy;
+
+/// Program used to verify an update, if no command is given.
+const DOCKER: &str = "docker";
+
+/// External command related errors.
+#[derive(Debug, thiserror::Error)]
+pub enum HookError {
+    #[error("Could not run command `{0}`: {1}")]
+    Io(String, std::io::Error),
+    #[error("Command `{0}` failed with {1}: {2}")]
+    Status(String, std::process::ExitStatus, String),
+    #[error("Command `{0}` printed no valid utf-8.")]
+    Output(String),
+}
+
+/// The input of the candidate command, written as json to its stdin.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CandidateRequest {
+    pub image:      String,
+    pub current:    String,
+    pub strategy:   Strategy,
+    pub fallbacks:  Vec<Strategy>,
+    /// The candidate the built-in selection would choose.
+    pub suggestion: Option<String>,
+    pub tags:       Vec<String>,
+}
+
+/// Runs the candidate command with the request on its stdin and returns the
+/// first line of its stdout, `None` if it printed nothing.
+///
+/// # Errors
+///
+/// This function will return an error if the command can not be run, exits
+/// with a non-zero status or prints no valid utf-8.
+pub fn run_candidate_cmd(cmd: &Path, request: &CandidateRequest) -> Result<Option<String>, HookError> {
+    let name = cmd.display().to_string();
+    debug!("Running candidate command `{name}` for `{}`.", request.image);
+    let mut child = Command::new(cmd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| HookError::Io(name.clone(), e))?;
+    let input = serde_json::to_vec(request).expect("Candidate request can be serialized.");
+    if let Some(mut stdin) = child.stdin.take() {
+        // The command may exit without reading its input, its status decides.
+        let _ = stdin.write_all(&input);
+    }
+    let output = child.wait_with_output().map_err(|e| HookError::Io(name.clone(), e))?;
+    if !output.status.success() {
+        return Err(HookError::Status(
+            name,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+    let stdout = String::from_utf8(output.stdout).map_err(|_| HookError::Output(name))?;
+    Ok(stdout.lines().next().map(str::trim).filter(|line| !line.is_empty()).map(str::to_owned))
+}
+
+/// Runs the post update command after the file was written. The file and the
+/// changed images (separated by spaces, in the same order) are passed via the
+/// `DIU_UPDATED_FILE`, `DIU_OLD_IMAGES` and `DIU_NEW_IMAGES` environment
+/// variables, the output of the command is logged.
+///
+/// # Errors
+///
+/// This function will return an error if the command can not be run or exits
+/// with a non-zero status.
+pub fn run_post_update_cmd(cmd: &Path, file: &Path, old_images: &[String], new_images: &[String]) -> Result<(), HookError> {
+    debug!("Running post update command `{}` for `{}`.", cmd.display(), file.display());
+    run_logged(
+        Command::new(cmd)
+            .env("DIU_UPDATED_FILE", file)
+            .env("DIU_OLD_IMAGES", old_images.join(" "))
+            .env("DIU_NEW_IMAGES", new_images.join(" ")),
+    )
+}
+
+/// Verifies the updated file by building it, with `docker build` in the
+/// folder of the file or with the given command. The command gets the file
+/// via the `DIU_UPDATED_FILE` environment variable.
+///
+/// # Errors
+///
+/// This function will return an error if the build can not be run or fails.
+pub fn run_verify_build(cmd: Option<&Path>, file: &Path) -> Result<(), HookError> {
+    debug!("Verifying the build of `{}`.", file.display());
+    let mut command = cmd.map_or_else(
+        || {
+            let context = file.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+            let mut command = Command::new(DOCKER);
+            command.arg("build").arg("--file").arg(file).arg(context);
+            command
+        },
+        Command::new,
+    );
+    run_logged(command.env("DIU_UPDATED_FILE", file))
+}
+
+/// Runs the command without input, logs its output and checks its status.
+fn run_logged(command: &mut Command) -> Result<(), HookError> {
+    let name = command.get_program().to_string_lossy().into_owned();
+    let output = command.stdin(Stdio::null()).output().map_err(|e| HookError::Io(name.clone(), e))?;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        info!("{name}: {line}");
+    }
+    if !output.status.success() {
+        return Err(HookError::Status(
+            name,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use std::path::Path;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::hook::{CandidateRequest, HookError, run_candidate_cmd, run_post_update_cmd, run_verify_build};
+    use crate::paths;
+    use crate::utils::Strategy;
+
+    #[cfg(unix)]
+    #[test]
+    fn candidate_command() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script = paths::temp_path("candidate").with_extension("sh");
+        // Answers with a fixed tag, if the request was passed on stdin.
+        std::fs::write(&script, "#!/bin/sh\ngrep -q '\"strategy\":\"next-minor\"' && echo 1.3.0\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let request = CandidateRequest {
+            image:      "library/node".to_owned(),
+            current:    "1.2.3".to_owned(),
+            strategy:   Strategy::NextMinor,
+            fallbacks:  vec![],
+            suggestion: Some("1.3.0".to_owned()),
+            tags:       vec!["1.2.3".to_owned(), "1.3.0".to_owned()],
+        };
+        assert_eq!(run_candidate_cmd(&script, &request).unwrap().as_deref(), Some("1.3.0"));
+
+        let request = CandidateRequest {
+            strategy: Strategy::Latest,
+            ..request
+        };
+        assert!(matches!(run_candidate_cmd(&script, &request), Err(HookError::Status(..))));
+        assert!(matches!(run_candidate_cmd(Path::new("/nonexistent/diu"), &request), Err(HookError::Io(..))));
+        std::fs::remove_file(&script).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn post_update_command() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script = paths::temp_path("post-update").with_extension("sh");
+        let result = script.with_extension("out");
+        std::fs::write(
+            &script,
+            format!("#!/bin/sh\necho \"$DIU_UPDATED_FILE|$DIU_OLD_IMAGES|$DIU_NEW_IMAGES\" > {}\n", result.display()),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let old_images = ["node:22.1.0".to_owned(), "alpine:3.20".to_owned()];
+        let new_images = ["node:22.2.0".to_owned(), "alpine:3.21".to_owned()];
+        run_post_update_cmd(&script, Path::new("Dockerfile"), &old_images, &new_images).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&result).unwrap(),
+            "Dockerfile|node:22.1.0 alpine:3.20|node:22.2.0 alpine:3.21\n"
+        );
+        assert!(matches!(
+            run_post_update_cmd(Path::new("/nonexistent/diu"), Path::new("Dockerfile"), &[], &[]),
+            Err(HookError::Io(..))
+        ));
+        std::fs::remove_file(&script).unwrap();
+        std::fs::remove_file(&result).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn verify_build_command() {
+        assert!(run_verify_build(Some(Path::new("true")), Path::new("Dockerfile")).is_ok());
+        let error = run_verify_build(Some(Path::new("false")), Path::new("Dockerfile")).unwrap_err();
+        assert!(matches!(error, HookError::Status(name, ..) if name == "false"));
+    }
+}
@@ -2,13 +2,45 @@ use clap::Parser;
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::{EnvFilter, fmt};
 
-use crate::utils::{check_update, handle_file, handle_input, handle_multi, handle_overview, handle_self_update};
+use crate::registries::http::{HttpMode, set_mode};
+use crate::registries::{http, limits, ratelimit};
+use crate::tag::channel::set_lts_majors;
+use crate::tag::scheme::set_schemes;
+use crate::utils::{
+    check_update, handle_batch, handle_cache, handle_compare, handle_consistency, handle_digest, handle_exists, handle_file, handle_input, handle_local,
+    handle_multi, handle_outdated, handle_overview, handle_self_update, handle_serve, handle_strategies,
+};
+use crate::yaml_path::set_yaml_paths;
 
+mod backup;
+mod cache;
 mod cli;
+mod config;
+mod consistency;
 mod container_image;
+mod daemon;
+mod digest;
+mod eol;
+mod hook;
+mod marker;
+mod metrics;
+mod patch;
+mod paths;
+mod pin;
 mod registries;
+mod report;
+mod risk;
+mod scanners;
+mod serve;
+mod skipped;
+mod stats;
 mod tag;
 mod utils;
+mod yaml_path;
+
+/// The exit code if some images could not be resolved, the other images were
+/// updated regardless.
+const RESOLVE_FAILURE_EXIT_CODE: i32 = 3;
 
 fn main() {
     // Needs to be initialised so that ureq can use rustls and not be dependendant
@@ -16,24 +48,26 @@ fn main() {
     rustls::crypto::ring::default_provider()
         .install_default()
         .expect("Failed to install rustls crypto provider");
+    stats::start();
     let cli = cli::Cli::parse();
-    let debug = match &cli.mode {
-        cli::Mode::File(file_mode) => file_mode.common.debug,
-        cli::Mode::Input(input_mode) => input_mode.common.debug,
-        cli::Mode::Multi(multi_file_mode) => multi_file_mode.common.debug,
-        cli::Mode::Overview(overview_mode) => overview_mode.common.debug,
-        &cli::Mode::SelfUpdate => false,
-    };
+    let print_stats = cli.mode.common().is_some_and(|common| common.stats);
+    let show_skipped = cli.mode.common().is_some_and(|common| common.show_skipped);
+    if show_skipped {
+        skipped::enable();
+    }
+    let verbose = cli.mode.common().map_or(0, |common| common.verbose);
+    let color = cli.mode.common().is_some_and(|common| common.color);
 
-    let color = match &cli.mode {
-        cli::Mode::File(file_mode) => file_mode.common.color,
-        cli::Mode::Input(input_mode) => input_mode.common.color,
-        cli::Mode::Multi(multi_file_mode) => multi_file_mode.common.color,
-        cli::Mode::Overview(overview_mode) => overview_mode.common.color,
-        &cli::Mode::SelfUpdate => false,
+    // The filter flag wins over `RUST_LOG`, which wins over the verbosity.
+    let env_filter = cli.mode.common().and_then(|common| common.log_filter.as_deref()).map_or_else(
+        || EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(cli::verbosity_filter(verbose))),
+        EnvFilter::new,
+    );
+    let env_filter = if cli.mode.common().is_some_and(|common| common.trace_http) {
+        env_filter.add_directive(http::TRACE_DIRECTIVE.parse().expect("Directive can be parsed."))
+    } else {
+        env_filter
     };
-
-    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(if debug { "debug" } else { "info" }));
     let custom_format = fmt::format()
         .with_target(false)
         .with_file(true)
@@ -45,40 +79,149 @@ fn main() {
 
     // If quiet flag is set, we do not initialise and use the tracing_subscriber.
     // Only (e)print(ln) will be printed.
-    if let cli::Mode::Input(input_mode) = &cli.mode {
-        if !input_mode.common.quiet {
-            tracing_subscriber::registry().with(env_filter).with(fmt_layer).init();
-        }
-    } else {
+    let quiet = cli.mode.is_machine_readable()
+        || match &cli.mode {
+            cli::Mode::Input(input_mode) => input_mode.common.quiet,
+            cli::Mode::Batch(batch_mode) => batch_mode.common.quiet,
+            cli::Mode::Local(local_mode) => local_mode.common.quiet,
+            cli::Mode::Outdated(outdated_mode) => outdated_mode.common.quiet,
+            cli::Mode::Exists(exists_mode) => exists_mode.common.quiet,
+            cli::Mode::Compare(compare_mode) => compare_mode.quiet,
+            // The list may be parsed by scripts.
+            cli::Mode::Strategies(_) => true,
+            _ => false,
+        };
+    if !quiet {
         tracing_subscriber::registry().with(env_filter).with(fmt_layer).init();
     }
 
-    // Check for update if not quiet mode is not enabled
-    if !match &cli.mode {
-        cli::Mode::Input(input_arguments) => input_arguments.common.quiet,
-        cli::Mode::Overview(overview_arguments) => overview_arguments.common.quiet,
-        cli::Mode::File(single_file_arguments) => single_file_arguments.common.quiet,
-        cli::Mode::Multi(multi_file_arguments) => multi_file_arguments.common.quiet,
-        &cli::Mode::SelfUpdate => false,
+    let http_mode = cli
+        .mode
+        .common()
+        .map_or(HttpMode::Live, |common| HttpMode::from_dirs(common.record.as_ref(), common.replay.as_ref()));
+    if http_mode != HttpMode::Live {
+        set_mode(http_mode);
+    }
+    ratelimit::set_max_requests(cli.mode.common().and_then(|common| common.max_requests));
+
+    if let Some(common) = cli.mode.common() {
+        let config = config::load_config(common.config.as_deref());
+        match config.and_then(|config| Ok((config.tag_schemes()?, config.lts_majors(), config.concurrency(), config.yaml_paths))) {
+            Ok((schemes, lts_majors, concurrency, yaml_paths)) => {
+                set_schemes(schemes);
+                set_lts_majors(lts_majors);
+                limits::set_concurrency(concurrency);
+                set_yaml_paths(yaml_paths);
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Check for update if not quiet mode is not enabled. Modes without common
+    // options (e.g. cache maintenance or comparing tags) do not need to reach out
+    // to the network. Json output may be captured by scripts.
+    if match &cli.mode {
+        cli::Mode::SelfUpdate => true,
+        mode => !mode.common().is_none_or(|common| common.quiet) && !mode.is_machine_readable(),
     } {
         check_update();
     }
 
-    match cli.mode {
-        cli::Mode::Input(input_mode) => {
-            handle_input(&input_mode);
-        }
-        cli::Mode::Overview(overview_mode) => {
-            handle_overview(&overview_mode);
-        }
-        cli::Mode::File(file_mode) => {
-            handle_file(&file_mode);
+    let exit_code = run(cli.mode);
+    ratelimit::log_budget();
+
+    if print_stats {
+        let summary = stats::summary();
+        // Without log lines the statistics would not be visible.
+        if quiet {
+            eprintln!("{}", serde_json::to_string(&summary).expect("Statistics can be turned into json string."));
+        } else {
+            summary.log();
         }
-        cli::Mode::Multi(multi_mode) => {
-            handle_multi(&multi_mode);
+    }
+    if show_skipped {
+        let report = skipped::report();
+        if quiet {
+            eprintln!(
+                "{}",
+                serde_json::to_string(&serde_json::json!({ "skipped": report })).expect("Skipped tags can be turned into json string.")
+            );
+        } else {
+            skipped::log(&report);
         }
+    }
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
+}
+
+/// Runs the mode and returns its exit code.
+fn run(mode: cli::Mode) -> i32 {
+    let success = match mode {
+        cli::Mode::Input(input_mode) => handle_input(&input_mode),
+        cli::Mode::Overview(overview_mode) => handle_overview(&overview_mode),
+        cli::Mode::File(file_mode) => return if handle_file(&file_mode) { 0 } else { RESOLVE_FAILURE_EXIT_CODE },
+        cli::Mode::Multi(multi_mode) => return if handle_multi(&multi_mode) { 0 } else { RESOLVE_FAILURE_EXIT_CODE },
         cli::Mode::SelfUpdate => {
             handle_self_update();
+            true
+        }
+        cli::Mode::Cache(cache_mode) => {
+            handle_cache(&cache_mode);
+            true
+        }
+        cli::Mode::Batch(batch_mode) => handle_batch(&batch_mode),
+        cli::Mode::Strategies(strategies_mode) => {
+            handle_strategies(&strategies_mode);
+            true
+        }
+        cli::Mode::Compare(compare_mode) => {
+            handle_compare(&compare_mode);
+            true
+        }
+        cli::Mode::Local(local_mode) => handle_local(&local_mode),
+        cli::Mode::Outdated(outdated_mode) => handle_outdated(&outdated_mode),
+        cli::Mode::Exists(exists_mode) => handle_exists(&exists_mode),
+        cli::Mode::Digest(digest_mode) => {
+            handle_digest(&digest_mode);
+            true
         }
+        cli::Mode::Consistency(consistency_mode) => handle_consistency(&consistency_mode),
+        cli::Mode::Serve(serve_mode) => handle_serve(&serve_mode),
+    };
+    i32::from(!success)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use std::fs;
+
+    use clap::Parser;
+    use pretty_assertions::assert_eq;
+
+    use crate::cli::Cli;
+    use crate::paths::temp_path;
+    use crate::registries::http::replay_fixtures;
+    use crate::{RESOLVE_FAILURE_EXIT_CODE, run};
+
+    #[test]
+    fn unresolvable_images() {
+        replay_fixtures();
+        let file = temp_path("unresolvable");
+        fs::write(&file, "FROM mock/missing:1.0.0\nFROM nginx:1.25.5-alpine3.19\n").unwrap();
+        let cli = Cli::try_parse_from(["diu", "file", &file.to_string_lossy(), "--tag-search-limit", "1000"]).unwrap();
+        let exit_code = run(cli.mode);
+        let written = fs::read_to_string(&file).unwrap();
+        fs::remove_file(&file).unwrap();
+
+        assert_eq!(exit_code, RESOLVE_FAILURE_EXIT_CODE);
+        let mut lines = written.lines();
+        assert_eq!(lines.next(), Some("FROM mock/missing:1.0.0"));
+        let updated = lines.next().unwrap();
+        assert!(updated.starts_with("FROM nginx:") && updated != "FROM nginx:1.25.5-alpine3.19", "{updated}");
     }
 }
@@ -0,0 +1,97 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+use tracing::warn;
+
+use crate::utils::Strategy;
+
+/// A marker of this tool at the end of a line, e.g. `# diu: image=nginx
+/// strategy=next-minor`.
+static DIU_MARKER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"#\s*diu:(?<options>[^#]*)$").expect("Marker regex is valid."));
+/// A marker of the Flux image automation at the end of a line, e.g. `#
+/// {"$imagepolicy": "flux-system:podinfo:tag"}`.
+static FLUX_MARKER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"#\s*\{\s*"\$imagepolicy"\s*:\s*"(?<policy>[^"]+)"\s*\}\s*$"#).expect("Flux marker regex is valid."));
+
+/// What the value of a marked line holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Marker {
+    /// The whole image, e.g. `nginx:1.27.0`.
+    Image { strategy: Option<Strategy> },
+    /// The tag of the image, e.g. `1.27.0` with `image=nginx`.
+    Tag { image: String, strategy: Option<Strategy> },
+    /// The tag of the repository of the Flux image policy, which is marked
+    /// with `:name` in a line before.
+    PolicyTag(String),
+    /// The repository of the Flux image policy, e.g.
+    /// `ghcr.io/stefanprodan/podinfo`.
+    PolicyName(String),
+}
+
+/// Parses the marker at the end of the line, if there is any. Markers with
+/// unknown options are warned about and ignored, so that no value is updated
+/// by accident.
+pub fn parse(line: &str) -> Option<Marker> {
+    if let Some(captures) = FLUX_MARKER.captures(line) {
+        let policy = &captures["policy"];
+        return Some(match policy.rsplit_once(':') {
+            Some((name, "tag")) => Marker::PolicyTag(name.to_owned()),
+            Some((name, "name")) => Marker::PolicyName(name.to_owned()),
+            _ => Marker::Image { strategy: None },
+        });
+    }
+    let options = DIU_MARKER.captures(line)?;
+    let mut image = None;
+    let mut strategy = None;
+    for option in options["options"].split_whitespace() {
+        match option.split_once('=') {
+            Some(("image", value)) => image = Some(value.to_owned()),
+            Some(("strategy", value)) => match value.parse() {
+                Ok(value) => strategy = Some(value),
+                Err(e) => {
+                    warn!("Ignoring the marker `{option}`: {e}");
+                    return None;
+                }
+            },
+            _ => {
+                warn!("Ignoring the marker `{option}`, expected `image=<name>` or `strategy=<strategy>`.");
+                return None;
+            }
+        }
+    }
+    Some(match image {
+        Some(image) => Marker::Tag { image, strategy },
+        None => Marker::Image { strategy },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::marker::{Marker, parse};
+    use crate::utils::Strategy;
+
+    #[test]
+    fn markers() {
+        assert_eq!(parse("image: nginx:1.27.0 # diu:"), Some(Marker::Image { strategy: None }));
+        assert_eq!(
+            parse("tag: 1.27.0 # diu: image=nginx strategy=next-minor"),
+            Some(Marker::Tag {
+                image:    "nginx".to_owned(),
+                strategy: Some(Strategy::NextMinor),
+            })
+        );
+        assert_eq!(parse("tag: 1.27.0 # diu: strategy=sometimes"), None);
+        assert_eq!(parse("tag: 1.27.0 # diu: img=nginx"), None);
+        assert_eq!(
+            parse(r#"image: ghcr.io/podinfo:6.5.0 # {"$imagepolicy": "flux-system:podinfo"}"#),
+            Some(Marker::Image { strategy: None })
+        );
+        assert_eq!(
+            parse(r#"tag: 6.5.0 # {"$imagepolicy": "flux-system:podinfo:tag"}"#),
+            Some(Marker::PolicyTag("flux-system:podinfo".to_owned()))
+        );
+        assert_eq!(parse("image: nginx:1.27.0 # pinned"), None);
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+use tracing::{debug, info};
+
+use crate::cache;
+use crate::tag::Bump;
+
+/// The metrics of the running process, see `listen`.
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+/// The severities updates are counted by, the other bumps are reported as
+/// `other`.
+const SEVERITIES: [Bump; 3] = [Bump::Major, Bump::Minor, Bump::Patch];
+
+/// The state of the tracked images, rendered in the Prometheus text format.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// The kind of the available update per image, `None` if it is up to date.
+    images:         Mutex<HashMap<String, Option<Bump>>>,
+    /// Seconds since the unix epoch, `0` before the first image was resolved.
+    last_scan:      AtomicU64,
+    /// Requests whose image could not be resolved, for any reason.
+    resolve_errors: AtomicU64,
+}
+
+impl Metrics {
+    /// Records a resolved image and the kind of its available update.
+    pub(crate) fn record_image(&self, image: &str, update: Option<Bump>) {
+        self.images.lock().expect("Metrics can be locked.").insert(image.to_owned(), update);
+        self.last_scan.store(cache::now(), Ordering::Relaxed);
+    }
+
+    /// Records an image that could not be resolved, e.g. because the registry
+    /// failed, the image is invalid or rejected by `--official-only`.
+    pub(crate) fn record_error(&self) {
+        self.resolve_errors.fetch_add(1, Ordering::Relaxed);
+        self.last_scan.store(cache::now(), Ordering::Relaxed);
+    }
+
+    /// Renders the metrics in the Prometheus text format.
+    pub(crate) fn render(&self) -> String {
+        let images = self.images.lock().expect("Metrics can be locked.");
+        let mut lines = vec![
+            "# HELP diu_images_tracked The images that were resolved.".to_owned(),
+            "# TYPE diu_images_tracked gauge".to_owned(),
+            format!("diu_images_tracked {}", images.len()),
+            "# HELP diu_updates_available The images with an available update, by the kind of the update.".to_owned(),
+            "# TYPE diu_updates_available gauge".to_owned(),
+        ];
+        for severity in SEVERITIES {
+            let count = images.values().filter(|update| **update == Some(severity)).count();
+            lines.push(format!("diu_updates_available{{severity=\"{severity}\"}} {count}"));
+        }
+        let other = images
+            .values()
+            .filter(|update| update.is_some_and(|update| !SEVERITIES.contains(&update)))
+            .count();
+        drop(images);
+        lines.extend([
+            format!("diu_updates_available{{severity=\"other\"}} {other}"),
+            "# HELP diu_last_scan_timestamp_seconds When the last image was resolved.".to_owned(),
+            "# TYPE diu_last_scan_timestamp_seconds gauge".to_owned(),
+            format!("diu_last_scan_timestamp_seconds {}", self.last_scan.load(Ordering::Relaxed)),
+            "# HELP diu_resolve_errors_total The requests whose image could not be resolved, because of the registry or the request.".to_owned(),
+            "# TYPE diu_resolve_errors_total counter".to_owned(),
+            format!("diu_resolve_errors_total {}", self.resolve_errors.load(Ordering::Relaxed)),
+        ]);
+        lines.join("\n") + "\n"
+    }
+}
+
+/// Serves `/metrics` on the address in a background thread. Returns the bound
+/// address, e.g. with the port chosen for `127.0.0.1:0`.
+///
+/// # Errors
+///
+/// This function will return an error if the address can not be bound.
+pub fn listen(addr: &str) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let bound = listener.local_addr()?;
+    info!("Serving metrics on http://{bound}/metrics.");
+    std::thread::spawn(move || {
+        for stream in listener.incoming().filter_map(Result::ok) {
+            if let Err(e) = respond(stream, &METRICS) {
+                debug!("Could not answer a metrics request: {e}");
+            }
+        }
+    });
+    Ok(bound)
+}
+
+/// Answers a single request, only `GET /metrics` is known.
+fn respond(mut stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let (status, content_type, body) = if request_line.starts_with("GET /metrics ") {
+        ("200 OK", "text/plain; version=0.0.4", metrics.render())
+    } else {
+        ("404 Not Found", "text/plain", "Not found, metrics are served on /metrics.\n".to_owned())
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use pretty_assertions::assert_eq;
+
+    use crate::metrics::Metrics;
+    use crate::tag::Bump;
+
+    #[test]
+    fn metrics_rendering() {
+        let metrics = Metrics::default();
+        metrics.record_image("node:20.1.0", Some(Bump::Major));
+        metrics.record_image("redis:7.4.0", Some(Bump::Patch));
+        metrics.record_image("alpine:3.22.0", None);
+        metrics.record_image("debian:bookworm", Some(Bump::Variant));
+        metrics.record_error();
+        let rendered = metrics.render();
+        let samples: Vec<&str> = rendered
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.starts_with("diu_last_scan"))
+            .collect();
+        assert_eq!(
+            samples,
+            [
+                "diu_images_tracked 4",
+                "diu_updates_available{severity=\"major\"} 1",
+                "diu_updates_available{severity=\"minor\"} 0",
+                "diu_updates_available{severity=\"patch\"} 1",
+                "diu_updates_available{severity=\"other\"} 1",
+                "diu_resolve_errors_total 1",
+            ]
+        );
+        assert!(!rendered.contains("diu_last_scan_timestamp_seconds 0\n"));
+    }
+}
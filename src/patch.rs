@@ -0,0 +1,171 @@
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::{fs, io};
+
+/// The lines around a change that are part of its hunk.
+const CONTEXT_LINES: usize = 3;
+
+/// The patch that is collected instead of writing the files, see `start`.
+static PATCH: LazyLock<Mutex<Option<Patch>>> = LazyLock::new(|| Mutex::new(None));
+
+/// The changes of the files that would be written.
+#[derive(Debug, Default)]
+pub struct Patch {
+    diffs: Vec<(PathBuf, String)>,
+}
+
+impl Patch {
+    /// Adds the change of the file, unchanged files are left out.
+    pub(crate) fn record(&mut self, file: &Path, content: &str) {
+        let current = fs::read_to_string(file).unwrap_or_default();
+        if current != content {
+            self.diffs.push((file.to_path_buf(), unified_diff(&patch_path(file), &current, content)));
+        }
+    }
+
+    /// Writes the changes as one patch, ordered by the paths of the files.
+    /// Returns the number of changed files.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the patch can not be written.
+    pub(crate) fn write(mut self, target: &Path) -> io::Result<usize> {
+        self.diffs.sort_by(|(a, _), (b, _)| a.cmp(b));
+        fs::write(target, self.diffs.iter().map(|(_, diff)| diff.as_str()).collect::<String>())?;
+        Ok(self.diffs.len())
+    }
+}
+
+/// Starts collecting the changes of the files instead of writing them.
+pub fn start() {
+    *PATCH.lock().expect("Patch can be locked.") = Some(Patch::default());
+}
+
+/// Adds the change of the file to the patch, if one is collected. Returns
+/// `false` if the content shall be written to the file instead.
+pub fn record(file: &Path, content: &str) -> bool {
+    PATCH
+        .lock()
+        .expect("Patch can be locked.")
+        .as_mut()
+        .map(|patch| patch.record(file, content))
+        .is_some()
+}
+
+/// Writes the collected patch and stops collecting, see `Patch::write`.
+///
+/// # Errors
+///
+/// This function will return an error if the patch can not be written.
+pub fn finish(target: &Path) -> io::Result<usize> {
+    PATCH.lock().expect("Patch can be locked.").take().unwrap_or_default().write(target)
+}
+
+/// Returns the path of the file as written in the patch, relative to the
+/// working directory, so that it can be applied with `git apply`.
+fn patch_path(file: &Path) -> String {
+    let relative = std::env::current_dir().ok().and_then(|dir| file.strip_prefix(dir).ok()).unwrap_or(file);
+    let path = relative.to_string_lossy().replace('\\', "/");
+    path.strip_prefix("./").unwrap_or(&path).to_owned()
+}
+
+/// Renders the change of a file as unified diff. Updates replace lines, so
+/// the lines of both versions are compared one by one; if the number of lines
+/// differs the whole file is replaced.
+pub fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    let mut diff = format!("--- a/{path}\n+++ b/{path}\n");
+    if old_lines.len() != new_lines.len() {
+        let _ = writeln!(diff, "@@ -{} +{} @@", hunk_range(0, old_lines.len()), hunk_range(0, new_lines.len()));
+        push_lines(&mut diff, '-', &old_lines);
+        push_lines(&mut diff, '+', &new_lines);
+        return diff;
+    }
+    let changed: Vec<usize> = (0..old_lines.len()).filter(|&index| old_lines[index] != new_lines[index]).collect();
+    // Changes whose context touches are joined to one hunk.
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &index in &changed {
+        let start = index.saturating_sub(CONTEXT_LINES);
+        let end = (index + CONTEXT_LINES + 1).min(old_lines.len());
+        match hunks.last_mut() {
+            Some(hunk) if start <= hunk.1 => hunk.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    for (start, end) in hunks {
+        let range = hunk_range(start, end - start);
+        let _ = writeln!(diff, "@@ -{range} +{range} @@");
+        let mut index = start;
+        while index < end {
+            let run_end = (index..end).find(|&line| old_lines[line] == new_lines[line]).unwrap_or(end);
+            if run_end == index {
+                push_lines(&mut diff, ' ', &old_lines[index..=index]);
+                index += 1;
+            } else {
+                push_lines(&mut diff, '-', &old_lines[index..run_end]);
+                push_lines(&mut diff, '+', &new_lines[index..run_end]);
+                index = run_end;
+            }
+        }
+    }
+    diff
+}
+
+/// Returns the range of a hunk header, e.g. `3,7`, lines are counted from 1.
+fn hunk_range(start: usize, len: usize) -> String {
+    if len == 0 { format!("{start},0") } else { format!("{},{len}", start + 1) }
+}
+
+fn push_lines(diff: &mut String, marker: char, lines: &[&str]) {
+    for line in lines {
+        diff.push(marker);
+        diff.push_str(line);
+        if !line.ends_with('\n') {
+            diff.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use pretty_assertions::assert_eq;
+
+    use crate::patch::{Patch, unified_diff};
+    use crate::paths;
+
+    #[test]
+    fn unified_diffs() {
+        let old = "FROM node:20.11 AS build\nRUN npm ci\nRUN npm run build\nRUN npm test\nCOPY . .\nLABEL a=b\nUSER node\nEXPOSE \
+                   80\nFROM nginx:1.27.0\nCOPY --from=build /app /usr/share/nginx/html";
+        let new = old.replace("node:20.11", "node:22.1").replace("nginx:1.27.0", "nginx:1.27.3");
+        assert_eq!(
+            unified_diff("app/Dockerfile", old, &new),
+            "--- a/app/Dockerfile\n+++ b/app/Dockerfile\n@@ -1,4 +1,4 @@\n-FROM node:20.11 AS build\n+FROM node:22.1 AS build\n RUN npm \
+             ci\n RUN npm run build\n RUN npm test\n@@ -6,5 +6,5 @@\n LABEL a=b\n USER node\n EXPOSE 80\n-FROM nginx:1.27.0\n+FROM \
+             nginx:1.27.3\n COPY --from=build /app /usr/share/nginx/html\n\\ No newline at end of file\n"
+        );
+        assert_eq!(
+            unified_diff("compose.yaml", "", "image: nginx\n"),
+            "--- a/compose.yaml\n+++ b/compose.yaml\n@@ -0,0 +1,1 @@\n+image: nginx\n"
+        );
+
+        let root = paths::temp_path("patch");
+        std::fs::create_dir_all(&root).unwrap();
+        let file = root.join("Dockerfile");
+        std::fs::write(&file, "FROM node:20.11\n").unwrap();
+        let mut patch = Patch::default();
+        patch.record(&file, "FROM node:22.1\n");
+        patch.record(&file, "FROM node:20.11\n");
+        assert_eq!(patch.write(&root.join("updates.patch")).unwrap(), 1);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "FROM node:20.11\n");
+        assert!(
+            std::fs::read_to_string(root.join("updates.patch"))
+                .unwrap()
+                .ends_with("@@ -1,1 +1,1 @@\n-FROM node:20.11\n+FROM node:22.1\n")
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+
+/// Returns the home directory of the user: `HOME`, or `USERPROFILE` on
+/// Windows.
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Returns the cache directory of the tool, inside the cache directory of the
+/// platform: `XDG_CACHE_HOME` or `~/.cache` on Linux, `~/Library/Caches` on
+/// macOS and `LOCALAPPDATA` on Windows. The temp directory is used if none of
+/// them is known.
+pub fn cache_dir() -> PathBuf {
+    let platform_dir = if cfg!(target_os = "macos") {
+        home_dir().map(|home| home.join("Library").join("Caches"))
+    } else if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        home_dir().map(|home| home.join(".cache"))
+    };
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or(platform_dir)
+        .unwrap_or_else(std::env::temp_dir)
+        .join(env!("CARGO_PKG_NAME"))
+}
+
+/// Returns the directory of the running binary, downloaded releases are placed
+/// next to it. Falls back to the working directory.
+pub fn install_dir() -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(PathBuf::from))
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Returns a path in the temp directory of the platform, that is unique for
+/// the process, e.g. `/tmp/diu-cache-1234` or `%TEMP%\diu-cache-1234`.
+#[cfg(test)]
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("diu-{name}-{}", std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::paths::{cache_dir, temp_path};
+
+    #[test]
+    fn platform_paths() {
+        assert!(cache_dir().ends_with(env!("CARGO_PKG_NAME")));
+        assert!(temp_path("paths").starts_with(std::env::temp_dir()));
+        assert!(temp_path("paths").to_string_lossy().ends_with(&format!("diu-paths-{}", std::process::id())));
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::{LazyLock, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use tracing::warn;
+
+/// The prefix of the condition of a time-boxed pin, e.g. `until:2025-12-31`.
+const UNTIL_PREFIX: &str = "until:";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Expired pins that were already warned about, so that every pin is only
+/// reported once per run.
+static WARNED: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Pin related errors.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum PinError {
+    #[error("The pin `{0}` needs a condition, e.g. `node:18=until:2025-12-31`.")]
+    MissingCondition(String),
+    #[error("Unknown condition `{0}`, expected `until:<yyyy-mm-dd>`.")]
+    UnknownCondition(String),
+    #[error("Invalid date `{0}`, expected `yyyy-mm-dd`.")]
+    InvalidDate(String),
+}
+
+/// A calendar date without time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    year:  u32,
+    month: u8,
+    day:   u8,
+}
+
+impl Date {
+    /// Returns the given date, it is not validated.
+    pub const fn new(year: u32, month: u8, day: u8) -> Self {
+        Self { year, month, day }
+    }
+
+    /// Returns the current date in UTC.
+    pub fn today() -> Self {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+        Self::from_days(seconds / SECONDS_PER_DAY)
+    }
+
+    /// Returns the amount of days since the unix epoch, the inverse of
+    /// `from_days`.
+    pub fn to_days(self) -> u64 {
+        let year = u64::from(self.year) - u64::from(self.month <= 2);
+        let era = year / 400;
+        let yoe = year - era * 400;
+        let mp = (u64::from(self.month) + 9) % 12;
+        let doy = (153 * mp + 2) / 5 + u64::from(self.day) - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        (era * 146_097 + doe).saturating_sub(719_468)
+    }
+
+    /// Returns the date the given amount of days after the unix epoch, see
+    /// <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+    fn from_days(days: u64) -> Self {
+        let z = days + 719_468;
+        let era = z / 146_097;
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + u64::from(month <= 2);
+        Self {
+            year:  u32::try_from(year).unwrap_or(u32::MAX),
+            month: u8::try_from(month).unwrap_or_default(),
+            day:   u8::try_from(day).unwrap_or_default(),
+        }
+    }
+
+    const fn days_in_month(year: u32, month: u8) -> u8 {
+        match month {
+            2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+}
+
+impl FromStr for Date {
+    type Err = PinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || PinError::InvalidDate(s.to_owned());
+        let mut parts = s.splitn(3, '-');
+        let (Some(year), Some(month), Some(day)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid());
+        };
+        let date = Self {
+            year:  year.parse().map_err(|_| invalid())?,
+            month: month.parse().map_err(|_| invalid())?,
+            day:   day.parse().map_err(|_| invalid())?,
+        };
+        if !(1..=12).contains(&date.month) || !(1..=Self::days_in_month(date.year, date.month)).contains(&date.day) {
+            return Err(invalid());
+        }
+        Ok(date)
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// An image that is not updated until the given date has passed, e.g.
+/// `node:18=until:2025-12-31` for a planned migration.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Pin {
+    /// The pinned image as it is written in the dockerfiles, e.g. `node:18`.
+    pub image: String,
+    /// The last day the image is pinned.
+    pub until: Date,
+}
+
+impl Pin {
+    /// Returns `true` if the pin still applies on the given day.
+    pub(crate) fn is_active(&self, today: Date) -> bool {
+        today <= self.until
+    }
+}
+
+impl FromStr for Pin {
+    type Err = PinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (image, condition) = s.rsplit_once('=').ok_or_else(|| PinError::MissingCondition(s.to_owned()))?;
+        let until = condition
+            .strip_prefix(UNTIL_PREFIX)
+            .ok_or_else(|| PinError::UnknownCondition(condition.to_owned()))?;
+        Ok(Self {
+            image: image.trim().to_owned(),
+            until: until.trim().parse()?,
+        })
+    }
+}
+
+impl TryFrom<String> for Pin {
+    type Error = PinError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Display for Pin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={UNTIL_PREFIX}{}", self.image, self.until)
+    }
+}
+
+/// Returns the images of the pins that still apply on the given day. Every
+/// expired pin is warned about once, so that it gets removed.
+pub fn active_images(pins: &[Pin], today: Date) -> Vec<String> {
+    pins.iter()
+        .filter(|pin| {
+            let active = pin.is_active(today);
+            if !active && WARNED.lock().expect("Warned pins can be locked.").insert(pin.to_string()) {
+                warn!(
+                    "The pin of `{}` expired on {}, its updates resume. Please remove the pin.",
+                    pin.image, pin.until
+                );
+            }
+            active
+        })
+        .map(|pin| pin.image.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use pretty_assertions::assert_eq;
+
+    use crate::pin::{Date, Pin, PinError, active_images};
+
+    #[test]
+    fn dates() {
+        assert_eq!(Date::from_days(0).to_string(), "1970-01-01");
+        assert_eq!(Date::from_days(19_782).to_string(), "2024-02-29");
+        assert_eq!(Date::from_days(20_453).to_string(), "2025-12-31");
+        assert_eq!("2024-02-29".parse::<Date>().unwrap(), Date::from_days(19_782));
+        assert_eq!(Date::from_days(20_453).to_days(), 20_453);
+        assert_eq!(Date::new(1970, 1, 1).to_days(), 0);
+        assert!("2025-02-29".parse::<Date>().is_err());
+        assert!("2025-13-01".parse::<Date>().is_err());
+        assert!("2025-12".parse::<Date>().is_err());
+    }
+
+    #[test]
+    fn time_boxed_pins() {
+        let pin: Pin = "node:18=until:2025-12-31".parse().unwrap();
+        assert_eq!(pin.image, "node:18");
+        assert_eq!(pin.to_string(), "node:18=until:2025-12-31");
+        assert_eq!("node:18".parse::<Pin>(), Err(PinError::MissingCondition("node:18".to_owned())));
+        assert_eq!(
+            "node:18=since:2025-12-31".parse::<Pin>(),
+            Err(PinError::UnknownCondition("since:2025-12-31".to_owned()))
+        );
+
+        let pins = [pin, "alpine:3.18=until:2026-06-30".parse().unwrap()];
+        assert_eq!(active_images(&pins, "2025-12-31".parse().unwrap()), ["node:18", "alpine:3.18"]);
+        assert_eq!(active_images(&pins, "2026-01-01".parse().unwrap()), ["alpine:3.18"]);
+    }
+}
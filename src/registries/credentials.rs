@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{LazyLock, Mutex, OnceLock};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::paths;
+use crate::registries::http::HttpClient;
+
+/// The key of Docker Hub in the docker config.
+pub const DOCKERHUB_SERVER: &str = "https://index.docker.io/v1/";
+/// Credential helpers are programs named e.g. `docker-credential-ecr-login`
+/// for the helper `ecr-login`.
+const HELPER_PREFIX: &str = "docker-credential-";
+/// Printed by the helpers if they have no credentials for the server.
+const HELPER_NOT_FOUND: &str = "credentials not found";
+/// Exchanges the credentials for a token of the Docker Hub API, which lists
+/// the tags, unlike the registry itself.
+const HUB_LOGIN_URL: &str = "https://hub.docker.com/v2/users/login";
+
+/// The credentials of every server, looked up once per run.
+static CREDENTIALS: LazyLock<Mutex<HashMap<String, Option<Credentials>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+/// The `Authorization` header for the Docker Hub API, logged in once per run.
+static HUB_AUTHORIZATION: OnceLock<Option<String>> = OnceLock::new();
+
+/// Docker config related errors.
+#[derive(Debug, thiserror::Error)]
+pub enum CredentialsError {
+    #[error("Could not read docker config `{0}`: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("Could not parse docker config `{0}`: {1}")]
+    Parse(PathBuf, String),
+    #[error("Could not run credential helper `{0}`: {1}")]
+    Helper(String, std::io::Error),
+    #[error("Credential helper `{0}` failed: {1}")]
+    HelperFailed(String, String),
+    #[error("Invalid credentials for `{0}`.")]
+    Invalid(String),
+    #[error("Could not log in to `{0}`: {1}")]
+    Login(String, String),
+}
+
+/// The parts of `~/.docker/config.json` that decide where credentials come
+/// from.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DockerConfig {
+    #[serde(default)]
+    auths:        HashMap<String, AuthEntry>,
+    /// The helper used for all servers without an entry in `cred_helpers`.
+    creds_store:  Option<String>,
+    /// The helper per server, e.g. `ecr-login` or `gcloud`.
+    #[serde(default)]
+    cred_helpers: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+struct AuthEntry {
+    /// Base64 encoded `username:password`.
+    auth: Option<String>,
+}
+
+/// The answer of a credential helper.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HelperResponse {
+    username: String,
+    secret:   String,
+}
+
+/// The answer of the Docker Hub login.
+#[derive(Debug, Deserialize)]
+struct HubLoginResponse {
+    token: String,
+}
+
+/// The credentials for a single server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub secret:   String,
+}
+
+impl Credentials {
+    /// Returns the value of the `Authorization` header for basic auth.
+    pub(crate) fn basic_auth(&self) -> String {
+        format!("Basic {}", STANDARD.encode(format!("{}:{}", self.username, self.secret)))
+    }
+}
+
+impl DockerConfig {
+    /// Returns the credentials of the server the same way the docker cli does:
+    /// the helper of the server, the default helper and the stored `auths`, in
+    /// this order.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the helper can not be run or
+    /// fails, or if the stored credentials are invalid.
+    pub(crate) fn credentials(&self, server: &str) -> Result<Option<Credentials>, CredentialsError> {
+        if let Some(helper) = self.cred_helpers.get(server).or(self.creds_store.as_ref()) {
+            return run_helper(Path::new(&format!("{HELPER_PREFIX}{helper}")), server);
+        }
+        let Some(auth) = self.auths.get(server).and_then(|entry| entry.auth.as_deref()) else {
+            return Ok(None);
+        };
+        let decoded = STANDARD.decode(auth).map_err(|_| CredentialsError::Invalid(server.to_owned()))?;
+        let decoded = String::from_utf8(decoded).map_err(|_| CredentialsError::Invalid(server.to_owned()))?;
+        let (username, secret) = decoded.split_once(':').ok_or_else(|| CredentialsError::Invalid(server.to_owned()))?;
+        Ok(Some(Credentials {
+            username: username.to_owned(),
+            secret:   secret.to_owned(),
+        }))
+    }
+}
+
+/// Returns the location of the docker config: `config.json` in
+/// `DOCKER_CONFIG` or in `~/.docker`.
+pub fn docker_config_path() -> Option<PathBuf> {
+    std::env::var_os("DOCKER_CONFIG")
+        .map(PathBuf::from)
+        .or_else(|| paths::home_dir().map(|home| home.join(".docker")))
+        .map(|dir| dir.join("config.json"))
+}
+
+/// Reads and parses the docker config.
+///
+/// # Errors
+///
+/// This function will return an error if the file can not be read or parsed.
+pub fn load_docker_config(path: &Path) -> Result<DockerConfig, CredentialsError> {
+    let content = fs::read_to_string(path).map_err(|e| CredentialsError::Io(path.to_path_buf(), e))?;
+    serde_json::from_str(&content).map_err(|e| CredentialsError::Parse(path.to_path_buf(), e.to_string()))
+}
+
+/// Runs `<helper> get` with the server on stdin. Returns `None` if the helper
+/// has no credentials for the server.
+///
+/// # Errors
+///
+/// This function will return an error if the helper can not be run, fails for
+/// another reason or prints no valid response.
+pub fn run_helper(helper: &Path, server: &str) -> Result<Option<Credentials>, CredentialsError> {
+    let name = helper.display().to_string();
+    debug!("Requesting credentials for `{server}` from `{name}`.");
+    let mut child = Command::new(helper)
+        .arg("get")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| CredentialsError::Helper(name.clone(), e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The helper may exit without reading its input, its status decides.
+        let _ = stdin.write_all(server.as_bytes());
+    }
+    let output = child.wait_with_output().map_err(|e| CredentialsError::Helper(name.clone(), e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        // Helpers print the reason on stdout.
+        let reason = if stdout.trim().is_empty() {
+            String::from_utf8_lossy(&output.stderr).trim().to_owned()
+        } else {
+            stdout.trim().to_owned()
+        };
+        if reason.to_ascii_lowercase().contains(HELPER_NOT_FOUND) {
+            return Ok(None);
+        }
+        return Err(CredentialsError::HelperFailed(name, reason));
+    }
+    let response: HelperResponse = serde_json::from_str(&stdout).map_err(|e| CredentialsError::HelperFailed(name, e.to_string()))?;
+    Ok(Some(Credentials {
+        username: response.username,
+        secret:   response.secret,
+    }))
+}
+
+/// Returns the credentials of the server from the docker config, if there are
+/// any. Failures are logged and treated as anonymous access.
+pub fn lookup(server: &str) -> Option<Credentials> {
+    let mut credentials = CREDENTIALS.lock().expect("Credentials can be locked.");
+    credentials
+        .entry(server.to_owned())
+        .or_insert_with(|| {
+            let path = docker_config_path().filter(|path| path.is_file())?;
+            load_docker_config(&path).and_then(|config| config.credentials(server)).unwrap_or_else(|e| {
+                warn!("Continuing without credentials for `{server}`: {e}");
+                None
+            })
+        })
+        .clone()
+}
+
+/// Logs in to the Docker Hub API with the credentials, e.g. a personal access
+/// token, and returns the value of the `Authorization` header for its
+/// requests.
+///
+/// # Errors
+///
+/// This function will return an error if the login is rejected or its answer
+/// can not be parsed.
+pub fn hub_login(client: &dyn HttpClient, credentials: &Credentials) -> Result<String, CredentialsError> {
+    let body = serde_json::json!({ "username": credentials.username, "password": credentials.secret }).to_string();
+    let login = |e: String| CredentialsError::Login(HUB_LOGIN_URL.to_owned(), e);
+    let response = client.post_json(HUB_LOGIN_URL, &body).map_err(|e| login(e.to_string()))?;
+    let response: HubLoginResponse = serde_json::from_str(&response).map_err(|e| login(e.to_string()))?;
+    Ok(format!("Bearer {}", response.token))
+}
+
+/// Returns the `Authorization` header for the Docker Hub API, so that the
+/// tags of private repositories are listed as well. The credentials of the
+/// docker config are only sent by clients that may send them, a failed login
+/// is logged and treated as anonymous access.
+pub fn hub_authorization(client: &dyn HttpClient) -> Option<String> {
+    if !client.sends_credentials() {
+        return None;
+    }
+    HUB_AUTHORIZATION
+        .get_or_init(|| {
+            let credentials = lookup(DOCKERHUB_SERVER)?;
+            hub_login(client, &credentials)
+                .inspect_err(|e| warn!("Continuing without credentials for the Docker Hub API: {e}"))
+                .ok()
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use pretty_assertions::assert_eq;
+
+    use crate::registries::credentials::{Credentials, CredentialsError, DOCKERHUB_SERVER, DockerConfig, HUB_LOGIN_URL, hub_authorization, hub_login};
+    use crate::registries::http::MockClient;
+
+    #[test]
+    fn stored_credentials() {
+        let config: DockerConfig = serde_json::from_str(
+            r#"{
+                "auths": {"https://index.docker.io/v1/": {"auth": "dXNlcjpzM2NyZXQ="}, "broken.example.com": {"auth": "invalid"}},
+                "credHelpers": {"123.dkr.ecr.eu-west-1.amazonaws.com": "ecr-login"}
+            }"#,
+        )
+        .unwrap();
+        let credentials = config.credentials(DOCKERHUB_SERVER).unwrap().unwrap();
+        assert_eq!(
+            credentials,
+            Credentials {
+                username: "user".to_owned(),
+                secret:   "s3cret".to_owned(),
+            }
+        );
+        assert_eq!(credentials.basic_auth(), "Basic dXNlcjpzM2NyZXQ=");
+        assert_eq!(config.credentials("ghcr.io").unwrap(), None);
+        assert!(matches!(config.credentials("broken.example.com"), Err(CredentialsError::Invalid(_))));
+    }
+
+    #[test]
+    fn hub_logins() {
+        let credentials = Credentials {
+            username: "user".to_owned(),
+            secret:   "dckr_pat_token".to_owned(),
+        };
+        let client = MockClient::default().with_response(HUB_LOGIN_URL, r#"{"token": "jwt"}"#);
+        assert_eq!(hub_login(&client, &credentials).unwrap(), "Bearer jwt");
+        assert!(matches!(hub_login(&MockClient::default(), &credentials), Err(CredentialsError::Login(..))));
+        // Test clients never send the credentials of the docker config.
+        assert_eq!(hub_authorization(&client), None);
+        assert_eq!(client.requests(), [HUB_LOGIN_URL]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn credential_helper() {
+        use std::os::unix::fs::PermissionsExt;
+
+        use crate::registries::credentials::run_helper;
+
+        let helper = std::env::temp_dir().join(format!("docker-credential-diu-{}", std::process::id()));
+        std::fs::write(
+            &helper,
+            "#!/bin/sh\nread server\nif [ \"$server\" = registry.example.com ]; then\n  echo '{\"ServerURL\":\"registry.example.com\",\"Username\":\"AWS\",\"Secret\":\"token\"}'\nelse\n  echo 'credentials not found in native keychain'\n  exit 1\nfi\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&helper, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let credentials = run_helper(&helper, "registry.example.com").unwrap().unwrap();
+        assert_eq!((credentials.username.as_str(), credentials.secret.as_str()), ("AWS", "token"));
+        assert_eq!(run_helper(&helper, "other.example.com").unwrap(), None);
+        std::fs::remove_file(&helper).unwrap();
+    }
+}
@@ -1,12 +1,22 @@
-use serde::Deserialize;
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::registries::TagPage;
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, Deserialize)]
 /// The inner response from Dockerhub when requesting a list of tags for a given
 /// image.
 pub struct DockerHubResult {
-    pub images:            Vec<HubImage>,
-    pub name:              String,
+    pub images:          Vec<HubImage>,
+    pub name:            String,
+    /// The digest of the manifest (index) of the tag.
+    #[serde(default)]
+    pub digest:          Option<String>,
+    /// When the tag was pushed last, e.g. `2025-01-01T10:00:00.000000Z`.
+    #[serde(default)]
+    pub tag_last_pushed: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -14,6 +24,8 @@ pub struct DockerHubResult {
 /// The image metadata for a dockerhub image.
 pub struct HubImage {
     pub architecture: String,
+    #[serde(default)]
+    pub digest:       Option<String>,
 }
 
 #[allow(dead_code)]
@@ -26,3 +38,76 @@ pub struct DockerHubResponse {
     previous:    Option<String>,
     pub results: Vec<DockerHubResult>,
 }
+
+impl DockerHubResponse {
+    /// Returns a response with the results of all fetched pages.
+    pub(crate) fn with_results(results: Vec<DockerHubResult>) -> Self {
+        Self { results, ..Self::default() }
+    }
+}
+
+/// The response of Dockerhub when requesting a repository, e.g.
+/// `hub.docker.com/v2/repositories/bitnami/redis/`.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct DockerHubRepository {
+    /// The trusted content program of the repository, e.g. `official` or
+    /// `verified_publisher`. Missing for community images.
+    #[serde(default)]
+    pub badge: Option<String>,
+}
+
+/// Who publishes an image, organizations may only allow the trusted content
+/// of Docker Hub, see `is_trusted`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PublisherKind {
+    /// A Docker Official Image, e.g. `node` or `library/python`.
+    Official,
+    /// An image of a Verified Publisher, e.g. `bitnami/redis`. The images
+    /// of the MCR are published by Microsoft, which is one.
+    VerifiedPublisher,
+    /// An image of the Docker-Sponsored Open Source program.
+    SponsoredOss,
+    Community,
+}
+
+impl PublisherKind {
+    /// Returns the publisher of a repository by its badge.
+    pub(crate) fn of(repository: &DockerHubRepository) -> Self {
+        match repository.badge.as_deref() {
+            Some("official") => Self::Official,
+            Some("verified_publisher") => Self::VerifiedPublisher,
+            Some("open_source") => Self::SponsoredOss,
+            _ => Self::Community,
+        }
+    }
+
+    /// Checks if the image is a Docker Official Image or one of a Verified
+    /// Publisher.
+    pub(crate) const fn is_trusted(self) -> bool {
+        matches!(self, Self::Official | Self::VerifiedPublisher)
+    }
+}
+
+impl Display for PublisherKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Official => write!(f, "Docker Official Image"),
+            Self::VerifiedPublisher => write!(f, "Verified Publisher"),
+            Self::SponsoredOss => write!(f, "Sponsored OSS"),
+            Self::Community => write!(f, "community"),
+        }
+    }
+}
+
+impl TagPage for DockerHubResponse {
+    type Entry = DockerHubResult;
+
+    fn into_parts(self) -> (Vec<DockerHubResult>, Option<String>) {
+        (self.results, self.next)
+    }
+
+    fn tag_name(entry: &DockerHubResult) -> &str {
+        &entry.name
+    }
+}
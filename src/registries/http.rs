@@ -0,0 +1,396 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, Instant};
+
+use tracing::{debug, info, trace};
+use ureq::Agent;
+
+use crate::registries::{limits, ratelimit};
+use crate::stats;
+
+/// Timeout for a single registry request.
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+/// Enables the request summaries of `--trace-http`, see `trace_request`.
+pub const TRACE_DIRECTIVE: &str = "dockerimage_updater::registries::http=trace";
+
+/// A global switch that decides if registry responses are fetched live,
+/// recorded to disk while fetching or replayed from disk.
+pub static HTTP_MODE: LazyLock<RwLock<HttpMode>> = LazyLock::new(|| RwLock::new(HttpMode::Live));
+
+/// Registry HTTP related errors.
+#[derive(Debug, thiserror::Error)]
+pub enum HttpError {
+    #[error("Request to `{0}` failed: {1}")]
+    Request(String, String),
+    #[error("Request to `{0}` failed with status {1}.")]
+    Status(String, u16),
+    #[error("Request to `{0}` was not sent, the request budget of the run is used up.")]
+    BudgetExhausted(String),
+    #[error("No recorded response for `{0}` found in `{1}`.")]
+    MissingRecording(String, PathBuf),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Describes how raw registry responses are obtained.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum HttpMode {
+    /// Query the registries directly.
+    #[default]
+    Live,
+    /// Query the registries and save every raw response into the folder.
+    Record(PathBuf),
+    /// Never query the registries, only read responses saved by `Record`.
+    Replay(PathBuf),
+}
+
+impl HttpMode {
+    /// Builds the mode from the optional record and replay folders given via
+    /// the cli. Replaying takes precedence, since it never touches the network.
+    pub(crate) fn from_dirs(record: Option<&PathBuf>, replay: Option<&PathBuf>) -> Self {
+        match (record, replay) {
+            (_, Some(replay)) => Self::Replay(replay.clone()),
+            (Some(record), None) => Self::Record(record.clone()),
+            (None, None) => Self::Live,
+        }
+    }
+}
+
+/// Sets the global http mode.
+pub fn set_mode(mode: HttpMode) {
+    info!("Using http mode: {mode:?}");
+    *HTTP_MODE.write().expect("Http mode can be written.") = mode;
+}
+
+/// Returns the file a response for the given url is recorded to. Every
+/// character that could be problematic in a file name is replaced by an
+/// underscore, e.g. `https://hub.docker.com/v2/repositories/library/node/tags?page_size=100`
+/// turns into
+/// `hub.docker.com_v2_repositories_library_node_tags_page_size_100.json`.
+pub fn recording_path(dir: &Path, url: &str) -> PathBuf {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let mut file_name: String = without_scheme
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect();
+    file_name.push_str(".json");
+    dir.join(file_name)
+}
+
+/// Returns the file a successful `HEAD` request for the given url is recorded
+/// to, i.e. the recording path prefixed with `HEAD_`.
+pub fn head_recording_path(dir: &Path, url: &str) -> PathBuf {
+    let path = recording_path(dir, url);
+    let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    dir.join(format!("HEAD_{file_name}"))
+}
+
+/// Abstraction over the http requests sent to the registries, so that the
+/// transport can be swapped, e.g. for recording, replaying or testing.
+pub trait HttpClient {
+    /// Fetches the raw body of the given url, sending the given headers.
+    fn get_with_headers(&self, url: &str, headers: &[(&str, &str)]) -> Result<String, HttpError>;
+
+    /// Sends a `HEAD` request and succeeds if the registry answers with a
+    /// success status.
+    fn head(&self, url: &str, headers: &[(&str, &str)]) -> Result<(), HttpError>;
+
+    /// Sends the json body with a `POST` request and returns the raw body of
+    /// the response, e.g. to log in to the Docker Hub API.
+    fn post_json(&self, url: &str, body: &str) -> Result<String, HttpError>;
+
+    /// Returns `true` if the fetched tags may be read from and written to the
+    /// cache files. Recording and replaying must not be influenced by
+    /// previously cached tags.
+    fn allows_file_cache(&self) -> bool {
+        false
+    }
+
+    /// Returns `true` if the credentials of the docker config may be sent.
+    /// Recordings must not contain tokens of the user and tests must not
+    /// depend on the local docker config.
+    fn sends_credentials(&self) -> bool {
+        false
+    }
+}
+
+/// Sends the requests directly to the registry.
+#[derive(Debug, Default)]
+pub struct UreqClient;
+
+/// Logs the summary of a request, e.g. `GET https://ghcr.io/v2/... -> 200 with
+/// 2048 bytes in 120 ms`. Failed requests are logged with their error.
+fn trace_request(method: &str, url: &str, started: Instant, outcome: &str) {
+    trace!("{method} {url} -> {outcome} in {} ms", started.elapsed().as_millis());
+}
+
+/// Keeps the status code of failed requests, e.g. to tell missing images apart
+/// from network errors.
+fn request_error(url: &str, error: ureq::Error) -> HttpError {
+    match error {
+        ureq::Error::StatusCode(status) => HttpError::Status(url.to_owned(), status),
+        e => HttpError::Request(url.to_owned(), e.to_string()),
+    }
+}
+
+impl UreqClient {
+    fn agent() -> Agent {
+        // build agent with global timeout
+        let config = Agent::config_builder().timeout_global(Some(Duration::from_secs(REQUEST_TIMEOUT_SECS))).build();
+        config.into()
+    }
+
+    /// Takes the request from the budget of the run, see `ratelimit::acquire`.
+    fn acquire(url: &str) -> Result<(), HttpError> {
+        let to_docker_hub = ["hub.docker.com", "docker.io"].iter().any(|host| url.contains(host));
+        if ratelimit::acquire(to_docker_hub) {
+            Ok(())
+        } else {
+            Err(HttpError::BudgetExhausted(url.to_owned()))
+        }
+    }
+
+    /// Records the rate limit budget Docker Hub sends with its responses.
+    fn record_budget<B>(response: &ureq::http::Response<B>) {
+        let header = |names: [&str; 2]| names.iter().find_map(|name| response.headers().get(*name)?.to_str().ok());
+        ratelimit::record(header(ratelimit::LIMIT_HEADERS), header(ratelimit::REMAINING_HEADERS));
+    }
+}
+
+impl HttpClient for UreqClient {
+    fn get_with_headers(&self, url: &str, headers: &[(&str, &str)]) -> Result<String, HttpError> {
+        Self::acquire(url)?;
+        let _permit = limits::acquire(url);
+        let mut request = Self::agent().get(url);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let started = Instant::now();
+        let mut response = request.call().map_err(|e| {
+            trace_request("GET", url, started, &e.to_string());
+            request_error(url, e)
+        })?;
+        debug!("Received response: {:?}", response);
+        Self::record_budget(&response);
+        let status = response.status();
+        let body = response
+            .body_mut()
+            .read_to_string()
+            .map_err(|e| HttpError::Request(url.to_owned(), e.to_string()))?;
+        trace_request("GET", url, started, &format!("{} with {} bytes", status.as_u16(), body.len()));
+        stats::http_request(body.len());
+        Ok(body)
+    }
+
+    fn head(&self, url: &str, headers: &[(&str, &str)]) -> Result<(), HttpError> {
+        Self::acquire(url)?;
+        let _permit = limits::acquire(url);
+        let mut request = Self::agent().head(url);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let started = Instant::now();
+        let response = request.call().map_err(|e| {
+            trace_request("HEAD", url, started, &e.to_string());
+            request_error(url, e)
+        })?;
+        trace_request("HEAD", url, started, &response.status().as_u16().to_string());
+        debug!("Received response: {:?}", response);
+        Self::record_budget(&response);
+        stats::http_request(0);
+        Ok(())
+    }
+
+    fn post_json(&self, url: &str, body: &str) -> Result<String, HttpError> {
+        Self::acquire(url)?;
+        let _permit = limits::acquire(url);
+        let started = Instant::now();
+        let mut response = Self::agent().post(url).header("Content-Type", "application/json").send(body).map_err(|e| {
+            trace_request("POST", url, started, &e.to_string());
+            request_error(url, e)
+        })?;
+        let status = response.status();
+        let body = response
+            .body_mut()
+            .read_to_string()
+            .map_err(|e| HttpError::Request(url.to_owned(), e.to_string()))?;
+        trace_request("POST", url, started, &format!("{} with {} bytes", status.as_u16(), body.len()));
+        stats::http_request(body.len());
+        Ok(body)
+    }
+
+    fn allows_file_cache(&self) -> bool {
+        true
+    }
+
+    fn sends_credentials(&self) -> bool {
+        true
+    }
+}
+
+/// Sends the requests to the registry and saves every raw response into a
+/// folder.
+#[derive(Debug)]
+pub struct RecordingClient {
+    inner: UreqClient,
+    dir:   PathBuf,
+}
+
+impl HttpClient for RecordingClient {
+    fn get_with_headers(&self, url: &str, headers: &[(&str, &str)]) -> Result<String, HttpError> {
+        let body = self.inner.get_with_headers(url, headers)?;
+        fs::create_dir_all(&self.dir)?;
+        let path = recording_path(&self.dir, url);
+        fs::write(&path, &body)?;
+        debug!("Recorded response for `{url}` to `{}`", path.display());
+        Ok(body)
+    }
+
+    /// Only successful requests are recorded, as empty files.
+    fn head(&self, url: &str, headers: &[(&str, &str)]) -> Result<(), HttpError> {
+        self.inner.head(url, headers)?;
+        fs::create_dir_all(&self.dir)?;
+        let path = head_recording_path(&self.dir, url);
+        fs::write(&path, "")?;
+        debug!("Recorded head response for `{url}` to `{}`", path.display());
+        Ok(())
+    }
+
+    /// Not recorded, the requests carry credentials.
+    fn post_json(&self, url: &str, body: &str) -> Result<String, HttpError> {
+        self.inner.post_json(url, body)
+    }
+}
+
+/// Never sends any request, but reads the responses saved by a
+/// `RecordingClient`.
+#[derive(Debug)]
+pub struct ReplayClient {
+    dir: PathBuf,
+}
+
+impl HttpClient for ReplayClient {
+    fn get_with_headers(&self, url: &str, _headers: &[(&str, &str)]) -> Result<String, HttpError> {
+        let path = recording_path(&self.dir, url);
+        debug!("Replaying response for `{url}` from `{}`", path.display());
+        fs::read_to_string(&path).map_err(|_| HttpError::MissingRecording(url.to_owned(), self.dir.clone()))
+    }
+
+    fn head(&self, url: &str, _headers: &[(&str, &str)]) -> Result<(), HttpError> {
+        let path = head_recording_path(&self.dir, url);
+        debug!("Replaying head response for `{url}` from `{}`", path.display());
+        if path.exists() {
+            Ok(())
+        } else {
+            Err(HttpError::MissingRecording(url.to_owned(), self.dir.clone()))
+        }
+    }
+
+    /// Logins are never recorded.
+    fn post_json(&self, url: &str, _body: &str) -> Result<String, HttpError> {
+        Err(HttpError::MissingRecording(url.to_owned(), self.dir.clone()))
+    }
+}
+
+/// Returns the client matching the global http mode.
+pub fn client() -> Box<dyn HttpClient> {
+    let mode = HTTP_MODE.read().expect("Http mode can be read.").clone();
+    match mode {
+        HttpMode::Live => Box::new(UreqClient),
+        HttpMode::Record(dir) => Box::new(RecordingClient { inner: UreqClient, dir }),
+        HttpMode::Replay(dir) => Box::new(ReplayClient { dir }),
+    }
+}
+
+/// Replays the recorded registry responses shipped with the repository, so that
+/// tests do not depend on the live registries.
+#[cfg(test)]
+pub fn replay_fixtures() {
+    set_mode(HttpMode::Replay(PathBuf::from("./tests/fixtures/registry")));
+}
+
+/// A client with canned responses, counting the requests it received.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MockClient {
+    responses: std::collections::HashMap<String, String>,
+    requests:  std::cell::RefCell<Vec<String>>,
+}
+
+#[cfg(test)]
+impl MockClient {
+    /// Adds a canned response for the given url.
+    pub(crate) fn with_response(mut self, url: &str, body: &str) -> Self {
+        self.responses.insert(url.to_owned(), body.to_owned());
+        self
+    }
+
+    /// Returns all urls that were requested so far.
+    pub(crate) fn requests(&self) -> Vec<String> {
+        self.requests.borrow().clone()
+    }
+}
+
+#[cfg(test)]
+impl HttpClient for MockClient {
+    fn get_with_headers(&self, url: &str, _headers: &[(&str, &str)]) -> Result<String, HttpError> {
+        self.requests.borrow_mut().push(url.to_owned());
+        self.responses.get(url).cloned().ok_or_else(|| HttpError::Status(url.to_owned(), 404))
+    }
+
+    fn head(&self, url: &str, headers: &[(&str, &str)]) -> Result<(), HttpError> {
+        self.get_with_headers(url, headers).map(|_| ())
+    }
+
+    fn post_json(&self, url: &str, _body: &str) -> Result<String, HttpError> {
+        self.get_with_headers(url, &[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use pretty_assertions::assert_eq;
+
+    use crate::registries::http::{HttpClient, HttpError, HttpMode, ReplayClient, recording_path};
+
+    #[test]
+    fn recording_file_names() {
+        let dir = Path::new("records");
+        assert_eq!(
+            recording_path(dir, "https://hub.docker.com/v2/repositories/library/node/tags?page_size=100"),
+            dir.join("hub.docker.com_v2_repositories_library_node_tags_page_size_100.json")
+        );
+        assert_eq!(
+            recording_path(dir, "https://mcr.microsoft.com/api/v1/catalog/dotnet/aspnet/tags?reg=mar"),
+            dir.join("mcr.microsoft.com_api_v1_catalog_dotnet_aspnet_tags_reg_mar.json")
+        );
+    }
+
+    #[test]
+    fn mode_from_dirs() {
+        let record = PathBuf::from("record");
+        let replay = PathBuf::from("replay");
+        assert_eq!(HttpMode::from_dirs(None, None), HttpMode::Live);
+        assert_eq!(HttpMode::from_dirs(Some(&record), None), HttpMode::Record(record.clone()));
+        assert_eq!(HttpMode::from_dirs(Some(&record), Some(&replay)), HttpMode::Replay(replay));
+    }
+
+    #[test]
+    fn replaying() {
+        let client = ReplayClient {
+            dir: PathBuf::from("./tests/fixtures/registry"),
+        };
+        let url = "https://hub.docker.com/v2/repositories/library/alpine/tags?page_size=100";
+        let body = client.get_with_headers(url, &[]).unwrap();
+        assert_eq!(body, fs::read_to_string(recording_path(Path::new("./tests/fixtures/registry"), url)).unwrap());
+        assert!(!client.allows_file_cache());
+
+        let missing = client.get_with_headers("https://hub.docker.com/v2/repositories/library/missing/tags?page_size=100", &[]);
+        assert!(matches!(missing, Err(HttpError::MissingRecording(_, _))));
+    }
+}
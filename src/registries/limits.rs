@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, LazyLock, Mutex, RwLock};
+
+use tracing::debug;
+
+/// The concurrency caps per registry host, see `set_concurrency`.
+static LIMITS: LazyLock<RwLock<HashMap<String, Arc<Semaphore>>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Allows a fixed amount of holders at the same time, further callers block
+/// until a permit is returned.
+#[derive(Debug)]
+pub struct Semaphore {
+    available: Mutex<usize>,
+    returned:  Condvar,
+}
+
+/// A taken permit, it is returned when dropped.
+#[derive(Debug)]
+pub struct Permit {
+    semaphore: Arc<Semaphore>,
+}
+
+impl Semaphore {
+    pub const fn new(permits: usize) -> Self {
+        Self {
+            available: Mutex::new(permits),
+            returned:  Condvar::new(),
+        }
+    }
+
+    /// Takes a permit, blocks until one is available.
+    pub fn acquire(self: &Arc<Self>) -> Permit {
+        let mut available = self
+            .returned
+            .wait_while(self.available.lock().expect("Semaphore can be locked."), |available| *available == 0)
+            .expect("Semaphore can be locked.");
+        *available -= 1;
+        drop(available);
+        Permit { semaphore: Arc::clone(self) }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        *self.semaphore.available.lock().expect("Semaphore can be locked.") += 1;
+        self.semaphore.returned.notify_one();
+    }
+}
+
+/// Sets the global concurrency caps, the keys are registry hosts, e.g.
+/// `docker.io` or `mcr.microsoft.com`. A cap of `0` is treated as `1`.
+pub fn set_concurrency(limits: HashMap<String, usize>) {
+    let limits = limits
+        .into_iter()
+        .map(|(host, permits)| (host, Arc::new(Semaphore::new(permits.max(1)))))
+        .collect();
+    *LIMITS.write().expect("Registry limits can be written.") = limits;
+}
+
+/// Returns the host of the url, e.g. `hub.docker.com` of
+/// `https://hub.docker.com/v2/repositories/library/node/tags`.
+fn host(url: &str) -> &str {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    without_scheme.split(['/', '?']).next().unwrap_or(without_scheme)
+}
+
+/// Takes a permit for a request to the url, if a cap is configured for its
+/// host or one of its parent domains, e.g. `docker.io` for
+/// `registry-1.docker.io`. The permit has to be held until the response is
+/// read.
+pub fn acquire(url: &str) -> Option<Permit> {
+    let host = host(url);
+    let semaphore = LIMITS
+        .read()
+        .expect("Registry limits can be read.")
+        .iter()
+        .find(|(registry, _)| host == registry.as_str() || host.ends_with(&format!(".{registry}")))
+        .map(|(_, semaphore)| Arc::clone(semaphore))?;
+    debug!("Waiting for a permit to request `{host}`.");
+    Some(semaphore.acquire())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Barrier};
+
+    use pretty_assertions::assert_eq;
+
+    use crate::registries::limits::{Semaphore, host};
+
+    #[test]
+    fn concurrency_caps() {
+        assert_eq!(host("https://registry-1.docker.io/v2/library/node/manifests/22"), "registry-1.docker.io");
+        assert_eq!(host("mcr.microsoft.com?x=1"), "mcr.microsoft.com");
+
+        let semaphore = Arc::new(Semaphore::new(2));
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        // Holders wait for each other, so this only finishes if two permits are
+        // taken at the same time.
+        let barrier = Barrier::new(2);
+        std::thread::scope(|scope| {
+            for _ in 0..6 {
+                scope.spawn(|| {
+                    let _permit = semaphore.acquire();
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    barrier.wait();
+                    running.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,155 @@
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::registries::credentials::Credentials;
+use crate::registries::http::{HttpClient, HttpError};
+
+/// The manifest media types that are accepted when verifying a tag. Listing the
+/// index types first makes the registries answer with the multi arch index, if
+/// there is one.
+pub const MANIFEST_ACCEPT: &str = "application/vnd.oci.image.index.v1+json, \
+                                   application/vnd.docker.distribution.manifest.list.v2+json, \
+                                   application/vnd.oci.image.manifest.v1+json, \
+                                   application/vnd.docker.distribution.manifest.v2+json";
+
+/// Manifest verification related errors.
+#[derive(Debug, thiserror::Error)]
+pub enum ManifestError {
+    #[error(transparent)]
+    Http(#[from] HttpError),
+    #[error("Could not parse response from `{0}`: {1}")]
+    InvalidResponse(String, String),
+    #[error("The manifest `{0}` does not contain the architecture `{1}`.")]
+    MissingArchitecture(String, String),
+}
+
+/// The response of the docker hub token endpoint.
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    token: String,
+}
+
+/// A manifest index (or a single manifest, in which case `manifests` is empty
+/// and the `config` names the blob with its architecture).
+#[derive(Debug, Default, Deserialize)]
+struct ManifestIndex {
+    #[serde(default)]
+    manifests: Vec<ManifestIndexEntry>,
+    #[serde(default)]
+    config:    Option<Descriptor>,
+}
+
+/// A reference to a blob of the repository, e.g. the config of an image.
+#[derive(Debug, Deserialize)]
+struct Descriptor {
+    digest: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestIndexEntry {
+    platform: Option<Platform>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Platform {
+    architecture: String,
+}
+
+/// The config blob of a single arch image, only its architecture is used.
+#[derive(Debug, Deserialize)]
+struct ImageConfig {
+    architecture: String,
+}
+
+/// Requests a pull token for the given repository from the given token
+/// endpoint, anonymously if no credentials are given.
+pub fn request_token(client: &dyn HttpClient, token_url: &str, credentials: Option<&Credentials>) -> Result<String, ManifestError> {
+    let authorization = credentials.map(Credentials::basic_auth);
+    let headers: Vec<(&str, &str)> = authorization.iter().map(|authorization| ("Authorization", authorization.as_str())).collect();
+    let body = client.get_with_headers(token_url, &headers)?;
+    serde_json::from_str::<TokenResponse>(&body)
+        .map(|response| response.token)
+        .map_err(|e| ManifestError::InvalidResponse(token_url.to_owned(), e.to_string()))
+}
+
+/// Verifies that the manifest behind the given url exists. Without an
+/// architecture a `HEAD` request is sufficient, otherwise the manifest index is
+/// fetched and has to list the architecture. Single arch manifests do not name
+/// their architecture, so the architecture of their config blob is compared.
+pub fn verify_manifest(client: &dyn HttpClient, manifest_url: &str, token: Option<&str>, arch: Option<&str>) -> Result<(), ManifestError> {
+    let authorization = token.map(|token| format!("Bearer {token}"));
+    let authorization: Vec<(&str, &str)> = authorization.iter().map(|authorization| ("Authorization", authorization.as_str())).collect();
+    let headers = [[("Accept", MANIFEST_ACCEPT)].as_slice(), &authorization].concat();
+
+    let Some(arch) = arch else {
+        debug!("Verifying manifest: {manifest_url}");
+        return Ok(client.head(manifest_url, &headers)?);
+    };
+
+    debug!("Verifying manifest for architecture `{arch}`: {manifest_url}");
+    let body = client.get_with_headers(manifest_url, &headers)?;
+    let index: ManifestIndex = serde_json::from_str(&body).map_err(|e| ManifestError::InvalidResponse(manifest_url.to_owned(), e.to_string()))?;
+    let found = if index.manifests.is_empty() {
+        let invalid = |reason: &str| ManifestError::InvalidResponse(manifest_url.to_owned(), reason.to_owned());
+        let config = index.config.ok_or_else(|| invalid("The manifest has neither manifests nor a config."))?;
+        // The config is a blob of the same repository, e.g.
+        // `/v2/library/node/blobs/sha256:...`.
+        let (repository, _) = manifest_url.rsplit_once("/manifests/").ok_or_else(|| invalid("The url is no manifest url."))?;
+        let config_url = format!("{repository}/blobs/{}", config.digest);
+        let body = client.get_with_headers(&config_url, &authorization)?;
+        let config: ImageConfig = serde_json::from_str(&body).map_err(|e| ManifestError::InvalidResponse(config_url.clone(), e.to_string()))?;
+        config.architecture == arch
+    } else {
+        index
+            .manifests
+            .iter()
+            .any(|entry| entry.platform.as_ref().is_some_and(|platform| platform.architecture == arch))
+    };
+    if found {
+        Ok(())
+    } else {
+        Err(ManifestError::MissingArchitecture(manifest_url.to_owned(), arch.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::registries::http::MockClient;
+    use crate::registries::manifest::{ManifestError, request_token, verify_manifest};
+
+    const URL: &str = "https://registry-1.docker.io/v2/library/node/manifests/24.7.0";
+
+    #[test]
+    fn verification() {
+        let index = r#"{"manifests":[{"platform":{"architecture":"amd64","os":"linux"}},{"platform":{"architecture":"arm64","os":"linux"}}]}"#;
+        let single = "https://example.com/v2/app/manifests/1.2.3-amd64";
+        let client = MockClient::default()
+            .with_response(URL, index)
+            .with_response(single, r#"{"config":{"digest":"sha256:config"}}"#)
+            .with_response("https://example.com/v2/app/blobs/sha256:config", r#"{"architecture":"amd64","os":"linux"}"#)
+            .with_response("https://example.com/v2/app/manifests/1.2.3-broken", r#"{"layers":[]}"#)
+            .with_response("https://auth.example.com/token", r#"{"token":"secret"}"#);
+
+        assert!(verify_manifest(&client, URL, None, None).is_ok());
+        assert!(verify_manifest(&client, URL, Some("secret"), Some("arm64")).is_ok());
+        assert!(matches!(
+            verify_manifest(&client, URL, None, Some("s390x")),
+            Err(ManifestError::MissingArchitecture(_, _))
+        ));
+        assert!(verify_manifest(&client, single, None, Some("amd64")).is_ok());
+        assert!(matches!(
+            verify_manifest(&client, single, None, Some("arm64")),
+            Err(ManifestError::MissingArchitecture(_, _))
+        ));
+        assert!(matches!(
+            verify_manifest(&client, "https://example.com/v2/app/manifests/1.2.3-broken", None, Some("amd64")),
+            Err(ManifestError::InvalidResponse(_, _))
+        ));
+        assert!(matches!(
+            verify_manifest(&client, "https://example.com/missing", None, None),
+            Err(ManifestError::Http(_))
+        ));
+
+        assert_eq!(request_token(&client, "https://auth.example.com/token", None).ok().as_deref(), Some("secret"));
+    }
+}
@@ -1,13 +1,59 @@
 use serde::Deserialize;
 
+use crate::registries::TagPage;
+
+/// The registry relative next links are resolved against.
+const MCR_HOST: &str = "https://mcr.microsoft.com";
+
 #[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 /// The inner response from Microsoft Container Registry when requesting a list
 /// of tags for a given image.
 pub struct McrResponseEntry {
-    pub name:            String,
-    pub architecture:    Option<String>,
+    pub name:               String,
+    pub architecture:       Option<String>,
+    pub digest:             Option<String>,
+    /// When the image was pushed last, e.g. `2025-01-01T18:00:00.000Z`.
+    #[serde(default)]
+    pub last_modified_date: Option<String>,
 }
 
 pub type McrResponse = Vec<McrResponseEntry>;
+
+/// A page of tags of the Microsoft Container Registry. Small repositories are
+/// answered with all entries at once, large ones (e.g. `dotnet/runtime`) with
+/// pages that link to the next one.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum McrPage {
+    Entries(McrResponse),
+    Paged {
+        #[serde(alias = "tags")]
+        value:     McrResponse,
+        /// An absolute url or a path on the registry, e.g.
+        /// `/api/v1/catalog/dotnet/runtime/tags?reg=mar&continuationToken=...`.
+        #[serde(default, rename = "nextLink")]
+        next_link: Option<String>,
+    },
+}
+
+impl TagPage for McrPage {
+    type Entry = McrResponseEntry;
+
+    fn into_parts(self) -> (McrResponse, Option<String>) {
+        match self {
+            Self::Entries(entries) => (entries, None),
+            Self::Paged { value, next_link } => (
+                value,
+                next_link
+                    .filter(|link| !link.is_empty())
+                    .map(|link| if link.starts_with('/') { format!("{MCR_HOST}{link}") } else { link }),
+            ),
+        }
+    }
+
+    fn tag_name(entry: &McrResponseEntry) -> &str {
+        &entry.name
+    }
+}
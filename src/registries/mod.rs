@@ -1,22 +1,185 @@
-use std::collections::HashMap;
-use std::sync::{LazyLock, RwLock};
+use std::collections::HashSet;
 
-use dockerhub::DockerHubResponse;
-use mcr::McrResponse;
+use dockerhub::{DockerHubResponse, DockerHubResult};
+use http::{HttpClient, HttpError};
+use mcr::{McrResponse, McrResponseEntry};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tracing::{info, warn};
 
+use crate::skipped::SkipStage;
 use crate::tag::Tag;
+use crate::tag::scheme::TagScheme;
+use crate::utils::Strategy;
 
+pub mod credentials;
 pub mod dockerhub;
+pub mod http;
+pub mod limits;
+pub mod manifest;
 pub mod mcr;
+pub mod ratelimit;
 
 /// The default limit of how many tags should be fetched. Can be overwritten
 /// with --tag-search-limit
 pub const TAG_RESULT_LIMIT: usize = 2000;
+/// In fast mode, fetching stops after this many consecutive pages that only
+/// contain versions lower than the current tag.
+pub const FAST_STOP_PAGES: usize = 2;
+/// Filtered queries returning fewer tags than this are repeated without the
+/// filter, in case the registry does not support it.
+pub const NAME_FILTER_MIN_RESULTS: usize = 2;
 /// Conversion constant
 pub const DURATION_HOUR_AS_SECS: u64 = 60 * 60;
-/// A cache for quicker lookups for repeated usage of already cached tags. Will
-/// be valid for max. 1 hour.
-pub static TAGS_CACHE: LazyLock<RwLock<HashMap<String, Vec<Tag>>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Settings that decide how the tags of an image are fetched.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FetchOptions {
+    /// Overrides `TAG_RESULT_LIMIT`.
+    pub limit:    Option<u16>,
+    /// Only keeps tags published for this architecture.
+    pub arch:     Option<String>,
+    /// Stops fetching tags once only older versions are returned.
+    pub fast:     bool,
+    /// Narrows the query down to the tags the strategy can select, if the
+    /// registry supports filtering by name.
+    pub strategy: Option<Strategy>,
+}
+
+impl FetchOptions {
+    /// Returns how many tags are fetched at most, `TAG_RESULT_LIMIT` unless
+    /// another limit is given.
+    pub(crate) fn max_tags(&self) -> usize {
+        self.limit.map_or(TAG_RESULT_LIMIT, usize::from)
+    }
+}
+
+/// A page of the tag list of a registry, see `fetch_pages`.
+pub trait TagPage: DeserializeOwned {
+    type Entry;
+
+    /// Returns the entries of the page and the url of the next page, if any.
+    fn into_parts(self) -> (Vec<Self::Entry>, Option<String>);
+
+    /// Returns the tag of the entry. Entries of the same tag, e.g. one per
+    /// architecture, count as a single tag towards the limit.
+    fn tag_name(entry: &Self::Entry) -> &str;
+}
+
+/// Why the first page of a tag list could not be fetched.
+#[derive(Debug, thiserror::Error)]
+pub enum PageError {
+    #[error(transparent)]
+    Request(#[from] HttpError),
+    #[error("Could not parse the tag list: {0}")]
+    Parse(#[from] serde_json::Error),
+}
+
+/// Fetches the tag list of a registry page by page, the same way for every
+/// registry: until there is no next page, the limit of the fetch options is
+/// reached or `stop` returns `true` for the entries of a page. A broken page
+/// after the first one keeps the entries fetched so far. The architecture
+/// filter is applied to the entries afterwards, see
+/// `RegistryResponse::get_tags`.
+///
+/// # Errors
+///
+/// This function will return an error if the first page can not be requested
+/// or parsed.
+pub fn fetch_pages<P: TagPage>(
+    client: &dyn HttpClient, url: String, headers: &[(&str, &str)], fetch: &FetchOptions, mut stop: impl FnMut(&[P::Entry]) -> bool,
+) -> Result<Vec<P::Entry>, PageError> {
+    let mut request_url = Some(url);
+    let mut entries = Vec::new();
+    let mut names = HashSet::new();
+    while let Some(url) = request_url.take() {
+        let page = client
+            .get_with_headers(&url, headers)
+            .map_err(PageError::from)
+            .and_then(|body| serde_json::from_str::<P>(&body).map_err(PageError::from));
+        let page = match page {
+            Ok(page) => page,
+            Err(e) if entries.is_empty() => return Err(e),
+            Err(e) => {
+                warn!("Stopped fetching tags at `{url}`, the tags fetched so far are used: {e}");
+                break;
+            }
+        };
+        let (page_entries, next) = page.into_parts();
+        if page_entries.is_empty() {
+            break;
+        }
+        let stop_here = stop(&page_entries);
+        names.extend(page_entries.iter().map(|entry| P::tag_name(entry).to_owned()));
+        entries.extend(page_entries);
+        info!("Fetched {}/{}.", names.len(), fetch.max_tags());
+        if names.len() >= fetch.max_tags() || stop_here {
+            break;
+        }
+        request_url = next;
+    }
+    info!("Fetching tags done!");
+    Ok(entries)
+}
+
+/// A single tag as published in a registry.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PublishedTag {
+    pub name:          String,
+    /// The digest of the tag, or of the image of the requested architecture.
+    pub digest:        Option<String>,
+    pub architectures: Vec<String>,
+    /// When the tag was pushed last, as reported by the registry.
+    pub pushed:        Option<String>,
+}
+
+impl PublishedTag {
+    /// Builds the published tag from a single dockerhub tag.
+    pub(crate) fn from_dockerhub(result: &DockerHubResult, arch: Option<&str>) -> Self {
+        let digest = arch.map_or_else(
+            || result.digest.clone(),
+            |arch| {
+                result
+                    .images
+                    .iter()
+                    .find(|image| image.architecture == arch)
+                    .and_then(|image| image.digest.clone())
+            },
+        );
+        Self {
+            name: result.name.clone(),
+            digest,
+            architectures: result.images.iter().map(|image| image.architecture.clone()).collect(),
+            pushed: result.tag_last_pushed.clone(),
+        }
+    }
+
+    /// Builds the published tag from the mcr entries of a single tag, there is
+    /// one entry per architecture.
+    pub(crate) fn from_mcr(name: &str, entries: &[&McrResponseEntry], arch: Option<&str>) -> Self {
+        let digest = match (arch, entries) {
+            (Some(arch), _) => entries
+                .iter()
+                .find(|entry| entry.architecture.as_deref() == Some(arch))
+                .and_then(|entry| entry.digest.clone()),
+            (None, [entry]) => entry.digest.clone(),
+            (None, _) => None,
+        };
+        Self {
+            name: name.to_owned(),
+            digest,
+            architectures: entries.iter().filter_map(|entry| entry.architecture.clone()).collect(),
+            // The timestamps have the same format, so the newest sorts last.
+            pushed: entries.iter().filter_map(|entry| entry.last_modified_date.clone()).max(),
+        }
+    }
+
+    /// Returns `true` if the tag is published for the given architecture, or
+    /// if no architecture was requested.
+    pub(crate) fn supports(&self, arch: Option<&str>) -> bool {
+        arch.is_none_or(|arch| self.architectures.iter().any(|published| published == arch))
+    }
+}
 
 #[derive(Debug)]
 pub enum RegistryResponse {
@@ -28,19 +191,39 @@ trait ResponseTagList {
     /// Returns all entries that match the given architecture (if any).
     fn filter_by_arch<'a>(&'a self, arch: Option<&str>) -> Box<dyn Iterator<Item = &'a str> + 'a>;
 
-    /// Parses tags from the filtered entries.
-    fn get_tags(&self, arch: Option<&str>) -> Vec<Tag> {
-        self.filter_by_arch(arch)
-            .filter_map(|name| {
-                // Parse the tag and return `Some(tag)` if successful, or `None` if parsing
-                // fails.
-                name.parse::<Tag>().ok()
-            })
-            .filter(|tag| tag.major.is_some() || tag.variant.is_some())
-            .collect()
+    /// Parses tags from the filtered entries. With a custom scheme, tags that
+    /// do not match it are skipped.
+    fn get_tags(&self, arch: Option<&str>, scheme: Option<&TagScheme>) -> Vec<Tag> {
+        self.filter_by_arch(arch).filter_map(|name| parse_name(name, scheme)).collect()
+    }
+
+    /// Returns the names of the entries that `get_tags` drops, together with
+    /// the filter that dropped them.
+    fn skipped_tags(&self, arch: Option<&str>, scheme: Option<&TagScheme>) -> Vec<(SkipStage, String)> {
+        let published: Vec<&str> = self.filter_by_arch(arch).collect();
+        let mut skipped: Vec<(SkipStage, String)> = self
+            .filter_by_arch(None)
+            .filter(|name| !published.contains(name))
+            .map(|name| (SkipStage::Arch, name.to_owned()))
+            .collect();
+        skipped.extend(
+            published
+                .into_iter()
+                .filter(|name| parse_name(name, scheme).is_none())
+                .map(|name| (SkipStage::Parse, name.to_owned())),
+        );
+        skipped
     }
 }
 
+/// Parses the name of a tag, with the custom scheme if there is one. Mutable
+/// tags like `latest` are kept, the candidate selection decides about them.
+fn parse_name(name: &str, scheme: Option<&TagScheme>) -> Option<Tag> {
+    scheme
+        .map_or_else(|| name.parse::<Tag>().ok(), |scheme| scheme.parse(name))
+        .filter(|tag| tag.major.is_some() || tag.variant.is_some() || tag.latest)
+}
+
 impl ResponseTagList for DockerHubResponse {
     fn filter_by_arch<'a>(&'a self, arch: Option<&str>) -> Box<dyn Iterator<Item = &'a str> + 'a> {
         let arch_owned = arch.map(std::string::ToString::to_string); // Clone `arch` to avoid lifetime issues
@@ -71,10 +254,79 @@ impl ResponseTagList for McrResponse {
 impl RegistryResponse {
     /// Returns the list of tags for a given image, optionally filtered by
     /// architecture.
-    pub(crate) fn get_tags(&self, arch: Option<&str>) -> Vec<Tag> {
+    pub(crate) fn get_tags(&self, arch: Option<&str>, scheme: Option<&TagScheme>) -> Vec<Tag> {
         match self {
-            Self::DockerHub(response) => response.get_tags(arch),
-            Self::MicrosoftContainerRegistry(response) => response.get_tags(arch),
+            Self::DockerHub(response) => response.get_tags(arch, scheme),
+            Self::MicrosoftContainerRegistry(response) => response.get_tags(arch, scheme),
         }
     }
+
+    /// Returns the names of the tags that are dropped by `get_tags`, with the
+    /// filter step that dropped them.
+    pub(crate) fn skipped_tags(&self, arch: Option<&str>, scheme: Option<&TagScheme>) -> Vec<(SkipStage, String)> {
+        match self {
+            Self::DockerHub(response) => response.skipped_tags(arch, scheme),
+            Self::MicrosoftContainerRegistry(response) => response.skipped_tags(arch, scheme),
+        }
+    }
+
+    /// Returns every tag of the response with its metadata, in the order of
+    /// the response.
+    pub(crate) fn published_tags(&self) -> Vec<PublishedTag> {
+        match self {
+            Self::DockerHub(response) => response.results.iter().map(|result| PublishedTag::from_dockerhub(result, None)).collect(),
+            Self::MicrosoftContainerRegistry(response) => {
+                let mut names: Vec<&str> = Vec::new();
+                for entry in response {
+                    if !names.contains(&entry.name.as_str()) {
+                        names.push(&entry.name);
+                    }
+                }
+                names
+                    .into_iter()
+                    .map(|name| {
+                        let entries: Vec<&McrResponseEntry> = response.iter().filter(|entry| entry.name == name).collect();
+                        PublishedTag::from_mcr(name, &entries, None)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use pretty_assertions::assert_eq;
+
+    use crate::registries::dockerhub::DockerHubResponse;
+    use crate::registries::{RegistryResponse, ResponseTagList};
+    use crate::skipped::SkipStage;
+    use crate::tag::scheme::TagScheme;
+
+    #[test]
+    fn skipped_tags() {
+        let response: DockerHubResponse = serde_json::from_str(
+            r#"{"results": [
+                {"name": "1.2.0", "images": [{"architecture": "amd64"}, {"architecture": "arm64"}]},
+                {"name": "1.3.0", "images": [{"architecture": "amd64"}]},
+                {"name": "1.3.0-rc1", "images": [{"architecture": "arm64"}]},
+                {"name": "v2", "images": [{"architecture": "arm64"}]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(response.skipped_tags(None, None), Vec::new());
+        assert_eq!(response.skipped_tags(Some("arm64"), None), vec![(SkipStage::Arch, "1.3.0".to_owned())]);
+
+        let scheme = TagScheme::new("app", r"^(?<major>\d+)\.(?<minor>\d+)\.(?<patch>\d+)$").unwrap();
+        let response = RegistryResponse::DockerHub(response);
+        assert_eq!(
+            response.skipped_tags(Some("arm64"), Some(&scheme)),
+            vec![
+                (SkipStage::Arch, "1.3.0".to_owned()),
+                (SkipStage::Parse, "1.3.0-rc1".to_owned()),
+                (SkipStage::Parse, "v2".to_owned()),
+            ]
+        );
+    }
 }
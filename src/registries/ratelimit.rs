@@ -0,0 +1,99 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use tracing::{info, warn};
+
+/// The rate limit headers of Docker Hub, the registry sends `ratelimit-*` and
+/// the hub api `x-ratelimit-*`, e.g. `ratelimit-remaining: 76;w=21600`.
+pub const LIMIT_HEADERS: [&str; 2] = ["ratelimit-limit", "x-ratelimit-limit"];
+pub const REMAINING_HEADERS: [&str; 2] = ["ratelimit-remaining", "x-ratelimit-remaining"];
+/// A warning is logged once the remaining requests drop below this share of
+/// the limit.
+const LOW_BUDGET_PERCENT: u64 = 10;
+
+/// The last budget reported by Docker Hub.
+static BUDGET: Mutex<Option<Budget>> = Mutex::new(None);
+/// The requests sent to the registries so far, see `set_max_requests`.
+static SENT: AtomicU64 = AtomicU64::new(0);
+static MAX_REQUESTS: AtomicU64 = AtomicU64::new(u64::MAX);
+/// The low budget is only warned about once per run.
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// The requests Docker Hub still allows within its window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    pub limit:     u64,
+    pub remaining: u64,
+}
+
+impl Budget {
+    /// Returns `true` if less than `LOW_BUDGET_PERCENT` of the limit is left.
+    pub const fn is_low(&self) -> bool {
+        self.remaining * 100 < self.limit * LOW_BUDGET_PERCENT
+    }
+}
+
+/// Parses the value of a rate limit header, the window is ignored, e.g. `76`
+/// of `76;w=21600`.
+pub fn parse_header(value: &str) -> Option<u64> {
+    value.split(';').next()?.trim().parse().ok()
+}
+
+/// Records the budget of a response, warns once if it runs low.
+pub fn record(limit: Option<&str>, remaining: Option<&str>) {
+    let (Some(limit), Some(remaining)) = (limit.and_then(parse_header), remaining.and_then(parse_header)) else {
+        return;
+    };
+    let budget = Budget { limit, remaining };
+    *BUDGET.lock().expect("Rate limit budget can be locked.") = Some(budget);
+    if budget.is_low() && !WARNED.swap(true, Ordering::Relaxed) {
+        warn!("Only {remaining} of {limit} Docker Hub requests are left, log in or use `--max-requests` to avoid hitting the rate limit.");
+    }
+}
+
+/// Returns the last budget reported by Docker Hub, if any.
+pub fn budget() -> Option<Budget> {
+    *BUDGET.lock().expect("Rate limit budget can be locked.")
+}
+
+/// Limits the requests sent to the registries during the run, `None` removes
+/// the limit.
+pub fn set_max_requests(max_requests: Option<u64>) {
+    MAX_REQUESTS.store(max_requests.unwrap_or(u64::MAX), Ordering::Relaxed);
+}
+
+/// Takes a request from the budget. Returns `false` if the maximum of
+/// requests is reached or Docker Hub reported an exhausted budget, so that
+/// the request is not sent.
+pub fn acquire(to_docker_hub: bool) -> bool {
+    if to_docker_hub && budget().is_some_and(|budget| budget.remaining == 0) {
+        return false;
+    }
+    SENT.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sent| {
+        (sent < MAX_REQUESTS.load(Ordering::Relaxed)).then_some(sent + 1)
+    })
+    .is_ok()
+}
+
+/// Logs the last budget reported by Docker Hub, if there is one.
+pub fn log_budget() {
+    if let Some(budget) = budget() {
+        info!("Docker Hub rate limit: {} of {} requests left.", budget.remaining, budget.limit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::registries::ratelimit::{Budget, parse_header};
+
+    #[test]
+    fn rate_limit_headers() {
+        assert_eq!(parse_header("76;w=21600"), Some(76));
+        assert_eq!(parse_header("100"), Some(100));
+        assert_eq!(parse_header("many"), None);
+        assert!(Budget { limit: 100, remaining: 9 }.is_low());
+        assert!(!Budget { limit: 100, remaining: 10 }.is_low());
+    }
+}
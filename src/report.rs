@@ -0,0 +1,1098 @@
+use std::path::PathBuf;
+
+use serde::{Serialize, Serializer};
+
+use crate::container_image::{ContainerImage, Dockerfile, ImageLocation};
+use crate::risk::Risk;
+use crate::tag::{Bump, Tag};
+use crate::utils::{ImageCandidates, OutputFormat};
+
+/// Prefix of the custom properties in the `CycloneDX` document.
+const PROPERTY_PREFIX: &str = env!("CARGO_PKG_NAME");
+
+/// The format the results of the file and multi file mode are reported in.
+#[derive(Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+#[clap(rename_all = "kebab-case")]
+pub enum ReportFormat {
+    /// Human readable log lines.
+    #[default]
+    Text,
+    /// A `CycloneDX` json document of all base images and their proposed
+    /// versions.
+    Cyclonedx,
+    /// A SARIF log with one result per outdated image, for code scanning.
+    Sarif,
+    /// Azure `DevOps` logging commands, one warning per outdated image.
+    Azure,
+    /// GitHub Actions workflow commands, one warning per outdated image.
+    GithubAnnotations,
+    /// A markdown table of the outdated images, e.g. for a pull request
+    /// comment.
+    Markdown,
+}
+
+/// A document of a mode, that can be written by every `Reporter`.
+pub trait Report: Serialize {
+    /// The human readable lines.
+    fn text(&self) -> Vec<String>;
+
+    /// The lines of a markdown document, usually a table.
+    fn markdown(&self) -> Vec<String>;
+
+    /// The findings for code scanning formats, most modes have none.
+    fn findings(&self) -> &[Finding] {
+        &[]
+    }
+
+    fn summary(&self) -> Option<&ScanSummary> {
+        None
+    }
+
+    /// The structural issues of the scanned files, they are reported next to
+    /// the findings.
+    fn diagnostics(&self) -> &[Diagnostic] {
+        &[]
+    }
+}
+
+/// Writes reports in one format. A new format only needs an implementation
+/// and a variant in `OutputFormat` or `ReportFormat`.
+pub trait Reporter {
+    fn render<R: Report>(&self, report: &R) -> String;
+}
+
+pub struct TextReporter;
+
+impl Reporter for TextReporter {
+    fn render<R: Report>(&self, report: &R) -> String {
+        report.text().join("\n")
+    }
+}
+
+pub struct JsonReporter;
+
+impl Reporter for JsonReporter {
+    fn render<R: Report>(&self, report: &R) -> String {
+        serde_json::to_string(report).expect("Report can be turned into json string.")
+    }
+}
+
+pub struct MarkdownReporter;
+
+impl Reporter for MarkdownReporter {
+    fn render<R: Report>(&self, report: &R) -> String {
+        report.markdown().join("\n")
+    }
+}
+
+/// Writes the findings of a report as SARIF log, see `sarif`.
+pub struct SarifReporter;
+
+impl Reporter for SarifReporter {
+    fn render<R: Report>(&self, report: &R) -> String {
+        let log = sarif(report.findings()).with_diagnostics(report.diagnostics());
+        let log = if let Some(summary) = report.summary() {
+            log.with_summary(summary)
+        } else {
+            log
+        };
+        serde_json::to_string_pretty(&log).expect("Report can be turned into json string.")
+    }
+}
+
+/// Prints the report of a mode in the given format.
+pub fn print<R: Report>(format: &OutputFormat, report: &R) {
+    let document = match format {
+        OutputFormat::Text => TextReporter.render(report),
+        OutputFormat::Json => JsonReporter.render(report),
+        OutputFormat::Markdown => MarkdownReporter.render(report),
+    };
+    println!("{document}");
+}
+
+/// Renders a markdown table with the given header, pipes within the cells are
+/// escaped.
+pub fn markdown_table<const N: usize>(header: [&str; N], rows: impl IntoIterator<Item = [String; N]>) -> Vec<String> {
+    let line = |cells: &[String]| format!("| {} |", cells.join(" | "));
+    let mut lines = vec![line(&header.map(str::to_owned)), line(&header.map(|_| "---".to_owned()))];
+    lines.extend(rows.into_iter().map(|row| line(&row.map(|cell| cell.replace('|', "\\|")))));
+    lines
+}
+
+/// Renders a plain text table with the given header, all columns but the last
+/// are padded to the same width.
+pub fn text_table<const N: usize>(header: [&str; N], rows: impl IntoIterator<Item = [String; N]>) -> Vec<String> {
+    let rows: Vec<[String; N]> = std::iter::once(header.map(str::to_owned)).chain(rows).collect();
+    let widths: [usize; N] = std::array::from_fn(|column| rows.iter().map(|row| row[column].chars().count()).max().unwrap_or_default());
+    rows.iter()
+        .map(|row| {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(widths)
+                .enumerate()
+                .map(|(column, (cell, width))| if column + 1 == N { cell.clone() } else { format!("{cell:<width$}") })
+                .collect();
+            cells.join("  ")
+        })
+        .collect()
+}
+
+/// A base image of a dockerfile, with its candidate if there is one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub file:      Option<PathBuf>,
+    /// The line of the `FROM` instruction, starting at 1.
+    pub line:      usize,
+    /// The column the image starts at, starting at 1.
+    pub column:    usize,
+    #[serde(serialize_with = "serialize_display")]
+    pub image:     ContainerImage,
+    #[serde(serialize_with = "serialize_optional_display")]
+    pub candidate: Option<Tag>,
+    /// The risk of the update to the candidate. Fresh tags are only detected
+    /// with `--max-risk`, since their push date needs another request.
+    pub risk:      Option<Risk>,
+}
+
+fn serialize_display<T: std::fmt::Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+#[allow(clippy::ref_option)]
+fn serialize_optional_display<T: std::fmt::Display, S: Serializer>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.collect_str(value),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// The findings of the file modes, with the summary of a scan of several
+/// files.
+#[derive(Debug, Serialize)]
+pub struct FindingsReport<'a> {
+    pub findings:    &'a [Finding],
+    pub summary:     Option<&'a ScanSummary>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub diagnostics: &'a [Diagnostic],
+}
+
+impl Report for FindingsReport<'_> {
+    fn text(&self) -> Vec<String> {
+        self.findings
+            .iter()
+            .filter_map(|finding| Some(format!("{}: {}", finding.location(), finding.message()?)))
+            .chain(
+                self.diagnostics
+                    .iter()
+                    .map(|diagnostic| format!("{}: warning: {}", diagnostic.location(), diagnostic.message)),
+            )
+            .chain(self.summary.map(ToString::to_string))
+            .collect()
+    }
+
+    /// One table per severity, so that e.g. all patch updates can be reviewed
+    /// at once.
+    fn markdown(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for severity in Severity::ALL {
+            let rows: Vec<[String; 4]> = self
+                .findings
+                .iter()
+                .filter(|finding| finding.severity() == Some(severity))
+                .filter_map(|finding| {
+                    let candidate = finding.candidate.as_ref()?;
+                    Some([
+                        format!("{}:{}", finding.path(), finding.line),
+                        format!("`{}`", finding.image),
+                        format!("`{}:{candidate}`", finding.image.get_dockerimage_name()),
+                        finding.risk.as_ref().map(ToString::to_string).unwrap_or_default(),
+                    ])
+                })
+                .collect();
+            if rows.is_empty() {
+                continue;
+            }
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.extend([format!("### {} updates ({})", severity.title(), rows.len()), String::new()]);
+            lines.extend(markdown_table(["File", "Image", "Candidate", "Risk"], rows));
+        }
+        if !self.diagnostics.is_empty() {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.extend([format!("### Warnings ({})", self.diagnostics.len()), String::new()]);
+            lines.extend(markdown_table(
+                ["File", "Warning"],
+                self.diagnostics
+                    .iter()
+                    .map(|diagnostic| [format!("{}:{}", diagnostic.path(), diagnostic.line), diagnostic.message.clone()]),
+            ));
+        }
+        if let Some(summary) = self.summary {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(summary.to_string());
+        }
+        lines
+    }
+
+    fn findings(&self) -> &[Finding] {
+        self.findings
+    }
+
+    fn summary(&self) -> Option<&ScanSummary> {
+        self.summary
+    }
+
+    fn diagnostics(&self) -> &[Diagnostic] {
+        self.diagnostics
+    }
+}
+
+/// A structural issue of a dockerfile, e.g. a duplicate stage name. The file
+/// is updated regardless, see `StructureWarning`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub file:    Option<PathBuf>,
+    /// The line of the issue, starting at 1.
+    pub line:    usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Returns the path of the file with forward slashes, e.g.
+    /// `app/Dockerfile`.
+    pub(crate) fn path(&self) -> String {
+        self.file
+            .as_ref()
+            .map(|file| file.to_string_lossy().trim_start_matches("./").replace('\\', "/"))
+            .unwrap_or_default()
+    }
+
+    /// Returns the location of the issue, e.g. `app/Dockerfile:3`.
+    pub(crate) fn location(&self) -> String {
+        format!(
+            "{}:{}",
+            self.file.as_ref().map(|file| file.display().to_string()).unwrap_or_default(),
+            self.line
+        )
+    }
+}
+
+/// Collects the structural issues of the given dockerfiles and the files they
+/// include.
+pub fn collect_diagnostics(dockerfiles: &[Dockerfile]) -> Vec<Diagnostic> {
+    dockerfiles
+        .iter()
+        .flat_map(Dockerfile::get_warnings)
+        .map(|(file, warning)| Diagnostic {
+            file:    file.cloned(),
+            line:    warning.line(),
+            message: warning.to_string(),
+        })
+        .collect()
+}
+
+impl Finding {
+    /// Returns the path of the file with forward slashes, e.g.
+    /// `app/Dockerfile`.
+    pub(crate) fn path(&self) -> String {
+        self.file
+            .as_ref()
+            .map(|file| file.to_string_lossy().trim_start_matches("./").replace('\\', "/"))
+            .unwrap_or_default()
+    }
+
+    /// Returns the message for an outdated image, `None` if the image has no
+    /// candidate.
+    pub(crate) fn message(&self) -> Option<String> {
+        self.candidate.as_ref().map(|candidate| {
+            let risk = self
+                .risk
+                .as_ref()
+                .filter(|risk| risk.score > 0)
+                .map(|risk| format!(" (risk {risk})"))
+                .unwrap_or_default();
+            format!(
+                "Base image `{}` can be updated to `{}:{candidate}`{risk}.",
+                self.image,
+                self.image.get_dockerimage_name()
+            )
+        })
+    }
+
+    /// Returns the severity of the update to the candidate, `None` if the image
+    /// has no candidate.
+    pub(crate) fn severity(&self) -> Option<Severity> {
+        self.candidate.as_ref().map(|candidate| Severity::between(self.image.get_tag(), candidate))
+    }
+
+    /// Returns the location of the image, e.g. `app/Dockerfile:3`.
+    pub(crate) fn location(&self) -> String {
+        format!(
+            "{}:{}",
+            self.file.as_ref().map(|file| file.display().to_string()).unwrap_or_default(),
+            self.line
+        )
+    }
+}
+
+/// The bucket a proposed update is grouped in, by the part of the version
+/// that changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+#[clap(rename_all = "kebab-case")]
+pub enum Severity {
+    /// Includes rebuilds, e.g. `-r8` to `-r9`, and candidates with the same
+    /// version.
+    Patch,
+    Minor,
+    Major,
+    /// The version stays the same, but the variant changes, e.g. `bookworm` to
+    /// `trixie`.
+    Variant,
+}
+
+impl Severity {
+    /// All severities, from the least to the most likely to break the image.
+    pub const ALL: [Self; 4] = [Self::Patch, Self::Minor, Self::Major, Self::Variant];
+
+    /// Returns the severity of the update from the current to the candidate
+    /// tag.
+    pub(crate) fn between(current: &Tag, candidate: &Tag) -> Self {
+        Self::of(current.bump(candidate))
+    }
+
+    /// Checks if updates of the severity are written, see `--apply`. All of
+    /// them are, if no severities are given.
+    pub(crate) fn is_applied(self, apply: &[Self]) -> bool {
+        apply.is_empty() || apply.contains(&self)
+    }
+
+    pub const fn of(bump: Bump) -> Self {
+        match bump {
+            Bump::Major => Self::Major,
+            Bump::Minor => Self::Minor,
+            Bump::Variant => Self::Variant,
+            Bump::Patch | Bump::Rebuild | Bump::None => Self::Patch,
+        }
+    }
+
+    const fn title(self) -> &'static str {
+        match self {
+            Self::Patch => "Patch",
+            Self::Minor => "Minor",
+            Self::Major => "Major",
+            Self::Variant => "Variant",
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.title().to_ascii_lowercase())
+    }
+}
+
+/// The number of proposed updates per severity.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SeverityCounts {
+    pub patch:   usize,
+    pub minor:   usize,
+    pub major:   usize,
+    pub variant: usize,
+}
+
+impl SeverityCounts {
+    pub(crate) const fn get(&self, severity: Severity) -> usize {
+        match severity {
+            Severity::Patch => self.patch,
+            Severity::Minor => self.minor,
+            Severity::Major => self.major,
+            Severity::Variant => self.variant,
+        }
+    }
+
+    const fn add(&mut self, severity: Severity) {
+        match severity {
+            Severity::Patch => self.patch += 1,
+            Severity::Minor => self.minor += 1,
+            Severity::Major => self.major += 1,
+            Severity::Variant => self.variant += 1,
+        }
+    }
+}
+
+/// The outcome of scanning several dockerfiles, e.g. `12 files scanned, 7
+/// updates available across 5 files (5 patch, 2 major), 2 errors`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ScanSummary {
+    pub files_scanned:      usize,
+    /// The outdated `FROM` instructions.
+    pub updates_available:  usize,
+    pub files_with_updates: usize,
+    /// The outdated `FROM` instructions grouped by the severity of the update.
+    pub by_severity:        SeverityCounts,
+    /// Files that could not be read or whose config is invalid, and images
+    /// whose tags could not be fetched.
+    pub errors:             usize,
+}
+
+impl ScanSummary {
+    pub fn new(files_scanned: usize, findings: &[Finding], errors: usize) -> Self {
+        let outdated: Vec<&Finding> = findings.iter().filter(|finding| finding.candidate.is_some()).collect();
+        let mut files: Vec<&Option<PathBuf>> = outdated.iter().map(|finding| &finding.file).collect();
+        files.sort();
+        files.dedup();
+        let mut by_severity = SeverityCounts::default();
+        for severity in outdated.iter().filter_map(|finding| finding.severity()) {
+            by_severity.add(severity);
+        }
+        Self {
+            files_scanned,
+            updates_available: outdated.len(),
+            files_with_updates: files.len(),
+            by_severity,
+            errors,
+        }
+    }
+}
+
+impl std::fmt::Display for ScanSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plural = |count: usize, word: &str| format!("{count} {word}{}", if count == 1 { "" } else { "s" });
+        let severities: Vec<String> = Severity::ALL
+            .iter()
+            .map(|&severity| (self.by_severity.get(severity), severity))
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, severity)| format!("{count} {severity}"))
+            .collect();
+        let severities = if severities.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", severities.join(", "))
+        };
+        write!(
+            f,
+            "{} scanned, {} available across {}{severities}, {}",
+            plural(self.files_scanned, "file"),
+            plural(self.updates_available, "update"),
+            plural(self.files_with_updates, "file"),
+            plural(self.errors, "error")
+        )
+    }
+}
+
+/// Collects all base images of the given dockerfiles with their candidates,
+/// ignored images are reported without a candidate.
+pub fn collect_findings(dockerfiles: &[Dockerfile], candidates: &ImageCandidates, ignore_versions: &[ContainerImage]) -> Vec<Finding> {
+    dockerfiles
+        .iter()
+        .flat_map(|dockerfile| {
+            dockerfile
+                .get_base_image_lines()
+                .into_iter()
+                .filter(|location| !location.image.is_empty())
+                .map(|ImageLocation { file, line, column, image }| {
+                    let candidate = if image.find_ignored(ignore_versions).is_some() {
+                        None
+                    } else {
+                        candidates.get(&image.to_string()).cloned()
+                    };
+                    Finding {
+                        file: file.cloned(),
+                        line,
+                        column,
+                        image: image.clone(),
+                        risk: candidate.as_ref().map(|candidate| Risk::assess(image, candidate, false)),
+                        candidate,
+                    }
+                })
+        })
+        .collect()
+}
+
+/// A `CycloneDX` bill of materials, only the needed fields are modelled.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bom {
+    #[serde(rename = "bomFormat")]
+    format:       &'static str,
+    spec_version: &'static str,
+    version:      u32,
+    metadata:     BomMetadata,
+    components:   Vec<BomComponent>,
+}
+
+#[derive(Debug, Serialize)]
+struct BomMetadata {
+    tools:      BomTools,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    properties: Vec<BomProperty>,
+}
+
+#[derive(Debug, Serialize)]
+struct BomTools {
+    components: Vec<BomTool>,
+}
+
+#[derive(Debug, Serialize)]
+struct BomTool {
+    #[serde(rename = "type")]
+    kind:    &'static str,
+    name:    &'static str,
+    version: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct BomComponent {
+    #[serde(rename = "type")]
+    kind:       &'static str,
+    #[serde(rename = "bom-ref")]
+    bom_ref:    String,
+    name:       String,
+    version:    String,
+    purl:       String,
+    properties: Vec<BomProperty>,
+}
+
+#[derive(Debug, Serialize)]
+struct BomProperty {
+    name:  String,
+    value: String,
+}
+
+impl BomProperty {
+    fn new(name: &str, value: String) -> Self {
+        Self {
+            name: format!("{PROPERTY_PREFIX}:{name}"),
+            value,
+        }
+    }
+}
+
+/// Builds a `CycloneDX` document with one component per unique image. The
+/// proposed version and the locations of the image are added as properties.
+pub fn cyclonedx(findings: &[Finding]) -> Bom {
+    let mut components = Vec::<BomComponent>::new();
+    for finding in findings {
+        let purl = finding.image.get_purl(finding.image.get_tag());
+        let location = BomProperty::new("location", finding.location());
+        if let Some(component) = components.iter_mut().find(|component| component.purl == purl) {
+            component.properties.push(location);
+            continue;
+        }
+        let mut properties = Vec::new();
+        if let Some(candidate) = &finding.candidate {
+            properties.push(BomProperty::new("proposed-version", candidate.to_string()));
+            properties.push(BomProperty::new("proposed-purl", finding.image.get_purl(candidate)));
+        }
+        properties.push(location);
+        components.push(BomComponent {
+            kind: "container",
+            bom_ref: purl.clone(),
+            name: finding.image.get_dockerimage_name(),
+            version: finding.image.get_tag().to_string(),
+            purl,
+            properties,
+        });
+    }
+    Bom {
+        format: "CycloneDX",
+        spec_version: "1.5",
+        version: 1,
+        metadata: BomMetadata {
+            tools:      BomTools {
+                components: vec![BomTool {
+                    kind:    "application",
+                    name:    env!("CARGO_PKG_NAME"),
+                    version: env!("CARGO_PKG_VERSION"),
+                }],
+            },
+            properties: Vec::new(),
+        },
+        components,
+    }
+}
+
+impl Bom {
+    /// Adds the summary of the scan as properties of the metadata.
+    pub(crate) fn with_summary(mut self, summary: &ScanSummary) -> Self {
+        self.metadata.properties = vec![
+            BomProperty::new("files-scanned", summary.files_scanned.to_string()),
+            BomProperty::new("updates-available", summary.updates_available.to_string()),
+            BomProperty::new("files-with-updates", summary.files_with_updates.to_string()),
+            BomProperty::new("patch-updates", summary.by_severity.patch.to_string()),
+            BomProperty::new("minor-updates", summary.by_severity.minor.to_string()),
+            BomProperty::new("major-updates", summary.by_severity.major.to_string()),
+            BomProperty::new("variant-updates", summary.by_severity.variant.to_string()),
+            BomProperty::new("errors", summary.errors.to_string()),
+        ];
+        self
+    }
+}
+
+/// Identifier of the rule all outdated images are reported with.
+const SARIF_RULE_ID: &str = "outdated-base-image";
+/// Identifier of the rule the structural issues are reported with.
+const SARIF_STRUCTURE_RULE_ID: &str = "dockerfile-structure";
+
+/// A SARIF log, only the needed fields are modelled.
+#[derive(Debug, Serialize)]
+pub struct SarifLog {
+    #[serde(rename = "$schema")]
+    schema:  &'static str,
+    version: &'static str,
+    runs:    Vec<SarifRun>,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifRun {
+    tool:       SarifTool,
+    results:    Vec<SarifResult>,
+    /// The property bag of the run, holds the summary of the scan.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    properties: Option<SarifProperties>,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifProperties {
+    summary: ScanSummary,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifTool {
+    driver: SarifDriver,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifDriver {
+    name:    &'static str,
+    version: &'static str,
+    rules:   Vec<SarifRule>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRule {
+    id:                &'static str,
+    short_description: SarifMessage,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifMessage {
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id:   &'static str,
+    level:     &'static str,
+    message:   SarifMessage,
+    locations: Vec<SarifLocation>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation {
+    physical_location: SarifPhysicalLocation,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysicalLocation {
+    artifact_location: SarifArtifactLocation,
+    region:            SarifRegion,
+}
+
+#[derive(Debug, Serialize)]
+struct SarifArtifactLocation {
+    uri: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRegion {
+    start_line:   usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_column: Option<usize>,
+}
+
+/// Builds a SARIF log with one result per outdated image, pointing at the line
+/// of its `FROM` instruction. Images without a candidate are not reported.
+pub fn sarif(findings: &[Finding]) -> SarifLog {
+    let results = findings
+        .iter()
+        .filter_map(|finding| {
+            Some(SarifResult {
+                rule_id:   SARIF_RULE_ID,
+                level:     "warning",
+                message:   SarifMessage { text: finding.message()? },
+                locations: vec![SarifLocation {
+                    physical_location: SarifPhysicalLocation {
+                        artifact_location: SarifArtifactLocation { uri: finding.path() },
+                        region:            SarifRegion {
+                            start_line:   finding.line,
+                            start_column: Some(finding.column),
+                        },
+                    },
+                }],
+            })
+        })
+        .collect();
+    SarifLog {
+        schema:  "https://json.schemastore.org/sarif-2.1.0.json",
+        version: "2.1.0",
+        runs:    vec![SarifRun {
+            tool: SarifTool {
+                driver: SarifDriver {
+                    name:    env!("CARGO_PKG_NAME"),
+                    version: env!("CARGO_PKG_VERSION"),
+                    rules:   vec![SarifRule {
+                        id:                SARIF_RULE_ID,
+                        short_description: SarifMessage {
+                            text: "A newer version of the base image is available.".to_owned(),
+                        },
+                    }],
+                },
+            },
+            results,
+            properties: None,
+        }],
+    }
+}
+
+impl SarifLog {
+    /// Adds the summary of the scan to the property bag of the run.
+    pub(crate) fn with_summary(mut self, summary: &ScanSummary) -> Self {
+        for run in &mut self.runs {
+            run.properties = Some(SarifProperties { summary: *summary });
+        }
+        self
+    }
+
+    /// Adds the structural issues as results of their own rule, which is only
+    /// listed if there are any.
+    pub(crate) fn with_diagnostics(mut self, diagnostics: &[Diagnostic]) -> Self {
+        if diagnostics.is_empty() {
+            return self;
+        }
+        for run in &mut self.runs {
+            run.tool.driver.rules.push(SarifRule {
+                id:                SARIF_STRUCTURE_RULE_ID,
+                short_description: SarifMessage {
+                    text: "The stages of the dockerfile are not structured as expected.".to_owned(),
+                },
+            });
+            run.results.extend(diagnostics.iter().map(|diagnostic| SarifResult {
+                rule_id:   SARIF_STRUCTURE_RULE_ID,
+                level:     "warning",
+                message:   SarifMessage {
+                    text: diagnostic.message.clone(),
+                },
+                locations: vec![SarifLocation {
+                    physical_location: SarifPhysicalLocation {
+                        artifact_location: SarifArtifactLocation { uri: diagnostic.path() },
+                        region:            SarifRegion {
+                            start_line:   diagnostic.line,
+                            start_column: None,
+                        },
+                    },
+                }],
+            }));
+        }
+        self
+    }
+}
+
+/// Escapes the data of a logging command, see
+/// <https://github.com/actions/toolkit/blob/main/packages/core/src/command.ts>.
+fn escape_data(data: &str) -> String {
+    data.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Escapes a property of a logging command, which may not contain the
+/// separators of the command either.
+fn escape_property(property: &str) -> String {
+    escape_data(property)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+        .replace(';', "%3B")
+        .replace(']', "%5D")
+}
+
+/// Returns one logging command per outdated image, in the format of the given
+/// pipeline.
+pub fn annotations(format: &ReportFormat, findings: &[Finding]) -> Vec<String> {
+    findings
+        .iter()
+        .filter_map(|finding| {
+            let message = escape_data(&finding.message()?);
+            let (path, line, column) = (escape_property(&finding.path()), finding.line, finding.column);
+            match format {
+                ReportFormat::Azure => Some(format!(
+                    "##vso[task.logissue type=warning;sourcepath={path};linenumber={line};columnnumber={column};]{message}"
+                )),
+                ReportFormat::GithubAnnotations => Some(format!("::warning file={path},line={line},col={column},title=Outdated base image::{message}")),
+                ReportFormat::Text | ReportFormat::Cyclonedx | ReportFormat::Sarif | ReportFormat::Markdown => None,
+            }
+        })
+        .collect()
+}
+
+/// Returns one logging command per structural issue, in the format of the
+/// given pipeline.
+pub fn diagnostic_annotations(format: &ReportFormat, diagnostics: &[Diagnostic]) -> Vec<String> {
+    diagnostics
+        .iter()
+        .filter_map(|diagnostic| {
+            let message = escape_data(&diagnostic.message);
+            let (path, line) = (escape_property(&diagnostic.path()), diagnostic.line);
+            match format {
+                ReportFormat::Azure => Some(format!("##vso[task.logissue type=warning;sourcepath={path};linenumber={line};]{message}")),
+                ReportFormat::GithubAnnotations => Some(format!("::warning file={path},line={line},title=Dockerfile structure::{message}")),
+                ReportFormat::Text | ReportFormat::Cyclonedx | ReportFormat::Sarif | ReportFormat::Markdown => None,
+            }
+        })
+        .collect()
+}
+
+/// Prints the findings in the given format, nothing is printed for the text
+/// format since the results are logged while processing. The summary of a
+/// scan of several files is added to the json documents, the structural
+/// issues are reported by the formats for code scanning and review.
+pub fn print_findings(format: &ReportFormat, findings: &[Finding], diagnostics: &[Diagnostic], summary: Option<&ScanSummary>) {
+    let report = FindingsReport {
+        findings,
+        summary,
+        diagnostics,
+    };
+    match format {
+        ReportFormat::Text => {}
+        ReportFormat::Cyclonedx => {
+            let bom = cyclonedx(findings);
+            let bom = if let Some(summary) = summary { bom.with_summary(summary) } else { bom };
+            println!("{}", serde_json::to_string_pretty(&bom).expect("Report can be turned into json string."));
+        }
+        ReportFormat::Sarif => println!("{}", SarifReporter.render(&report)),
+        ReportFormat::Markdown => println!("{}", MarkdownReporter.render(&report)),
+        ReportFormat::Azure | ReportFormat::GithubAnnotations => {
+            for annotation in annotations(format, findings).into_iter().chain(diagnostic_annotations(format, diagnostics)) {
+                println!("{annotation}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use std::path::PathBuf;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::container_image::Dockerfile;
+    use crate::report::{
+        Diagnostic, FindingsReport, JsonReporter, MarkdownReporter, Report, ReportFormat, Reporter, SarifReporter, ScanSummary, Severity, SeverityCounts,
+        TextReporter, annotations, collect_diagnostics, collect_findings, cyclonedx, diagnostic_annotations, markdown_table, sarif,
+    };
+    use crate::tag::Bump;
+    use crate::utils::ImageCandidates;
+
+    const CONTENT: &str = "FROM node:22.1.0 AS build\nRUN npm ci\nFROM mcr.microsoft.com/dotnet/aspnet:9.0.0\nFROM build\nFROM node:22.1.0\n";
+
+    fn findings() -> Vec<crate::report::Finding> {
+        let mut dockerfile = Dockerfile::parse(CONTENT).unwrap();
+        dockerfile.set_path("app/Dockerfile");
+        let candidates = ImageCandidates::from([("node:22.1.0".to_owned(), "22.2.0".parse().unwrap())]);
+        collect_findings(&[dockerfile], &candidates, &[])
+    }
+
+    #[test]
+    fn findings_with_lines() {
+        let findings = findings();
+        let lines: Vec<(String, Option<String>)> = findings
+            .iter()
+            .map(|finding| (finding.location(), finding.candidate.as_ref().map(ToString::to_string)))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                ("app/Dockerfile:1".to_owned(), Some("22.2.0".to_owned())),
+                ("app/Dockerfile:3".to_owned(), None),
+                ("app/Dockerfile:5".to_owned(), Some("22.2.0".to_owned())),
+            ]
+        );
+        assert_eq!(findings[0].file, Some(PathBuf::from("app/Dockerfile")));
+    }
+
+    #[test]
+    fn scan_summary() {
+        let summary = ScanSummary::new(3, &findings(), 1);
+        assert_eq!(summary.to_string(), "3 files scanned, 2 updates available across 1 file (2 minor), 1 error");
+        assert_eq!(
+            ScanSummary::new(1, &[], 0).to_string(),
+            "1 file scanned, 0 updates available across 0 files, 0 errors"
+        );
+
+        let bom = serde_json::to_value(cyclonedx(&findings()).with_summary(&summary)).unwrap();
+        assert_eq!(
+            bom["metadata"]["properties"][1],
+            serde_json::json!({"name": "dockerimage-updater:updates-available", "value": "2"})
+        );
+        let log = serde_json::to_value(sarif(&findings()).with_summary(&summary)).unwrap();
+        assert_eq!(
+            log["runs"][0]["properties"]["summary"],
+            serde_json::json!({
+                "files_scanned": 3,
+                "updates_available": 2,
+                "files_with_updates": 1,
+                "by_severity": {"patch": 0, "minor": 2, "major": 0, "variant": 0},
+                "errors": 1
+            })
+        );
+        assert!(serde_json::to_value(sarif(&findings())).unwrap()["runs"][0].get("properties").is_none());
+
+        let mut mixed = findings();
+        mixed[0].candidate = Some("23.0.0".parse().unwrap());
+        let summary = ScanSummary::new(1, &mixed, 0);
+        assert_eq!(
+            summary.by_severity,
+            SeverityCounts {
+                patch:   0,
+                minor:   1,
+                major:   1,
+                variant: 0,
+            }
+        );
+        let report = FindingsReport {
+            findings:    &mixed,
+            summary:     Some(&summary),
+            diagnostics: &[],
+        };
+        let headings: Vec<String> = report.markdown().into_iter().filter(|line| line.starts_with("###")).collect();
+        assert_eq!(headings, ["### Minor updates (1)", "### Major updates (1)"]);
+        assert_eq!(Severity::of(Bump::Rebuild), Severity::Patch);
+    }
+
+    #[test]
+    fn reporters() {
+        let findings = findings();
+        let summary = ScanSummary::new(1, &findings, 0);
+        let report = FindingsReport {
+            findings:    &findings,
+            summary:     Some(&summary),
+            diagnostics: &[],
+        };
+        assert_eq!(
+            MarkdownReporter.render(&report),
+            "### Minor updates (2)\n\n| File | Image | Candidate | Risk |\n| --- | --- | --- | --- |\n| app/Dockerfile:1 | `node:22.1.0` | \
+             `node:22.2.0` | 0 |\n| app/Dockerfile:5 | `node:22.1.0` | `node:22.2.0` | 0 |\n\n1 file scanned, 2 updates available across 1 file (2 \
+             minor), 0 errors"
+        );
+        assert_eq!(
+            TextReporter.render(&report).lines().next(),
+            Some("app/Dockerfile:1: Base image `node:22.1.0` can be updated to `node:22.2.0`.")
+        );
+        let json: serde_json::Value = serde_json::from_str(&JsonReporter.render(&report)).unwrap();
+        assert_eq!(
+            json["findings"][0],
+            serde_json::json!({
+                "file": "app/Dockerfile",
+                "line": 1,
+                "column": 6,
+                "image": "node:22.1.0",
+                "candidate": "22.2.0",
+                "risk": {"score": 0, "factors": []}
+            })
+        );
+        let log: serde_json::Value = serde_json::from_str(&SarifReporter.render(&report)).unwrap();
+        assert_eq!(log["runs"][0]["results"].as_array().unwrap().len(), 2);
+
+        assert_eq!(markdown_table(["A"], [["x|y".to_owned()]]), ["| A |", "| --- |", "| x\\|y |"]);
+    }
+
+    #[test]
+    fn cyclonedx_document() {
+        let bom = serde_json::to_value(cyclonedx(&findings())).unwrap();
+        assert_eq!(bom["bomFormat"], "CycloneDX");
+        let components = bom["components"].as_array().unwrap();
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[0]["purl"], "pkg:docker/node@22.1.0");
+        assert_eq!(components[0]["version"], "22.1.0");
+        assert_eq!(
+            components[0]["properties"],
+            serde_json::json!([
+                {"name": "dockerimage-updater:proposed-version", "value": "22.2.0"},
+                {"name": "dockerimage-updater:proposed-purl", "value": "pkg:docker/node@22.2.0"},
+                {"name": "dockerimage-updater:location", "value": "app/Dockerfile:1"},
+                {"name": "dockerimage-updater:location", "value": "app/Dockerfile:5"},
+            ])
+        );
+        assert_eq!(components[1]["purl"], "pkg:docker/dotnet/aspnet@9.0.0?repository_url=mcr.microsoft.com");
+        assert_eq!(components[1]["name"], "mcr.microsoft.com/dotnet/aspnet");
+    }
+
+    #[test]
+    fn sarif_log() {
+        let log = serde_json::to_value(sarif(&findings())).unwrap();
+        assert_eq!(log["version"], "2.1.0");
+        let results = log["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["ruleId"], "outdated-base-image");
+        assert_eq!(results[0]["message"]["text"], "Base image `node:22.1.0` can be updated to `node:22.2.0`.");
+        assert_eq!(
+            results[1]["locations"][0]["physicalLocation"],
+            serde_json::json!({"artifactLocation": {"uri": "app/Dockerfile"}, "region": {"startLine": 5, "startColumn": 6}})
+        );
+    }
+
+    #[test]
+    fn diagnostics() {
+        let mut dockerfile = Dockerfile::parse("FROM node:22.1.0 AS build\nFROM node:22.1.0 AS build\n").unwrap();
+        dockerfile.set_path("app/Dockerfile");
+        let diagnostics = collect_diagnostics(&[dockerfile]);
+        assert_eq!(
+            diagnostics,
+            [Diagnostic {
+                file:    Some(PathBuf::from("app/Dockerfile")),
+                line:    2,
+                message: "The stage `build` is already defined in line 1.".to_owned(),
+            }]
+        );
+        let log = serde_json::to_value(sarif(&findings()).with_diagnostics(&diagnostics)).unwrap();
+        assert_eq!(log["runs"][0]["tool"]["driver"]["rules"][1]["id"], "dockerfile-structure");
+        assert_eq!(
+            log["runs"][0]["results"][2]["locations"][0]["physicalLocation"]["region"],
+            serde_json::json!({"startLine": 2})
+        );
+        assert_eq!(
+            diagnostic_annotations(&ReportFormat::GithubAnnotations, &diagnostics),
+            ["::warning file=app/Dockerfile,line=2,title=Dockerfile structure::The stage `build` is already defined in line 1."]
+        );
+    }
+
+    #[test]
+    fn pipeline_annotations() {
+        assert_eq!(
+            annotations(&ReportFormat::GithubAnnotations, &findings()),
+            [
+                "::warning file=app/Dockerfile,line=1,col=6,title=Outdated base image::Base image `node:22.1.0` can be updated to `node:22.2.0`.",
+                "::warning file=app/Dockerfile,line=5,col=6,title=Outdated base image::Base image `node:22.1.0` can be updated to `node:22.2.0`.",
+            ]
+        );
+        let mut findings = findings();
+        findings[0].file = Some("./app,v2;x/Dockerfile".into());
+        assert_eq!(
+            annotations(&ReportFormat::Azure, &findings[..1]),
+            [
+                "##vso[task.logissue type=warning;sourcepath=app%2Cv2%3Bx/Dockerfile;linenumber=1;columnnumber=6;]Base image `node:22.1.0` can be updated to `node:22.2.0`."
+            ]
+        );
+        assert!(annotations(&ReportFormat::Sarif, &findings).is_empty());
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::container_image::ContainerImage;
+use crate::eol;
+use crate::tag::Tag;
+
+/// A property of an update that makes it more likely to break the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RiskFactor {
+    /// The major version changes.
+    Major,
+    /// The variant changes, e.g. `alpine3.21` to `alpine3.22`.
+    Variant,
+    /// The candidate is a pre-release, e.g. `10.0.0-rc.1`.
+    Prerelease,
+    /// The candidate was pushed only recently, see `--min-age`.
+    Fresh,
+    /// The candidate belongs to another release cycle with its own end of
+    /// life, e.g. `alpine:3.21` to `alpine:3.22`.
+    EolBoundary,
+}
+
+impl RiskFactor {
+    /// The amount the factor adds to the score.
+    pub const fn weight(self) -> u8 {
+        match self {
+            Self::Major => 3,
+            Self::Variant | Self::Prerelease | Self::EolBoundary => 2,
+            Self::Fresh => 1,
+        }
+    }
+}
+
+impl Display for RiskFactor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Major => write!(f, "major"),
+            Self::Variant => write!(f, "variant"),
+            Self::Prerelease => write!(f, "pre-release"),
+            Self::Fresh => write!(f, "fresh"),
+            Self::EolBoundary => write!(f, "eol boundary"),
+        }
+    }
+}
+
+/// The risk of an update, the sum of the weights of its factors.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Risk {
+    pub score:   u8,
+    pub factors: Vec<RiskFactor>,
+}
+
+impl Risk {
+    /// Assesses the update of the image to the candidate. Whether the candidate
+    /// is fresh needs its push date, so it is decided by the caller.
+    pub fn assess(image: &ContainerImage, candidate: &Tag, fresh: bool) -> Self {
+        let current = image.get_tag();
+        let name = image.get_dockerimage_name();
+        let crosses_eol = eol::find_cycle(&name, current).is_some_and(|cycle| eol::find_cycle(&name, candidate) != Some(cycle));
+        let factors: Vec<RiskFactor> = [
+            (current.major != candidate.major, RiskFactor::Major),
+            (current.release_variant() != candidate.release_variant(), RiskFactor::Variant),
+            (candidate.is_prerelease(), RiskFactor::Prerelease),
+            (fresh, RiskFactor::Fresh),
+            (crosses_eol, RiskFactor::EolBoundary),
+        ]
+        .into_iter()
+        .filter_map(|(applies, factor)| applies.then_some(factor))
+        .collect();
+        Self {
+            score: factors.iter().map(|factor| factor.weight()).sum(),
+            factors,
+        }
+    }
+}
+
+impl Display for Risk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.factors.is_empty() {
+            return write!(f, "{}", self.score);
+        }
+        let factors: Vec<String> = self.factors.iter().map(ToString::to_string).collect();
+        write!(f, "{} ({})", self.score, factors.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use pretty_assertions::assert_eq;
+
+    use crate::container_image::ContainerImage;
+    use crate::risk::{Risk, RiskFactor};
+
+    #[test]
+    fn risk_scores() {
+        let risk = |image: &str, candidate: &str, fresh| Risk::assess(&image.parse::<ContainerImage>().unwrap(), &candidate.parse().unwrap(), fresh);
+        assert_eq!(risk("node:22.1.0", "22.2.0", false), Risk::default());
+        assert_eq!(risk("node:20.1.0", "22.2.0", false).factors, [RiskFactor::Major, RiskFactor::EolBoundary]);
+        assert_eq!(risk("node:20.1.0", "22.2.0", true).score, 6);
+        assert_eq!(risk("alpine:3.21.0", "3.22.1", false).to_string(), "2 (eol boundary)");
+        assert_eq!(risk("redis:7.2-alpine3.21", "7.4-alpine3.22", false).factors, [RiskFactor::Variant]);
+        assert_eq!(risk("dotnet/aspnet:9.0.0", "10.0.0-rc.1", false).score, 5);
+    }
+}
@@ -0,0 +1,1240 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::{Captures, Regex};
+
+use crate::marker::{self, Marker};
+use crate::yaml_path::{self, KeyPath, YamlPath};
+
+/// The `<image>` element of a jib configuration in a `pom.xml`, e.g.
+/// `<image>eclipse-temurin:21-jre</image>`.
+static MAVEN_IMAGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?<prefix>.*?<image>\s*)(?<image>[^<\s]+)(?<suffix>\s*</image>.*)$").expect("Maven image regex is valid."));
+/// An image assignment of a jib configuration in a `build.gradle(.kts)`, e.g.
+/// `jib.from.image = 'eclipse-temurin:21-jre'` or `image = "..."` within a
+/// `from` block.
+static GRADLE_IMAGE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(?<prefix>.*?\b(?<from>(?:jib\.)?from\.)?image\s*=\s*['"])(?<image>[^'"\s]+)(?<suffix>['"].*)$"#).expect("Gradle image regex is valid.")
+});
+/// The opening of a `from { ... }` block in a gradle file.
+static GRADLE_FROM_BLOCK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\bfrom\s*\{").expect("Gradle from block regex is valid."));
+/// The opening of a terraform variable, e.g. `variable "sidecar_image" {`.
+static TERRAFORM_VARIABLE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"^\s*variable\s+"[^"]*"\s*\{"#).expect("Terraform variable regex is valid."));
+/// A string assignment in terraform, e.g. `default =
+/// "envoyproxy/envoy:v1.31.0"`.
+static TERRAFORM_ASSIGNMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(?<prefix>\s*(?<name>[A-Za-z_][A-Za-z0-9_-]*)\s*=\s*")(?<image>[^"\s]+)(?<suffix>".*)$"#).expect("Terraform assignment regex is valid.")
+});
+/// A tagged image reference, strings in terraform are only updated if they
+/// look like one, e.g. `envoyproxy/envoy:v1.31.0`.
+static IMAGE_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[a-z0-9][a-z0-9._/-]*(?::[0-9]+/[a-z0-9._/-]+)?:[A-Za-z0-9_][A-Za-z0-9._-]*$").expect("Image pattern regex is valid."));
+/// The opening of the docker builder of a packer template, e.g. `source
+/// "docker" "ubuntu" {`.
+static PACKER_DOCKER_SOURCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^\s*source\s+"docker"\s+"[^"]*"\s*\{\s*$"#).expect("Packer docker source regex is valid."));
+/// The task key of the ansible modules that run containers, e.g.
+/// `- community.docker.docker_container:`.
+static ANSIBLE_MODULE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?<indent>\s*(?:-\s+)?)(?:community\.docker\.)?docker_(?:container|swarm_service)\s*:\s*(?:#.*)?$").expect("Ansible module regex is valid.")
+});
+/// The `image` option of an ansible module, e.g. `image: "nginx:1.27.0"`.
+static ANSIBLE_IMAGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^(?<prefix>\s*image\s*:\s*["']?)(?<image>[^"'\s#]+)(?<suffix>["']?(?:\s.*)?)$"#).expect("Ansible image regex is valid."));
+/// A scalar item of a YAML list, e.g. `- postgres:16.3` or `- "redis:7.4.0"`.
+static YAML_ITEM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^(?<prefix>\s*-\s+["']?)(?<image>[^"'\s#|>&*!{\[]+)(?<suffix>["']?(?:\s.*)?)$"#).expect("YAML item regex is valid."));
+/// A scalar value of a YAML key, e.g. `image: "nginx:1.27.0"`, `tag: 7.4.0`
+/// or the anchor definition `x-base-image: &base-image node:20.11`.
+static YAML_VALUE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(?<prefix>\s*(?:-\s+)?["']?[\w./-]+["']?\s*:\s+(?:&[\w.-]+\s+)?["']?)(?<image>[^"'\s#|>&*!{\[]+)(?<suffix>["']?(?:\s.*)?)$"#)
+        .expect("YAML value regex is valid.")
+});
+/// The scalar of a line with a marker comment, e.g. `tag: 1.27.0 # diu:
+/// image=nginx` or `- nginx:1.27.0 # diu:`.
+static MARKED_VALUE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(?<prefix>\s*(?:-\s+)?(?:["']?[\w./-]+["']?\s*:\s+)?["']?)(?<image>[^"'\s#]+)(?<suffix>["']?\s*#.*)$"#)
+        .expect("Marked value regex is valid.")
+});
+/// A field of a kustomize image, e.g. `- name: nginx` or `newTag: "1.27.0"`.
+static KUSTOMIZE_FIELD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(?<prefix>\s*(?:-\s+)?(?<key>name|newName|newTag|digest)\s*:\s*["']?)(?<value>[^"'\s#]+)(?<suffix>["']?(?:\s.*)?)$"#)
+        .expect("Kustomize field regex is valid.")
+});
+/// The opening of an image pull of bazel, e.g. `oci.pull(` of `rules_oci` or
+/// `container_pull(` of `rules_docker`.
+static BAZEL_PULL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(?:oci\.pull|container_pull)\s*\(\s*$").expect("Bazel pull regex is valid."));
+/// An attribute of a bazel image pull, e.g. `tag = "1.27.0",`.
+static BAZEL_ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(?<prefix>\s*(?<key>image|registry|repository|tag|digest)\s*=\s*")(?<value>[^"\s]+)(?<suffix>".*)$"#)
+        .expect("Bazel attribute regex is valid.")
+});
+
+/// The anchor of a YAML value, e.g. `&base-image` of `x-base-image:
+/// &base-image node:20.11`.
+static YAML_ANCHOR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"&(?<anchor>[\w.-]+)\s").expect("YAML anchor regex is valid."));
+/// An alias of a YAML value, e.g. `image: *base-image`.
+static YAML_ALIAS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*image\s*:\s*\*(?<alias>[\w.-]+)\s*(?:#.*)?$").expect("YAML alias regex is valid."));
+
+/// The values of a compose file that are updated: the images of the services
+/// and the images of the `x-` extension fields that are merged into the
+/// services (e.g. `<<: *base`). The scalars of `x-` fields are only updated if
+/// their anchor is the image of a service (e.g. `image: *base-image`), see
+/// `LineScanner::with_image_aliases`.
+const COMPOSE_PATHS: [&str; 2] = ["services.*.image", "x-*.image"];
+/// The images of a GitLab CI config: the default and the job images, and the
+/// services, each either as string or as mapping with a `name`.
+const GITLAB_CI_PATHS: [&str; 8] = [
+    "image",
+    "*.image",
+    "image.name",
+    "*.image.name",
+    "services[*]",
+    "*.services[*]",
+    "services[*].name",
+    "*.services[*].name",
+];
+/// The images of the docker executors of the jobs and the reusable executors
+/// of a `.circleci/config.yml`.
+const CIRCLECI_PATHS: [&str; 2] = ["jobs.*.docker[*].image", "executors.*.docker[*].image"];
+/// The images of the steps and services of a Drone or Woodpecker pipeline,
+/// listed or keyed by their name, and of the `pipeline` of older Woodpecker
+/// versions.
+const DRONE_PATHS: [&str; 5] = ["steps[*].image", "steps.*.image", "services[*].image", "services.*.image", "pipeline.*.image"];
+
+/// The hidden files and folders of CI configs, that are searched with `--ci`
+/// even though hidden files are skipped.
+pub const HIDDEN_CI_CONFIGS: [&str; 8] = [
+    ".gitlab-ci.yml",
+    ".gitlab-ci.yaml",
+    ".circleci",
+    ".drone.yml",
+    ".drone.yaml",
+    ".woodpecker.yml",
+    ".woodpecker.yaml",
+    ".woodpecker",
+];
+
+/// The hosts of Docker Hub, which are left out of the image names of bazel
+/// pulls, e.g. `index.docker.io/library/nginx`.
+const DOCKERHUB_HOSTS: [&str; 3] = ["index.docker.io", "registry-1.docker.io", "docker.io"];
+
+/// Marks the terraform variables and locals, whose images are updated, e.g.
+/// `# dockerimage-updater` in the line above or at the end of the line.
+const MARKER: &str = "dockerimage-updater";
+
+/// The kinds of files whose image references can be updated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileKind {
+    #[default]
+    Dockerfile,
+    /// The jib plugin configuration in a `pom.xml`.
+    Maven,
+    /// The jib plugin configuration in a `build.gradle` or `build.gradle.kts`.
+    Gradle,
+    /// The variables and locals of a `.tf` file, that are marked with a
+    /// comment.
+    Terraform,
+    /// The `docker_container` and `docker_swarm_service` tasks of an ansible
+    /// playbook, and the marked values and the values of the configured YAML
+    /// paths of any YAML file.
+    Ansible,
+    /// The `images` transformer of a `kustomization.yaml`.
+    Kustomize,
+    /// The services and the `x-` extension fields of a compose file, e.g.
+    /// `compose.yaml` or `docker-compose.prod.yml`, and the marked values and
+    /// the values of the configured YAML paths like in any YAML file.
+    Compose,
+    /// The image pulls of `rules_oci` and `rules_docker` in a `MODULE.bazel`
+    /// or `WORKSPACE` file.
+    Bazel,
+    /// The `image` of the docker builders of a packer template, e.g.
+    /// `ubuntu.pkr.hcl`.
+    Packer,
+    /// The job images and services of a `.gitlab-ci.yml`, and the marked
+    /// values and the values of the configured YAML paths.
+    GitlabCi,
+    /// The docker executors of a `.circleci/config.yml`, and the marked values
+    /// and the values of the configured YAML paths.
+    CircleCi,
+    /// The step and service images of a `.drone.yml`, or of a Woodpecker
+    /// pipeline which has the same keys (`.woodpecker.yml` or the files of the
+    /// `.woodpecker` folder), and the marked values and the values of the
+    /// configured YAML paths.
+    Drone,
+}
+
+impl FileKind {
+    /// Decides the kind by the file name, every unknown file is read as
+    /// dockerfile.
+    pub(crate) fn of(path: &Path) -> Self {
+        match path.file_name().map(|name| name.to_string_lossy().to_ascii_lowercase()).as_deref() {
+            Some("pom.xml") => Self::Maven,
+            Some("build.gradle" | "build.gradle.kts") => Self::Gradle,
+            Some("kustomization.yaml" | "kustomization.yml" | "kustomization") => Self::Kustomize,
+            Some(name) if is_compose_file(name) => Self::Compose,
+            Some("module.bazel" | "workspace" | "workspace.bazel") => Self::Bazel,
+            Some(name) if name.ends_with(".pkr.hcl") => Self::Packer,
+            Some(".gitlab-ci.yml" | ".gitlab-ci.yaml") => Self::GitlabCi,
+            Some("config.yml" | "config.yaml") if path.parent().and_then(Path::file_name).is_some_and(|parent| parent == ".circleci") => Self::CircleCi,
+            Some(".drone.yml" | ".drone.yaml" | ".woodpecker.yml" | ".woodpecker.yaml") => Self::Drone,
+            _ if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("tf")) => Self::Terraform,
+            _ if is_yaml(path) && path.parent().and_then(Path::file_name).is_some_and(|parent| parent == ".woodpecker") => Self::Drone,
+            _ if is_yaml(path) => Self::Ansible,
+            _ => Self::Dockerfile,
+        }
+    }
+
+    /// Returns the YAML paths whose values are updated in files of the kind,
+    /// on top of the configured ones.
+    const fn builtin_yaml_paths(self) -> &'static [&'static str] {
+        match self {
+            Self::Compose => &COMPOSE_PATHS,
+            Self::GitlabCi => &GITLAB_CI_PATHS,
+            Self::CircleCi => &CIRCLECI_PATHS,
+            Self::Drone => &DRONE_PATHS,
+            Self::Dockerfile | Self::Maven | Self::Gradle | Self::Terraform | Self::Ansible | Self::Kustomize | Self::Bazel | Self::Packer => &[],
+        }
+    }
+}
+
+/// Checks if the file has a YAML extension, `.yml` or `.yaml`.
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("yml") || extension.eq_ignore_ascii_case("yaml"))
+}
+
+/// Checks if the lowercase file name is a compose file, including overrides
+/// like `docker-compose.override.yml`.
+fn is_compose_file(name: &str) -> bool {
+    name.strip_suffix(".yml").or_else(|| name.strip_suffix(".yaml")).is_some_and(|stem| {
+        ["compose", "docker-compose"]
+            .iter()
+            .any(|base| stem.strip_prefix(base).is_some_and(|rest| rest.is_empty() || rest.starts_with('.')))
+    })
+}
+
+/// The part of an image a reference holds.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ReferencePart {
+    /// The whole image, e.g. `nginx:1.27.0`.
+    #[default]
+    Image,
+    /// Only the tag, e.g. `newTag: 1.27.0` of a kustomize image.
+    Tag,
+    /// The digest of the tag referenced before, e.g. `digest: sha256:...` of a
+    /// kustomize image. It is not updated on its own, but looked up again once
+    /// the tag changed.
+    Digest(String),
+}
+
+/// An image reference within a line, with the text around it that is kept as
+/// it is. References to a part of an image carry the whole image, e.g.
+/// `nginx:1.27.0` for `newTag: 1.27.0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageReference<'a> {
+    pub prefix: &'a str,
+    pub image:  Cow<'a, str>,
+    pub suffix: &'a str,
+    pub part:   ReferencePart,
+}
+
+impl<'a> ImageReference<'a> {
+    fn new(prefix: &'a str, image: Cow<'a, str>, suffix: &'a str, part: ReferencePart) -> Option<Self> {
+        // Images set by variables (`${...}`, `{{ ... }}`), pinned by digest or taken
+        // from the local daemon (`docker://`) are left alone.
+        if image.contains(['$', '{', '@']) || image.contains("://") {
+            return None;
+        }
+        Some(Self { prefix, image, suffix, part })
+    }
+
+    fn from_captures(captures: &Captures<'a>) -> Option<Self> {
+        Self::new(
+            captures.name("prefix")?.as_str(),
+            Cow::Borrowed(captures.name("image")?.as_str()),
+            captures.name("suffix")?.as_str(),
+            ReferencePart::Image,
+        )
+    }
+}
+
+/// The scanners that search YAML files besides the YAML paths and markers,
+/// see `DiscoveryOptions::yaml_scanners`. A file that is given explicitly is
+/// searched by all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct YamlScanners {
+    /// The `image` options of ansible container tasks, see `--ansible`.
+    pub ansible: bool,
+    /// The built-in YAML paths of compose files, see `--compose`.
+    pub compose: bool,
+    /// The built-in YAML paths of CI configs, see `--ci`.
+    pub ci:      bool,
+}
+
+impl YamlScanners {
+    pub const ALL: Self = Self {
+        ansible: true,
+        compose: true,
+        ci:      true,
+    };
+
+    /// Returns whether the built-in YAML paths of the kind are searched, e.g.
+    /// the service images of compose files only with `--compose`.
+    const fn searches_builtin(self, kind: FileKind) -> bool {
+        match kind {
+            FileKind::Compose => self.compose,
+            FileKind::GitlabCi | FileKind::CircleCi | FileKind::Drone => self.ci,
+            FileKind::Dockerfile
+            | FileKind::Maven
+            | FileKind::Gradle
+            | FileKind::Terraform
+            | FileKind::Ansible
+            | FileKind::Kustomize
+            | FileKind::Bazel
+            | FileKind::Packer => false,
+        }
+    }
+}
+
+impl Default for YamlScanners {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+/// The attributes of a bazel image pull that make up its image, collected
+/// before its lines are scanned, so that their order does not matter.
+#[derive(Debug, Default)]
+struct BazelPull {
+    image:    Option<String>,
+    registry: Option<String>,
+    tag:      Option<String>,
+}
+
+/// Finds the base image references of a build file line by line. For jib only
+/// the image of the `from` configuration is returned, the image that is built
+/// (`to`) is skipped.
+// Every kind of file keeps its own state.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Default)]
+pub struct LineScanner {
+    kind:           FileKind,
+    /// The brace depth within a gradle `from` block or the docker source of a
+    /// packer template, `0` outside of it.
+    from_depth:     usize,
+    /// Whether the line is within a maven `<from>` element.
+    in_from:        bool,
+    /// Whether a marker comment was seen, that was not used by an assignment
+    /// yet.
+    marked:         bool,
+    /// Whether the line is within a marked terraform variable, only its
+    /// `default` is updated then.
+    in_variable:    bool,
+    /// The indentation of the options of the ansible module the line is in.
+    module_indent:  Option<usize>,
+    /// Whether the line is within the `images` of a kustomization.
+    in_images:      bool,
+    /// Whether the line is within the arguments of a bazel image pull.
+    in_pull:        bool,
+    /// The image of the current kustomize entry or bazel pull, `newName`
+    /// replaces `name`.
+    entry_image:    Option<String>,
+    /// The `registry` of the current `container_pull`, its `repository` is the
+    /// image.
+    entry_registry: Option<String>,
+    /// The `newTag` of the current kustomize entry or the `tag` of the bazel
+    /// pull.
+    entry_tag:      Option<String>,
+    /// The selectors of the values that are updated in YAML files.
+    yaml_paths:     Vec<YamlPath>,
+    /// The scanners of YAML files that run.
+    scanners:       YamlScanners,
+    /// The keys of the current YAML line.
+    keys:           KeyPath,
+    /// The last `repository` value and the keys of its mapping, for a `tag`
+    /// that follows.
+    repository:     Option<(Vec<String>, String)>,
+    /// The repositories of the Flux image policies, marked with `:name`.
+    policy_names:   HashMap<String, String>,
+    /// The anchors that are used as image of a compose service, their `x-`
+    /// definitions are updated.
+    image_aliases:  HashSet<String>,
+    /// The bazel pulls that are not scanned yet, see `with_bazel_pulls`.
+    bazel_pulls:    VecDeque<BazelPull>,
+}
+
+impl LineScanner {
+    pub(crate) fn new(kind: FileKind, scanners: YamlScanners) -> Self {
+        let builtin = if scanners.searches_builtin(kind) { kind.builtin_yaml_paths() } else { &[] };
+        let yaml_paths = match kind {
+            FileKind::Ansible | FileKind::Compose | FileKind::GitlabCi | FileKind::CircleCi | FileKind::Drone => yaml_path::yaml_paths()
+                .into_iter()
+                .chain(builtin.iter().map(|path| path.parse().expect("Built-in YAML paths are valid.")))
+                .collect(),
+            FileKind::Dockerfile | FileKind::Maven | FileKind::Gradle | FileKind::Terraform | FileKind::Kustomize | FileKind::Bazel | FileKind::Packer => {
+                Vec::new()
+            }
+        };
+        Self {
+            kind,
+            yaml_paths,
+            scanners,
+            ..Self::default()
+        }
+    }
+
+    /// Collects the aliases that are used as image of a compose service, e.g.
+    /// `base-image` of `image: *base-image`, so that their anchor definitions
+    /// are updated. Other kinds have no such aliases.
+    pub(crate) fn with_image_aliases(mut self, content: &str) -> Self {
+        if self.kind != FileKind::Compose {
+            return self;
+        }
+        let service_image: YamlPath = "services.*.image".parse().expect("Service image path is valid.");
+        let mut keys = KeyPath::default();
+        for line in content.lines() {
+            if keys.advance(line)
+                && service_image.matches(&keys.segments())
+                && let Some(captures) = YAML_ALIAS.captures(line)
+            {
+                self.image_aliases.insert(captures["alias"].to_owned());
+            }
+        }
+        self
+    }
+
+    /// Collects the attributes of the bazel pulls up to their closing `)`, so
+    /// that a `digest` before the `tag` or an `image` after it is found as
+    /// well. Other kinds have no such pulls.
+    pub(crate) fn with_bazel_pulls(mut self, content: &str) -> Self {
+        if self.kind != FileKind::Bazel {
+            return self;
+        }
+        let mut pull = None;
+        for line in content.lines() {
+            if BAZEL_PULL.is_match(line) {
+                // An unclosed pull still counts, so that the pulls stay in line.
+                self.bazel_pulls.extend(pull.replace(BazelPull::default()));
+            } else if line.trim_start().starts_with(')') {
+                self.bazel_pulls.extend(pull.take());
+            } else if let Some(pull) = pull.as_mut()
+                && let Some(captures) = BAZEL_ATTRIBUTE.captures(line)
+            {
+                let value = Some(captures["value"].to_owned());
+                match &captures["key"] {
+                    "image" | "repository" => pull.image = value,
+                    "registry" => pull.registry = value,
+                    "tag" => pull.tag = value,
+                    _ => {}
+                }
+            }
+        }
+        self.bazel_pulls.extend(pull);
+        self
+    }
+
+    /// Returns the base image reference of the line, if any.
+    pub(crate) fn scan<'a>(&mut self, line: &'a str) -> Option<ImageReference<'a>> {
+        match self.kind {
+            FileKind::Dockerfile => None,
+            FileKind::Maven => self.scan_maven(line),
+            FileKind::Gradle => self.scan_gradle(line),
+            FileKind::Terraform => self.scan_terraform(line),
+            // The other YAML files have no ansible tasks, but are searched like playbooks.
+            FileKind::Ansible | FileKind::Compose | FileKind::GitlabCi | FileKind::CircleCi | FileKind::Drone => {
+                // Every scanner has to follow the lines, markers take precedence.
+                let selected = self.scan_yaml_paths(line);
+                let module = if self.scanners.ansible { self.scan_ansible(line) } else { None };
+                self.scan_marker(line).or(selected).or(module)
+            }
+            FileKind::Kustomize => self.scan_kustomize(line),
+            FileKind::Bazel => self.scan_bazel(line),
+            FileKind::Packer => self.scan_packer(line),
+        }
+    }
+
+    fn scan_maven<'a>(&mut self, line: &'a str) -> Option<ImageReference<'a>> {
+        let reference = MAVEN_IMAGE
+            .captures(line)
+            .filter(|captures| within_from(&captures["prefix"], self.in_from))
+            .and_then(|captures| ImageReference::from_captures(&captures));
+        self.in_from = within_from(line, self.in_from);
+        reference
+    }
+
+    fn scan_gradle<'a>(&mut self, line: &'a str) -> Option<ImageReference<'a>> {
+        let reference = GRADLE_IMAGE
+            .captures(line)
+            .filter(|captures| captures.name("from").is_some() || self.from_depth > 0 || GRADLE_FROM_BLOCK.is_match(&captures["prefix"]))
+            .and_then(|captures| ImageReference::from_captures(&captures));
+        let rest = if self.from_depth == 0 {
+            let Some(block) = GRADLE_FROM_BLOCK.find(line) else {
+                return reference;
+            };
+            self.from_depth = 1;
+            &line[block.end()..]
+        } else {
+            line
+        };
+        for character in rest.chars() {
+            match character {
+                '{' => self.from_depth += 1,
+                '}' => self.from_depth -= 1,
+                _ => {}
+            }
+            if self.from_depth == 0 {
+                break;
+            }
+        }
+        reference
+    }
+
+    fn scan_terraform<'a>(&mut self, line: &'a str) -> Option<ImageReference<'a>> {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('#') || trimmed.starts_with("//") {
+            self.marked |= trimmed.contains(MARKER);
+            return None;
+        }
+        if !self.marked && !has_marker_comment(line) {
+            return None;
+        }
+        if TERRAFORM_VARIABLE.is_match(line) {
+            self.in_variable = true;
+            return None;
+        }
+        let captures = TERRAFORM_ASSIGNMENT.captures(line);
+        if self.in_variable && captures.as_ref().is_none_or(|captures| &captures["name"] != "default") {
+            // A variable without a default ends with its block.
+            if trimmed.starts_with('}') {
+                self.marked = false;
+                self.in_variable = false;
+            }
+            return None;
+        }
+        self.marked = false;
+        self.in_variable = false;
+        captures
+            .filter(|captures| IMAGE_PATTERN.is_match(&captures["image"]))
+            .and_then(|captures| ImageReference::from_captures(&captures))
+    }
+
+    /// Returns the `image` of a multi-line `source "docker"` block, the
+    /// attributes of nested blocks are skipped.
+    fn scan_packer<'a>(&mut self, line: &'a str) -> Option<ImageReference<'a>> {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('#') || trimmed.starts_with("//") {
+            return None;
+        }
+        if self.from_depth == 0 {
+            if PACKER_DOCKER_SOURCE.is_match(line) {
+                self.from_depth = 1;
+            }
+            return None;
+        }
+        let reference = TERRAFORM_ASSIGNMENT
+            .captures(line)
+            .filter(|captures| self.from_depth == 1 && &captures["name"] == "image")
+            .and_then(|captures| ImageReference::from_captures(&captures));
+        for character in line.chars() {
+            match character {
+                '{' => self.from_depth += 1,
+                '}' => self.from_depth = self.from_depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        reference
+    }
+
+    fn scan_ansible<'a>(&mut self, line: &'a str) -> Option<ImageReference<'a>> {
+        if let Some(module) = ANSIBLE_MODULE.captures(line) {
+            // The options are indented deeper than the module name, which follows the
+            // list item marker if any.
+            self.module_indent = Some(module["indent"].len() + 1);
+            return None;
+        }
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        let module_indent = self.module_indent?;
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            return None;
+        }
+        if indent < module_indent {
+            self.module_indent = None;
+            return None;
+        }
+        ANSIBLE_IMAGE.captures(line).and_then(|captures| ImageReference::from_captures(&captures))
+    }
+
+    /// Returns the value of a line that is marked with `# diu:` or a Flux image
+    /// policy, see `marker::parse`. The strategy of a marker stays in the
+    /// suffix of the reference, see `Dockerfile::marked_image_strategies`.
+    fn scan_marker<'a>(&mut self, line: &'a str) -> Option<ImageReference<'a>> {
+        let marker = marker::parse(line)?;
+        let captures = MARKED_VALUE.captures(line)?;
+        let value = captures.name("image")?.as_str();
+        let (image, part) = match marker {
+            Marker::Image { .. } => (value.to_owned(), ReferencePart::Image),
+            Marker::Tag { image, .. } => (format!("{image}:{value}"), ReferencePart::Tag),
+            Marker::PolicyTag(policy) => (format!("{}:{value}", self.policy_names.get(&policy)?), ReferencePart::Tag),
+            Marker::PolicyName(policy) => {
+                self.policy_names.insert(policy, value.to_owned());
+                return None;
+            }
+        };
+        ImageReference::new(captures.name("prefix")?.as_str(), Cow::Owned(image), captures.name("suffix")?.as_str(), part)
+    }
+
+    /// Returns the value of a selected YAML path, e.g. `spec.values.image` of a
+    /// Flux `HelmRelease`. If the path selects a mapping, its `tag` is returned
+    /// with the `repository` that comes before it, like in most Helm charts.
+    fn scan_yaml_paths<'a>(&mut self, line: &'a str) -> Option<ImageReference<'a>> {
+        if self.yaml_paths.is_empty() {
+            return None;
+        }
+        if !self.keys.advance(line) {
+            // A scalar list item, e.g. `- postgres:16.3` of the `services` of a job.
+            let path = self.keys.segments();
+            return YAML_ITEM
+                .captures(line)
+                .filter(|captures| IMAGE_PATTERN.is_match(&captures["image"]) && self.yaml_paths.iter().any(|selector| selector.matches(&path)))
+                .and_then(|captures| ImageReference::from_captures(&captures));
+        }
+        let path = self.keys.segments();
+        let (parent, key) = path.split_at(path.len() - 1);
+        let captures = YAML_VALUE.captures(line);
+        if key == ["repository"] {
+            self.repository = captures.as_ref().map(|captures| (parent.to_vec(), captures["image"].to_owned()));
+        }
+        let captures = captures?;
+        if self.yaml_paths.iter().any(|selector| selector.matches(&path)) || self.is_image_anchor(&path, &captures["prefix"]) {
+            return Some(captures)
+                .filter(|captures| IMAGE_PATTERN.is_match(&captures["image"]))
+                .and_then(|captures| ImageReference::from_captures(&captures));
+        }
+        if key != ["tag"] || !self.yaml_paths.iter().any(|selector| selector.matches(parent)) {
+            return None;
+        }
+        let repository = self
+            .repository
+            .as_ref()
+            .filter(|(mapping, _)| mapping == parent)
+            .map(|(_, repository)| repository)?;
+        ImageReference::new(
+            captures.name("prefix")?.as_str(),
+            Cow::Owned(format!("{repository}:{}", &captures["image"])),
+            captures.name("suffix")?.as_str(),
+            ReferencePart::Tag,
+        )
+    }
+
+    /// Checks if the value is the anchor definition of an `x-` field whose
+    /// alias is the image of a compose service.
+    fn is_image_anchor(&self, path: &[String], prefix: &str) -> bool {
+        matches!(path, [key] if key.starts_with("x-"))
+            && YAML_ANCHOR
+                .captures(prefix)
+                .is_some_and(|captures| self.image_aliases.contains(&captures["anchor"]))
+    }
+
+    /// Returns the `newTag` of a kustomize image, the `name` (or `newName`)
+    /// has to come first. A `digest` after the `newTag` is returned as well,
+    /// so that it can be looked up for the new tag.
+    fn scan_kustomize<'a>(&mut self, line: &'a str) -> Option<ImageReference<'a>> {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            return None;
+        }
+        if line.len() == trimmed.len() && !trimmed.starts_with('-') {
+            self.in_images = trimmed.starts_with("images:");
+            self.entry_image = None;
+            self.entry_tag = None;
+            return None;
+        }
+        if !self.in_images {
+            return None;
+        }
+        if trimmed.starts_with('-') {
+            self.entry_image = None;
+            self.entry_tag = None;
+        }
+        let captures = KUSTOMIZE_FIELD.captures(line)?;
+        let (prefix, value, suffix) = (
+            captures.name("prefix")?.as_str(),
+            captures.name("value")?.as_str(),
+            captures.name("suffix")?.as_str(),
+        );
+        match &captures["key"] {
+            "name" if self.entry_image.is_none() => self.entry_image = Some(value.to_owned()),
+            "newName" => self.entry_image = Some(value.to_owned()),
+            "newTag" => {
+                self.entry_tag = Some(value.to_owned());
+                let image = format!("{}:{value}", self.entry_image.as_ref()?);
+                return ImageReference::new(prefix, Cow::Owned(image), suffix, ReferencePart::Tag);
+            }
+            "digest" => {
+                let image = format!("{}:{}", self.entry_image.as_ref()?, self.entry_tag.as_ref()?);
+                return ImageReference::new(prefix, Cow::Owned(image), suffix, ReferencePart::Digest(value.to_owned()));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Returns the `tag` of a multi-line bazel image pull, the `image` (or the
+    /// `registry` and `repository`) has to come first. A `digest` after the
+    /// `tag` is returned as well, so that it can be looked up for the new tag.
+    fn scan_bazel<'a>(&mut self, line: &'a str) -> Option<ImageReference<'a>> {
+        if BAZEL_PULL.is_match(line) {
+            let pull = self.bazel_pulls.pop_front().unwrap_or_default();
+            self.in_pull = true;
+            self.entry_image = pull.image;
+            self.entry_registry = pull.registry;
+            self.entry_tag = pull.tag;
+            return None;
+        }
+        if !self.in_pull {
+            return None;
+        }
+        if line.trim_start().starts_with(')') {
+            self.in_pull = false;
+            return None;
+        }
+        let captures = BAZEL_ATTRIBUTE.captures(line)?;
+        let (prefix, value, suffix) = (
+            captures.name("prefix")?.as_str(),
+            captures.name("value")?.as_str(),
+            captures.name("suffix")?.as_str(),
+        );
+        match &captures["key"] {
+            "image" | "repository" => self.entry_image = Some(value.to_owned()),
+            "registry" => self.entry_registry = Some(value.to_owned()),
+            "tag" => {
+                self.entry_tag = Some(value.to_owned());
+                let image = format!("{}:{value}", self.bazel_image()?);
+                return ImageReference::new(prefix, Cow::Owned(image), suffix, ReferencePart::Tag);
+            }
+            "digest" => {
+                let image = format!("{}:{}", self.bazel_image()?, self.entry_tag.as_ref()?);
+                return ImageReference::new(prefix, Cow::Owned(image), suffix, ReferencePart::Digest(value.to_owned()));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Returns the image of the current bazel pull, without a Docker Hub host.
+    fn bazel_image(&self) -> Option<String> {
+        let image = match &self.entry_registry {
+            Some(registry) => format!("{registry}/{}", self.entry_image.as_ref()?),
+            None => self.entry_image.clone()?,
+        };
+        Some(
+            DOCKERHUB_HOSTS
+                .iter()
+                .find_map(|host| image.strip_prefix(host)?.strip_prefix('/'))
+                .map_or_else(|| image.clone(), ToOwned::to_owned),
+        )
+    }
+}
+
+/// Returns whether the line ends with a marker comment, e.g.
+/// `sidecar = "nginx:1.27.0" # dockerimage-updater`.
+fn has_marker_comment(line: &str) -> bool {
+    line.split_once('#')
+        .or_else(|| line.split_once("//"))
+        .is_some_and(|(_, comment)| comment.contains(MARKER))
+}
+
+/// Returns whether the end of the text is within a maven `<from>` element,
+/// `current` is the state at its start.
+fn within_from(text: &str, current: bool) -> bool {
+    match (text.rfind("<from>"), text.rfind("</from>")) {
+        (Some(open), Some(close)) => open > close,
+        (Some(_), None) => true,
+        (None, Some(_)) => false,
+        (None, None) => current,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::container_image::{DockerInstruction, Dockerfile};
+    use crate::scanners::{FileKind, LineScanner, ReferencePart, YamlScanners};
+    use crate::tag::Tag;
+    use crate::utils::{DockerfileUpdate, Strategy};
+
+    #[test]
+    fn jib_references() {
+        assert_eq!(FileKind::of(Path::new("app/pom.xml")), FileKind::Maven);
+        assert_eq!(FileKind::of(Path::new("app/build.gradle.kts")), FileKind::Gradle);
+        assert_eq!(FileKind::of(Path::new("app/Dockerfile")), FileKind::Dockerfile);
+
+        let pom = "<plugin>\n  <artifactId>jib-maven-plugin</artifactId>\n  <configuration>\n    <from>\n      \
+                   <image>eclipse-temurin:21.0.4_7-jre</image>\n    </from>\n    <to>\n      <image>registry.example.com/app:1.0.0</image>\n    \
+                   </to>\n  </configuration>\n</plugin>\n<from><image>${base.image}</image></from>\n";
+        let maven = Dockerfile::parse_as(pom, FileKind::Maven).unwrap();
+        let images: Vec<String> = maven.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["eclipse-temurin:21.0.4_7-jre"]);
+        assert_eq!(maven.to_string(), pom);
+        assert_eq!(maven.get_base_image_lines()[0].line, 5);
+
+        let gradle = "jib.from.image = 'eclipse-temurin:21.0.4_7-jre'\njib {\n    from {\n        image = \"node:24.6.0-alpine\"\n        \
+                      platforms {\n            platform { os = 'linux' }\n        }\n        image = 'alpine:3.22.0'\n    }\n    to {\n        \
+                      image = 'app:1.0.0'\n    }\n}\n";
+        let gradle = Dockerfile::parse_as(gradle, FileKind::Gradle).unwrap();
+        let images: Vec<String> = gradle.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["eclipse-temurin:21.0.4_7-jre", "node:24.6.0-alpine", "alpine:3.22.0"]);
+
+        let update = DockerfileUpdate {
+            dockerfile: gradle,
+            updates:    vec![(1, "24.11.1-alpine".parse::<Tag>().unwrap())],
+        };
+        assert!(update.apply().to_string().contains("        image = \"node:24.11.1-alpine\"\n"));
+    }
+
+    #[test]
+    fn terraform_references() {
+        assert_eq!(FileKind::of(Path::new("infra/main.tf")), FileKind::Terraform);
+
+        let content = r#"# dockerimage-updater
+variable "sidecar_image" {
+  description = "The image of the sidecar"
+  type        = string
+  default     = "envoyproxy/envoy:v1.31.0"
+}
+
+variable "app_image" {
+  default = "registry.example.com/app:1.0.0"
+}
+
+locals {
+  # dockerimage-updater
+  proxy = "nginx:1.27.0-alpine"
+  cache = "redis:7.4.0" // dockerimage-updater
+  name  = "cache:v1" # not an image
+  tags  = "${var.app_image}" # dockerimage-updater
+}
+"#;
+        let terraform = Dockerfile::parse_as(content, FileKind::Terraform).unwrap();
+        let images: Vec<String> = terraform.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["envoyproxy/envoy:v1.31.0", "nginx:1.27.0-alpine", "redis:7.4.0"]);
+        assert_eq!(terraform.to_string(), content);
+    }
+
+    #[test]
+    fn ansible_references() {
+        assert_eq!(FileKind::of(Path::new("playbooks/site.yml")), FileKind::Ansible);
+
+        let content = r#"- hosts: web
+  tasks:
+    - name: Start the proxy
+      community.docker.docker_container:
+        name: proxy
+        # The image of the proxy
+        image: "nginx:1.27.0-alpine"
+        ports:
+          - "80:80"
+    - docker_container:
+        name: cache
+        image: redis:7.4.0 # pinned
+    - name: Unrelated image key
+      set_fact:
+        image: alpine:3.22.0
+    - docker_container:
+        image: "{{ app_image }}"
+"#;
+        let playbook = Dockerfile::parse_as(content, FileKind::Ansible).unwrap();
+        let images: Vec<String> = playbook.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["nginx:1.27.0-alpine", "redis:7.4.0"]);
+        assert_eq!(playbook.to_string(), content);
+
+        // Without `--ansible`, e.g. for YAML paths or markers, tasks are not searched.
+        let unscanned = Dockerfile::parse_with(
+            content,
+            FileKind::Ansible,
+            YamlScanners {
+                ansible: false,
+                ..YamlScanners::ALL
+            },
+        )
+        .unwrap();
+        assert!(unscanned.get_base_images().is_empty());
+    }
+
+    #[test]
+    fn kustomize_references() {
+        assert_eq!(FileKind::of(Path::new("overlays/prod/kustomization.yaml")), FileKind::Kustomize);
+
+        let content = r#"resources:
+  - deployment.yaml
+images:
+  - name: nginx
+    newTag: "1.27.0-alpine"
+  - name: app
+    newName: redis
+    newTag: 7.4.0
+    digest: sha256:0123456789abcdef
+  # Only renamed, there is no tag to update.
+  - name: busybox
+    newName: registry.example.com/busybox
+patches:
+  - target:
+      name: nginx
+    newTag: 1.0.0
+"#;
+        let kustomization = Dockerfile::parse_as(content, FileKind::Kustomize).unwrap();
+        let images: Vec<String> = kustomization.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["nginx:1.27.0-alpine", "redis:7.4.0"]);
+        assert_eq!(kustomization.to_string(), content);
+
+        let update = DockerfileUpdate {
+            dockerfile: kustomization,
+            updates:    vec![(1, "7.4.2".parse::<Tag>().unwrap())],
+        };
+        let updated = update.apply().to_string();
+        assert!(updated.contains("    newName: redis\n    newTag: 7.4.2\n    digest: sha256:0123456789abcdef\n"));
+    }
+
+    #[test]
+    fn compose_references() {
+        assert_eq!(FileKind::of(Path::new("deploy/compose.yaml")), FileKind::Compose);
+        assert_eq!(FileKind::of(Path::new("docker-compose.override.yml")), FileKind::Compose);
+        assert_eq!(FileKind::of(Path::new("composer.yml")), FileKind::Ansible);
+
+        let content = r#"x-image-base: &base-image node:20.11.0-alpine
+x-worker: &worker
+  image: "python:3.12.4-slim"
+  restart: always
+x-logging: &logging
+  driver: json-file
+services:
+  api:
+    image: *base-image
+    logging: *logging
+  web:
+    image: &web-image nginx:1.27.0
+  proxy:
+    image: *web-image
+  queue:
+    <<: *worker
+    command: celery
+  db:
+    image: "postgres:16.3"
+    environment:
+      image: alpine:3.22.0
+"#;
+        let compose = Dockerfile::parse_as(content, FileKind::Compose).unwrap();
+        let images: Vec<String> = compose.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["node:20.11.0-alpine", "python:3.12.4-slim", "nginx:1.27.0", "postgres:16.3"]);
+        assert_eq!(compose.to_string(), content);
+
+        // Without `--compose`, e.g. for YAML paths or markers, the services are not
+        // searched.
+        let scanners = YamlScanners {
+            compose: false,
+            ..YamlScanners::ALL
+        };
+        assert!(
+            Dockerfile::parse_with(content, FileKind::Compose, scanners)
+                .unwrap()
+                .get_base_images()
+                .is_empty()
+        );
+
+        let update = DockerfileUpdate {
+            dockerfile: compose,
+            updates:    vec![(0, "20.15.1-alpine".parse::<Tag>().unwrap())],
+        };
+        assert!(update.apply().to_string().starts_with("x-image-base: &base-image node:20.15.1-alpine\n"));
+
+        // Other scalars of `x-` fields are no images, even if they look like one.
+        let content = "x-db-url: postgres:5432\nx-ports: \"8080:80\"\nx-unused: &unused redis:7.4.0\nservices:\n  db:\n    image: \
+                       postgres:16.3\n    ports:\n      - *unused\n";
+        let compose = Dockerfile::parse_as(content, FileKind::Compose).unwrap();
+        let images: Vec<String> = compose.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["postgres:16.3"]);
+    }
+
+    #[test]
+    fn bazel_references() {
+        assert_eq!(FileKind::of(Path::new("MODULE.bazel")), FileKind::Bazel);
+        assert_eq!(FileKind::of(Path::new("third_party/WORKSPACE")), FileKind::Bazel);
+
+        let content = r#"oci = use_extension("@rules_oci//oci:extensions.bzl", "oci")
+oci.pull(
+    name = "nginx",
+    image = "index.docker.io/library/nginx",
+    tag = "1.27.0-alpine",
+    digest = "sha256:0123456789abcdef",
+    platforms = [
+        "linux/amd64",
+    ],
+)
+oci.pull(
+    name = "distroless",
+    image = "gcr.io/distroless/static",
+    digest = "sha256:fedcba9876543210",
+)
+container_pull(
+    name = "redis",
+    registry = "docker.io",
+    repository = "library/redis",
+    tag = "7.4.0",
+)
+oci.pull(
+    name = "postgres",
+    digest = "sha256:0011223344556677",
+    tag = "16.3",
+    image = "postgres",
+)
+use_repo(oci, "nginx", "distroless")
+"#;
+        let module = Dockerfile::parse_as(content, FileKind::Bazel).unwrap();
+        let images: Vec<String> = module.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["library/nginx:1.27.0-alpine", "library/redis:7.4.0", "postgres:16.3"]);
+        // The digest before the tag belongs to the tag of its pull.
+        let digests: Vec<(String, &ReferencePart)> = module
+            .get_instructions()
+            .iter()
+            .filter_map(|instruction| match instruction {
+                DockerInstruction::Reference {
+                    image,
+                    part: part @ ReferencePart::Digest(_),
+                    ..
+                } => Some((image.to_string(), part)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            digests,
+            [
+                (
+                    "library/nginx:1.27.0-alpine".to_owned(),
+                    &ReferencePart::Digest("sha256:0123456789abcdef".to_owned())
+                ),
+                ("postgres:16.3".to_owned(), &ReferencePart::Digest("sha256:0011223344556677".to_owned())),
+            ]
+        );
+        assert_eq!(module.to_string(), content);
+
+        let update = DockerfileUpdate {
+            dockerfile: module,
+            updates:    vec![(1, "7.4.2".parse::<Tag>().unwrap())],
+        };
+        assert!(
+            update
+                .apply()
+                .to_string()
+                .contains("    repository = \"library/redis\",\n    tag = \"7.4.2\",\n")
+        );
+    }
+
+    #[test]
+    fn packer_references() {
+        assert_eq!(FileKind::of(Path::new("images/ubuntu.pkr.hcl")), FileKind::Packer);
+
+        let content = r#"source "docker" "ubuntu" {
+  image  = "ubuntu:22.04"
+  commit = true
+  run_command = ["-d", "-i", "-t", "{{.Image}}", "/bin/bash"]
+}
+
+source "docker" "app" {
+  // The base of the application
+  image = "node:20.11.0-alpine"
+  changes = [
+    "ENV image=busybox:1.36.0",
+  ]
+  platform = "linux/amd64"
+}
+
+source "amazon-ebs" "vm" {
+  image = "ami-123456"
+}
+
+build {
+  sources = ["source.docker.ubuntu", "source.docker.app"]
+}
+"#;
+        let template = Dockerfile::parse_as(content, FileKind::Packer).unwrap();
+        let images: Vec<String> = template.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["ubuntu:22.04", "node:20.11.0-alpine"]);
+        assert_eq!(template.to_string(), content);
+    }
+
+    #[test]
+    fn ci_references() {
+        assert_eq!(FileKind::of(Path::new(".gitlab-ci.yml")), FileKind::GitlabCi);
+        assert_eq!(FileKind::of(Path::new("app/.circleci/config.yml")), FileKind::CircleCi);
+        assert_eq!(FileKind::of(Path::new("app/config.yml")), FileKind::Ansible);
+
+        let content = r#"image: node:20.11.0-alpine
+variables:
+  IMAGE: alpine:3.22.0
+test:
+  image:
+    name: python:3.12.4-slim
+    entrypoint: [""]
+  services:
+    - postgres:16.3
+    - name: "redis:7.4.0"
+      alias: cache
+  script:
+    - docker run busybox:1.36.0
+build:
+  image: $CI_REGISTRY_IMAGE/builder:latest
+"#;
+        let gitlab = Dockerfile::parse_as(content, FileKind::GitlabCi).unwrap();
+        let images: Vec<String> = gitlab.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["node:20.11.0-alpine", "python:3.12.4-slim", "postgres:16.3", "redis:7.4.0"]);
+        assert_eq!(gitlab.to_string(), content);
+
+        // Without `--ci` the jobs are not searched.
+        let scanners = YamlScanners {
+            ci: false,
+            ..YamlScanners::ALL
+        };
+        assert!(
+            Dockerfile::parse_with(content, FileKind::GitlabCi, scanners)
+                .unwrap()
+                .get_base_images()
+                .is_empty()
+        );
+
+        let content = r"version: 2.1
+executors:
+  node:
+    docker:
+      - image: cimg/node:20.11.0
+jobs:
+  test:
+    docker:
+      - image: cimg/python:3.12.4
+        auth:
+          username: bot
+      - image: cimg/postgres:16.3
+    steps:
+      - checkout
+";
+        let circleci = Dockerfile::parse_as(content, FileKind::CircleCi).unwrap();
+        let images: Vec<String> = circleci.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["cimg/node:20.11.0", "cimg/python:3.12.4", "cimg/postgres:16.3"]);
+        assert_eq!(circleci.to_string(), content);
+    }
+
+    #[test]
+    fn drone_references() {
+        assert_eq!(FileKind::of(Path::new(".drone.yml")), FileKind::Drone);
+        assert_eq!(FileKind::of(Path::new(".woodpecker/test.yaml")), FileKind::Drone);
+
+        let content = r"kind: pipeline
+type: docker
+name: test
+steps:
+  - name: test
+    image: golang:1.22.5
+    commands:
+      - go test ./...
+services:
+  - name: db
+    image: postgres:16.3
+---
+steps:
+  build:
+    image: node:20.11.0-alpine
+    environment:
+      image: alpine:3.22.0
+services:
+  cache:
+    image: redis:7.4.0
+";
+        let pipeline = Dockerfile::parse_as(content, FileKind::Drone).unwrap();
+        let images: Vec<String> = pipeline.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["golang:1.22.5", "postgres:16.3", "node:20.11.0-alpine", "redis:7.4.0"]);
+        assert_eq!(pipeline.to_string(), content);
+    }
+
+    #[test]
+    fn yaml_path_references() {
+        let content = r#"apiVersion: helm.toolkit.fluxcd.io/v2
+kind: HelmRelease
+spec:
+  values:
+    image:
+      repository: bitnami/nginx
+      tag: "1.27.0"
+    sidecars:
+      - name: proxy
+        image: envoyproxy/envoy:v1.31.0
+    replicas: 2
+---
+apiVersion: argoproj.io/v1alpha1
+kind: Application
+spec:
+  source:
+    helm:
+      values: |
+        image: redis:7.4.0
+"#;
+        let mut scanner = LineScanner {
+            yaml_paths: ["spec.values.image", "spec.values.sidecars[*].image", "spec.*.helm.values.image"]
+                .map(|path| path.parse().unwrap())
+                .to_vec(),
+            ..LineScanner::new(FileKind::Ansible, YamlScanners::ALL)
+        };
+        let references: Vec<(String, ReferencePart)> = content
+            .lines()
+            .filter_map(|line| scanner.scan(line))
+            .map(|reference| (reference.image.into_owned(), reference.part))
+            .collect();
+        assert_eq!(
+            references,
+            [
+                ("bitnami/nginx:1.27.0".to_owned(), ReferencePart::Tag),
+                ("envoyproxy/envoy:v1.31.0".to_owned(), ReferencePart::Image),
+                ("redis:7.4.0".to_owned(), ReferencePart::Image),
+            ]
+        );
+
+        // The paths alone do not search the ansible tasks of a playbook.
+        let mut scanner = LineScanner {
+            yaml_paths: vec!["spec.values.image".parse().unwrap()],
+            ..LineScanner::new(
+                FileKind::Ansible,
+                YamlScanners {
+                    ansible: false,
+                    ..YamlScanners::ALL
+                },
+            )
+        };
+        let playbook = "- hosts: web\n  tasks:\n    - docker_container:\n        image: nginx:1.27.0\n";
+        assert!(playbook.lines().all(|line| scanner.scan(line).is_none()));
+    }
+
+    #[test]
+    fn marked_references() {
+        let content = r#"app:
+  image: nginx:1.27.0 # diu:
+  cache:
+    tag: "7.4.0" # diu: image=redis strategy=next-patch
+  unmarked: alpine:3.22.0
+podinfo:
+  repository: ghcr.io/stefanprodan/podinfo # {"$imagepolicy": "flux-system:podinfo:name"}
+  tag: 6.5.0 # {"$imagepolicy": "flux-system:podinfo:tag"}
+"#;
+        let playbook = Dockerfile::parse_as(content, FileKind::Ansible).unwrap();
+        let images: Vec<String> = playbook.get_base_images().iter().map(ToString::to_string).collect();
+        assert_eq!(images, ["nginx:1.27.0", "redis:7.4.0", "ghcr.io/stefanprodan/podinfo:6.5.0"]);
+        assert_eq!(playbook.to_string(), content);
+        assert_eq!(playbook.marked_image_strategies(), BTreeMap::from([("redis".to_owned(), Strategy::NextPatch)]));
+    }
+}
@@ -0,0 +1,193 @@
+use std::io::{self, BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::utils::Strategy;
+
+/// The error codes of JSON-RPC 2.0, see <https://www.jsonrpc.org/specification>.
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The image could not be resolved, e.g. the registry was not reachable.
+const RESOLVE_ERROR: i64 = -32000;
+
+/// A request of a client, one per line, e.g. `{"jsonrpc": "2.0", "id": 1,
+/// "method": "candidate", "params": {"image": "node:22.1.0"}}`.
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id:     Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// The params of the `candidate` method. Without `tags` the tags are fetched
+/// from the registry, which are cached for the following requests.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CandidateParams {
+    pub image:    String,
+    #[serde(default)]
+    pub strategy: Option<Strategy>,
+    #[serde(default)]
+    pub tags:     Option<Vec<String>>,
+    #[serde(default)]
+    pub arch:     Option<String>,
+}
+
+/// The result of the `candidate` method.
+#[derive(Debug, Serialize)]
+struct CandidateResult<'a> {
+    image:     &'a str,
+    candidate: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct RpcError {
+    code:    i64,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id:      Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result:  Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error:   Option<RpcError>,
+}
+
+impl Response {
+    fn new(id: Value, result: Result<Value, RpcError>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result,
+            error,
+        }
+    }
+}
+
+/// Answers the requests of the input line by line until it ends or the
+/// `shutdown` method is called. The resolver gets the params of a `candidate`
+/// request and returns the candidate image or an error message.
+///
+/// # Errors
+///
+/// This function will return an error if the input can not be read or the
+/// output can not be written.
+pub fn serve<R, W, F>(input: R, mut output: W, mut resolve: F) -> io::Result<()>
+where
+    R: BufRead,
+    W: Write,
+    F: FnMut(&CandidateParams) -> Result<Option<String>, String>,
+{
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (response, shutdown) = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let shutdown = request.method == "shutdown";
+                (Response::new(request.id.clone(), answer(&request, &mut resolve)), shutdown)
+            }
+            Err(e) => (
+                Response::new(
+                    Value::Null,
+                    Err(RpcError {
+                        code:    PARSE_ERROR,
+                        message: e.to_string(),
+                    }),
+                ),
+                false,
+            ),
+        };
+        writeln!(
+            output,
+            "{}",
+            serde_json::to_string(&response).expect("Response can be turned into json string.")
+        )?;
+        output.flush()?;
+        if shutdown {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Answers a single request.
+fn answer<F>(request: &Request, resolve: &mut F) -> Result<Value, RpcError>
+where
+    F: FnMut(&CandidateParams) -> Result<Option<String>, String>,
+{
+    match request.method.as_str() {
+        "candidate" => {
+            let params: CandidateParams = serde_json::from_value(request.params.clone()).map_err(|e| RpcError {
+                code:    INVALID_PARAMS,
+                message: e.to_string(),
+            })?;
+            let candidate = resolve(&params).map_err(|message| RpcError { code: RESOLVE_ERROR, message })?;
+            let result = CandidateResult {
+                image: &params.image,
+                candidate,
+            };
+            Ok(serde_json::to_value(result).expect("Result can be turned into json value."))
+        }
+        "shutdown" => Ok(Value::Null),
+        method => Err(RpcError {
+            code:    METHOD_NOT_FOUND,
+            message: format!("Unknown method `{method}`, expected `candidate` or `shutdown`."),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use pretty_assertions::assert_eq;
+
+    use crate::serve::serve;
+    use crate::utils::Strategy;
+
+    #[test]
+    fn serve_requests() {
+        let input = r#"{"jsonrpc": "2.0", "id": 1, "method": "candidate", "params": {"image": "node:22.1.0", "strategy": "minor"}}
+
+{"jsonrpc": "2.0", "id": 2, "method": "candidate", "params": {"image": "redis:7.4.0"}}
+{"jsonrpc": "2.0", "id": 3, "method": "candidate", "params": {"tag": "7.4.0"}}
+{"jsonrpc": "2.0", "id": 4, "method": "restart"}
+not json
+{"jsonrpc": "2.0", "id": 5, "method": "shutdown"}
+{"jsonrpc": "2.0", "id": 6, "method": "candidate", "params": {"image": "node:22.1.0"}}
+"#;
+        let mut output = Vec::new();
+        serve(input.as_bytes(), &mut output, |params| match params.image.as_str() {
+            "node:22.1.0" => {
+                assert_eq!(params.strategy, Some(Strategy::LatestMinor));
+                Ok(Some("node:22.9.0".to_owned()))
+            }
+            _ => Err("Registry is not reachable.".to_owned()),
+        })
+        .unwrap();
+        let responses: Vec<serde_json::Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 6);
+        assert_eq!(responses[0]["result"]["candidate"], "node:22.9.0");
+        assert_eq!(responses[1]["error"]["code"], -32000);
+        assert_eq!(responses[2]["error"]["code"], -32602);
+        assert_eq!(responses[3]["error"]["code"], -32601);
+        assert_eq!(responses[4]["error"]["code"], -32700);
+        assert_eq!(responses[5]["id"], 5);
+    }
+}
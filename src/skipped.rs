@@ -0,0 +1,80 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+use serde::Serialize;
+use tracing::info;
+
+/// Whether the skipped tags are recorded, see `enable`.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// The skipped tags per image and filter step.
+static SKIPPED: LazyLock<Mutex<Report>> = LazyLock::new(|| Mutex::new(Report::default()));
+
+/// The filter step that dropped a fetched tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SkipStage {
+    /// The tag is not published for the requested architecture.
+    Arch,
+    /// The tag is no version, or does not match the tag scheme of the image.
+    Parse,
+    /// The tag has another variant than the current tag, e.g. `alpine`
+    /// instead of `slim`.
+    Variant,
+    /// The tag is no update within the strategy, e.g. a major update with
+    /// `next-minor` or an older version.
+    Strategy,
+}
+
+impl Display for SkipStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Arch => write!(f, "arch"),
+            Self::Parse => write!(f, "parse"),
+            Self::Variant => write!(f, "variant"),
+            Self::Strategy => write!(f, "strategy"),
+        }
+    }
+}
+
+/// The skipped tags of the run, keyed by the image and the filter step.
+pub type Report = BTreeMap<String, BTreeMap<SkipStage, BTreeSet<String>>>;
+
+/// Starts recording the skipped tags, nothing is recorded otherwise.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Records a tag of the image that was dropped by the filter step.
+pub fn record(image: &str, stage: SkipStage, tag: String) {
+    if is_enabled() {
+        SKIPPED
+            .lock()
+            .expect("Skipped tags can be locked.")
+            .entry(image.to_owned())
+            .or_default()
+            .entry(stage)
+            .or_default()
+            .insert(tag);
+    }
+}
+
+/// Returns the skipped tags of the run so far.
+pub fn report() -> Report {
+    SKIPPED.lock().expect("Skipped tags can be locked.").clone()
+}
+
+/// Logs the skipped tags, one line per image and filter step.
+pub fn log(report: &Report) {
+    for (image, stages) in report {
+        for (stage, tags) in stages {
+            let tags: Vec<&str> = tags.iter().map(String::as_str).collect();
+            info!("Skipped {} tags of `{image}` at the {stage} filter: {}", tags.len(), tags.join(", "));
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::info;
+
+/// The start of the run, see `start`.
+static START: LazyLock<Instant> = LazyLock::new(Instant::now);
+static FILES_SCANNED: AtomicU64 = AtomicU64::new(0);
+static IMAGES_RESOLVED: AtomicU64 = AtomicU64::new(0);
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+static HTTP_REQUESTS: AtomicU64 = AtomicU64::new(0);
+static BYTES_FETCHED: AtomicU64 = AtomicU64::new(0);
+/// The elapsed time per phase, in the order the phases were first entered.
+static PHASES: LazyLock<Mutex<Vec<(&'static str, Duration)>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// The statistics of a run, printed with `--stats`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub files_scanned:   u64,
+    pub images_resolved: u64,
+    /// Lookups of the cache store, tags fetched during the run are not
+    /// counted.
+    pub cache_hits:      u64,
+    pub cache_misses:    u64,
+    /// `None` if the cache store was not used.
+    pub cache_hit_rate:  Option<f64>,
+    /// Requests sent to the registries, replayed responses are not counted.
+    pub http_requests:   u64,
+    pub bytes_fetched:   u64,
+    pub elapsed_secs:    f64,
+    pub phases:          Vec<PhaseSummary>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PhaseSummary {
+    pub name:         &'static str,
+    pub elapsed_secs: f64,
+}
+
+/// Measures the time until it is dropped and adds it to its phase.
+#[derive(Debug)]
+pub struct Phase {
+    name:    &'static str,
+    started: Instant,
+}
+
+impl Drop for Phase {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        let mut phases = PHASES.lock().expect("Phases can be locked.");
+        if let Some((_, total)) = phases.iter_mut().find(|(name, _)| *name == self.name) {
+            *total += elapsed;
+        } else {
+            phases.push((self.name, elapsed));
+        }
+    }
+}
+
+/// Starts the clock of the run.
+pub fn start() {
+    LazyLock::force(&START);
+}
+
+/// Starts measuring a phase, e.g. `discovery` or `resolving`, until the
+/// returned guard is dropped. Phases entered more than once are summed up.
+pub fn phase(name: &'static str) -> Phase {
+    Phase { name, started: Instant::now() }
+}
+
+pub fn file_scanned() {
+    FILES_SCANNED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn image_resolved() {
+    IMAGES_RESOLVED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn cache_lookup(hit: bool) {
+    if hit { &CACHE_HITS } else { &CACHE_MISSES }.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn http_request(bytes: usize) {
+    HTTP_REQUESTS.fetch_add(1, Ordering::Relaxed);
+    BYTES_FETCHED.fetch_add(u64::try_from(bytes).unwrap_or(u64::MAX), Ordering::Relaxed);
+}
+
+/// Returns the share of cache hits, `None` if there were no lookups.
+#[allow(clippy::cast_precision_loss)]
+fn hit_rate(hits: u64, misses: u64) -> Option<f64> {
+    let lookups = hits + misses;
+    (lookups > 0).then(|| hits as f64 / lookups as f64)
+}
+
+/// Returns the statistics of the run so far.
+pub fn summary() -> Summary {
+    let (cache_hits, cache_misses) = (CACHE_HITS.load(Ordering::Relaxed), CACHE_MISSES.load(Ordering::Relaxed));
+    Summary {
+        files_scanned: FILES_SCANNED.load(Ordering::Relaxed),
+        images_resolved: IMAGES_RESOLVED.load(Ordering::Relaxed),
+        cache_hits,
+        cache_misses,
+        cache_hit_rate: hit_rate(cache_hits, cache_misses),
+        http_requests: HTTP_REQUESTS.load(Ordering::Relaxed),
+        bytes_fetched: BYTES_FETCHED.load(Ordering::Relaxed),
+        elapsed_secs: START.elapsed().as_secs_f64(),
+        phases: PHASES
+            .lock()
+            .expect("Phases can be locked.")
+            .iter()
+            .map(|(name, elapsed)| PhaseSummary {
+                name,
+                elapsed_secs: elapsed.as_secs_f64(),
+            })
+            .collect(),
+    }
+}
+
+impl Summary {
+    /// Logs the statistics as human readable lines.
+    pub(crate) fn log(&self) {
+        info!("Files scanned:\t{}", self.files_scanned);
+        info!("Images resolved:\t{}", self.images_resolved);
+        info!(
+            "Cache store:\t{} hits, {} misses{}",
+            self.cache_hits,
+            self.cache_misses,
+            self.cache_hit_rate.map(|rate| format!(" ({:.0}% hit rate)", rate * 100.0)).unwrap_or_default()
+        );
+        info!("Http requests:\t{} ({} bytes)", self.http_requests, self.bytes_fetched);
+        for phase in &self.phases {
+            info!("Phase {}:\t{:.3}s", phase.name, phase.elapsed_secs);
+        }
+        info!("Elapsed:\t{:.3}s", self.elapsed_secs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::stats::{hit_rate, phase, summary};
+
+    #[test]
+    fn statistics() {
+        assert_eq!(hit_rate(0, 0), None);
+        assert_eq!(hit_rate(3, 1), Some(0.75));
+
+        drop(phase("test-phase"));
+        drop(phase("test-phase"));
+        let phases = summary().phases;
+        assert_eq!(phases.iter().filter(|phase| phase.name == "test-phase").count(), 1);
+    }
+}
@@ -0,0 +1,25 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, RwLock};
+
+use crate::tag::scheme::normalize;
+
+/// The configured LTS majors per image, see `set_lts_majors`.
+pub static LTS_MAJORS: LazyLock<RwLock<HashMap<String, Vec<u64>>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Sets the global LTS majors, the keys are the names of the images.
+pub fn set_lts_majors(majors: HashMap<String, Vec<u64>>) {
+    let majors = majors.into_iter().map(|(image, majors)| (normalize(&image).to_owned(), majors)).collect();
+    *LTS_MAJORS.write().expect("LTS majors can be written.") = majors;
+}
+
+/// Returns the LTS majors of the image, if they are configured. Images without
+/// configured majors use the even majors, like Node.
+pub fn lts_majors_for(image: &str) -> Option<Vec<u64>> {
+    LTS_MAJORS.read().expect("LTS majors can be read.").get(normalize(image)).cloned()
+}
+
+/// Checks if the major is an LTS line, i.e. one of the configured majors or
+/// an even major if none are configured.
+pub fn is_lts_major(major: u64, lts_majors: Option<&[u64]>) -> bool {
+    lts_majors.map_or_else(|| major.is_multiple_of(2), |majors| majors.contains(&major))
+}
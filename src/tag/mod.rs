@@ -1,49 +1,280 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt::Display;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
 use crate::container_image::Error;
-use crate::tag::variant::TagVariant;
+use crate::eol;
+use crate::pin::Date;
+use crate::skipped::SkipStage;
+use crate::tag::variant::{TagVariant, VariantPin};
 use crate::utils::Strategy;
 
+pub mod channel;
+pub mod scheme;
 pub mod variant;
 
+/// Marks the rebuild counter of bitnami style tags, e.g. `-r8` in
+/// `9.0.1-debian-12-r8`.
+const REVISION_PREFIX: &str = "-r";
+/// Numeric components with at least this many digits are build metadata, e.g.
+/// the date in `1.27.1-20240910`, and not a version number.
+const BUILD_DIGITS: usize = 8;
+/// Pre-release channels in the order they are published, e.g. `9.0.0-preview.7`
+/// comes before `9.0.0-rc.1`, which comes before the release `9.0.0`.
+const PRERELEASE_CHANNELS: [&str; 2] = ["preview", "rc"];
+/// Tags that move along with the releases, in the order they are proposed
+/// with `CandidateOptions::include_latest`.
+const MUTABLE_TAGS: [&str; 4] = ["latest", "stable", "lts", "edge"];
+
+/// Settings that decide which of the fetched tags is chosen as candidate.
+// The options mirror the cli flags, which are bools by nature.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CandidateOptions {
+    pub strategy:            Strategy,
+    /// Strategies that are tried in order, if the strategy yields no candidate,
+    /// e.g. prefer a minor update and otherwise take the major update.
+    pub fallbacks:           Vec<Strategy>,
+    /// Switches to a variant with this suffix (e.g. `slim`), whenever the same
+    /// version is published with it.
+    pub prefer_suffix:       Option<String>,
+    /// Only considers tags with as many version numbers as the current tag,
+    /// e.g. `16` to `17` and `3.12` to `3.13`.
+    pub same_precision:      bool,
+    /// Writes the candidate with all its version numbers, instead of matching
+    /// the specificity of the current tag.
+    pub full_precision:      bool,
+    /// Only accepts a candidate if its manifest can be fetched from the
+    /// registry, for the requested architecture if any.
+    pub verify:              bool,
+    /// An external program that chooses the candidate from the fetched tags,
+    /// see `hook::run_candidate_cmd`.
+    pub candidate_cmd:       Option<PathBuf>,
+    /// Proposes pre-releases like `10.0.0-preview.7` for released tags,
+    /// pre-release tags always move on to later pre-releases.
+    pub allow_prerelease:    bool,
+    /// Proposes mutable tags like `latest` with the latest strategy, instead
+    /// of skipping them.
+    pub include_latest:      bool,
+    /// The LTS majors of the image for `Strategy::LtsOnly`, even majors are
+    /// used if there are none.
+    pub lts_majors:          Option<Vec<u64>>,
+    /// Skips candidates with a higher risk score, see `Risk::assess`.
+    pub max_risk:            Option<u8>,
+    /// Candidates pushed within fewer days are fresh, which adds to their risk.
+    pub min_age:             u64,
+    /// Skips candidates with another version of a pinned variant, e.g.
+    /// `alpine3.21` with `alpine=3.20`.
+    pub variant_pins:        Vec<VariantPin>,
+    /// Moves tags whose variant is based on an operating system past its end
+    /// of life to a supported release, e.g. `3.11.4-slim-buster` to
+    /// `3.11.4-slim-bookworm`.
+    pub update_eol_variants: bool,
+    /// Only resolves Docker Official Images and images of Verified Publishers,
+    /// the other images fail to resolve.
+    pub official_only:       bool,
+    /// The strategies that markers give to images, they replace the strategy
+    /// for these images, see `Dockerfile::marked_image_strategies`.
+    pub image_strategies:    BTreeMap<String, Strategy>,
+}
+
+impl CandidateOptions {
+    /// Checks if the variant of the candidate is allowed by all variant pins.
+    fn allows_variant(&self, current: &Tag, candidate: &Tag) -> bool {
+        candidate
+            .variant
+            .as_ref()
+            .is_none_or(|variant| self.variant_pins.iter().all(|pin| pin.allows(current.variant.as_ref(), variant)))
+    }
+
+    /// Checks if the candidate has the precision of the current tag, if
+    /// required, and a variant that is allowed by all variant pins.
+    fn admits(&self, current: &Tag, candidate: &Tag) -> bool {
+        (!self.same_precision || candidate.precision() == current.precision()) && self.allows_variant(current, candidate)
+    }
+
+    /// Returns the filter step that rules out the tag as candidate for the
+    /// current tag, `None` if the strategy or one of the fallbacks accepts it.
+    /// Tags that no strategy accepts are reported with the reason of the
+    /// strategy.
+    pub(crate) fn skip_reason(&self, current: &Tag, tag: &Tag) -> Option<SkipStage> {
+        if !self.allows_variant(current, tag) {
+            return Some(SkipStage::Variant);
+        }
+        if self.same_precision && tag.precision() != current.precision() {
+            return Some(SkipStage::Strategy);
+        }
+        if self.include_latest && self.strategy == Strategy::Latest && current.find_mutable_tag(std::slice::from_ref(tag)).is_some() {
+            return None;
+        }
+        let reason = |strategy: &Strategy| current.skip_reason(tag, strategy, self.allow_prerelease, self.lts_majors.as_deref());
+        let skipped = reason(&self.strategy)?;
+        self.fallbacks.iter().all(|strategy| reason(strategy).is_some()).then_some(skipped)
+    }
+
+    /// Renders the candidate the way it will be written, e.g. `1.30.2` is
+    /// written as `1.30` if the current tag is `1.29`. Only representations
+    /// that exist in the list are used, falling back to the next more specific
+    /// one, so that no tag is written that can not be pulled.
+    pub(crate) fn render(&self, current: &Tag, candidate: &Tag, tag_list: &[Tag]) -> Tag {
+        // The template of a custom scheme decides which version numbers are written.
+        if self.full_precision || current.template.is_some() {
+            return candidate.clone();
+        }
+        let representations = candidate.representations(current);
+        let rendered = representations
+            .iter()
+            .find(|representation| {
+                let rendered = representation.to_string();
+                // Comparing the version numbers first avoids rendering most of the tags.
+                tag_list.iter().any(|tag| {
+                    (tag.major, tag.minor, tag.patch) == (representation.major, representation.minor, representation.patch) && tag.to_string() == rendered
+                })
+            })
+            .unwrap_or(candidate);
+        if let Some(preferred) = representations.first()
+            && preferred != rendered
+        {
+            debug!("Tag `{preferred}` does not exist, using `{rendered}` instead.");
+        }
+        rendered.clone()
+    }
+}
+
+impl From<Strategy> for CandidateOptions {
+    fn from(strategy: Strategy) -> Self {
+        Self { strategy, ..Self::default() }
+    }
+}
+
+/// The kind of change between two tags, see `Tag::bump`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Bump {
+    Major,
+    Minor,
+    Patch,
+    /// The version stays the same, but the variant changes, e.g. `alpine3.22`
+    /// to `alpine3.23` or `bookworm` to `trixie`.
+    Variant,
+    /// Only the rebuild counter or the build changes, e.g. `-r8` to `-r9`.
+    Rebuild,
+    None,
+}
+
+impl Display for Bump {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Major => write!(f, "major"),
+            Self::Minor => write!(f, "minor"),
+            Self::Patch => write!(f, "patch"),
+            Self::Variant => write!(f, "variant"),
+            Self::Rebuild => write!(f, "rebuild"),
+            Self::None => write!(f, "none"),
+        }
+    }
+}
+
 /// `Tag` is build with the following components:
-/// `(major).(minor).(patch)(variant)`
-#[derive(Debug, Clone, Default, Eq, PartialEq, PartialOrd, Ord, Deserialize, Serialize)]
+/// `(major).(minor).(patch)(build)(variant)(-r revision)`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct Tag {
     pub major:           Option<u64>,
     pub minor:           Option<u64>,
     pub patch:           Option<u64>,
+    /// Build metadata including its separator, e.g. the date `-20240910` in
+    /// `1.27.1-20240910`.
+    #[serde(default)]
+    pub build:           Option<String>,
     pub variant:         Option<TagVariant>,
+    /// The rebuild counter, the application version stays the same.
+    #[serde(default)]
+    pub revision:        Option<u64>,
+    /// The amount of digits of major, minor and patch as written, to keep zero
+    /// padded versions like `24.04`.
+    #[serde(default)]
+    pub digits:          [usize; 3],
     /// needed for images that reference other stages
     pub allowed_missing: bool,
     pub latest:          bool,
+    /// The tag with placeholders like `{major}`, if it was parsed with a custom
+    /// `TagScheme`, e.g. `release-{major}.{minor}-build{build}`.
+    #[serde(default)]
+    pub template:        Option<String>,
+}
+
+/// Tags are ordered by their precedence: major, minor and patch, then the
+/// version numbers of the variant (e.g. `3.22` of `alpine3.22`), then the
+/// suffix of the variant (e.g. `-slim`), then the rest of the variant, the
+/// build and the revision. A missing number orders before any number, e.g. `3`
+/// before `3.0` and `latest` before all versions. The remaining fields only
+/// keep the order consistent with `Eq`. The digits as written are left out,
+/// e.g. `24.04` equals `24.4`.
+impl Ord for Tag {
+    fn cmp(&self, rhs: &Self) -> Ordering {
+        let variant_version = |tag: &Self| tag.variant.as_ref().map(|variant| (variant.major, variant.minor, variant.patch));
+        let suffix = |tag: &Self| tag.variant.as_ref().and_then(|variant| variant.suffix.clone());
+        (self.major, self.minor, self.patch)
+            .cmp(&(rhs.major, rhs.minor, rhs.patch))
+            .then_with(|| variant_version(self).cmp(&variant_version(rhs)))
+            .then_with(|| suffix(self).cmp(&suffix(rhs)))
+            .then_with(|| self.variant.cmp(&rhs.variant))
+            .then_with(|| self.build_number().cmp(&rhs.build_number()))
+            .then_with(|| self.build.cmp(&rhs.build))
+            .then_with(|| self.revision.cmp(&rhs.revision))
+            .then_with(|| (self.latest, self.allowed_missing, &self.template).cmp(&(rhs.latest, rhs.allowed_missing, &rhs.template)))
+    }
+}
+
+impl PartialEq for Tag {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.cmp(rhs) == Ordering::Equal
+    }
+}
+
+impl Eq for Tag {}
+
+impl PartialOrd for Tag {
+    fn partial_cmp(&self, rhs: &Self) -> Option<Ordering> {
+        Some(self.cmp(rhs))
+    }
 }
 
 impl Display for Tag {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if self.latest {
             write!(f, "latest")
+        } else if let Some(template) = &self.template {
+            write!(f, "{}", self.render_template(template))
         } else {
+            let [major_width, minor_width, patch_width] = self.digits;
             match self.major {
-                Some(major) => write!(f, "{major}")?,
+                Some(major) => write!(f, "{major:0major_width$}")?,
                 None => write!(f, "")?,
             }
             match self.minor {
-                Some(minor) => write!(f, ".{minor}")?,
+                Some(minor) => write!(f, ".{minor:0minor_width$}")?,
                 None => write!(f, "")?,
             }
             match self.patch {
-                Some(patch) => write!(f, ".{patch}")?,
+                Some(patch) => write!(f, ".{patch:0patch_width$}")?,
+                None => write!(f, "")?,
+            }
+            match &self.build {
+                Some(build) => write!(f, "{build}")?,
                 None => write!(f, "")?,
             }
             match &self.variant {
-                Some(variant) => {
-                    write!(f, "{variant}")
-                }
+                Some(variant) => write!(f, "{variant}")?,
+                None => write!(f, "")?,
+            }
+            match self.revision {
+                Some(revision) => write!(f, "{REVISION_PREFIX}{revision}"),
                 None => write!(f, ""),
             }
         }
@@ -61,6 +292,59 @@ fn split_version_and_rest(s: &str) -> (&str, &str) {
     s.split_at(split_at)
 }
 
+/// Splits a trailing revision, e.g. `-r8`, from the rest of a tag.
+fn split_revision(rest: &str) -> (&str, Option<u64>) {
+    rest.rfind(REVISION_PREFIX)
+        .and_then(|index| {
+            let digits = &rest[index + REVISION_PREFIX.len()..];
+            if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+                digits.parse().ok().map(|revision| (&rest[..index], Some(revision)))
+            } else {
+                None
+            }
+        })
+        .unwrap_or((rest, None))
+}
+
+/// Splits build metadata from the version and the rest of a tag, e.g.
+/// `.20240910` of `1.27.1.20240910`, `-20240910` of `1.27.1-20240910` or the
+/// build number `_35` of `21_35-jdk`.
+fn split_build<'a>(version: &'a str, rest: &'a str) -> (&'a str, Option<String>, &'a str) {
+    let is_build = |part: &str| part.len() >= BUILD_DIGITS && part.chars().all(|c| c.is_ascii_digit());
+    // The first number is never build metadata, as plain dates are used as versions
+    // as well.
+    if let Some(index) = version.match_indices('.').map(|(index, _)| index).find(|index| {
+        let part = version[index + 1..].split('.').next().unwrap_or_default();
+        is_build(part)
+    }) {
+        return (&version[..index], Some(version[index..].to_owned()), rest);
+    }
+    if let Some(after_separator) = rest.strip_prefix(['-', '_', '.']) {
+        let end = after_separator.find(|c: char| !c.is_ascii_digit()).unwrap_or(after_separator.len());
+        // Build numbers after an underscore are short, e.g. `35` of `21_35-jdk`.
+        let is_build_number = rest.starts_with('_') && end > 0 && (end == after_separator.len() || after_separator[end..].starts_with(['-', '_', '.']));
+        if is_build_number || is_build(&after_separator[..end]) {
+            return (version, Some(rest[..=end].to_owned()), &rest[end + 1..]);
+        }
+    }
+    (version, None, rest)
+}
+
+/// Splits the pre-release channel off a variant, e.g. `-rc.1-bookworm-slim`
+/// into the rank of `rc`, the number 1 and the rest `-bookworm-slim`.
+fn split_prerelease(variant: &str) -> Option<(usize, u64, &str)> {
+    let variant = variant.strip_prefix('-').unwrap_or(variant);
+    PRERELEASE_CHANNELS.iter().enumerate().find_map(|(rank, channel)| {
+        let rest = variant
+            .get(..channel.len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case(channel))
+            .and_then(|_| variant.get(channel.len()..))?;
+        let rest = rest.strip_prefix('.').unwrap_or(rest);
+        let (number, rest) = rest.split_at(rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len());
+        (rest.is_empty() || rest.starts_with(['-', '_'])).then(|| (rank, number.parse().unwrap_or_default(), rest))
+    })
+}
+
 impl FromStr for Tag {
     type Err = Error;
 
@@ -70,17 +354,25 @@ impl FromStr for Tag {
                 major:           None,
                 minor:           None,
                 patch:           None,
+                build:           None,
                 variant:         None,
+                revision:        None,
+                digits:          [0; 3],
                 allowed_missing: false,
                 latest:          true,
+                template:        None,
             });
         }
         let (version, rest) = split_version_and_rest(s);
+        let (version, build, rest) = split_build(version, rest);
+        let (rest, revision) = split_revision(rest);
 
         let version_nums: Vec<&str> = version.split('.').collect();
         let major = version_nums.first().and_then(|v| v.parse().ok());
         let minor = version_nums.get(1).and_then(|v| v.parse().ok());
         let patch = version_nums.get(2).and_then(|v| v.parse().ok());
+        let width = |index: usize| version_nums.get(index).map_or(0, |v| v.len());
+        let digits = [width(0), width(1), width(2)];
 
         let variant = if rest.is_empty() { None } else { Some(TagVariant::from_str(rest)?) };
 
@@ -88,9 +380,13 @@ impl FromStr for Tag {
             major,
             minor,
             patch,
+            build,
             variant,
+            revision,
+            digits,
             allowed_missing: false,
             latest: false,
+            template: None,
         })
     }
 }
@@ -124,18 +420,136 @@ impl Tag {
         self.minor.is_some()
     }
 
-    /// Ensures that the variant prefix and suffix match properly
+    /// Returns the beginning all candidates of the strategy share, e.g. `1.29`
+    /// for the patch strategies and `1.` for the minor strategies of `1.29.3`,
+    /// so that the registry query can be narrowed down.
+    pub(crate) fn name_filter(&self, strategy: &Strategy) -> Option<String> {
+        let [major_width, minor_width, _] = self.digits;
+        match (strategy, self.major, self.minor) {
+            _ if self.latest => None,
+            (Strategy::NextPatch | Strategy::LatestPatch, Some(major), Some(minor)) => Some(format!("{major:0major_width$}.{minor:0minor_width$}")),
+            (Strategy::NextMinor | Strategy::LatestMinor, Some(major), _) => Some(format!("{major:0major_width$}.")),
+            _ => None,
+        }
+    }
+
+    /// Returns the amount of given version numbers, e.g. `1` for `16`, `2` for
+    /// `3.12` and `3` for `1.29.3-alpine`.
+    pub(crate) fn precision(&self) -> usize {
+        [self.major, self.minor, self.patch].iter().filter(|part| part.is_some()).count()
+    }
+
+    /// Returns the build metadata as number, e.g. `20240910` for
+    /// `1.27.1-20240910`.
+    fn build_number(&self) -> Option<u64> {
+        self.build
+            .as_deref()
+            .and_then(|build| build.trim_start_matches(|c: char| !c.is_ascii_digit()).parse().ok())
+    }
+
+    /// Fills the placeholders of a `TagScheme` template with the parts of the
+    /// tag.
+    fn render_template(&self, template: &str) -> String {
+        let number = |number: Option<u64>, width: usize| number.map(|number| format!("{number:0width$}")).unwrap_or_default();
+        template
+            .replace("{major}", &number(self.major, self.digits[0]))
+            .replace("{minor}", &number(self.minor, self.digits[1]))
+            .replace("{patch}", &number(self.patch, self.digits[2]))
+            .replace("{variant}", &self.variant.as_ref().map(ToString::to_string).unwrap_or_default())
+            .replace("{build}", self.build.as_deref().unwrap_or_default())
+    }
+
+    /// Ensures that the variant prefix, suffix and architecture match properly,
+    /// that either both or none of the tags carry build metadata and that both
+    /// are written in the same scheme.
     pub(crate) fn is_same_variant(&self, rhs: &Self) -> bool {
-        match (self.variant.as_ref(), rhs.variant.as_ref()) {
-            (Some(_), None) | (None, Some(_)) => false,
-            (None, None) => true,
-            (Some(current), Some(next)) => current.is_same_prefix(next) && current.is_same_suffix(next) && current.is_same_affix(next),
+        self.build.is_some() == rhs.build.is_some()
+            && self.template == rhs.template
+            && match (self.variant.as_ref(), rhs.variant.as_ref()) {
+                (Some(_), None) | (None, Some(_)) => false,
+                (None, None) => true,
+                (Some(current), Some(next)) => {
+                    current.is_same_prefix(next) && current.is_same_suffix(next) && current.is_same_affix(next) && current.is_same_arch(next)
+                }
+            }
+    }
+
+    /// Checks if the tag is a mutable convenience tag, see `MUTABLE_TAGS`.
+    pub(crate) fn is_mutable(&self) -> bool {
+        self.latest
+            || self.major.is_none()
+                && self.build.is_none()
+                && self
+                    .variant
+                    .as_ref()
+                    .is_some_and(|variant| MUTABLE_TAGS.iter().any(|name| variant.to_string().eq_ignore_ascii_case(name)))
+    }
+
+    /// Returns the first published mutable tag, see `MUTABLE_TAGS`. Only plain
+    /// versions are moved to them, since the tags carry no variant.
+    fn find_mutable_tag<'a>(&self, tag_list: &'a [Self]) -> Option<&'a Self> {
+        if self.is_mutable() || self.variant.is_some() || self.template.is_some() {
+            return None;
+        }
+        MUTABLE_TAGS
+            .iter()
+            .find_map(|name| tag_list.iter().find(|tag| tag.is_mutable() && tag.to_string().eq_ignore_ascii_case(name)))
+    }
+
+    /// Returns the rank of the release channel and the number of the
+    /// pre-release, a release ranks above all pre-releases of its version.
+    fn release_rank(&self) -> (usize, u64) {
+        let variant = self.variant.as_ref().map(ToString::to_string).unwrap_or_default();
+        split_prerelease(&variant).map_or((PRERELEASE_CHANNELS.len(), 0), |(rank, number, _)| (rank, number))
+    }
+
+    pub(crate) fn is_prerelease(&self) -> bool {
+        self.release_rank().0 < PRERELEASE_CHANNELS.len()
+    }
+
+    /// Returns the variant without the pre-release, e.g. `-bookworm-slim` for
+    /// `9.0.0-rc.1-bookworm-slim`.
+    pub(crate) fn release_variant(&self) -> String {
+        let variant = self.variant.as_ref().map(ToString::to_string).unwrap_or_default();
+        split_prerelease(&variant).map_or_else(|| variant.clone(), |(_, _, rest)| rest.to_owned())
+    }
+
+    /// Like `is_next_*`, but for pairs that involve a pre-release. Released
+    /// tags only get pre-releases if they are allowed, while pre-releases
+    /// move on to later pre-releases and the release of the same version,
+    /// e.g. `9.0.0-preview.7` to `9.0.0-rc.1` and `9.0.0`.
+    fn is_next_prerelease(&self, rhs: &Self, strategy: &Strategy, allow_prerelease: bool) -> bool {
+        if rhs.is_prerelease() && !self.is_prerelease() && !allow_prerelease {
+            return false;
+        }
+        if self.build.is_some() != rhs.build.is_some() || self.template != rhs.template || self.release_variant() != rhs.release_variant() {
+            return false;
+        }
+        if (self.major, self.minor, self.patch) == (rhs.major, rhs.minor, rhs.patch) {
+            return self.release_rank() < rhs.release_rank();
+        }
+        let is_next_patch = self.is_same_major(rhs) && self.is_same_minor(rhs) && self.patch < rhs.patch;
+        match strategy {
+            Strategy::NextPatch | Strategy::LatestPatch => is_next_patch,
+            Strategy::NextMinor | Strategy::LatestMinor => self.is_next_minor(rhs),
+            Strategy::NextMajor | Strategy::LatestMajor => self.is_next_major(rhs),
+            Strategy::Latest | Strategy::LtsOnly => self.is_next_major(rhs) || self.is_next_minor(rhs) || is_next_patch,
         }
     }
 
+    /// Orders by the version, then by the release channel, so that a release
+    /// comes after its pre-releases, and then by all other components.
+    fn cmp_release(&self, rhs: &Self) -> Ordering {
+        (self.major, self.minor, self.patch, self.release_rank())
+            .cmp(&(rhs.major, rhs.minor, rhs.patch, rhs.release_rank()))
+            .then_with(|| self.cmp(rhs))
+    }
+
     /// Checks if the next major version is greater than the current version.
+    /// The next version needs a minor version, unless the current version is
+    /// a bare major as well, e.g. `16` to `17`.
     pub(crate) const fn is_next_major(&self, rhs: &Self) -> bool {
-        rhs.has_minor()
+        (rhs.has_minor() || !self.has_minor())
             && match (self.major, rhs.major) {
                 (None | Some(_), None) | (None, Some(_)) => false,
                 (Some(current), Some(next)) => current < next,
@@ -162,7 +576,7 @@ impl Tag {
                 (Some(current), Some(next)) => {
                     current < next
                         || current == next
-                            && match (self.variant.as_ref(), rhs.variant.as_ref()) {
+                            && (match (self.variant.as_ref(), rhs.variant.as_ref()) {
                                 (None | Some(_), None) | (None, Some(_)) => false,
                                 (Some(current_variant), Some(next_variant)) => {
                                     current_variant.is_same_prefix(next_variant)
@@ -171,56 +585,280 @@ impl Tag {
                                         || current_variant.is_next_minor(next_variant)
                                         || current_variant.is_next_patch(next_variant)
                                 }
-                            }
+                            } || self.is_rebuild(rhs))
                 }
             }
     }
 
-    /// Will return an Option, to an item in the list, with a tag that matches
-    /// the strategy.
-    pub(crate) fn find_candidate_tag<'a>(&self, tag_list: &'a [Self], strategy: &Strategy) -> Option<&'a Self> {
-        let mut filtered_tags: Vec<&Self> = tag_list
-            .iter()
-            .filter(|tag| {
-                self.is_same_variant(tag)
-                    && match strategy {
-                        Strategy::NextPatch | Strategy::LatestPatch => self.is_next_patch(tag),
-                        Strategy::NextMinor | Strategy::LatestMinor => self.is_next_minor(tag),
-                        Strategy::NextMajor | Strategy::LatestMajor => self.is_next_major(tag),
-                        Strategy::Latest => self.is_next_major(tag) || self.is_next_minor(tag) || self.is_next_patch(tag),
-                    }
-            })
-            .collect();
+    /// Checks if the next tag only increases the revision or the build, while
+    /// the version and the variant stay the same, e.g. `9.0.1-debian-12-r8` to
+    /// `9.0.1-debian-12-r9` or `1.27.1-20240910` to `1.27.1-20241001`.
+    pub(crate) fn is_rebuild(&self, rhs: &Self) -> bool {
+        self.major == rhs.major
+            && self.minor == rhs.minor
+            && self.patch == rhs.patch
+            && self.variant == rhs.variant
+            && match (self.build_number(), rhs.build_number()) {
+                (Some(current), Some(next)) if current != next => current < next,
+                _ => match (self.revision, rhs.revision) {
+                    (None | Some(_), None) | (None, Some(_)) => false,
+                    (Some(current), Some(next)) => current < next,
+                },
+            }
+    }
 
-        if filtered_tags.is_empty() {
-            debug!("No matching tags found");
+    /// Compares the version numbers, then the version numbers of the variant,
+    /// then the build and then the revision. Tags that only differ in the names
+    /// of the variant, e.g. `bookworm` and `trixie`, are equal.
+    pub(crate) fn cmp_version(&self, rhs: &Self) -> Ordering {
+        let variant_version = |tag: &Self| tag.variant.as_ref().map(|variant| (variant.major, variant.minor, variant.patch));
+        (self.latest, self.major, self.minor, self.patch)
+            .cmp(&(rhs.latest, rhs.major, rhs.minor, rhs.patch))
+            .then_with(|| variant_version(self).cmp(&variant_version(rhs)))
+            .then_with(|| self.build_number().cmp(&rhs.build_number()))
+            .then_with(|| self.revision.cmp(&rhs.revision))
+    }
+
+    /// Returns the most significant part that differs between both tags, e.g.
+    /// `minor` for `1.29.3` and `1.30.0`.
+    pub(crate) fn bump(&self, rhs: &Self) -> Bump {
+        if self.latest != rhs.latest || self.major != rhs.major {
+            Bump::Major
+        } else if self.minor != rhs.minor {
+            Bump::Minor
+        } else if self.patch != rhs.patch {
+            Bump::Patch
+        } else if self.variant != rhs.variant {
+            Bump::Variant
+        } else if self.build != rhs.build || self.revision != rhs.revision {
+            Bump::Rebuild
+        } else {
+            Bump::None
+        }
+    }
+
+    /// Will return an Option, to the latest rebuild of the current version in
+    /// the list.
+    pub(crate) fn find_rebuild_tag<'a>(&self, tag_list: &'a [Self]) -> Option<&'a Self> {
+        tag_list.iter().filter(|tag| self.is_rebuild(tag)).max()
+    }
+
+    /// Drops the version numbers and the revision the other tag does not have,
+    /// so that e.g. `1.30.2` turns into `1.30` for `1.29` and into `2` for `1`.
+    pub(crate) fn with_specificity_of(&self, other: &Self) -> Self {
+        let mut result = self.clone();
+        if other.major.is_some() {
+            if other.minor.is_none() {
+                result.minor = None;
+                result.digits[1] = 0;
+            }
+            if other.patch.is_none() {
+                result.patch = None;
+                result.digits[2] = 0;
+            }
+        }
+        if other.revision.is_none() {
+            result.revision = None;
+        }
+        result
+    }
+
+    /// Returns the ways this tag can be written, starting with the specificity
+    /// of the other tag and ending with the full tag, e.g. `1.30`, `1.30.2`
+    /// for `1.29`.
+    pub(crate) fn representations(&self, other: &Self) -> Vec<Self> {
+        let mut template = other.clone();
+        let mut result = vec![self.with_specificity_of(&template)];
+        template.minor = template.minor.or(self.minor);
+        result.push(self.with_specificity_of(&template));
+        template.patch = template.patch.or(self.patch);
+        result.push(self.with_specificity_of(&template));
+        result.push(self.clone());
+        result.dedup();
+        result
+    }
+
+    /// Returns the same version with the given suffix appended to the variant,
+    /// e.g. `24.6.0-bookworm` and `slim` turn into `24.6.0-bookworm-slim`.
+    /// Returns `None` if the variant already contains the suffix.
+    pub(crate) fn with_suffix(&self, suffix: &str) -> Option<Self> {
+        if self.latest || self.allowed_missing || self.template.is_some() {
             return None;
         }
+        let variant = self.variant.as_ref().map(ToString::to_string).unwrap_or_default();
+        if variant.split(['-', '_']).any(|part| part == suffix) {
+            return None;
+        }
+        let without_revision = Self {
+            revision: None,
+            ..self.clone()
+        };
+        let mut result: Self = format!("{without_revision}-{suffix}").parse().ok()?;
+        result.revision = self.revision;
+        Some(result)
+    }
+
+    /// Will return the rendered candidate for an item in the list, with a tag
+    /// that matches the options. Compared to `find_candidate_tag` this also
+    /// respects the preferred suffix and the specificity of the current tag.
+    pub(crate) fn select_candidate(&self, tag_list: &[Self], options: &CandidateOptions) -> Option<Self> {
+        self.find_preferred_candidate(tag_list, options)
+            .map(|candidate| options.render(self, candidate, tag_list))
+    }
+
+    /// Returns up to `count` rendered candidates of the first strategy that
+    /// yields any, the best one first. Tags that render the same, e.g. `3.13`
+    /// for `3.13.1` and `3.13.2`, are listed once.
+    pub(crate) fn select_candidates(&self, tag_list: &[Self], options: &CandidateOptions, count: usize) -> Vec<Self> {
+        let matching = |strategy: &Strategy| {
+            let mut tags: Vec<&Self> = tag_list
+                .iter()
+                .filter(|tag| options.admits(self, tag))
+                .filter(|tag| {
+                    self.skip_reason(tag, strategy, options.allow_prerelease, options.lts_majors.as_deref())
+                        .is_none()
+                })
+                .collect();
+            match strategy {
+                Strategy::NextMajor | Strategy::NextMinor | Strategy::NextPatch => tags.sort_by(|a, b| a.cmp_release(b)),
+                Strategy::LatestMajor | Strategy::LatestMinor | Strategy::LatestPatch | Strategy::Latest | Strategy::LtsOnly => {
+                    tags.sort_by(|a, b| b.cmp_release(a));
+                }
+            }
+            tags
+        };
+        let tags = std::iter::once(&options.strategy)
+            .chain(&options.fallbacks)
+            .map(matching)
+            .find(|tags| !tags.is_empty())
+            .unwrap_or_default();
+        let mut candidates: Vec<Self> = Vec::new();
+        for tag in tags {
+            let rendered = options.render(self, tag, tag_list);
+            if !candidates.contains(&rendered) {
+                candidates.push(rendered);
+            }
+            if candidates.len() == count {
+                break;
+            }
+        }
+        candidates
+    }
 
-        // Ensuring that the results are sorted, in ascending order,
-        // so that the first entry is closes to the starting tag.
-        // The last entry in the list is the latest one depending on the chosen
-        // strategy.
-        filtered_tags.sort();
+    /// Returns the item in the list, that matches the options.
+    fn find_preferred_candidate<'a>(&self, tag_list: &'a [Self], options: &CandidateOptions) -> Option<&'a Self> {
+        if options.include_latest
+            && options.strategy == Strategy::Latest
+            && let Some(mutable) = self.find_mutable_tag(tag_list)
+        {
+            return Some(mutable);
+        }
+        let find_candidate = |strategy: &Strategy| {
+            if options.same_precision || !options.variant_pins.is_empty() {
+                let tags = tag_list.iter().filter(|tag| options.admits(self, tag));
+                self.find_candidate_in(tags, strategy, options.allow_prerelease, options.lts_majors.as_deref())
+            } else {
+                self.find_candidate_tag(tag_list, strategy, options.allow_prerelease, options.lts_majors.as_deref())
+            }
+        };
+        let candidate = std::iter::once(&options.strategy).chain(&options.fallbacks).find_map(|strategy| {
+            let candidate = find_candidate(strategy);
+            if candidate.is_some() && *strategy != options.strategy {
+                debug!("No candidate found for {}, using {strategy} instead.", options.strategy);
+            }
+            candidate
+        });
+        // The version of the candidate is kept, only its variant is moved to a
+        // supported release the other options allow.
+        let candidate = if options.update_eol_variants {
+            let tags = tag_list.iter().filter(|tag| options.admits(self, tag));
+            eol::find_supported_variant(candidate.unwrap_or(self), tags, Date::today()).or(candidate)
+        } else {
+            candidate
+        };
+        let Some(suffix) = options.prefer_suffix.as_deref() else {
+            return candidate;
+        };
+        // Switch to the preferred suffix, if the very same version is published with
+        // it.
+        candidate
+            .unwrap_or(self)
+            .with_suffix(suffix)
+            .and_then(|preferred| tag_list.iter().find(|tag| **tag == preferred))
+            .or(candidate)
+    }
 
-        for result_tag in &filtered_tags {
-            debug!("{result_tag}");
+    /// Will return an Option, to an item in the list, with a tag that matches
+    /// the strategy.
+    pub(crate) fn find_candidate_tag<'a>(
+        &self, tag_list: &'a [Self], strategy: &Strategy, allow_prerelease: bool, lts_majors: Option<&[u64]>,
+    ) -> Option<&'a Self> {
+        self.find_candidate_in(tag_list, strategy, allow_prerelease, lts_majors)
+    }
+
+    /// Returns the filter step that rules out the tag as candidate for the
+    /// strategy, `None` if it is a candidate.
+    pub(crate) fn skip_reason(&self, tag: &Self, strategy: &Strategy, allow_prerelease: bool, lts_majors: Option<&[u64]>) -> Option<SkipStage> {
+        // Mutable tags are no versions, see `find_mutable_tag`.
+        if tag.is_mutable() {
+            return Some(SkipStage::Strategy);
+        }
+        if *strategy == Strategy::LtsOnly && !tag.major.is_some_and(|major| channel::is_lts_major(major, lts_majors)) {
+            return Some(SkipStage::Strategy);
         }
+        if self.is_prerelease() || tag.is_prerelease() {
+            return (!self.is_next_prerelease(tag, strategy, allow_prerelease)).then_some(SkipStage::Strategy);
+        }
+        if !self.is_same_variant(tag) {
+            return Some(SkipStage::Variant);
+        }
+        let is_update = match strategy {
+            Strategy::NextPatch | Strategy::LatestPatch => self.is_next_patch(tag),
+            Strategy::NextMinor | Strategy::LatestMinor => self.is_next_minor(tag),
+            Strategy::NextMajor | Strategy::LatestMajor => self.is_next_major(tag),
+            Strategy::Latest | Strategy::LtsOnly => self.is_next_major(tag) || self.is_next_minor(tag) || self.is_next_patch(tag),
+        };
+        (!is_update).then_some(SkipStage::Strategy)
+    }
 
-        match strategy {
-            Strategy::NextMajor | Strategy::NextMinor | Strategy::NextPatch => filtered_tags.first().copied(),
-            Strategy::LatestMajor | Strategy::LatestMinor | Strategy::LatestPatch | Strategy::Latest => filtered_tags.last().copied(),
+    /// See `find_candidate_tag`, but works on any selection of tags.
+    fn find_candidate_in<'a, I>(&self, tags: I, strategy: &Strategy, allow_prerelease: bool, lts_majors: Option<&[u64]>) -> Option<&'a Self>
+    where
+        I: IntoIterator<Item = &'a Self>,
+    {
+        let filtered_tags = tags
+            .into_iter()
+            .filter(|tag| self.skip_reason(tag, strategy, allow_prerelease, lts_majors).is_none())
+            .inspect(|result_tag| debug!("{result_tag}"));
+
+        // Only the closest or the latest matching tag is of interest, so the matches
+        // are not collected and sorted, but selected in a single pass.
+        let candidate = match strategy {
+            Strategy::NextMajor | Strategy::NextMinor | Strategy::NextPatch => filtered_tags.min_by(|a, b| a.cmp_release(b)),
+            Strategy::LatestMajor | Strategy::LatestMinor | Strategy::LatestPatch | Strategy::Latest | Strategy::LtsOnly => {
+                filtered_tags.max_by(|a, b| a.cmp_release(b))
+            }
+        };
+        if candidate.is_none() {
+            debug!("No matching tags found");
         }
+        candidate
     }
 }
 
 #[cfg(test)]
 mod tests {
     #![allow(clippy::unwrap_used)]
+    use std::cmp::Ordering;
+
     use pretty_assertions::assert_eq;
+    use proptest::prelude::{prop_assert, prop_assert_eq, proptest};
+    use proptest::strategy::Strategy as _;
 
-    use crate::tag::Tag;
-    use crate::tag::variant::TagVariant;
+    use crate::skipped::SkipStage;
+    use crate::tag::variant::{TagVariant, VariantPin};
+    use crate::tag::{Bump, CandidateOptions, Tag};
+    use crate::utils::Strategy;
 
     #[test]
     #[allow(clippy::too_many_lines)]
@@ -306,6 +944,8 @@ mod tests {
                 patch:   None,
                 affixes: vec![],
                 suffix:  None,
+                digits:  vec![1, 2],
+                arch:    None,
             })
         );
         assert_eq!(tag.to_string(), expected);
@@ -324,6 +964,8 @@ mod tests {
                 patch:   Some(1),
                 affixes: vec![],
                 suffix:  None,
+                digits:  vec![1, 2, 1],
+                arch:    None,
             })
         );
         assert_eq!(tag.to_string(), expected);
@@ -347,8 +989,8 @@ mod tests {
         assert_eq!(tag.variant.clone().unwrap().prefix, Some("-azurelinux".to_owned()));
         assert_eq!(tag.variant.clone().unwrap().major, Some(3));
         assert_eq!(tag.variant.clone().unwrap().minor, Some(0));
-        assert_eq!(tag.variant.clone().unwrap().affixes.get(1), Some("-amd".to_owned()).as_ref());
-        assert_eq!(tag.variant.clone().unwrap().patch, Some(64));
+        assert_eq!(tag.variant.clone().unwrap().patch, None);
+        assert_eq!(tag.variant.clone().unwrap().arch, Some("-amd64".to_owned()));
         assert_eq!(tag.to_string(), expected);
     }
 
@@ -402,6 +1044,111 @@ mod tests {
         assert!(current.is_next_patch(&next));
     }
 
+    #[test]
+    fn strategy_fallbacks() {
+        let tags: Vec<Tag> = ["1.2.3", "2.0.0", "3.1.0"].iter().map(|tag| tag.parse().unwrap()).collect();
+        let current: Tag = "1.2.3".parse().unwrap();
+        let options = CandidateOptions {
+            strategy: Strategy::NextMinor,
+            fallbacks: vec![Strategy::NextPatch, Strategy::LatestMajor],
+            ..CandidateOptions::default()
+        };
+        assert_eq!(current.select_candidate(&tags, &options).unwrap().to_string(), "3.1.0");
+
+        let tags: Vec<Tag> = ["1.2.3", "1.3.0", "3.1.0"].iter().map(|tag| tag.parse().unwrap()).collect();
+        assert_eq!(current.select_candidate(&tags, &options).unwrap().to_string(), "1.3.0");
+        assert_eq!(current.select_candidate(&tags[..1], &options), None);
+    }
+
+    #[test]
+    fn build_metadata() {
+        let tag: Tag = "1.27.1-20240910".parse().unwrap();
+        assert_eq!((tag.major, tag.minor, tag.patch), (Some(1), Some(27), Some(1)));
+        assert_eq!(tag.build.as_deref(), Some("-20240910"));
+        assert_eq!(tag.variant, None);
+        assert_eq!(tag.to_string(), "1.27.1-20240910");
+
+        let tag: Tag = "1.27.20240910".parse().unwrap();
+        assert_eq!((tag.major, tag.minor, tag.patch), (Some(1), Some(27), None));
+        assert_eq!(tag.build.as_deref(), Some(".20240910"));
+        assert_eq!(tag.to_string(), "1.27.20240910");
+
+        let tag: Tag = "1.27.1-20240910-alpine".parse().unwrap();
+        assert_eq!(tag.build.as_deref(), Some("-20240910"));
+        assert_eq!(tag.variant.clone().unwrap().prefix.as_deref(), Some("-alpine"));
+        assert_eq!(tag.to_string(), "1.27.1-20240910-alpine");
+
+        let tag: Tag = "21_35-jdk".parse().unwrap();
+        assert_eq!(tag.major, Some(21));
+        assert_eq!(tag.build.as_deref(), Some("_35"));
+        assert_eq!(tag.build_number(), Some(35));
+        assert_eq!(tag.variant.clone().unwrap().prefix.as_deref(), Some("-jdk"));
+        assert_eq!(tag.to_string(), "21_35-jdk");
+        let tag: Tag = "21.0.4_7-jre".parse().unwrap();
+        assert_eq!((tag.patch, tag.build_number()), (Some(4), Some(7)));
+        assert!(tag < "21.0.4_11-jre".parse().unwrap());
+
+        let tag: Tag = "20240910".parse().unwrap();
+        assert_eq!(tag.major, Some(20_240_910));
+        assert_eq!(tag.build, None);
+
+        let tags: Vec<Tag> = ["1.27.1-20240910", "1.27.1-20241001", "1.27.2", "1.27.2-20241005"]
+            .iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+        let current = &tags[0];
+        let options = CandidateOptions::from(Strategy::NextPatch);
+        assert_eq!(current.select_candidate(&tags, &options).unwrap().to_string(), "1.27.1-20241001");
+        let options = CandidateOptions::from(Strategy::LatestPatch);
+        assert_eq!(current.select_candidate(&tags, &options).unwrap().to_string(), "1.27.2-20241005");
+        assert_eq!(current.bump(&tags[1]), Bump::Rebuild);
+        assert_eq!(current.cmp_version(&tags[1]), Ordering::Less);
+    }
+
+    #[test]
+    fn arch_suffixes() {
+        let tag: Tag = "1.2.3-alpine3.20-arm64".parse().unwrap();
+        let variant = tag.variant.clone().unwrap();
+        assert_eq!(variant.prefix.as_deref(), Some("-alpine"));
+        assert_eq!((variant.major, variant.minor, variant.patch), (Some(3), Some(20), None));
+        assert_eq!(variant.arch.as_deref(), Some("-arm64"));
+        assert_eq!(tag.to_string(), "1.2.3-alpine3.20-arm64");
+
+        let tag: Tag = "1.2.3_amd64".parse().unwrap();
+        assert_eq!(tag.variant.clone().unwrap().arch.as_deref(), Some("_amd64"));
+        assert_eq!(tag.to_string(), "1.2.3_amd64");
+
+        let tags: Vec<Tag> = ["1.2.3", "1.2.4-amd64", "1.2.4-arm64v8", "1.2.4", "1.2.5-amd64"]
+            .iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+        let options = CandidateOptions::default();
+        let current: Tag = "1.2.3".parse().unwrap();
+        assert_eq!(current.select_candidate(&tags, &options).unwrap().to_string(), "1.2.4");
+        let current: Tag = "1.2.3-amd64".parse().unwrap();
+        assert_eq!(current.select_candidate(&tags, &options).unwrap().to_string(), "1.2.5-amd64");
+    }
+
+    #[test]
+    fn bumps() {
+        for (lhs, rhs, ordering, bump) in [
+            ("1.29.3", "2.0.0", Ordering::Less, Bump::Major),
+            ("1.30.0", "1.29.3", Ordering::Greater, Bump::Minor),
+            ("1.29", "1.29.3", Ordering::Less, Bump::Patch),
+            ("1.29.3-alpine3.22", "1.29.3-alpine3.23", Ordering::Less, Bump::Variant),
+            ("24.12.0-bookworm-slim", "24.12.0-trixie-slim", Ordering::Equal, Bump::Variant),
+            ("9.0.1-debian-12-r9", "9.0.1-debian-12-r8", Ordering::Greater, Bump::Rebuild),
+            ("1.29.3-alpine", "1.29.3-alpine", Ordering::Equal, Bump::None),
+            // `latest` is newer than every version, unlike in the order of tag lists.
+            ("latest", "2.0.0", Ordering::Greater, Bump::Major),
+        ] {
+            let lhs: Tag = lhs.parse().unwrap();
+            let rhs: Tag = rhs.parse().unwrap();
+            assert_eq!(lhs.cmp_version(&rhs), ordering, "{lhs} {rhs}");
+            assert_eq!(lhs.bump(&rhs), bump, "{lhs} {rhs}");
+        }
+    }
+
     #[test]
     fn next_patch() {
         let cases = [
@@ -457,6 +1204,10 @@ mod tests {
             ("2.6.9-bookworm-slim", "3.6.10-bookworm-slim", true),
             ("8.0.1-debian-12-r8", "9.0.1-debian-12-r8", true),
             ("2.6.9", "2.7.9", false),
+            ("16", "17", true),
+            ("16", "17.2", true),
+            ("16.1", "17", false),
+            ("17", "16", false),
         ];
 
         for (current, next, expect) in &cases {
@@ -466,4 +1217,484 @@ mod tests {
             assert_eq!(got, *expect, "is_next_major({}, {}) → expected {}, got {}", current, next, expect, got);
         }
     }
+
+    #[test]
+    fn revisions() {
+        let tag: Tag = "9.0.1-debian-12-r8".parse().unwrap();
+        assert_eq!(tag.revision, Some(8));
+        assert_eq!(tag.variant.clone().unwrap().prefix, Some("-debian-".to_owned()));
+        assert_eq!(tag.variant.clone().unwrap().major, Some(12));
+        assert!(tag.variant.clone().unwrap().affixes.is_empty());
+        assert_eq!(tag.to_string(), "9.0.1-debian-12-r8");
+
+        let tag: Tag = "3.18.4-r0".parse().unwrap();
+        assert_eq!(tag.variant, None);
+        assert_eq!(tag.revision, Some(0));
+        assert_eq!(tag.to_string(), "3.18.4-r0");
+
+        // Only a trailing number is a revision.
+        let tag: Tag = "1.2.3-rc1-alpine".parse().unwrap();
+        assert_eq!(tag.revision, None);
+        assert_eq!(tag.to_string(), "1.2.3-rc1-alpine");
+
+        let current: Tag = "9.0.1-debian-12-r8".parse().unwrap();
+        let cases = [
+            ("9.0.1-debian-12-r9", true),
+            ("9.0.1-debian-12-r7", false),
+            ("9.0.1-debian-13-r9", false),
+            ("9.0.2-debian-12-r9", false),
+        ];
+        for (next, expect) in &cases {
+            let n = next.parse::<Tag>().expect("right tag valid");
+            assert_eq!(current.is_rebuild(&n), *expect, "is_rebuild({current}, {next})");
+        }
+
+        let tags: Vec<Tag> = ["9.0.1-debian-12-r9", "9.0.1-debian-12-r11", "9.0.1-debian-12-r10", "9.0.2-debian-12-r0"]
+            .iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+        assert_eq!(current.find_rebuild_tag(&tags).unwrap().to_string(), "9.0.1-debian-12-r11");
+        let latest_patch = current.find_candidate_tag(&tags, &Strategy::LatestPatch, false, None).unwrap();
+        assert_eq!(latest_patch.to_string(), "9.0.2-debian-12-r0");
+
+        // The latest strategy prefers the highest revision for the same version.
+        let tags: Vec<Tag> = ["9.0.1-debian-12-r9", "9.0.1-debian-12-r11", "9.0.1-debian-12-r10"]
+            .iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+        assert_eq!(
+            current.find_candidate_tag(&tags, &Strategy::Latest, false, None).unwrap().to_string(),
+            "9.0.1-debian-12-r11"
+        );
+    }
+
+    #[test]
+    fn preferred_suffix() {
+        let tag: Tag = "24.6.0-bookworm".parse().unwrap();
+        assert_eq!(tag.with_suffix("slim").unwrap().to_string(), "24.6.0-bookworm-slim");
+        let tag: Tag = "3.12.3".parse().unwrap();
+        assert_eq!(tag.with_suffix("slim").unwrap().to_string(), "3.12.3-slim");
+        let tag: Tag = "9.0.1-debian-12-r8".parse().unwrap();
+        assert_eq!(tag.with_suffix("slim").unwrap().to_string(), "9.0.1-debian-12-slim-r8");
+        let tag: Tag = "24.6.0-bookworm-slim".parse().unwrap();
+        assert_eq!(tag.with_suffix("slim"), None);
+        let tag: Tag = "3.12.3-slim-bookworm".parse().unwrap();
+        assert_eq!(tag.with_suffix("slim"), None);
+
+        let tags: Vec<Tag> = [
+            "24.6.0-bookworm",
+            "24.6.0-bookworm-slim",
+            "24.7.0-bookworm",
+            "24.7.0-bookworm-slim",
+            "24.8.0-bookworm",
+        ]
+        .iter()
+        .map(|tag| tag.parse().unwrap())
+        .collect();
+        let options = CandidateOptions {
+            strategy: Strategy::NextMinor,
+            prefer_suffix: Some("slim".to_owned()),
+            ..CandidateOptions::default()
+        };
+        let current: Tag = "24.6.0-bookworm".parse().unwrap();
+        assert_eq!(current.select_candidate(&tags, &options).unwrap().to_string(), "24.7.0-bookworm-slim");
+        assert_eq!(
+            current.select_candidate(&tags, &Strategy::NextMinor.into()).unwrap().to_string(),
+            "24.7.0-bookworm"
+        );
+
+        // Without a slim equivalent the regular candidate is kept.
+        let options = CandidateOptions {
+            strategy: Strategy::LatestMinor,
+            prefer_suffix: Some("slim".to_owned()),
+            ..CandidateOptions::default()
+        };
+        assert_eq!(current.select_candidate(&tags, &options).unwrap().to_string(), "24.8.0-bookworm");
+
+        // The same version is switched, even if there is no newer one.
+        let current: Tag = "24.8.0-bookworm".parse().unwrap();
+        let tags: Vec<Tag> = ["24.8.0-bookworm", "24.8.0-bookworm-slim"].iter().map(|tag| tag.parse().unwrap()).collect();
+        assert_eq!(current.select_candidate(&tags, &options).unwrap().to_string(), "24.8.0-bookworm-slim");
+    }
+
+    #[test]
+    fn ordering() {
+        let mut tags: Vec<Tag> = [
+            "2",
+            "latest",
+            "1.10.0",
+            "1.9.1-alpine3.10-slim",
+            "1.9.1-alpine3.10",
+            "1.9.1-alpine3.9",
+            "1.9.1",
+            "1.9",
+        ]
+        .iter()
+        .map(|tag| tag.parse().unwrap())
+        .collect();
+        tags.sort();
+        let sorted: Vec<String> = tags.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            sorted,
+            [
+                "latest",
+                "1.9",
+                "1.9.1",
+                "1.9.1-alpine3.9",
+                "1.9.1-alpine3.10",
+                "1.9.1-alpine3.10-slim",
+                "1.10.0",
+                "2"
+            ]
+        );
+    }
+
+    /// Generates realistic tags, e.g. `1.27.3-alpine3.20`, `24.4` or
+    /// `3.12.4-slim-bookworm-r2`.
+    fn realistic_tags() -> impl proptest::strategy::Strategy<Value = String> {
+        let version = proptest::collection::vec(0..30_u64, 1..=3).prop_map(|numbers| numbers.iter().map(ToString::to_string).collect::<Vec<_>>().join("."));
+        let variant = proptest::sample::select(vec![
+            "",
+            "-alpine",
+            "-alpine3.20",
+            "-slim",
+            "-bookworm",
+            "-slim-bookworm",
+            "-bookworm-slim",
+            "-jre-jammy",
+            "-windowsservercore-ltsc2022",
+            "-rc.1",
+            "-preview.7",
+            "-amd64",
+            "-debian-12",
+        ]);
+        let revision = proptest::option::of(0..20_u64).prop_map(|revision| revision.map(|revision| format!("-r{revision}")).unwrap_or_default());
+        (version, variant, revision).prop_map(|(version, variant, revision)| format!("{version}{variant}{revision}"))
+    }
+
+    proptest! {
+        #[test]
+        fn tag_round_trip(text in realistic_tags()) {
+            let tag: Tag = text.parse().unwrap();
+            prop_assert_eq!(tag.to_string(), text);
+            prop_assert_eq!(tag.to_string().parse::<Tag>().unwrap(), tag);
+        }
+
+        #[test]
+        fn tag_parsing_never_panics(text in "\\PC{0,24}") {
+            if let Ok(tag) = text.parse::<Tag>() {
+                prop_assert_eq!(tag.to_string().parse::<Tag>().map(|reparsed| reparsed.to_string()).ok(), Some(tag.to_string()));
+            }
+        }
+
+        #[test]
+        fn tag_ordering(a in realistic_tags(), b in realistic_tags(), c in realistic_tags()) {
+            let (a, b, c): (Tag, Tag, Tag) = (a.parse().unwrap(), b.parse().unwrap(), c.parse().unwrap());
+            prop_assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+            prop_assert_eq!(a.cmp(&b) == Ordering::Equal, a == b);
+            if a <= b && b <= c {
+                prop_assert!(a <= c);
+            }
+        }
+    }
+
+    #[test]
+    fn variant_pins() {
+        let tags: Vec<Tag> = ["1.27.3-alpine3.20", "1.27.4-alpine3.21", "1.27.4-alpine", "1.27.4-bookworm"]
+            .iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+        let options = CandidateOptions {
+            strategy: Strategy::Latest,
+            variant_pins: vec!["alpine=3.20".parse().unwrap()],
+            ..CandidateOptions::default()
+        };
+        let current: Tag = "1.27.2-alpine3.20".parse().unwrap();
+        assert_eq!(current.select_candidate(&tags, &options).unwrap().to_string(), "1.27.3-alpine3.20");
+        assert_eq!(
+            current.select_candidate(&tags, &Strategy::Latest.into()).unwrap().to_string(),
+            "1.27.4-alpine3.21"
+        );
+        // Variants without a version are only pinned for versioned variants.
+        let current: Tag = "1.27.2-alpine".parse().unwrap();
+        assert_eq!(current.select_candidate(&tags, &options).unwrap().to_string(), "1.27.4-alpine");
+        // Moving a variant past its end of life keeps to the pins as well.
+        let tags: Vec<Tag> = ["20.11.1-alpine3.17", "20.11.1-alpine3.22"].iter().map(|tag| tag.parse().unwrap()).collect();
+        let current: Tag = "20.11.1-alpine3.17".parse().unwrap();
+        let options = CandidateOptions {
+            strategy: Strategy::Latest,
+            update_eol_variants: true,
+            ..CandidateOptions::default()
+        };
+        assert_eq!(current.select_candidate(&tags, &options).unwrap().to_string(), "20.11.1-alpine3.22");
+        let options = CandidateOptions {
+            variant_pins: vec!["alpine=3.17".parse().unwrap()],
+            ..options
+        };
+        assert_eq!(current.select_candidate(&tags, &options), None);
+        assert!("alpine".parse::<VariantPin>().is_err());
+        assert!("alpine=3.x".parse::<VariantPin>().is_err());
+    }
+
+    #[test]
+    fn same_precision() {
+        assert_eq!("16".parse::<Tag>().unwrap().precision(), 1);
+        assert_eq!("3.12-alpine".parse::<Tag>().unwrap().precision(), 2);
+        assert_eq!("1.29.3-alpine3.22".parse::<Tag>().unwrap().precision(), 3);
+
+        let tags: Vec<Tag> = ["16", "16.4", "17", "17.2", "17.2.1", "18", "18.0"]
+            .iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+        let current: Tag = "16".parse().unwrap();
+        let options = CandidateOptions {
+            strategy: Strategy::LatestMajor,
+            same_precision: true,
+            ..CandidateOptions::default()
+        };
+        assert_eq!(current.select_candidate(&tags, &options).unwrap().to_string(), "18");
+        let options = CandidateOptions {
+            strategy: Strategy::NextMajor,
+            same_precision: true,
+            ..CandidateOptions::default()
+        };
+        assert_eq!(current.select_candidate(&tags, &options).unwrap().to_string(), "17");
+
+        let tags: Vec<Tag> = ["3.12", "3.12.4", "3.13", "3.13.1", "3.14.0"].iter().map(|tag| tag.parse().unwrap()).collect();
+        let current: Tag = "3.12".parse().unwrap();
+        let options = CandidateOptions {
+            strategy: Strategy::Latest,
+            same_precision: true,
+            ..CandidateOptions::default()
+        };
+        assert_eq!(current.select_candidate(&tags, &options).unwrap().to_string(), "3.13");
+        let options = CandidateOptions {
+            strategy: Strategy::Latest,
+            full_precision: true,
+            ..CandidateOptions::default()
+        };
+        assert_eq!(current.select_candidate(&tags, &options).unwrap().to_string(), "3.14.0");
+    }
+
+    #[test]
+    fn top_candidates() {
+        let tags: Vec<Tag> = ["3.10", "3.10.4", "3.11", "3.11.2", "3.12", "3.12.1", "3.12.3", "3.13", "3.13.0", "4.0"]
+            .iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+        let render = |candidates: Vec<Tag>| candidates.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let current: Tag = "3.10".parse().unwrap();
+        let options: CandidateOptions = Strategy::LatestMinor.into();
+        assert_eq!(render(current.select_candidates(&tags, &options, 3)), ["3.13", "3.12", "3.11"]);
+        assert_eq!(render(current.select_candidates(&tags, &Strategy::NextMinor.into(), 2)), ["3.11", "3.12"]);
+        let current: Tag = "3.12.1".parse().unwrap();
+        assert_eq!(render(current.select_candidates(&tags, &Strategy::Latest.into(), 3)), ["4.0", "3.13.0", "3.13"]);
+        let options = CandidateOptions {
+            strategy: Strategy::NextPatch,
+            fallbacks: vec![Strategy::NextMinor],
+            ..CandidateOptions::default()
+        };
+        let current: Tag = "3.12.3".parse().unwrap();
+        assert_eq!(render(current.select_candidates(&tags, &options, 5)), ["3.13", "3.13.0"]);
+        assert!(current.select_candidates(&tags, &Strategy::NextPatch.into(), 5).is_empty());
+    }
+
+    #[test]
+    fn prereleases() {
+        let tags: Vec<Tag> = [
+            "8.0.8",
+            "9.0.0-preview.7",
+            "9.0.0-rc.1",
+            "9.0.0-rc.2",
+            "9.0.0",
+            "9.0.1",
+            "10.0.0-preview.1",
+            "9.0.0-rc.1-bookworm-slim",
+            "9.0.0-bookworm-slim",
+        ]
+        .iter()
+        .map(|tag| tag.parse().unwrap())
+        .collect();
+        let candidate = |current: &str, strategy, allow_prerelease| {
+            let options = CandidateOptions {
+                strategy,
+                allow_prerelease,
+                ..CandidateOptions::default()
+            };
+            current.parse::<Tag>().unwrap().select_candidate(&tags, &options).map(|tag| tag.to_string())
+        };
+
+        assert_eq!(candidate("8.0.8", Strategy::Latest, false).as_deref(), Some("9.0.1"));
+        assert_eq!(candidate("8.0.8", Strategy::Latest, true).as_deref(), Some("10.0.0-preview.1"));
+        assert_eq!(candidate("9.0.0-preview.7", Strategy::NextPatch, false).as_deref(), Some("9.0.0-rc.1"));
+        assert_eq!(candidate("9.0.0-preview.7", Strategy::LatestPatch, false).as_deref(), Some("9.0.1"));
+        assert_eq!(candidate("9.0.0-preview.7", Strategy::Latest, false).as_deref(), Some("10.0.0-preview.1"));
+        assert_eq!(
+            candidate("9.0.0-rc.1-bookworm-slim", Strategy::Latest, false).as_deref(),
+            Some("9.0.0-bookworm-slim")
+        );
+        assert_eq!(candidate("9.0.1", Strategy::Latest, false), None);
+        assert!("9.0.0-RC.1".parse::<Tag>().unwrap().is_prerelease());
+        assert!(!"9.0.0-bookworm-slim".parse::<Tag>().unwrap().is_prerelease());
+    }
+
+    #[test]
+    fn mutable_tags() {
+        let tags: Vec<Tag> = ["3.20.0", "3.21.0", "3.21.0-slim", "latest", "stable", "edge"]
+            .iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+        assert!(tags[3..].iter().all(Tag::is_mutable));
+        assert!(!tags[0].is_mutable());
+        let candidate = |current: &str, strategy, include_latest| {
+            let options = CandidateOptions {
+                strategy,
+                include_latest,
+                ..CandidateOptions::default()
+            };
+            current.parse::<Tag>().unwrap().select_candidate(&tags, &options).map(|tag| tag.to_string())
+        };
+
+        assert_eq!(candidate("3.20.0", Strategy::Latest, false).as_deref(), Some("3.21.0"));
+        assert_eq!(candidate("3.20.0", Strategy::Latest, true).as_deref(), Some("latest"));
+        assert_eq!(candidate("3.20.0", Strategy::LatestMinor, true).as_deref(), Some("3.21.0"));
+        assert_eq!(candidate("3.20.0-slim", Strategy::Latest, true).as_deref(), Some("3.21.0-slim"));
+        assert_eq!(candidate("stable", Strategy::Latest, true), None);
+    }
+
+    #[test]
+    fn lts_only() {
+        let tags: Vec<Tag> = ["20.18.0", "21.7.3", "22.11.0", "23.3.0", "24.1.0", "25.0.1"]
+            .iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+        let candidate = |current: &str, lts_majors: Option<Vec<u64>>| {
+            let options = CandidateOptions {
+                strategy: Strategy::LtsOnly,
+                lts_majors,
+                ..CandidateOptions::default()
+            };
+            current.parse::<Tag>().unwrap().select_candidate(&tags, &options).map(|tag| tag.to_string())
+        };
+
+        assert_eq!(candidate("20.17.0", None).as_deref(), Some("24.1.0"));
+        assert_eq!(candidate("21.7.3", None).as_deref(), Some("24.1.0"));
+        assert_eq!(candidate("20.17.0", Some(vec![20, 21, 25])).as_deref(), Some("25.0.1"));
+        assert_eq!(candidate("20.17.0", Some(vec![20])).as_deref(), Some("20.18.0"));
+        assert_eq!(candidate("24.1.0", None), None);
+    }
+
+    #[test]
+    fn keep_specificity() {
+        let cases = [
+            ("1.30.2", "1.29", "1.30"),
+            ("2.1.0", "1", "2"),
+            ("1.30.2-alpine3.22", "1.29-alpine3.21", "1.30-alpine3.22"),
+            ("1.30.2", "1.29.0", "1.30.2"),
+            ("9.0.2-debian-12-r3", "9.0.1-debian-12", "9.0.2-debian-12"),
+            ("9.0.2-debian-12-r3", "9.0.1-debian-12-r8", "9.0.2-debian-12-r3"),
+        ];
+        for (candidate, current, expect) in &cases {
+            let candidate = candidate.parse::<Tag>().unwrap();
+            let current = current.parse::<Tag>().unwrap();
+            assert_eq!(candidate.with_specificity_of(&current).to_string(), *expect);
+        }
+
+        let tags: Vec<Tag> = ["1.29", "1.29.0", "1.29.3", "1.30", "1.30.0", "1.30.2"]
+            .iter()
+            .map(|tag| tag.parse().unwrap())
+            .collect();
+        let current: Tag = "1.29".parse().unwrap();
+        assert_eq!(current.select_candidate(&tags, &Strategy::Latest.into()).unwrap().to_string(), "1.30");
+    }
+
+    #[test]
+    fn existing_representation() {
+        let candidate: Tag = "2.1.3-alpine".parse().unwrap();
+        let current: Tag = "1-alpine".parse().unwrap();
+        let representations: Vec<String> = candidate.representations(&current).iter().map(ToString::to_string).collect();
+        assert_eq!(representations, ["2-alpine", "2.1-alpine", "2.1.3-alpine"]);
+
+        // `1.30` is not published, so the full tag is written instead.
+        let tags: Vec<Tag> = ["1.29", "1.29.3", "1.30.0", "1.30.2"].iter().map(|tag| tag.parse().unwrap()).collect();
+        let current: Tag = "1.29".parse().unwrap();
+        assert_eq!(current.select_candidate(&tags, &Strategy::Latest.into()).unwrap().to_string(), "1.30.2");
+
+        let tags: Vec<Tag> = ["1", "1.4.0", "2.1", "2.1.3"].iter().map(|tag| tag.parse().unwrap()).collect();
+        let current: Tag = "1".parse().unwrap();
+        assert_eq!(current.select_candidate(&tags, &Strategy::Latest.into()).unwrap().to_string(), "2.1");
+    }
+
+    #[test]
+    fn name_filters() {
+        let tag: Tag = "1.29.3-alpine".parse().unwrap();
+        assert_eq!(tag.name_filter(&Strategy::LatestPatch).as_deref(), Some("1.29"));
+        assert_eq!(tag.name_filter(&Strategy::NextMinor).as_deref(), Some("1."));
+        assert_eq!(tag.name_filter(&Strategy::LatestMajor), None);
+        assert_eq!(tag.name_filter(&Strategy::Latest), None);
+
+        let tag: Tag = "24.04".parse().unwrap();
+        assert_eq!(tag.name_filter(&Strategy::NextPatch).as_deref(), Some("24.04"));
+        let tag: Tag = "16".parse().unwrap();
+        assert_eq!(tag.name_filter(&Strategy::NextPatch), None);
+        assert_eq!(tag.name_filter(&Strategy::LatestMinor).as_deref(), Some("16."));
+        let tag: Tag = "latest".parse().unwrap();
+        assert_eq!(tag.name_filter(&Strategy::LatestMinor), None);
+    }
+
+    #[test]
+    fn zero_padding() {
+        for expected in ["24.04", "2.5.01", "22.04.1-noble", "1.29.3-alpine3.09", "8.0.100-preview.07", "2024.01.05"] {
+            let tag: Tag = expected.parse().unwrap();
+            assert_eq!(tag.to_string(), expected);
+        }
+        let tag: Tag = "24.04".parse().unwrap();
+        assert_eq!(tag.minor, Some(4));
+        assert_eq!(tag.digits, [2, 2, 0]);
+
+        let tags: Vec<Tag> = ["22.04", "22.10", "24.04", "24.04.1"].iter().map(|tag| tag.parse().unwrap()).collect();
+        let current: Tag = "22.04".parse().unwrap();
+        assert_eq!(current.select_candidate(&tags, &Strategy::NextMinor.into()).unwrap().to_string(), "22.10");
+        let latest = current.select_candidate(&tags, &Strategy::Latest.into()).unwrap();
+        assert_eq!(latest.to_string(), "24.04");
+        assert!(tags.contains(&latest));
+
+        // The padding is only kept for writing, it is the same version.
+        let unpadded: Tag = "24.4".parse().unwrap();
+        assert_eq!(tag, unpadded);
+        assert_eq!(tag.cmp(&unpadded), Ordering::Equal);
+        let variant: Tag = "1.29.3-alpine3.9".parse().unwrap();
+        assert_eq!(variant, "1.29.3-alpine3.09".parse().unwrap());
+    }
+
+    #[test]
+    fn skip_reasons() {
+        let current: Tag = "3.12.1-slim".parse().unwrap();
+        let reason = |tag: &str, strategy: &Strategy| current.skip_reason(&tag.parse().unwrap(), strategy, false, None);
+        assert_eq!(reason("3.12.4-slim", &Strategy::NextPatch), None);
+        assert_eq!(reason("3.12.4-alpine", &Strategy::NextPatch), Some(SkipStage::Variant));
+        assert_eq!(reason("3.13.0-slim", &Strategy::NextPatch), Some(SkipStage::Strategy));
+        assert_eq!(reason("3.11.9-slim", &Strategy::Latest), Some(SkipStage::Strategy));
+        assert_eq!(reason("latest", &Strategy::Latest), Some(SkipStage::Strategy));
+
+        let options = CandidateOptions {
+            strategy: Strategy::NextPatch,
+            fallbacks: vec![Strategy::NextMinor],
+            same_precision: true,
+            variant_pins: vec!["alpine=3.20".parse().unwrap()],
+            ..CandidateOptions::default()
+        };
+        let reason = |current: &str, tag: &str| options.skip_reason(&current.parse().unwrap(), &tag.parse().unwrap());
+        assert_eq!(reason("3.12.1-slim", "3.13.0-slim"), None);
+        assert_eq!(reason("3.12.1-slim", "4.0.0-slim"), Some(SkipStage::Strategy));
+        assert_eq!(reason("3.12.1-slim", "3.13-slim"), Some(SkipStage::Strategy));
+        assert_eq!(reason("3.12.1-alpine3.20", "3.12.2-alpine3.21"), Some(SkipStage::Variant));
+        let options = CandidateOptions {
+            strategy: Strategy::Latest,
+            include_latest: true,
+            ..CandidateOptions::default()
+        };
+        assert_eq!(options.skip_reason(&current, &"latest".parse().unwrap()), Some(SkipStage::Strategy));
+        assert_eq!(options.skip_reason(&"3.12.1".parse().unwrap(), &"latest".parse().unwrap()), None);
+    }
 }
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, RwLock};
+
+use regex::Regex;
+
+use crate::container_image::Error;
+use crate::tag::Tag;
+use crate::tag::variant::TagVariant;
+
+/// The custom tag schemes per image, see `set_schemes`.
+pub static TAG_SCHEMES: LazyLock<RwLock<HashMap<String, TagScheme>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Capture groups a scheme can use, in the order they are applied to the tag.
+const GROUPS: [&str; 5] = ["major", "minor", "patch", "variant", "build"];
+/// Registry prefixes that are dropped, so that e.g. `node` and `library/node`
+/// use the same scheme.
+const IMPLICIT_PREFIXES: [&str; 2] = ["library/", "mcr.microsoft.com/"];
+/// The Windows images of the MCR, they are tagged by their long term servicing
+/// channel release, e.g. `ltsc2022`.
+const LTSC_IMAGES: [&str; 4] = ["windows/servercore", "windows/nanoserver", "windows/server", "windows"];
+/// The year of the release is compared like a major version, so that
+/// `ltsc2019 < ltsc2022 < ltsc2025`. Suffixes like `-amd64` are kept.
+const LTSC_PATTERN: &str = r"^ltsc(?<major>\d{4})(?<variant>-[a-z0-9]+)?$";
+
+/// The built-in scheme of the Windows images, used unless another scheme is
+/// configured for them.
+static LTSC_SCHEME: LazyLock<TagScheme> = LazyLock::new(|| TagScheme::new("windows", LTSC_PATTERN).expect("LTSC scheme is valid."));
+
+/// Tag scheme related errors.
+#[derive(Debug, thiserror::Error)]
+pub enum SchemeError {
+    #[error("Invalid tag scheme for `{0}`: {1}")]
+    Regex(String, regex::Error),
+    #[error("The tag scheme for `{0}` needs a `major` capture group.")]
+    MissingMajor(String),
+}
+
+/// A custom parser for images with tags `Tag::from_str` can not make sense of,
+/// e.g. `release-2024.3-build77`. The regex uses the named capture groups
+/// `major`, `minor`, `patch`, `variant` and `build`.
+#[derive(Debug, Clone)]
+pub struct TagScheme {
+    regex: Regex,
+}
+
+impl TagScheme {
+    /// Compiles the scheme of the given image.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the pattern is no valid regex or
+    /// has no `major` capture group.
+    pub(crate) fn new(image: &str, pattern: &str) -> Result<Self, SchemeError> {
+        let regex = Regex::new(pattern).map_err(|e| SchemeError::Regex(image.to_owned(), e))?;
+        if !regex.capture_names().any(|name| name == Some("major")) {
+            return Err(SchemeError::MissingMajor(image.to_owned()));
+        }
+        Ok(Self { regex })
+    }
+
+    /// Returns the pattern the scheme was compiled from.
+    pub(crate) fn as_str(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    /// Parses the tag, returns `None` if it does not match the scheme. The
+    /// captured parts are replaced by placeholders in the template of the tag,
+    /// so that candidates are written in the same scheme.
+    pub(crate) fn parse(&self, s: &str) -> Option<Tag> {
+        let captures = self.regex.captures(s)?;
+        let mut tag = Tag::default();
+        let mut parts: Vec<(usize, usize, &str)> = Vec::new();
+        for name in GROUPS {
+            let Some(part) = captures.name(name) else {
+                continue;
+            };
+            let text = part.as_str();
+            match name {
+                "major" => (tag.major, tag.digits[0]) = (Some(text.parse().ok()?), text.len()),
+                "minor" => (tag.minor, tag.digits[1]) = (Some(text.parse().ok()?), text.len()),
+                "patch" => (tag.patch, tag.digits[2]) = (Some(text.parse().ok()?), text.len()),
+                "variant" => tag.variant = Some(text.parse::<TagVariant>().ok()?),
+                _ => tag.build = Some(text.to_owned()),
+            }
+            parts.push((part.start(), part.end(), name));
+        }
+        parts.sort_unstable();
+        let mut template = String::new();
+        let mut position = 0;
+        for (start, end, name) in parts {
+            template.push_str(&s[position..start]);
+            template.push('{');
+            template.push_str(name);
+            template.push('}');
+            position = end;
+        }
+        template.push_str(&s[position..]);
+        tag.template = Some(template);
+        Some(tag)
+    }
+}
+
+/// Removes the registry prefixes images of Docker Hub and MCR may be written
+/// with.
+pub fn normalize(image: &str) -> &str {
+    IMPLICIT_PREFIXES.iter().find_map(|prefix| image.strip_prefix(prefix)).unwrap_or(image)
+}
+
+/// Sets the global tag schemes, the keys are the names of the images.
+pub fn set_schemes(schemes: HashMap<String, TagScheme>) {
+    let schemes = schemes.into_iter().map(|(image, scheme)| (normalize(&image).to_owned(), scheme)).collect();
+    *TAG_SCHEMES.write().expect("Tag schemes can be written.") = schemes;
+}
+
+/// Returns the scheme of the image, if one is configured or built in.
+pub fn scheme_for(image: &str) -> Option<TagScheme> {
+    let image = normalize(image);
+    TAG_SCHEMES
+        .read()
+        .expect("Tag schemes can be read.")
+        .get(image)
+        .cloned()
+        .or_else(|| LTSC_IMAGES.contains(&image).then(|| LTSC_SCHEME.clone()))
+}
+
+/// Parses the tag with the scheme of the image, if there is one, and with
+/// `Tag::from_str` otherwise.
+///
+/// # Errors
+///
+/// This function will return an error if the tag can not be parsed.
+pub fn parse_tag(image: &str, tag: &str) -> Result<Tag, Error> {
+    scheme_for(image).and_then(|scheme| scheme.parse(tag)).map_or_else(|| tag.parse(), Ok)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use pretty_assertions::assert_eq;
+
+    use crate::tag::scheme::{SchemeError, TagScheme, parse_tag, scheme_for};
+    use crate::tag::{CandidateOptions, Tag};
+    use crate::utils::Strategy;
+
+    #[test]
+    fn custom_scheme() {
+        let scheme = TagScheme::new("internal/app", r"^release-(?<major>\d+)\.(?<minor>\d+)-build(?<build>\d+)$").unwrap();
+        let tag = scheme.parse("release-2024.3-build77").unwrap();
+        assert_eq!((tag.major, tag.minor, tag.patch), (Some(2024), Some(3), None));
+        assert_eq!(tag.build.as_deref(), Some("77"));
+        assert_eq!(tag.template.as_deref(), Some("release-{major}.{minor}-build{build}"));
+        assert_eq!(tag.to_string(), "release-2024.3-build77");
+        assert_eq!(scheme.parse("2024.3.1"), None);
+
+        let tags: Vec<Tag> = ["release-2024.3-build80", "release-2024.4-build12", "release-2025.1-build3"]
+            .iter()
+            .filter_map(|tag| scheme.parse(tag))
+            .collect();
+        let candidate = tag.select_candidate(&tags, &CandidateOptions::default()).unwrap();
+        assert_eq!(candidate.to_string(), "release-2025.1-build3");
+
+        let scheme = TagScheme::new("app", r"^v(?<major>\d+)(?<variant>-[a-z]+)?$").unwrap();
+        assert_eq!(scheme.parse("v7-slim").unwrap().to_string(), "v7-slim");
+        assert_eq!(scheme.parse("v7").unwrap().to_string(), "v7");
+
+        assert!(matches!(TagScheme::new("app", r"(?<minor>\d+)"), Err(SchemeError::MissingMajor(_))));
+        assert!(matches!(TagScheme::new("app", r"(?<major>\d+"), Err(SchemeError::Regex(..))));
+    }
+
+    #[test]
+    fn windows_releases() {
+        assert!(scheme_for("mcr.microsoft.com/windows/servercore").is_some());
+        assert!(scheme_for("windows/nanoserver").is_some());
+        assert!(scheme_for("dotnet/aspnet").is_none());
+
+        let current = parse_tag("windows/servercore", "ltsc2019").unwrap();
+        assert_eq!(current.major, Some(2019));
+        assert_eq!(current.to_string(), "ltsc2019");
+        let tags: Vec<Tag> = ["ltsc2019", "ltsc2022", "ltsc2025", "ltsc2022-amd64", "10.0.20348.2849", "1809"]
+            .iter()
+            .filter_map(|tag| scheme_for("windows/servercore").unwrap().parse(tag))
+            .collect();
+        assert_eq!(tags.len(), 4);
+        let candidate = |strategy| {
+            current
+                .select_candidate(
+                    &tags,
+                    &CandidateOptions {
+                        strategy,
+                        ..CandidateOptions::default()
+                    },
+                )
+                .map(|tag| tag.to_string())
+        };
+        assert_eq!(candidate(Strategy::NextMajor).as_deref(), Some("ltsc2022"));
+        assert_eq!(candidate(Strategy::LatestMajor).as_deref(), Some("ltsc2025"));
+        assert_eq!(candidate(Strategy::NextMinor), None);
+    }
+}
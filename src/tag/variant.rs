@@ -1,3 +1,4 @@
+use std::cmp::Ordering;
 use std::fmt::Display;
 use std::str::FromStr;
 
@@ -5,9 +6,73 @@ use serde::{Deserialize, Serialize};
 
 use crate::container_image::Error;
 
+/// Architectures registries publish dedicated tags for, e.g. `1.2.3-amd64`.
+const ARCHITECTURES: [&str; 14] = [
+    "amd64", "arm64", "arm64v8", "arm32v7", "arm32v6", "armhf", "armv7", "aarch64", "x86_64", "i386", "386", "ppc64le", "s390x", "riscv64",
+];
+
+/// Variant pin related errors.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum VariantPinError {
+    #[error("Invalid variant pin `{0}`, expected `<variant>=<version>`, e.g. `alpine=3.20`.")]
+    Invalid(String),
+}
+
+/// Keeps the candidates on a version of a variant, e.g. `alpine=3.20` keeps
+/// `1.27.3-alpine3.20` even if `1.27.4-alpine3.21` exists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariantPin {
+    /// The name of the variant, e.g. `alpine`.
+    name:    String,
+    /// The pinned version numbers, as many as were given.
+    version: Vec<u64>,
+}
+
+impl VariantPin {
+    /// Checks if the candidate variant is allowed by the pin. Variants with
+    /// another name are not pinned, variants without a version (e.g. `-alpine`)
+    /// only if the current variant has none either.
+    pub(crate) fn allows(&self, current: Option<&TagVariant>, candidate: &TagVariant) -> bool {
+        let name = candidate.prefix.as_deref().unwrap_or_default().trim_matches(['-', '_', '.']);
+        if !name.eq_ignore_ascii_case(&self.name) {
+            return true;
+        }
+        if candidate.major.is_none() {
+            return current.is_none_or(|current| current.major.is_none());
+        }
+        self.version
+            .iter()
+            .zip([candidate.major, candidate.minor, candidate.patch])
+            .all(|(pinned, number)| Some(*pinned) == number)
+    }
+}
+
+impl FromStr for VariantPin {
+    type Err = VariantPinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || VariantPinError::Invalid(s.to_owned());
+        let (name, version) = s.split_once('=').ok_or_else(invalid)?;
+        let version = version
+            .trim()
+            .split('.')
+            .map(str::parse)
+            .collect::<Result<Vec<u64>, _>>()
+            .map_err(|_| invalid())?;
+        let name = name.trim();
+        if name.is_empty() || version.len() > 3 {
+            return Err(invalid());
+        }
+        Ok(Self {
+            name: name.to_owned(),
+            version,
+        })
+    }
+}
+
 /// `TagVariant` is build with the following components:
-/// `(prefix)(major)(affix)(minor)(affix)(patch)(suffix)`
-#[derive(Debug, Clone, Default, Eq, PartialEq, PartialOrd, Ord, Deserialize, Serialize)]
+/// `(prefix)(major)(affix)(minor)(affix)(patch)(suffix)(arch)`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct TagVariant {
     pub prefix:  Option<String>,
     pub major:   Option<u64>,
@@ -15,8 +80,45 @@ pub struct TagVariant {
     pub patch:   Option<u64>,
     pub affixes: Vec<String>,
     pub suffix:  Option<String>,
+    /// The amount of digits of each version number as written, to keep zero
+    /// padded versions like `3.09`.
+    #[serde(default)]
+    pub digits:  Vec<usize>,
+    /// A trailing architecture including its separator, e.g. `-amd64`.
+    #[serde(default)]
+    pub arch:    Option<String>,
 }
 
+/// Variants are ordered by their components in the order they are written, the
+/// digits as written are left out, e.g. `alpine3.09` equals `alpine3.9`.
+impl Ord for TagVariant {
+    fn cmp(&self, rhs: &Self) -> Ordering {
+        (&self.prefix, self.major, self.minor, self.patch, &self.affixes, &self.suffix, &self.arch).cmp(&(
+            &rhs.prefix,
+            rhs.major,
+            rhs.minor,
+            rhs.patch,
+            &rhs.affixes,
+            &rhs.suffix,
+            &rhs.arch,
+        ))
+    }
+}
+
+impl PartialOrd for TagVariant {
+    fn partial_cmp(&self, rhs: &Self) -> Option<Ordering> {
+        Some(self.cmp(rhs))
+    }
+}
+
+impl PartialEq for TagVariant {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.cmp(rhs) == Ordering::Equal
+    }
+}
+
+impl Eq for TagVariant {}
+
 impl Display for TagVariant {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.prefix {
@@ -24,15 +126,15 @@ impl Display for TagVariant {
             None => write!(f, "")?,
         }
         match self.major {
-            Some(major) => write!(f, "{major}")?,
+            Some(major) => write!(f, "{major:0width$}", width = self.width(0))?,
             None => write!(f, "")?,
         }
         match self.minor {
             Some(minor) => {
                 if self.affixes.is_empty() {
-                    write!(f, ".{minor}")?;
+                    write!(f, ".{minor:0width$}", width = self.width(1))?;
                 } else {
-                    write!(f, "{}{minor}", self.affixes.first().expect("Affixes exists"))?;
+                    write!(f, "{}{minor:0width$}", self.affixes.first().expect("Affixes exists"), width = self.width(1))?;
                 }
             }
             None => write!(f, "")?,
@@ -40,15 +142,19 @@ impl Display for TagVariant {
         match self.patch {
             Some(patch) => {
                 if self.affixes.len() < 2 {
-                    write!(f, ".{patch}")?;
+                    write!(f, ".{patch:0width$}", width = self.width(2))?;
                 } else {
-                    write!(f, "{}{patch}", self.affixes.get(1).expect("Affixes exists"))?;
+                    write!(f, "{}{patch:0width$}", self.affixes.get(1).expect("Affixes exists"), width = self.width(2))?;
                 }
             }
             None => write!(f, "")?,
         }
         match &self.suffix {
-            Some(suffix) => write!(f, "{suffix}"),
+            Some(suffix) => write!(f, "{suffix}")?,
+            None => write!(f, "")?,
+        }
+        match &self.arch {
+            Some(arch) => write!(f, "{arch}"),
             None => write!(f, ""),
         }
     }
@@ -62,8 +168,10 @@ impl FromStr for TagVariant {
         let mut suffix = None;
         let mut affixes = Vec::new();
 
-        let mut current = s;
+        // Extract a trailing architecture, so its digits are not taken as versions
+        let (mut current, arch) = split_arch(s);
         let mut version_parts = Vec::new();
+        let mut digits = Vec::new();
 
         // Extract prefix (non-digit characters at the start)
         let mut prefix_end = 0;
@@ -101,6 +209,7 @@ impl FromStr for TagVariant {
             if num_end > 0 {
                 if let Ok(num) = current[..num_end].parse::<u64>() {
                     version_parts.push(num);
+                    digits.push(num_end);
                 }
                 current = &current[num_end..];
             }
@@ -123,11 +232,31 @@ impl FromStr for TagVariant {
             patch,
             affixes,
             suffix,
+            digits,
+            arch,
         })
     }
 }
 
+/// Splits a trailing architecture like `-arm64` off the variant.
+fn split_arch(s: &str) -> (&str, Option<String>) {
+    ARCHITECTURES
+        .iter()
+        .find_map(|arch| {
+            let rest = s.strip_suffix(arch)?;
+            let rest = rest.strip_suffix('-').or_else(|| rest.strip_suffix('_'))?;
+            Some((rest, Some(s[rest.len()..].to_owned())))
+        })
+        .unwrap_or((s, None))
+}
+
 impl TagVariant {
+    /// Returns the amount of digits the version number at the index was written
+    /// with.
+    fn width(&self, index: usize) -> usize {
+        self.digits.get(index).copied().unwrap_or_default()
+    }
+
     /// Checks if the prefixes match.
     pub(crate) fn is_same_prefix(&self, rhs: &Self) -> bool {
         match (self.prefix.as_ref(), rhs.prefix.as_ref()) {
@@ -146,6 +275,12 @@ impl TagVariant {
         }
     }
 
+    /// Checks if the architectures match, tags of another architecture are
+    /// only candidates if the current tag names the same one.
+    pub(crate) fn is_same_arch(&self, rhs: &Self) -> bool {
+        self.arch == rhs.arch
+    }
+
     /// Checks if the affixes match.
     pub(crate) fn is_same_affix(&self, rhs: &Self) -> bool {
         self.affixes == rhs.affixes
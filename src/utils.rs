@@ -1,34 +1,122 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::fmt::Display;
-use std::fs::File;
-use std::io::copy;
+use std::fs::{self, File};
+use std::io::{self, copy};
 use std::path::{Path, PathBuf};
-use std::time::Duration;
-use std::{env, fs};
+use std::str::FromStr;
 
+use clap::ValueEnum;
 use clap::builder::OsStr;
-use serde::Deserialize;
-use tracing::{debug, error, info};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
 use ureq::Agent;
 use walkdir::WalkDir;
 
-use crate::cli;
-use crate::container_image::{ContainerImage, Dockerfile};
-use crate::registries::{DURATION_HOUR_AS_SECS, TAGS_CACHE};
-use crate::tag::Tag;
+use crate::cache::{self, CacheStore};
+use crate::config::{ConfigError, FILE_CONFIG_SUFFIX};
+use crate::container_image::{ContainerImage, Dockerfile, ParseError, ResolveError, StageFilter};
+use crate::digest::{self, DigestPin};
+use crate::metrics::{self, METRICS};
+use crate::pin::{self, Date, Pin};
+use crate::registries::dockerhub::PublisherKind;
+use crate::registries::http::HttpClient;
+use crate::registries::{self, FetchOptions, PublishedTag};
+use crate::report::{self, Report, ReportFormat, ScanSummary, Severity};
+use crate::scanners::{FileKind, YamlScanners};
+use crate::tag::{Bump, CandidateOptions, Tag};
+use crate::{backup, cli, config, consistency, daemon, hook, patch, paths, serve, stats};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-#[derive(Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+/// Strategy parsing related errors.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum StrategyError {
+    #[error("Unknown strategy `{0}`, did you mean `{1}`?")]
+    Misspelled(String, String),
+    #[error("Unknown strategy `{0}`, expected one of {1}.")]
+    Unknown(String, String),
+}
+
+/// Parsed the same way on the command line, in config files and batch files,
+/// aliases like `minor` select the highest version of their scope.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
 #[clap(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case", try_from = "String")]
 pub enum Strategy {
+    /// The highest available version, across all major versions.
     #[default]
     Latest,
+    /// The next patch version within the same minor version.
     NextPatch,
+    /// The highest patch version within the same minor version.
+    #[value(alias = "patch")]
     LatestPatch,
+    /// The next minor version within the same major version.
     NextMinor,
+    /// The highest minor version within the same major version.
+    #[value(alias = "minor")]
     LatestMinor,
+    /// The next major version.
     NextMajor,
+    /// The highest major version.
+    #[value(alias = "major")]
     LatestMajor,
+    /// The highest version of an LTS major, even majors unless configured.
+    #[value(alias = "lts")]
+    LtsOnly,
+}
+
+/// Strategies within this edit distance of an unknown input are suggested.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// The strategies shown by the overview and local mode.
+const OVERVIEW_STRATEGIES: [Strategy; 6] = [
+    Strategy::NextPatch,
+    Strategy::LatestPatch,
+    Strategy::NextMinor,
+    Strategy::LatestMinor,
+    Strategy::NextMajor,
+    Strategy::LatestMajor,
+];
+
+/// The current tag and the published tags the examples of the strategies are
+/// generated from, see `Strategy::example`.
+const EXAMPLE_TAG: &str = "1.2.3";
+const EXAMPLE_TAGS: [&str; 7] = ["1.2.3", "1.2.4", "1.2.5", "1.3.0", "1.4.1", "2.0.0", "3.1.0"];
+
+impl Strategy {
+    /// Returns the one line description of the strategy, taken from its doc
+    /// comment.
+    pub(crate) fn description(&self) -> String {
+        self.to_possible_value()
+            .and_then(|value| value.get_help().map(ToString::to_string))
+            .unwrap_or_default()
+    }
+
+    /// Returns the current tag and the tag the strategy selects for it, from a
+    /// fixed list of published tags.
+    pub(crate) fn example(&self) -> (Tag, Option<Tag>) {
+        let current: Tag = EXAMPLE_TAG.parse().expect("Example tag can be parsed.");
+        let tags: Vec<Tag> = EXAMPLE_TAGS.iter().map(|tag| tag.parse().expect("Example tag can be parsed.")).collect();
+        let candidate = current.select_candidate(&tags, &self.clone().into());
+        (current, candidate)
+    }
+}
+
+/// The format results are printed in.
+#[derive(Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+#[clap(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    /// Human readable log lines.
+    #[default]
+    Text,
+    /// A single json document on standard output.
+    Json,
+    /// A markdown table on standard output, e.g. for a pull request comment.
+    Markdown,
 }
 
 // This needs to be OsStr since it is used by clap.
@@ -42,8 +130,60 @@ impl From<Strategy> for OsStr {
             Strategy::LatestMinor => Self::from("latest-minor"),
             Strategy::NextMajor => Self::from("next-major"),
             Strategy::LatestMajor => Self::from("latest-major"),
+            Strategy::LtsOnly => Self::from("lts-only"),
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = StrategyError;
+
+    /// Accepts the names and aliases of the strategies, ignoring case and
+    /// whether words are separated by `-`, `_` or spaces. Close misspellings
+    /// are answered with a suggestion.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let input = s.trim().to_ascii_lowercase().replace(['_', ' '], "-");
+        if let Ok(strategy) = <Self as ValueEnum>::from_str(&input, true) {
+            return Ok(strategy);
+        }
+        let names: Vec<String> = Self::value_variants()
+            .iter()
+            .filter_map(ValueEnum::to_possible_value)
+            .flat_map(|value| value.get_name_and_aliases().map(ToOwned::to_owned).collect::<Vec<_>>())
+            .collect();
+        let suggestion = names
+            .iter()
+            .map(|name| (edit_distance(&input, name), name))
+            .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+            .min_by_key(|(distance, _)| *distance);
+        Err(match suggestion {
+            Some((_, name)) => StrategyError::Misspelled(s.to_owned(), name.clone()),
+            None => StrategyError::Unknown(s.to_owned(), names.join(", ")),
+        })
+    }
+}
+
+impl TryFrom<String> for Strategy {
+    type Error = StrategyError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// Returns the number of single character edits between the strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
         }
+        previous = current;
     }
+    previous[b.len()]
 }
 
 impl Display for Strategy {
@@ -55,6 +195,7 @@ impl Display for Strategy {
             Self::LatestMinor => write!(f, "latest minor"),
             Self::NextMajor => write!(f, "next major"),
             Self::LatestMajor => write!(f, "latest major"),
+            Self::LtsOnly => write!(f, "lts only"),
             Self::Latest => write!(f, "latest"),
         }
     }
@@ -62,6 +203,9 @@ impl Display for Strategy {
 
 type StageIndex = usize;
 type ImageUpdate = (StageIndex, Tag);
+/// The candidates of all unique images, keyed by the image reference, e.g.
+/// `node:22.1.0`. Ordered, so that iterating them is reproducible.
+pub type ImageCandidates = BTreeMap<String, Tag>;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DockerfileUpdate {
@@ -83,18 +227,44 @@ impl DockerfileUpdate {
     }
 }
 
-/// Handles data from standard input
-pub fn handle_input(input_mode: &cli::InputArguments) {
+/// Handles data from standard input, returns `false` if the json query could
+/// not be answered or the image is rejected by `--official-only`.
+pub fn handle_input(input_mode: &cli::InputArguments) -> bool {
+    if input_mode.json {
+        return handle_json_query(input_mode);
+    }
     let docker_image: ContainerImage = input_mode.input.parse().expect("Image could be parsed.");
-    let mut docker_image_tags = docker_image
-        .get_remote_tags(input_mode.common.tag_search_limit, input_mode.common.arch.as_ref())
+    let options = input_mode.common.candidate_options(&input_mode.strat);
+    if let Err(e) = docker_image.check_publisher(registries::http::client().as_ref(), &options) {
+        error!("{e}");
+        return false;
+    }
+    let docker_image_tags = docker_image
+        .get_remote_tags(&input_mode.common.fetch_options(&input_mode.strat))
         .expect("Getting tags finishes sucessful.");
-    docker_image_tags.sort();
-    if let Some(found_tag) = docker_image.get_tag().find_candidate_tag(&docker_image_tags, &input_mode.strat) {
+    if let Some(count) = input_mode.candidates {
+        let found_tags = docker_image.find_candidates(&docker_image_tags, &options, count.into());
+        if found_tags.is_empty() {
+            info!("===> No candidate found.");
+        }
+        for found_tag in found_tags {
+            info!("===> Candidate tag: {}:{found_tag}", docker_image.get_full_name());
+            if input_mode.common.quiet {
+                println!("{}:{}", docker_image.get_dockerimage_name(), found_tag.to_string().trim_end_matches('.'));
+            }
+        }
+        return true;
+    }
+    if let Some(found_tag) = docker_image.find_update(&docker_image_tags, &options, input_mode.common.arch.as_ref()) {
         info!(
-            "===> Candidate tag: {}:{found_tag} (from: {})",
+            "===> Candidate tag: {}:{found_tag} (from: {}){}",
             docker_image.get_full_name(),
             docker_image.get_full_tagged_name(),
+            if docker_image.get_tag().is_rebuild(&found_tag) {
+                " (rebuild only)"
+            } else {
+                ""
+            },
         );
         if input_mode.common.quiet {
             println!("{}:{}", docker_image.get_dockerimage_name(), found_tag.to_string().trim_end_matches('.'));
@@ -105,71 +275,342 @@ pub fn handle_input(input_mode: &cli::InputArguments) {
             println!();
         }
     }
+    true
+}
+
+/// Answers the json query of the input mode with a json line like the batch
+/// mode, the query is read from standard input for `-`.
+fn handle_json_query(input_mode: &cli::InputArguments) -> bool {
+    let query = if input_mode.input == "-" {
+        io::read_to_string(io::stdin()).map_err(|e| e.to_string())
+    } else {
+        Ok(input_mode.input.clone())
+    };
+    let params = query.and_then(|query| serde_json::from_str::<serve::CandidateParams>(&query).map_err(|e| e.to_string()));
+    let report = match params {
+        Ok(params) => {
+            let strategies = params.strategy.as_ref().map_or(input_mode.strat.as_slice(), std::slice::from_ref);
+            let result = resolve_image(&input_mode.common, strategies, &params.image, params.arch.as_ref(), params.tags.as_deref());
+            BatchReport::new(params.image, strategies, result)
+        }
+        Err(e) => BatchReport::new(String::new(), &input_mode.strat, Err(format!("Invalid query: {e}"))),
+    };
+    report::print(&OutputFormat::Json, &report);
+    report.error.is_none()
+}
+
+/// The candidate of a strategy in the overview, with the metadata of its tag.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct OverviewCandidate {
+    strategy:      String,
+    /// `None` if the strategy found no candidate.
+    candidate:     Option<String>,
+    /// When the candidate was pushed last, as reported by the registry.
+    pushed:        Option<String>,
+    architectures: Vec<String>,
+}
+
+/// The result of the overview mode in json output.
+#[derive(Debug, Serialize)]
+struct OverviewReport {
+    image:      String,
+    /// `None` if the publisher could not be looked up.
+    publisher:  Option<PublisherKind>,
+    candidates: Vec<OverviewCandidate>,
+}
+
+impl OverviewReport {
+    /// Returns the image with its publisher, e.g. `node:22 (Docker Official
+    /// Image)`.
+    fn title(&self, image: &str) -> String {
+        self.publisher.map_or_else(|| image.to_owned(), |publisher| format!("{image} ({publisher})"))
+    }
 }
 
-/// Handles data from standard input
-pub fn handle_overview(overview_mode: &cli::OverviewArguments) {
+impl OverviewCandidate {
+    fn new(image: &ContainerImage, strategy: String, found_tag: Option<&Tag>, published_tags: &[PublishedTag]) -> Self {
+        let tag = found_tag.map(|found_tag| found_tag.to_string().trim_end_matches('.').to_owned());
+        let published = tag.as_ref().and_then(|tag| published_tags.iter().find(|published| published.name == *tag));
+        let mut architectures: Vec<String> = published
+            .map(|published| published.architectures.clone())
+            .unwrap_or_default()
+            .into_iter()
+            // Attestation manifests are listed with an unknown architecture.
+            .filter(|architecture| architecture != "unknown")
+            .collect();
+        architectures.sort();
+        architectures.dedup();
+        Self {
+            strategy,
+            candidate: tag.map(|tag| format!("{}:{tag}", image.get_dockerimage_name())),
+            pushed: published.and_then(|published| published.pushed.clone()),
+            architectures,
+        }
+    }
+}
+
+impl Report for OverviewReport {
+    fn text(&self) -> Vec<String> {
+        std::iter::once(format!("Results for:\t{}", self.title(&self.image)))
+            .chain(overview_table(&self.candidates))
+            .collect()
+    }
+
+    fn markdown(&self) -> Vec<String> {
+        let rows = self.candidates.iter().map(|candidate| {
+            [
+                candidate.strategy.clone(),
+                candidate
+                    .candidate
+                    .as_ref()
+                    .map_or_else(|| "-".to_owned(), |candidate| format!("`{candidate}`")),
+                candidate.pushed.clone().unwrap_or_else(|| "-".to_owned()),
+                candidate.architectures.join(", "),
+            ]
+        });
+        [format!("### {}", self.title(&format!("`{}`", self.image))), String::new()]
+            .into_iter()
+            .chain(report::markdown_table(["Strategy", "Candidate", "Pushed", "Architectures"], rows))
+            .collect()
+    }
+}
+
+/// Renders the candidates of the overview as table with aligned columns, the
+/// first line is the header.
+fn overview_table(candidates: &[OverviewCandidate]) -> Vec<String> {
+    let rows = candidates.iter().map(|candidate| {
+        [
+            candidate.strategy.clone(),
+            candidate.candidate.clone().unwrap_or_else(|| "-".to_owned()),
+            // The date is sufficient, e.g. `2025-01-01` of `2025-01-01T10:00:00.000000Z`.
+            candidate.pushed.as_deref().map_or("-", |pushed| pushed.get(..10).unwrap_or(pushed)).to_owned(),
+            if candidate.architectures.is_empty() {
+                "-".to_owned()
+            } else {
+                candidate.architectures.join(", ")
+            },
+        ]
+    });
+    report::text_table(["STRATEGY", "CANDIDATE", "PUSHED", "ARCHITECTURES"], rows)
+}
+
+/// Handles data from standard input, returns `false` if the image is rejected
+/// by `--official-only`.
+pub fn handle_overview(overview_mode: &cli::OverviewArguments) -> bool {
     let docker_image: ContainerImage = overview_mode.input.parse().expect("Image could be parsed.");
-    let mut docker_image_tags = docker_image
-        .get_remote_tags(overview_mode.common.tag_search_limit, overview_mode.common.arch.as_ref())
+    let client = registries::http::client();
+    if let Err(e) = docker_image.check_publisher(client.as_ref(), &overview_mode.common.candidate_options(&[])) {
+        error!("{e}");
+        return false;
+    }
+    let (docker_image_tags, published_tags) = docker_image
+        // All strategies are shown, so the tags are not narrowed down.
+        .fetch_published_tags(client.as_ref(), &overview_mode.common.fetch_options(&[]))
         .expect("Getting tags finishes sucessful.");
-    docker_image_tags.sort();
 
-    if overview_mode.common.quiet {
-        println!("Results for:\t{}", docker_image.get_full_tagged_name());
-    } else {
-        info!("Results for:\t{}", docker_image.get_full_tagged_name());
-    }
-    // create one found tag for every Strat
-    for strat in [
-        Strategy::NextPatch,
-        Strategy::LatestPatch,
-        Strategy::NextMinor,
-        Strategy::LatestMinor,
-        Strategy::NextMajor,
-        Strategy::LatestMajor,
-    ] {
-        if let Some(found_tag) = docker_image.get_tag().find_candidate_tag(&docker_image_tags, &strat) {
-            if overview_mode.common.quiet {
-                println!(
-                    "{strat}:\t{}:{}",
-                    docker_image.get_dockerimage_name(),
-                    found_tag.to_string().trim_end_matches('.')
-                );
-            } else {
-                info!("===> {strat}:\t{}:{found_tag}", docker_image.get_dockerimage_name(),);
+    // create one found tag for every Strat, or the requested number of them
+    let mut candidates: Vec<OverviewCandidate> = OVERVIEW_STRATEGIES
+        .iter()
+        .flat_map(|strat| {
+            let options = overview_mode.common.candidate_options(std::slice::from_ref(strat));
+            let found_tags = overview_mode.candidates.map_or_else(
+                || {
+                    docker_image
+                        .find_update(&docker_image_tags, &options, overview_mode.common.arch.as_ref())
+                        .into_iter()
+                        .collect()
+                },
+                |count| docker_image.find_candidates(&docker_image_tags, &options, count.into()),
+            );
+            if found_tags.is_empty() {
+                return vec![OverviewCandidate::new(&docker_image, strat.to_string(), None, &published_tags)];
             }
-        } else if !overview_mode.common.quiet {
-            info!("===> No candidate found for {strat}.");
+            found_tags
+                .iter()
+                .map(|found_tag| OverviewCandidate::new(&docker_image, strat.to_string(), Some(found_tag), &published_tags))
+                .collect()
+        })
+        .collect();
+    // Rebuilds keep the application version, so they are listed separately.
+    if let Some(rebuild_tag) = docker_image.get_tag().find_rebuild_tag(&docker_image_tags) {
+        candidates.push(OverviewCandidate::new(
+            &docker_image,
+            "rebuild only".to_owned(),
+            Some(rebuild_tag),
+            &published_tags,
+        ));
+    }
+
+    let publisher = docker_image
+        .fetch_publisher(client.as_ref())
+        .inspect_err(|e| warn!("Could not look up the publisher of `{docker_image}`: {e}"))
+        .ok();
+    let report = OverviewReport {
+        image: docker_image.get_full_tagged_name(),
+        publisher,
+        candidates,
+    };
+    report::print(&overview_mode.output, &report);
+    true
+}
+
+/// Handles a single dockerfile, returns `false` if some of its images could
+/// not be resolved.
+pub fn handle_file(file_mode: &cli::SingleFileArguments) -> bool {
+    collect_patch(file_mode.write_patch.as_deref(), || update_file(file_mode))
+}
+
+/// Collects the changes of the update as patch instead of writing the files,
+/// if a patch file is given. Returns `false` if the update failed or the patch
+/// could not be written.
+fn collect_patch(target: Option<&Path>, update: impl FnOnce() -> bool) -> bool {
+    let Some(target) = target else {
+        return update();
+    };
+    patch::start();
+    let updated = update();
+    match patch::finish(target) {
+        Ok(files) => {
+            info!("Wrote the changes of {files} files to the patch `{}`.", target.display());
+            updated
+        }
+        Err(e) => {
+            error!("Could not write the patch `{}`: {e}", target.display());
+            false
         }
     }
 }
 
-pub fn handle_file(file_mode: &cli::SingleFileArguments) {
+fn update_file(file_mode: &cli::SingleFileArguments) -> bool {
     let file = file_mode.file.to_string_lossy().into_owned();
     let path = Path::new(&file);
     info!("Processing dockerfile: {}", path.canonicalize().expect("Path can be canonicalised.").display());
-    let mut dockerfile = Dockerfile::read(&file_mode.file).expect("File is readable and a valid dockerfile");
-    dockerfile.update_images(
-        !file_mode.dry_run,
-        &file_mode.strat,
-        file_mode.common.tag_search_limit,
-        file_mode.common.arch.as_ref(),
+    backup::set_backup(file_mode.backup.backup());
+    let parsing = stats::phase("parsing");
+    let mut dockerfile = if file_mode.resolve_includes {
+        Dockerfile::read_with_includes(&file_mode.file, YamlScanners::ALL)
+    } else {
+        Dockerfile::read(&file_mode.file)
+    }
+    .expect("File is readable and a valid dockerfile");
+    drop(parsing);
+    if let Some(filter) = file_mode.stage.clone().map(StageFilter::Named).or_else(|| file_mode.stages.filter())
+        && !dockerfile.retain_stage(&filter)
+    {
+        let stages: Vec<String> = dockerfile.stages().iter().map(ToString::to_string).collect();
+        error!("The dockerfile has no {filter}, nothing was updated. Its stages are: {}", stages.join(", "));
+        return false;
+    }
+    let options = CandidateOptions {
+        image_strategies: dockerfile.marked_image_strategies(),
+        ..file_mode.common.candidate_options(&file_mode.strat)
+    };
+    let fetch = file_mode.common.fetch_options(&file_mode.strat);
+    let ignored_images = parse_ignored_images(&pin::active_images(&file_mode.pin, Date::today()));
+    // Kept as written, so that a failed verification restores the very same file.
+    let original_content = fs::read_to_string(&file_mode.file).ok();
+    let _resolving = stats::phase("resolving");
+    if file_mode.output == ReportFormat::Text {
+        let original = dockerfile.clone();
+        let (written, failures) = dockerfile.update_images(!file_mode.dry_run, &file_mode.apply, file_mode.pin_digest, &ignored_images, &options, &fetch);
+        if written {
+            run_hooks(&file_mode.hooks, &original, original_content.as_deref(), &dockerfile);
+        }
+        return report_failures(&failures);
+    }
+
+    let dockerfiles = [dockerfile];
+    let (candidates, failures) = resolve_candidates(&dockerfiles, &options, &fetch);
+    report::print_findings(
+        &file_mode.output,
+        &report::collect_findings(&dockerfiles, &candidates, &ignored_images),
+        &report::collect_diagnostics(&dockerfiles),
+        None,
     );
+    if !file_mode.dry_run {
+        let candidates = applied_candidates(&dockerfiles[0], &candidates, &file_mode.apply);
+        let mut dockerfile_updated = dockerfiles[0].generate_image_updates(&candidates, &ignored_images).apply();
+        dockerfile_updated.refresh_digests(fetch.arch.as_deref());
+        if let Some(arch) = fetch.arch.as_deref().filter(|_| file_mode.pin_digest) {
+            dockerfile_updated.pin_digests(registries::http::client().as_ref(), &dockerfiles[0], arch);
+        }
+        if dockerfile_updated.write().is_ok() {
+            run_hooks(&file_mode.hooks, &dockerfiles[0], original_content.as_deref(), &dockerfile_updated);
+        }
+    }
+    report_failures(&failures)
+}
+
+/// Returns the candidates of the images of the dockerfile whose update has one
+/// of the severities to apply, the other updates are only reported.
+fn applied_candidates(dockerfile: &Dockerfile, candidates: &ImageCandidates, apply: &[Severity]) -> ImageCandidates {
+    let mut applied = candidates.clone();
+    for image in dockerfile.get_base_images() {
+        let key = image.to_string();
+        if let Some(candidate) = candidates.get(&key) {
+            let severity = Severity::between(image.get_tag(), candidate);
+            if !severity.is_applied(apply) {
+                info!("Not applying the {severity} update of `{image}` to `{candidate}`.");
+                applied.remove(&key);
+            }
+        }
+    }
+    applied
+}
+
+/// Runs the hooks for a written dockerfile, if any of its images changed. The
+/// build is verified first and the original content is restored if it fails,
+/// otherwise the post update command is run.
+fn run_hooks(hooks: &cli::UpdateHooks, original: &Dockerfile, original_content: Option<&str>, updated: &Dockerfile) {
+    let Some(path) = updated.get_path() else {
+        return;
+    };
+    let (old_images, new_images): (Vec<String>, Vec<String>) = original
+        .get_base_images()
+        .into_iter()
+        .zip(updated.get_base_images())
+        .filter(|(old, new)| old != new)
+        .map(|(old, new)| (old.to_string(), new.to_string()))
+        .unzip();
+    if old_images.is_empty() {
+        return;
+    }
+    if hooks.verify_build || hooks.verify_cmd.is_some() {
+        if let Err(e) = hook::run_verify_build(hooks.verify_cmd.as_deref(), path) {
+            error!("Build of `{}` failed, reverting the update: {e}", path.display());
+            let reverted = original_content.map_or_else(
+                || original.revert(),
+                |content| fs::write(path, content).map_err(Into::into).and_then(|()| original.write_includes(false)),
+            );
+            if let Err(e) = reverted {
+                error!("Could not revert `{}`: {e}", path.display());
+            }
+            return;
+        }
+        info!("Verified the build of `{}`.", path.display());
+    }
+    if let Some(cmd) = &hooks.post_update_cmd
+        && let Err(e) = hook::run_post_update_cmd(cmd, path, &old_images, &new_images)
+    {
+        error!("Post update command for `{}` failed: {e}", path.display());
+    }
 }
 
 /// Handling function that will handle multiple files at once, with a given
-/// ignore for single files or specific images.
-pub fn handle_multi(multi_mode: &cli::MultiFileArguments) {
+/// ignore for single files or specific images. Returns `false` if some images
+/// could not be resolved.
+pub fn handle_multi(multi_mode: &cli::MultiFileArguments) -> bool {
+    collect_patch(multi_mode.write_patch.as_deref(), || update_folder(multi_mode))
+}
+
+fn update_folder(multi_mode: &cli::MultiFileArguments) -> bool {
     let folder = multi_mode.folder.to_str().unwrap_or_default().to_owned();
     let path = Path::new(&folder);
     info!("Processing folder: {}", path.canonicalize().expect("Path can be canonicalised.").display());
-    let mut dockerfiles_to_process = Vec::<String>::new();
-    for entry in WalkDir::new(path).into_iter().filter_map(std::result::Result::ok) {
-        if entry.file_name().to_string_lossy().to_ascii_lowercase().starts_with("dockerfile") {
-            dockerfiles_to_process.push(entry.path().display().to_string());
-        }
-    }
+    backup::set_backup(multi_mode.backup.backup());
+    let discovery = stats::phase("discovery");
+    let mut dockerfiles_to_process = discover_dockerfiles(path, &multi_mode.discovery);
+    drop(discovery);
     if !multi_mode.exclude_file.is_empty() {
         info!("Ignoring files: {:?}", &multi_mode.exclude_file);
         for excluded in &multi_mode.exclude_file {
@@ -177,71 +618,1152 @@ pub fn handle_multi(multi_mode: &cli::MultiFileArguments) {
         }
     }
     info!("Found files: {dockerfiles_to_process:?}");
-    for dockerfile_to_process in &dockerfiles_to_process {
-        match Dockerfile::read(&PathBuf::from(dockerfile_to_process)) {
-            Ok(dockerfile) => {
-                let ignored_images: Vec<ContainerImage> = multi_mode
-                    .ignore_versions
-                    .iter()
-                    .map(|image| image.parse().expect("Image could be parsed."))
-                    .collect();
-                if !ignored_images.is_empty() {
-                    debug!("Skipping image updates:");
-                    for image in &ignored_images {
-                        debug!("\t\t{}", image.get_name());
-                    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(multi_mode.jobs.unwrap_or_default())
+        .build()
+        .expect("Thread pool can be built.");
+    let failures = pool.install(|| process_files(multi_mode, &dockerfiles_to_process));
+    report_failures(&failures)
+}
+
+/// Returns all dockerfiles in the folder and its subfolders, and the other
+/// build files enabled by the discovery options. The files are ordered by their
+/// path, independent of the file system, so that runs are reproducible.
+pub fn discover_dockerfiles(folder: &Path, discovery: &cli::DiscoveryOptions) -> Vec<String> {
+    let mut walker = WalkDir::new(folder).follow_links(discovery.follow_symlinks).sort_by_file_name();
+    if let Some(max_depth) = discovery.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+    walker
+        .into_iter()
+        // The folder itself is always searched, even if it is e.g. `.`.
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            discovery.include_hidden || entry.depth() == 0 || !name.starts_with('.') || discovery.searches_hidden(&name)
+        })
+        .filter_map(std::result::Result::ok)
+        .filter(|entry| {
+            // The config file of a dockerfile, e.g. `Dockerfile.diu.toml`, and the copies
+            // of `--backup` would be read as dockerfiles.
+            !entry.file_type().is_dir()
+                && !entry.file_name().to_string_lossy().ends_with(FILE_CONFIG_SUFFIX)
+                && !backup::is_copy(&entry.file_name().to_string_lossy())
+                && match FileKind::of(entry.path()) {
+                    FileKind::Dockerfile => entry.file_name().to_string_lossy().to_ascii_lowercase().starts_with("dockerfile"),
+                    kind => discovery.searches(kind),
                 }
-                let possible_updates = dockerfile.generate_image_updates(
-                    &multi_mode.strat,
-                    multi_mode.common.tag_search_limit,
-                    multi_mode.common.arch.as_ref(),
-                    &ignored_images,
-                );
-                let dockerfile_updated = possible_updates.apply();
-                if multi_mode.dry_run {
-                    info!(
-                        "Updated dockerfile `{}` would look like:\n{dockerfile_updated}",
-                        dockerfile.get_path().expect("Path is not empty.").display()
-                    );
-                } else {
-                    let _ = dockerfile_updated.write();
+        })
+        .map(|entry| entry.path().display().to_string())
+        .collect()
+}
+
+/// Parses the given files and resolves their images in parallel, the results
+/// are written in the order of the files. Returns the images that could not be
+/// resolved.
+fn process_files(multi_mode: &cli::MultiFileArguments, dockerfiles_to_process: &[String]) -> Vec<ResolveError> {
+    let parsing = stats::phase("parsing");
+    let (dockerfiles, file_strategies): (Vec<Dockerfile>, Vec<Option<Vec<Strategy>>>) = dockerfiles_to_process
+        .par_iter()
+        .filter_map(|dockerfile_to_process| read_multi_file(multi_mode, dockerfile_to_process))
+        .unzip();
+
+    drop(parsing);
+    let mut errors = dockerfiles_to_process.len() - dockerfiles.len();
+
+    // Dockerfiles with the same settings are resolved together, so that shared
+    // images are only looked up once per group.
+    let resolving = stats::phase("resolving");
+    let mut groups: Vec<(FileSettings, Vec<Dockerfile>)> = Vec::new();
+    let mut file_groups = Vec::with_capacity(dockerfiles.len());
+    let mut folder_settings = HashMap::<PathBuf, Option<FileSettings>>::new();
+    for (dockerfile, strategies) in dockerfiles.iter().zip(file_strategies) {
+        let folder = dockerfile.get_path().and_then(|path| path.parent()).unwrap_or(&multi_mode.folder).to_path_buf();
+        let settings = folder_settings.entry(folder).or_insert_with_key(|folder| FileSettings::new(multi_mode, folder));
+        let Some(mut settings) = settings.clone() else {
+            errors += 1;
+            file_groups.push(None);
+            continue;
+        };
+        if let Some(strategies) = strategies {
+            settings.strategies = strategies;
+        }
+        settings.image_strategies = dockerfile.marked_image_strategies();
+        let index = groups.iter().position(|(group, _)| *group == settings).unwrap_or_else(|| {
+            groups.push((settings, Vec::new()));
+            groups.len() - 1
+        });
+        groups[index].1.push(dockerfile.clone());
+        file_groups.push(Some(index));
+    }
+
+    let mut failures = Vec::new();
+    let group_candidates: Vec<ImageCandidates> = groups
+        .iter()
+        .map(|(settings, group)| {
+            let (candidates, group_failures) = resolve_candidates(
+                group,
+                &CandidateOptions {
+                    image_strategies: settings.image_strategies.clone(),
+                    ..multi_mode.common.candidate_options(&settings.strategies)
+                },
+                &multi_mode.common.fetch_options(&settings.strategies),
+            );
+            failures.extend(group_failures);
+            candidates
+        })
+        .collect();
+    let findings = dockerfiles
+        .iter()
+        .zip(&file_groups)
+        .filter_map(|(dockerfile, group)| group.map(|group| (dockerfile, group)))
+        .flat_map(|(dockerfile, group)| report::collect_findings(std::slice::from_ref(dockerfile), &group_candidates[group], &groups[group].0.ignored_images))
+        .collect::<Vec<_>>();
+    drop(resolving);
+    // The failures are collected per group of settings, they are reported by
+    // image.
+    failures.sort_by(|a, b| a.image.cmp(&b.image));
+    let summary = ScanSummary::new(dockerfiles_to_process.len(), &findings, errors + failures.len());
+    report::print_findings(&multi_mode.output, &findings, &report::collect_diagnostics(&dockerfiles), Some(&summary));
+    let _writing = stats::phase("writing");
+    for (dockerfile, group) in dockerfiles.iter().zip(file_groups) {
+        let Some(group) = group else {
+            continue;
+        };
+        let candidates = applied_candidates(dockerfile, &group_candidates[group], &multi_mode.apply);
+        let mut dockerfile_updated = dockerfile.generate_image_updates(&candidates, &groups[group].0.ignored_images).apply();
+        dockerfile_updated.refresh_digests(multi_mode.common.arch.as_deref());
+        if let Some(arch) = multi_mode.common.arch.as_deref().filter(|_| multi_mode.pin_digest) {
+            dockerfile_updated.pin_digests(registries::http::client().as_ref(), dockerfile, arch);
+        }
+        if multi_mode.dry_run {
+            info!(
+                "Updated dockerfile `{}` would look like:\n{dockerfile_updated}",
+                dockerfile.get_path().expect("Path is not empty.").display()
+            );
+        } else {
+            let original_content = dockerfile.get_path().and_then(|path| fs::read_to_string(path).ok());
+            if dockerfile_updated.write().is_ok() {
+                run_hooks(&multi_mode.hooks, dockerfile, original_content.as_deref(), &dockerfile_updated);
+            }
+        }
+    }
+    if multi_mode.dry_run {
+        info!("Dry run finished: {summary}");
+    }
+    failures
+}
+
+/// Reads a dockerfile of the folder together with its own strategies, see
+/// `file_strategies`. Returns `None` if it can not be read.
+fn read_multi_file(multi_mode: &cli::MultiFileArguments, dockerfile_to_process: &str) -> Option<(Dockerfile, Option<Vec<Strategy>>)> {
+    let path = PathBuf::from(dockerfile_to_process);
+    let scanners = multi_mode.discovery.yaml_scanners();
+    let dockerfile = if multi_mode.resolve_includes {
+        Dockerfile::read_with_includes(&path, scanners)
+    } else {
+        Dockerfile::read_with(&path, scanners)
+    };
+    match dockerfile {
+        Ok(mut dockerfile) => {
+            // Files without stages, e.g. compose files, are kept as they are.
+            if let Some(filter) = multi_mode.stages.filter() {
+                dockerfile.retain_stage(&filter);
+            }
+            match file_strategies(&path, &dockerfile) {
+                Ok(strategies) => Some((dockerfile, strategies)),
+                Err(e) => {
+                    error!("Skipping the dockerfile `{dockerfile_to_process}`: {e}");
+                    None
                 }
             }
+        }
+        Err(e) => {
+            error!("Could not read dockerfile: `{dockerfile_to_process}` with error: {e}");
+            None
+        }
+    }
+}
+
+/// Returns the strategies of the dockerfile itself, which replace the ones of
+/// the run and the folder: a marker in its first line (e.g. `#
+/// diu:strategy=next-minor`) or else the `strat` of its own config file (e.g.
+/// `Dockerfile.diu.toml`).
+fn file_strategies(path: &Path, dockerfile: &Dockerfile) -> Result<Option<Vec<Strategy>>, ConfigError> {
+    if let Some(strategy) = dockerfile.marked_strategy() {
+        return Ok(Some(vec![strategy]));
+    }
+    Ok(config::load_file_config(path)?
+        .map(|file_config| file_config.strat)
+        .filter(|strategies| !strategies.is_empty()))
+}
+
+/// The strategies and ignored images of the dockerfiles in a folder, the
+/// config files of the folder and the folders above it are merged with the
+/// flags. The strategies of marked images are those of the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileSettings {
+    strategies:       Vec<Strategy>,
+    ignored_images:   Vec<ContainerImage>,
+    image_strategies: BTreeMap<String, Strategy>,
+}
+
+impl FileSettings {
+    /// Returns `None` if a config file is invalid, the files of the folder are
+    /// skipped then. Strategies given as flag take precedence over the config
+    /// files, ignored images and pins are combined.
+    fn new(multi_mode: &cli::MultiFileArguments, folder: &Path) -> Option<Self> {
+        let config = match config::load_folder_config(&multi_mode.folder, folder) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("Skipping the dockerfiles in `{}`: {e}", folder.display());
+                return None;
+            }
+        };
+        let strategies = if !multi_mode.strat.is_empty() {
+            multi_mode.strat.clone()
+        } else if !config.strat.is_empty() {
+            config.strat
+        } else {
+            vec![Strategy::default()]
+        };
+        let pins: Vec<Pin> = multi_mode.pin.iter().chain(&config.pins).cloned().collect();
+        let pinned_images = pin::active_images(&pins, Date::today());
+        let ignored_images = parse_ignored_images(multi_mode.ignore_versions.iter().chain(&config.ignore_versions).chain(&pinned_images));
+        if !ignored_images.is_empty() {
+            debug!("Skipping image updates in `{}`:", folder.display());
+            for image in &ignored_images {
+                debug!("\t\t{}", image.get_name());
+            }
+        }
+        Some(Self {
+            strategies,
+            ignored_images,
+            image_strategies: BTreeMap::new(),
+        })
+    }
+}
+
+/// Parses the ignored images, the images that can not be parsed are reported
+/// and left out.
+fn parse_ignored_images<'a>(images: impl IntoIterator<Item = &'a String>) -> Vec<ContainerImage> {
+    images
+        .into_iter()
+        .filter_map(|image| match image.parse() {
+            Ok(image) => Some(image),
+            Err(e) => {
+                error!("Could not parse ignored image `{image}`: {e}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Resolves the candidate of every unique image used in the given dockerfiles
+/// once, so that images shared between files are only looked up a single time.
+/// Images that can not be resolved are returned next to the candidates.
+pub fn resolve_candidates(dockerfiles: &[Dockerfile], options: &CandidateOptions, fetch: &FetchOptions) -> (ImageCandidates, Vec<ResolveError>) {
+    let mut unique_images = Vec::<&ContainerImage>::new();
+    for image in dockerfiles.iter().flat_map(Dockerfile::get_base_images) {
+        if !image.is_empty() && !unique_images.contains(&image) {
+            unique_images.push(image);
+        }
+    }
+    info!("Resolving {} unique images.", unique_images.len());
+
+    let results: Vec<Result<Option<(String, Tag)>, ResolveError>> = unique_images
+        .into_par_iter()
+        .map(|image| {
+            image
+                .resolve_update(options, fetch)
+                .map(|found_tag| found_tag.map(|found_tag| (image.to_string(), found_tag)))
+        })
+        .collect();
+    let mut candidates = ImageCandidates::new();
+    let mut failures = Vec::new();
+    for result in results {
+        match result {
+            Ok(candidate) => candidates.extend(candidate),
             Err(e) => {
-                error!("Could not read dockerfile: `{dockerfile_to_process}` with error: {e}");
+                error!("{e}");
+                failures.push(e);
             }
         }
     }
+    (candidates, failures)
+}
+
+/// Reports the images that could not be resolved at the end of the run.
+/// Returns `true` if there were none.
+pub fn report_failures(failures: &[ResolveError]) -> bool {
+    if failures.is_empty() {
+        return true;
+    }
+    error!("{} images could not be resolved, their updates were skipped:", failures.len());
+    for failure in failures {
+        error!("\t`{}`: {}", failure.image, failure.reason);
+    }
+    false
+}
+
+/// A single image of a batch file, either given as plain image reference or as
+/// json object with an optional strategy and architecture.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct BatchEntry {
+    pub image:    String,
+    #[serde(default)]
+    pub strategy: Option<Strategy>,
+    #[serde(default)]
+    pub arch:     Option<String>,
+}
+
+impl BatchEntry {
+    /// Returns the strategy of the entry, or the given default strategies.
+    pub(crate) fn strategies<'a>(&'a self, default: &'a [Strategy]) -> &'a [Strategy] {
+        self.strategy.as_ref().map_or(default, std::slice::from_ref)
+    }
 }
 
-/// Reads already fetched data into the program's memory (global variable).
+/// Parses a batch file with one image per line, either as plain reference
+/// (e.g. `node:22.1.0`) or as json object (e.g. `{"image": "node:22.1.0",
+/// "strategy": "next-minor", "arch": "arm64"}`). Empty lines and lines
+/// starting with `#` are skipped.
 ///
-/// Cache invalidates after `DURATION_HOUR_AS_SECS` seconds, to ensure the data
-/// is up to date.
-pub fn extract_cache_from_file(full_name: &str, tags: &mut Vec<Tag>, cache_file_name: &str) -> Result<(), Box<dyn std::error::Error>> {
-    if fs::exists(cache_file_name)? {
-        debug!("Cache file `{cache_file_name}`exists.");
-        let file_metadata = fs::metadata(cache_file_name).expect("Cache file exists");
-        if let Ok(time) = file_metadata.modified() {
-            if time.elapsed().expect("No error with systime occured.") < Duration::new(DURATION_HOUR_AS_SECS, 0) {
-                let cache_file_content = fs::read_to_string(cache_file_name).expect("File exists for reading.");
-                if let Ok(read_tags) = &serde_json::from_str(&cache_file_content) {
-                    tags.clone_from(read_tags);
-                    let mut cache = TAGS_CACHE.write().expect("Cache can be written.");
-                    if cache.insert(full_name.to_string(), tags.clone()).is_none() {
-                        debug!("Populated cache successfully.");
-                    }
+/// # Errors
+///
+/// This function will return an error if a json line can not be parsed.
+pub fn parse_batch(content: &str) -> Result<Vec<BatchEntry>, ParseError> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            if line.starts_with('{') {
+                serde_json::from_str(line).map_err(|e| ParseError::InvalidBatchLine(number, e.to_string()))
+            } else {
+                Ok(BatchEntry {
+                    image: line.to_owned(),
+                    ..BatchEntry::default()
+                })
+            }
+        })
+        .collect()
+}
+
+/// The columns of the markdown table of the batch mode.
+const BATCH_COLUMNS: [&str; 4] = ["Image", "Strategy", "Candidate", "Error"];
+
+/// The result of a single image in batch mode, printed as one json line.
+#[derive(Debug, Serialize)]
+struct BatchReport {
+    image:     String,
+    strategy:  String,
+    candidate: Option<String>,
+    error:     Option<String>,
+}
+
+impl BatchReport {
+    fn new(image: String, strategies: &[Strategy], result: Result<Option<String>, impl Display>) -> Self {
+        let (candidate, error) = match result {
+            Ok(candidate) => (candidate, None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        Self {
+            image,
+            strategy: strategies
+                .iter()
+                .filter_map(|strategy| strategy.to_possible_value().map(|value| value.get_name().to_owned()))
+                .collect::<Vec<_>>()
+                .join(","),
+            candidate,
+            error,
+        }
+    }
+
+    fn markdown_row(&self) -> [String; 4] {
+        [
+            format!("`{}`", self.image),
+            self.strategy.clone(),
+            self.candidate.as_ref().map_or_else(|| "-".to_owned(), |candidate| format!("`{candidate}`")),
+            self.error.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+impl Report for BatchReport {
+    fn text(&self) -> Vec<String> {
+        vec![match (&self.candidate, &self.error) {
+            (_, Some(error)) => format!("Could not resolve `{}`: {error}", self.image),
+            (Some(candidate), None) => format!("===> Candidate for `{}` ({}): {candidate}", self.image, self.strategy),
+            (None, None) => format!("===> No candidate found for `{}` ({}).", self.image, self.strategy),
+        }]
+    }
+
+    fn markdown(&self) -> Vec<String> {
+        report::markdown_table(BATCH_COLUMNS, [self.markdown_row()])
+    }
+}
+
+impl Report for Vec<BatchReport> {
+    fn text(&self) -> Vec<String> {
+        self.iter().flat_map(Report::text).collect()
+    }
+
+    fn markdown(&self) -> Vec<String> {
+        report::markdown_table(BATCH_COLUMNS, self.iter().map(BatchReport::markdown_row))
+    }
+}
+
+/// Resolves the candidate of a single batch entry, the registries are
+/// requested with the given client.
+fn resolve_batch_entry(
+    client: &dyn HttpClient, batch_mode: &cli::BatchArguments, entry: &BatchEntry,
+) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+    resolve_image_with(
+        client,
+        &batch_mode.common,
+        entry.strategies(&batch_mode.strat),
+        &entry.image,
+        entry.arch.as_ref(),
+        None,
+    )
+}
+
+/// Resolves the candidate of an image, from the given tags or from the tags
+/// of its registry.
+fn resolve_image(
+    common: &cli::CommonOptions, strategies: &[Strategy], image: &str, arch: Option<&String>, tags: Option<&[String]>,
+) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+    resolve_image_with(registries::http::client().as_ref(), common, strategies, image, arch, tags)
+}
+
+/// Resolves the candidate of an image like `resolve_image`, the registries are
+/// requested with the given client.
+fn resolve_image_with(
+    client: &dyn HttpClient, common: &cli::CommonOptions, strategies: &[Strategy], image: &str, arch: Option<&String>, tags: Option<&[String]>,
+) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let docker_image: ContainerImage = image.parse()?;
+    let options = common.candidate_options(strategies);
+    docker_image.check_publisher(client, &options)?;
+    let mut fetch = common.fetch_options(strategies);
+    if arch.is_some() {
+        fetch.arch = arch.cloned();
+    }
+    let docker_image_tags = match tags {
+        Some(tags) => tags.iter().filter_map(|tag| tag.parse().ok()).collect(),
+        None => docker_image.get_remote_tags_with(client, &fetch).map_err(|e| e.to_string())?,
+    };
+    Ok(docker_image
+        .find_update(&docker_image_tags, &options, fetch.arch.as_ref())
+        .map(|found_tag| format!("{}:{}", docker_image.get_dockerimage_name(), found_tag.to_string().trim_end_matches('.'))))
+}
+
+/// Answers requests for candidates on stdin until it is closed, see
+/// `serve::serve`. Returns `false` if stdin or stdout failed.
+pub fn handle_serve(serve_mode: &cli::ServeArguments) -> bool {
+    if let Some(addr) = &serve_mode.metrics_addr
+        && let Err(e) = metrics::listen(addr)
+    {
+        eprintln!("Could not serve metrics on `{addr}`: {e}");
+        return false;
+    }
+    let result = serve::serve(io::stdin().lock(), io::stdout().lock(), |params| {
+        let candidate = resolve_request(registries::http::client().as_ref(), serve_mode, params);
+        record_metrics(&params.image, candidate.as_ref());
+        candidate
+    });
+    if let Err(e) = &result {
+        eprintln!("Could not serve requests: {e}");
+    }
+    result.is_ok()
+}
+
+/// Resolves the candidate of a `candidate` request of the serve mode, the
+/// registries are requested with the given client.
+fn resolve_request(client: &dyn HttpClient, serve_mode: &cli::ServeArguments, params: &serve::CandidateParams) -> Result<Option<String>, String> {
+    let strategies = params.strategy.as_ref().map_or(serve_mode.strat.as_slice(), std::slice::from_ref);
+    resolve_image_with(
+        client,
+        &serve_mode.common,
+        strategies,
+        &params.image,
+        params.arch.as_ref(),
+        params.tags.as_deref(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Records a resolved image and the kind of its update for the metrics
+/// endpoint.
+fn record_metrics(image: &str, candidate: Result<&Option<String>, &String>) {
+    let Ok(candidate) = candidate else {
+        METRICS.record_error();
+        return;
+    };
+    let update = image
+        .parse::<ContainerImage>()
+        .ok()
+        .zip(candidate.as_ref().and_then(|candidate| candidate.parse::<ContainerImage>().ok()))
+        .map(|(current, candidate)| current.get_tag().bump(candidate.get_tag()));
+    METRICS.record_image(image, update);
+}
+
+/// Handles a file with a list of images, the bulk version of the input mode.
+/// Returns `false` if the file can not be read or parsed, or if some of its
+/// images could not be resolved.
+pub fn handle_batch(batch_mode: &cli::BatchArguments) -> bool {
+    let content = match fs::read_to_string(&batch_mode.file) {
+        Ok(content) => content,
+        Err(e) => {
+            error!("Could not read the batch file `{}`: {e}", batch_mode.file.display());
+            return false;
+        }
+    };
+    let entries = match parse_batch(&content) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("{e}");
+            return false;
+        }
+    };
+    info!("Resolving {} images.", entries.len());
+    // The order of the results matches the order of the file.
+    let resolving = stats::phase("resolving");
+    let results: Vec<_> = entries
+        .par_iter()
+        .map(|entry| (entry, resolve_batch_entry(registries::http::client().as_ref(), batch_mode, entry)))
+        .collect();
+    drop(resolving);
+    let resolved = results.iter().all(|(_, result)| result.is_ok());
+
+    let mut reports = Vec::new();
+    for (entry, result) in results {
+        let strategies = entry.strategies(&batch_mode.strat);
+        match batch_mode.output {
+            OutputFormat::Json | OutputFormat::Markdown => {
+                let report = BatchReport::new(entry.image.clone(), strategies, result);
+                // Json is printed as one line per image, markdown as a single table.
+                if batch_mode.output == OutputFormat::Json {
+                    report::print(&batch_mode.output, &report);
                 } else {
-                    error!("Could not read tags from file");
+                    reports.push(report);
                 }
-            } else {
-                info!("Cache file is older than {DURATION_HOUR_AS_SECS} seconds. Fetching new data instead.");
             }
+            OutputFormat::Text => {
+                let strategy = strategies.iter().map(ToString::to_string).collect::<Vec<_>>().join(", then ");
+                match result {
+                    Ok(Some(candidate)) => {
+                        info!("===> Candidate for `{}` ({strategy}): {candidate}", entry.image);
+                        if batch_mode.common.quiet {
+                            println!("{candidate}");
+                        }
+                    }
+                    Ok(None) => {
+                        info!("===> No candidate found for `{}` ({strategy}).", entry.image);
+                        if batch_mode.common.quiet {
+                            println!();
+                        }
+                    }
+                    Err(e) => {
+                        error!("Could not resolve `{}`: {e}", entry.image);
+                        if batch_mode.common.quiet {
+                            println!();
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if batch_mode.output == OutputFormat::Markdown {
+        report::print(&batch_mode.output, &reports);
+    }
+    resolved
+}
+
+/// The candidates of a single local image in json output, keyed by strategy.
+#[derive(Debug, Serialize)]
+struct LocalReport {
+    image:      String,
+    candidates: BTreeMap<String, String>,
+}
+
+impl Report for Vec<LocalReport> {
+    fn text(&self) -> Vec<String> {
+        let outdated: Vec<&LocalReport> = self.iter().filter(|report| !report.candidates.is_empty()).collect();
+        outdated
+            .iter()
+            .flat_map(|report| {
+                std::iter::once(format!("Results for:\t{}", report.image))
+                    .chain(report.candidates.iter().map(|(strategy, candidate)| format!("===> {strategy}:\t{candidate}")))
+            })
+            .chain(std::iter::once(format!("{} of {} local images have newer tags.", outdated.len(), self.len())))
+            .collect()
+    }
+
+    fn markdown(&self) -> Vec<String> {
+        let rows = self.iter().flat_map(|report| {
+            report
+                .candidates
+                .iter()
+                .map(|(strategy, candidate)| [format!("`{}`", report.image), strategy.clone(), format!("`{candidate}`")])
+        });
+        report::markdown_table(["Image", "Strategy", "Candidate"], rows)
+    }
+}
+
+/// Lists the images of the local docker or podman daemon (or of containerd)
+/// and reports the candidates of each for the chosen strategies. Returns
+/// `false` if the images could not be listed.
+pub fn handle_local(local_mode: &cli::LocalArguments) -> bool {
+    let repo_tags = match local_mode.runtime {
+        daemon::Runtime::Docker => {
+            let socket = local_mode.socket.clone().unwrap_or_else(daemon::socket_path);
+            daemon::list_images(&socket).map_err(|e| format!("Could not list the images of the daemon at `{}`: {e}", socket.display()))
+        }
+        daemon::Runtime::Nerdctl => {
+            daemon::list_nerdctl_images(local_mode.namespace.as_deref()).map_err(|e| format!("Could not list the images of containerd: {e}"))
         }
+    };
+    let repo_tags = match repo_tags {
+        Ok(repo_tags) => repo_tags,
+        Err(e) => {
+            error!("{e}");
+            return false;
+        }
+    };
+    let images: Vec<ContainerImage> = repo_tags
+        .iter()
+        .filter_map(|repo_tag| {
+            let reference = daemon::registry_reference(repo_tag);
+            if reference.is_none() {
+                debug!("Skipping `{repo_tag}`, its registry is not supported.");
+            }
+            reference?.parse().ok()
+        })
+        .collect();
+    info!("Found {} local images of supported registries.", images.len());
+    let strategies = if local_mode.strat.is_empty() {
+        OVERVIEW_STRATEGIES.to_vec()
     } else {
-        info!("No cache file exists under `{cache_file_name}`, fetching info from docker hub.");
+        local_mode.strat.clone()
+    };
+
+    let reports: Vec<LocalReport> = images
+        .par_iter()
+        .filter_map(|image| {
+            if let Err(e) = image.check_publisher(registries::http::client().as_ref(), &local_mode.common.candidate_options(&[])) {
+                warn!("{e}");
+                return None;
+            }
+            // All strategies are shown, so the tags are not narrowed down.
+            let tags = match image.get_remote_tags(&local_mode.common.fetch_options(&[])) {
+                Ok(tags) => tags,
+                Err(e) => {
+                    warn!("Could not fetch tags of `{}`: {e}", image.get_full_tagged_name());
+                    return None;
+                }
+            };
+            let candidates: BTreeMap<String, String> = strategies
+                .iter()
+                .filter_map(|strategy| {
+                    let options = local_mode.common.candidate_options(std::slice::from_ref(strategy));
+                    let found_tag = image.find_update(&tags, &options, local_mode.common.arch.as_ref())?;
+                    Some((strategy.to_string(), format!("{}:{found_tag}", image.get_dockerimage_name())))
+                })
+                .collect();
+            Some(LocalReport {
+                image: format!("{}:{}", image.get_dockerimage_name(), image.get_tag()),
+                candidates,
+            })
+        })
+        .collect();
+
+    match local_mode.output {
+        // The text output is part of the log.
+        OutputFormat::Text => {
+            for line in reports.text() {
+                info!("{line}");
+            }
+        }
+        ref format => report::print(format, &reports),
+    }
+    true
+}
+
+/// The strategies of the columns of the outdated mode, in their order.
+const OUTDATED_STRATEGIES: [Strategy; 3] = [Strategy::LatestPatch, Strategy::LatestMinor, Strategy::LatestMajor];
+
+/// An image of the outdated mode with the best candidate of each column.
+#[derive(Debug, Serialize)]
+struct OutdatedImage {
+    image:   String,
+    current: String,
+    patch:   Option<String>,
+    minor:   Option<String>,
+    major:   Option<String>,
+}
+
+impl OutdatedImage {
+    /// Returns the cells of the row, `-` marks columns without candidate.
+    fn cells(&self, quote: fn(&str) -> String) -> [String; 5] {
+        let candidate = |candidate: &Option<String>| candidate.as_deref().map_or_else(|| "-".to_owned(), quote);
+        [
+            quote(&self.image),
+            quote(&self.current),
+            candidate(&self.patch),
+            candidate(&self.minor),
+            candidate(&self.major),
+        ]
+    }
+}
+
+impl Report for Vec<OutdatedImage> {
+    fn text(&self) -> Vec<String> {
+        report::text_table(
+            ["IMAGE", "CURRENT", "PATCH", "MINOR", "MAJOR"],
+            self.iter().map(|image| image.cells(str::to_owned)),
+        )
+    }
+
+    fn markdown(&self) -> Vec<String> {
+        report::markdown_table(
+            ["Image", "Current", "Patch", "Minor", "Major"],
+            self.iter().map(|image| image.cells(|cell| format!("`{cell}`"))),
+        )
+    }
+}
+
+/// Lists the images of a dockerfile or of all dockerfiles of a folder with the
+/// latest patch, minor and major candidate, without modifying any file. The
+/// tags of every image are fetched once for all columns. Returns `false` if a
+/// file could not be read or the tags of an image could not be fetched.
+pub fn handle_outdated(outdated_mode: &cli::OutdatedArguments) -> bool {
+    // A file that is given explicitly is searched by all scanners.
+    let (files, scanners) = match (&outdated_mode.file, &outdated_mode.folder) {
+        (Some(file), _) => (vec![file.display().to_string()], YamlScanners::ALL),
+        (None, Some(folder)) => (discover_dockerfiles(folder, &outdated_mode.discovery), outdated_mode.discovery.yaml_scanners()),
+        (None, None) => (Vec::new(), YamlScanners::ALL),
+    };
+    let mut success = true;
+    let dockerfiles: Vec<Dockerfile> = files
+        .iter()
+        .filter_map(|file| match Dockerfile::read_with(file, scanners) {
+            Ok(dockerfile) => Some(dockerfile),
+            Err(e) => {
+                error!("Could not read dockerfile: `{file}` with error: {e}");
+                success = false;
+                None
+            }
+        })
+        .collect();
+    let mut unique_images = Vec::<&ContainerImage>::new();
+    for image in dockerfiles.iter().flat_map(Dockerfile::get_base_images) {
+        if !image.is_empty() && !unique_images.contains(&image) {
+            unique_images.push(image);
+        }
+    }
+
+    // All columns are filled from the same tags, so they are not narrowed down.
+    let fetch = outdated_mode.common.fetch_options(&[]);
+    let results: Vec<Option<OutdatedImage>> = unique_images
+        .par_iter()
+        .map(|image| {
+            if let Err(e) = image.check_publisher(registries::http::client().as_ref(), &outdated_mode.common.candidate_options(&[])) {
+                warn!("{e}");
+                return None;
+            }
+            let tags = match image.get_remote_tags(&fetch) {
+                Ok(tags) => tags,
+                Err(e) => {
+                    warn!("Could not fetch tags of `{}`: {e}", image.get_full_tagged_name());
+                    return None;
+                }
+            };
+            let [patch, minor, major] = OUTDATED_STRATEGIES.map(|strategy| {
+                let options = outdated_mode.common.candidate_options(&[strategy]);
+                image
+                    .find_update(&tags, &options, outdated_mode.common.arch.as_ref())
+                    .map(|found_tag| found_tag.to_string())
+            });
+            Some(OutdatedImage {
+                image: image.get_dockerimage_name(),
+                current: image.get_tag().to_string(),
+                patch,
+                minor,
+                major,
+            })
+        })
+        .collect();
+    success &= results.iter().all(Option::is_some);
+    let images: Vec<OutdatedImage> = results.into_iter().flatten().collect();
+
+    match outdated_mode.output {
+        // The text output is part of the log.
+        OutputFormat::Text => {
+            for line in images.text() {
+                info!("{line}");
+            }
+        }
+        ref format => report::print(format, &images),
+    }
+    success
+}
+
+/// The result of the `exists` mode in json output.
+#[derive(Debug, Serialize)]
+struct ExistsReport<'a> {
+    image:         String,
+    exists:        bool,
+    digest:        Option<&'a str>,
+    architectures: &'a [String],
+}
+
+impl Report for ExistsReport<'_> {
+    fn text(&self) -> Vec<String> {
+        vec![if self.exists {
+            format!("===> `{}` exists, digest: {}", self.image, self.digest.unwrap_or("unknown"))
+        } else {
+            format!("===> `{}` does not exist.", self.image)
+        }]
+    }
+
+    fn markdown(&self) -> Vec<String> {
+        report::markdown_table(
+            ["Image", "Exists", "Digest", "Architectures"],
+            [[
+                format!("`{}`", self.image),
+                if self.exists { "yes" } else { "no" }.to_owned(),
+                self.digest.map_or_else(|| "-".to_owned(), |digest| format!("`{digest}`")),
+                self.architectures.join(", "),
+            ]],
+        )
+    }
+}
+
+/// Checks if the given image exists in its registry. Returns `true` if it
+/// does, so that the exit code can reflect it.
+pub fn handle_exists(exists_mode: &cli::ExistsArguments) -> bool {
+    let docker_image: ContainerImage = exists_mode.input.parse().expect("Image could be parsed.");
+    let published = match docker_image.lookup_tag(registries::http::client().as_ref(), exists_mode.common.arch.as_deref()) {
+        Ok(published) => published,
+        Err(e) => {
+            error!("Could not look up `{}`: {e}", docker_image.get_full_tagged_name());
+            return false;
+        }
+    };
+    let report = ExistsReport {
+        image:         format!("{}:{}", docker_image.get_dockerimage_name(), docker_image.get_tag()),
+        exists:        published.is_some(),
+        digest:        published.as_ref().and_then(|published| published.digest.as_deref()),
+        architectures: published.as_ref().map_or(&[], |published| published.architectures.as_slice()),
+    };
+
+    match exists_mode.output {
+        OutputFormat::Text => {
+            for line in report.text() {
+                info!("{line}");
+            }
+            if exists_mode.common.quiet && report.exists {
+                println!("{}", report.digest.unwrap_or_default());
+            }
+        }
+        ref format => report::print(format, &report),
+    }
+    report.exists
+}
+
+/// The result of the `compare` mode in json output.
+#[derive(Debug, Serialize)]
+struct CompareReport<'a> {
+    image: &'a str,
+    from:  String,
+    to:    String,
+    /// The newer of both tags, `None` if both are equal.
+    newer: Option<String>,
+    bump:  Bump,
+}
+
+impl Report for CompareReport<'_> {
+    fn text(&self) -> Vec<String> {
+        vec![self.newer.as_ref().map_or_else(
+            || format!("===> `{}` and `{}` have the same version (changed: {}).", self.from, self.to, self.bump),
+            |newer| format!("===> `{}:{newer}` is newer ({} update).", self.image, self.bump),
+        )]
+    }
+
+    fn markdown(&self) -> Vec<String> {
+        report::markdown_table(
+            ["Image", "From", "To", "Newer", "Bump"],
+            [[
+                format!("`{}`", self.image),
+                self.from.clone(),
+                self.to.clone(),
+                self.newer.clone().unwrap_or_else(|| "-".to_owned()),
+                self.bump.to_string(),
+            ]],
+        )
+    }
+}
+
+/// Compares two tags of an image, without querying the registry.
+pub fn handle_compare(compare_mode: &cli::CompareArguments) {
+    // Parsing the tags along the image validates them the same way as in all
+    // other modes.
+    let parse = |tag: &str| -> ContainerImage { format!("{}:{tag}", compare_mode.image).parse().expect("Image could be parsed.") };
+    let (image_a, image_b) = (parse(&compare_mode.tag_a), parse(&compare_mode.tag_b));
+    let (tag_a, tag_b) = (image_a.get_tag(), image_b.get_tag());
+    let newer = match tag_a.cmp_version(tag_b) {
+        Ordering::Less => Some(tag_b),
+        Ordering::Equal => None,
+        Ordering::Greater => Some(tag_a),
+    };
+    let report = CompareReport {
+        image: &compare_mode.image,
+        from:  tag_a.to_string(),
+        to:    tag_b.to_string(),
+        newer: newer.map(ToString::to_string),
+        bump:  tag_a.bump(tag_b),
+    };
+
+    match compare_mode.output {
+        OutputFormat::Text if compare_mode.quiet => println!("{}", report.newer.unwrap_or_default()),
+        OutputFormat::Text => {
+            for line in report.text() {
+                info!("{line}");
+            }
+        }
+        ref format => report::print(format, &report),
+    }
+}
+
+/// Refreshes the digests of all pinned images in the dockerfile or in the
+/// dockerfiles of the folder, see `digest::refresh_pins`.
+pub fn handle_digest(digest_mode: &cli::DigestArguments) {
+    let files = if digest_mode.path.is_dir() {
+        discover_dockerfiles(&digest_mode.path, &digest_mode.discovery)
+    } else {
+        vec![digest_mode.path.display().to_string()]
+    };
+    let options = digest_mode.common.candidate_options(&digest_mode.strat);
+    let fetch = digest_mode.common.fetch_options(&digest_mode.strat);
+    let arch = digest_mode.common.arch.as_deref();
+    let client = registries::http::client();
+    let resolve = |pin: &DigestPin| -> Option<(String, String)> {
+        let image: ContainerImage = match format!("{}:{}", pin.image, pin.tag).parse() {
+            Ok(image) => image,
+            Err(e) => {
+                error!("Could not parse pinned image `{}:{}`: {e}", pin.image, pin.tag);
+                return None;
+            }
+        };
+        let mut candidate = image.clone();
+        if !digest_mode.strat.is_empty() {
+            if let Err(e) = image.check_publisher(client.as_ref(), &options) {
+                error!("{e}");
+                return None;
+            }
+            match image.get_remote_tags(&fetch) {
+                Ok(tags) => {
+                    if let Some(found_tag) = image.find_update(&tags, &options, digest_mode.common.arch.as_ref()) {
+                        candidate.update_image_tag(&found_tag);
+                    }
+                }
+                Err(e) => {
+                    error!("Could not get the tags of `{}`: {e}", image.get_full_tagged_name());
+                    return None;
+                }
+            }
+        }
+        match candidate.lookup_tag(client.as_ref(), arch) {
+            Ok(Some(PublishedTag { digest: Some(digest), .. })) => Some((candidate.get_tag().to_string(), digest)),
+            Ok(_) => {
+                warn!("No digest is published for `{}`.", candidate.get_full_tagged_name());
+                None
+            }
+            Err(e) => {
+                error!("Could not look up `{}`: {e}", candidate.get_full_tagged_name());
+                None
+            }
+        }
+    };
+
+    for file in &files {
+        let content = match fs::read_to_string(file) {
+            Ok(content) => {
+                stats::file_scanned();
+                content
+            }
+            Err(e) => {
+                error!("Could not read dockerfile: `{file}` with error: {e}");
+                continue;
+            }
+        };
+        let (refreshed, updates) = digest::refresh_pins(&content, resolve);
+        for update in &updates {
+            info!(
+                "===> {file}:{}: `{}:{}@{}` to `{}:{}@{}`",
+                update.line, update.image, update.from_tag, update.from_digest, update.image, update.to_tag, update.to_digest
+            );
+        }
+        if updates.is_empty() {
+            continue;
+        }
+        if digest_mode.dry_run {
+            info!("Updated dockerfile `{file}` would look like:\n{refreshed}");
+        } else if let Err(e) = fs::write(file, refreshed) {
+            error!("Could not write dockerfile `{file}`: {e}");
+        } else {
+            info!("Successfully written new dockerfile to: {file}");
+        }
+    }
+}
+
+/// Reports images that are used with different tags in the dockerfiles of the
+/// folder and aligns them, if requested. Returns `true` if no inconsistency
+/// is left in the files, so that the exit code can reflect it.
+pub fn handle_consistency(consistency_mode: &cli::ConsistencyArguments) -> bool {
+    let mut files = discover_dockerfiles(&consistency_mode.folder, &consistency_mode.discovery);
+    for excluded in &consistency_mode.exclude_file {
+        files.retain(|file| !file.ends_with(excluded));
+    }
+    let dockerfiles: Vec<Dockerfile> = files
+        .iter()
+        .filter_map(|file| match Dockerfile::read_with(file, consistency_mode.discovery.yaml_scanners()) {
+            Ok(dockerfile) => Some(dockerfile),
+            Err(e) => {
+                error!("Could not read dockerfile: `{file}` with error: {e}");
+                None
+            }
+        })
+        .collect();
+    let targets: Vec<ContainerImage> = consistency_mode
+        .align_to
+        .iter()
+        .filter_map(|image| match image.parse() {
+            Ok(image) => Some(image),
+            Err(e) => {
+                error!("Could not parse image `{image}`: {e}");
+                None
+            }
+        })
+        .collect();
+
+    let mut inconsistencies = consistency::find_inconsistencies(&dockerfiles);
+    let mut candidates = ImageCandidates::new();
+    for inconsistency in &mut inconsistencies {
+        let target = targets
+            .iter()
+            .find(|target| consistency::image_key(target) == inconsistency.image)
+            .map(|target| target.get_tag().clone());
+        if let Some(tag) = target.or_else(|| consistency_mode.align.then(|| inconsistency.newest_tag().clone())) {
+            inconsistency.align_to(&tag, &mut candidates);
+        }
+    }
+
+    match consistency_mode.output {
+        OutputFormat::Text => {
+            for line in inconsistencies.text() {
+                info!("{line}");
+            }
+        }
+        ref format => report::print(format, &inconsistencies),
+    }
+
+    if !candidates.is_empty() {
+        for dockerfile in &dockerfiles {
+            let update = dockerfile.generate_image_updates(&candidates, &[]);
+            if update.updates.is_empty() {
+                continue;
+            }
+            let dockerfile_aligned = update.apply();
+            if consistency_mode.dry_run {
+                info!(
+                    "Aligned dockerfile `{}` would look like:\n{dockerfile_aligned}",
+                    dockerfile.get_path().expect("Path is not empty.").display()
+                );
+            } else if dockerfile_aligned.write().is_err() {
+                return false;
+            }
+        }
+    }
+    // A dry run leaves the files as they are.
+    inconsistencies
+        .iter()
+        .all(|inconsistency| inconsistency.aligned_to.is_some() && !consistency_mode.dry_run)
+}
+
+/// A single strategy in the json output of the `strategies` mode.
+#[derive(Debug, Serialize)]
+struct StrategyReport {
+    name:        String,
+    description: String,
+    from:        String,
+    to:          Option<String>,
+}
+
+impl Report for Vec<StrategyReport> {
+    fn text(&self) -> Vec<String> {
+        self.iter()
+            .map(|report| {
+                format!(
+                    "{:<14}{:<60}e.g. {} -> {}",
+                    report.name,
+                    report.description,
+                    report.from,
+                    report.to.as_deref().unwrap_or("-")
+                )
+            })
+            .collect()
+    }
+
+    fn markdown(&self) -> Vec<String> {
+        let rows = self.iter().map(|report| {
+            [
+                format!("`{}`", report.name),
+                report.description.clone(),
+                format!("`{}` -> `{}`", report.from, report.to.as_deref().unwrap_or("-")),
+            ]
+        });
+        report::markdown_table(["Strategy", "Description", "Example"], rows)
+    }
+}
+
+/// Lists all strategies with a description and an example.
+pub fn handle_strategies(strategies_mode: &cli::StrategiesArguments) {
+    let reports: Vec<StrategyReport> = Strategy::value_variants()
+        .iter()
+        .map(|strategy| {
+            let (current, candidate) = strategy.example();
+            StrategyReport {
+                name:        strategy.to_possible_value().map(|value| value.get_name().to_owned()).unwrap_or_default(),
+                description: strategy.description(),
+                from:        current.to_string(),
+                to:          candidate.map(|candidate| candidate.to_string()),
+            }
+        })
+        .collect();
+
+    report::print(&strategies_mode.output, &reports);
+}
+
+/// Handles the maintenance of the cache store.
+pub fn handle_cache(cache_mode: &cli::CacheArguments) {
+    let path = cache::store_path();
+    match cache_mode.command {
+        cli::CacheCommand::Stats => match CacheStore::load(&path) {
+            Ok(store) => {
+                let stats = store.stats(cache::now());
+                println!("Cache store:\t{}", path.display());
+                println!("Images:\t\t{} ({} expired)", stats.images, stats.expired);
+                println!("Tags:\t\t{}", stats.tags);
+            }
+            Err(e) => eprintln!("Could not read cache store: {e}"),
+        },
+        cli::CacheCommand::Prune { all } => match cache::prune(all) {
+            Ok(removed) => println!("Removed {removed} entries from `{}`.", path.display()),
+            Err(e) => eprintln!("Could not prune cache store: {e}"),
+        },
     }
-    Ok(())
 }
 
 #[derive(Debug, Deserialize)]
@@ -317,8 +1839,7 @@ pub fn handle_self_update() {
 
     let file_name = format!("dockerimage-updater-v{latest}{extension}");
     let download_url = format!("https://github.com/ksgk1/dockerimage-updater/releases/download/v{latest}/{file_name}");
-    let mut full_path = env::current_dir().expect("Valid current dir");
-    full_path.push(&file_name);
+    let full_path = paths::install_dir().join(&file_name);
 
     match download_file(&agent, &download_url, full_path.to_str().expect("Valid path")) {
         Ok(()) => println!("Successfully downloaded new version to: {}", full_path.display()),
@@ -328,15 +1849,260 @@ pub fn handle_self_update() {
 
 #[cfg(test)]
 mod tests {
+    #![allow(clippy::unwrap_used)]
     use std::path::Path;
     use std::{fs, io};
 
+    use clap::Parser;
     use tracing_subscriber::layer::SubscriberExt;
     use tracing_subscriber::util::SubscriberInitExt;
     use tracing_subscriber::{EnvFilter, fmt};
 
-    use crate::cli::{CommonOptions, InputArguments, MultiFileArguments, SingleFileArguments};
-    use crate::utils::{Strategy, handle_file, handle_input, handle_multi};
+    use crate::cli::{
+        BackupOptions, Cli, CommonOptions, DiscoveryOptions, InputArguments, Mode, MultiFileArguments, SingleFileArguments, StageOptions, UpdateHooks,
+    };
+    use crate::container_image::{ContainerImage, Dockerfile, ParseError};
+    use crate::paths;
+    use crate::registries::PublishedTag;
+    use crate::registries::http::{MockClient, replay_fixtures};
+    use crate::report::{Report, ReportFormat, Severity};
+    use crate::serve::CandidateParams;
+    use crate::utils::{
+        BatchEntry, ImageCandidates, OutdatedImage, OverviewCandidate, Strategy, StrategyError, applied_candidates, discover_dockerfiles, file_strategies,
+        handle_batch, handle_file, handle_input, handle_multi, overview_table, parse_batch, resolve_batch_entry, resolve_image_with, resolve_request,
+    };
+
+    #[test]
+    fn applied_severities() {
+        let dockerfile = Dockerfile::parse("FROM node:22.1.0 AS build\nFROM redis:7.4.0\n").unwrap();
+        let candidates = ImageCandidates::from([
+            ("node:22.1.0".to_owned(), "23.0.0".parse().unwrap()),
+            ("redis:7.4.0".to_owned(), "7.4.1".parse().unwrap()),
+        ]);
+        assert_eq!(applied_candidates(&dockerfile, &candidates, &[]), candidates);
+        let applied = applied_candidates(&dockerfile, &candidates, &[Severity::Patch, Severity::Minor]);
+        assert_eq!(applied.keys().collect::<Vec<_>>(), ["redis:7.4.0"]);
+        assert!(applied_candidates(&dockerfile, &candidates, &[Severity::Variant]).is_empty());
+    }
+
+    #[test]
+    fn batch_file() {
+        let content = r#"
+            # inventory of the build hosts
+            node:22.1.0
+            {"image": "mcr.microsoft.com/dotnet/aspnet:9.0.0", "strategy": "next-patch", "arch": "arm64"}
+            {"image": "alpine:3.20"}
+        "#;
+        assert_eq!(
+            parse_batch(content).unwrap(),
+            [
+                BatchEntry {
+                    image: "node:22.1.0".to_owned(),
+                    ..BatchEntry::default()
+                },
+                BatchEntry {
+                    image:    "mcr.microsoft.com/dotnet/aspnet:9.0.0".to_owned(),
+                    strategy: Some(Strategy::NextPatch),
+                    arch:     Some("arm64".to_owned()),
+                },
+                BatchEntry {
+                    image: "alpine:3.20".to_owned(),
+                    ..BatchEntry::default()
+                },
+            ]
+        );
+        assert!(matches!(
+            parse_batch("node:22.1.0\n{\"image\": \"node:22.1.0\", \"strategy\": \"fastest\"}"),
+            Err(ParseError::InvalidBatchLine(2, _))
+        ));
+    }
+
+    #[test]
+    fn batch_arches() {
+        let url = "https://hub.docker.com/v2/repositories/mock/batch-arches/tags?page_size=100";
+        let page = serde_json::json!({
+            "results": [
+                { "name": "22.2.0", "images": [{ "architecture": "amd64" }, { "architecture": "s390x" }] },
+                { "name": "22.3.0", "images": [{ "architecture": "arm64" }] },
+                { "name": "22.1.0", "images": [{ "architecture": "arm64" }, { "architecture": "s390x" }] },
+            ]
+        });
+        let client = MockClient::default().with_response(url, &page.to_string());
+        let cli = Cli::try_parse_from(["diu", "batch", "images.txt"]).unwrap();
+        let Mode::Batch(batch_mode) = cli.mode else {
+            panic!("Batch mode was parsed.");
+        };
+        let entries = parse_batch(
+            r#"{"image": "mock/batch-arches:22.1.0", "arch": "arm64"}
+{"image": "mock/batch-arches:22.1.0", "arch": "s390x"}"#,
+        )
+        .unwrap();
+        let candidates: Vec<Option<String>> = entries.iter().map(|entry| resolve_batch_entry(&client, &batch_mode, entry).unwrap()).collect();
+        assert_eq!(
+            candidates,
+            [Some("mock/batch-arches:22.3.0".to_owned()), Some("mock/batch-arches:22.2.0".to_owned())]
+        );
+        // The tags of every architecture are cached on their own.
+        assert_eq!(client.requests(), [url, url]);
+    }
+
+    #[test]
+    fn batch_file_errors() {
+        let file = paths::temp_path("batch-errors");
+        let path = file.to_string_lossy().into_owned();
+        let cli = Cli::try_parse_from(["diu", "batch", &path]).unwrap();
+        let Mode::Batch(batch_mode) = cli.mode else {
+            panic!("Batch mode was parsed.");
+        };
+        assert!(!handle_batch(&batch_mode));
+        fs::write(&file, "node:22.1.0\n{\"image\": \n").unwrap();
+        let parsed = handle_batch(&batch_mode);
+        fs::remove_file(&file).unwrap();
+        assert!(!parsed);
+    }
+
+    #[test]
+    fn serve_arches() {
+        let url = "https://hub.docker.com/v2/repositories/mock/serve-arches/tags?page_size=100";
+        let page = serde_json::json!({
+            "results": [
+                { "name": "1.2.0", "images": [{ "architecture": "amd64" }] },
+                { "name": "1.1.0", "images": [{ "architecture": "amd64" }, { "architecture": "arm64" }] },
+                { "name": "1.0.0", "images": [{ "architecture": "amd64" }, { "architecture": "arm64" }] },
+            ]
+        });
+        let client = MockClient::default().with_response(url, &page.to_string());
+        let cli = Cli::try_parse_from(["diu", "serve"]).unwrap();
+        let Mode::Serve(serve_mode) = cli.mode else {
+            panic!("Serve mode was parsed.");
+        };
+        let request = |arch: &str| {
+            let params: CandidateParams = serde_json::from_value(serde_json::json!({ "image": "mock/serve-arches:1.0.0", "arch": arch })).unwrap();
+            resolve_request(&client, &serve_mode, &params).unwrap()
+        };
+        assert_eq!(request("arm64").as_deref(), Some("mock/serve-arches:1.1.0"));
+        assert_eq!(request("amd64").as_deref(), Some("mock/serve-arches:1.2.0"));
+        assert_eq!(request("arm64").as_deref(), Some("mock/serve-arches:1.1.0"));
+        assert_eq!(client.requests(), [url, url]);
+    }
+
+    #[test]
+    fn serve_official_only() {
+        let cli = Cli::try_parse_from(["diu", "serve", "--official-only"]).unwrap();
+        let common = cli.mode.common().unwrap();
+        let client = MockClient::default().with_response("https://hub.docker.com/v2/repositories/mock/app/", r#"{"name":"app"}"#);
+        let tags = ["1.0.0".to_owned(), "1.1.0".to_owned()];
+
+        let official = resolve_image_with(&client, common, &[Strategy::Latest], "node:1.0.0", None, Some(&tags)).unwrap();
+        assert_eq!(official.as_deref(), Some("node:1.1.0"));
+        let community = resolve_image_with(&client, common, &[Strategy::Latest], "mock/app:1.0.0", None, Some(&tags)).unwrap_err();
+        assert!(community.to_string().contains("no Docker Official Image"), "{community}");
+        assert_eq!(client.requests(), ["https://hub.docker.com/v2/repositories/mock/app/"]);
+    }
+
+    #[test]
+    fn file_pins() {
+        let file = paths::temp_path("file-pins");
+        fs::write(&file, "FROM mock/pinned:1.0.0\n").unwrap();
+        let path = file.to_string_lossy().into_owned();
+        let cli = Cli::try_parse_from(["diu", "file", &path, "--dry-run", "--pin", "mock/pinned:1=until:2999-12-31"]).unwrap();
+        let Mode::File(file_mode) = cli.mode else {
+            panic!("File mode was parsed.");
+        };
+        // The pinned image is not looked up, so nothing fails to resolve.
+        let resolved = handle_file(&file_mode);
+        fs::remove_file(&file).unwrap();
+        assert!(resolved);
+    }
+
+    #[test]
+    fn strategy_examples() {
+        let examples: Vec<(String, String)> = [
+            Strategy::Latest,
+            Strategy::NextPatch,
+            Strategy::LatestPatch,
+            Strategy::NextMinor,
+            Strategy::LatestMinor,
+            Strategy::NextMajor,
+            Strategy::LatestMajor,
+        ]
+        .iter()
+        .map(|strategy| {
+            let (current, candidate) = strategy.example();
+            (current.to_string(), candidate.map(|candidate| candidate.to_string()).unwrap_or_default())
+        })
+        .collect();
+        let to: Vec<&str> = examples.iter().map(|(_, to)| to.as_str()).collect();
+        assert_eq!(to, ["3.1.0", "1.2.4", "1.2.5", "1.3.0", "1.4.1", "2.0.0", "3.1.0"]);
+        assert!(examples.iter().all(|(from, _)| from == "1.2.3"));
+        assert_eq!(Strategy::NextMinor.description(), "The next minor version within the same major version");
+    }
+
+    #[test]
+    fn strategy_parsing() {
+        assert_eq!("minor".parse(), Ok(Strategy::LatestMinor));
+        assert_eq!("Next_Patch".parse(), Ok(Strategy::NextPatch));
+        assert_eq!("latest-patch".parse(), Ok(Strategy::LatestPatch));
+        assert_eq!(
+            "next-minr".parse::<Strategy>(),
+            Err(StrategyError::Misspelled("next-minr".to_owned(), "next-minor".to_owned()))
+        );
+        assert!(matches!("sometimes".parse::<Strategy>(), Err(StrategyError::Unknown(..))));
+        assert_eq!(serde_json::from_str::<Strategy>("\"major\"").unwrap(), Strategy::LatestMajor);
+    }
+
+    #[test]
+    fn overview_rows() {
+        let image: ContainerImage = "node:18.0.0".parse().unwrap();
+        let published = [PublishedTag {
+            name: "20.9".to_owned(),
+            architectures: vec!["arm64".to_owned(), "unknown".to_owned(), "amd64".to_owned(), "arm64".to_owned()],
+            pushed: Some("2025-06-04T10:00:00.000000Z".to_owned()),
+            ..PublishedTag::default()
+        }];
+        let candidates = [
+            OverviewCandidate::new(&image, "next patch".to_owned(), None, &published),
+            OverviewCandidate::new(&image, "next major".to_owned(), Some(&"20.9".parse().unwrap()), &published),
+        ];
+        assert_eq!(candidates[1].architectures, ["amd64", "arm64"]);
+        assert_eq!(
+            overview_table(&candidates),
+            [
+                "STRATEGY    CANDIDATE  PUSHED      ARCHITECTURES",
+                "next patch  -          -           -",
+                "next major  node:20.9  2025-06-04  amd64, arm64",
+            ]
+        );
+    }
+
+    #[test]
+    fn outdated_rows() {
+        let images = vec![
+            OutdatedImage {
+                image:   "node".to_owned(),
+                current: "20.10.0".to_owned(),
+                patch:   Some("20.10.1".to_owned()),
+                minor:   Some("20.18.0".to_owned()),
+                major:   Some("24.1.0".to_owned()),
+            },
+            OutdatedImage {
+                image:   "mcr.microsoft.com/dotnet/aspnet".to_owned(),
+                current: "8.0".to_owned(),
+                patch:   None,
+                minor:   None,
+                major:   Some("9.0".to_owned()),
+            },
+        ];
+        assert_eq!(
+            images.text(),
+            [
+                "IMAGE                            CURRENT  PATCH    MINOR    MAJOR",
+                "node                             20.10.0  20.10.1  20.18.0  24.1.0",
+                "mcr.microsoft.com/dotnet/aspnet  8.0      -        -        9.0",
+            ]
+        );
+        assert_eq!(images.markdown()[3], "| `mcr.microsoft.com/dotnet/aspnet` | `8.0` | - | - | `9.0` |");
+    }
 
     fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> io::Result<()> {
         fs::create_dir_all(&dst)?;
@@ -352,6 +2118,102 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn discovery() {
+        let root = paths::temp_path("discovery");
+        std::fs::create_dir_all(root.join(".hidden")).unwrap();
+        std::fs::create_dir_all(root.join("service").join("nested")).unwrap();
+        for file in ["Dockerfile", ".hidden/Dockerfile", "service/Dockerfile.prod", "service/nested/Dockerfile"] {
+            std::fs::write(root.join(file), "FROM alpine:3.20\n").unwrap();
+        }
+        std::fs::write(root.join("pom.xml"), "<from><image>alpine:3.20</image></from>\n").unwrap();
+        std::fs::write(root.join("Dockerfile.diu.toml"), "strat = [\"next-minor\"]\n").unwrap();
+        std::fs::create_dir_all(root.join(".circleci")).unwrap();
+        std::fs::write(root.join(".circleci").join("config.yml"), "jobs: {}\n").unwrap();
+        std::fs::write(root.join(".gitlab-ci.yml"), "image: alpine:3.20\n").unwrap();
+        std::os::unix::fs::symlink(root.join("service"), root.join("linked")).unwrap();
+        let found = |discovery: &DiscoveryOptions| {
+            discover_dockerfiles(&root, discovery)
+                .iter()
+                .map(|file| file.strip_prefix(&root.display().to_string()).unwrap().to_owned())
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(
+            found(&DiscoveryOptions::default()),
+            ["/Dockerfile", "/service/Dockerfile.prod", "/service/nested/Dockerfile"]
+        );
+        let discovery = DiscoveryOptions {
+            follow_symlinks: true,
+            include_hidden:  true,
+            max_depth:       Some(2),
+            jib:             true,
+            terraform:       false,
+            ansible:         false,
+            markers:         false,
+            kustomize:       false,
+            compose:         false,
+            bazel:           false,
+            packer:          false,
+            ci:              false,
+        };
+        assert_eq!(
+            found(&discovery),
+            [
+                "/.hidden/Dockerfile",
+                "/Dockerfile",
+                "/linked/Dockerfile.prod",
+                "/pom.xml",
+                "/service/Dockerfile.prod"
+            ]
+        );
+        let discovery = DiscoveryOptions {
+            ci: true,
+            max_depth: Some(2),
+            ..DiscoveryOptions::default()
+        };
+        assert_eq!(
+            found(&discovery),
+            ["/.circleci/config.yml", "/.gitlab-ci.yml", "/Dockerfile", "/service/Dockerfile.prod"]
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn per_file_strategies() {
+        let root = paths::temp_path("file-strategies");
+        std::fs::create_dir_all(&root).unwrap();
+        let read = |name: &str, content: &str, config: Option<&str>| {
+            let path = root.join(name);
+            std::fs::write(&path, content).unwrap();
+            if let Some(config) = config {
+                std::fs::write(root.join(format!("{name}.diu.toml")), config).unwrap();
+            }
+            file_strategies(&path, &Dockerfile::read(&path).unwrap())
+        };
+
+        assert_eq!(read("Dockerfile", "FROM alpine:3.20\n", None).unwrap(), None);
+        assert_eq!(
+            read(
+                "Dockerfile.marked",
+                "# diu:strategy=next-minor\nFROM alpine:3.20\n",
+                Some("strat = [\"latest\"]")
+            )
+            .unwrap(),
+            Some(vec![Strategy::NextMinor])
+        );
+        assert_eq!(
+            read("Dockerfile.sidecar", "FROM alpine:3.20\n", Some("strat = [\"next-patch\", \"latest-minor\"]")).unwrap(),
+            Some(vec![Strategy::NextPatch, Strategy::LatestMinor])
+        );
+        assert_eq!(read("Dockerfile.late", "FROM alpine:3.20\n# diu:strategy=next-minor\n", None).unwrap(), None);
+        assert!(read("Dockerfile.invalid", "FROM alpine:3.20\n", Some("strategy = \"latest\"")).is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    // Spelling out all arguments of every mode takes its lines.
+    #[allow(clippy::too_many_lines)]
     #[test]
     fn input_single_multi() {
         let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
@@ -363,16 +2225,40 @@ mod tests {
             .compact();
         let fmt_layer = fmt::layer().event_format(custom_format);
         tracing_subscriber::registry().with(env_filter).with(fmt_layer).init();
+        replay_fixtures();
 
         let mut i = InputArguments {
-            input:  "clamav/clamav:1.5.1-11_base".into(),
-            strat:  Strategy::Latest,
-            common: CommonOptions {
-                arch:             None,
-                tag_search_limit: Some(1000),
-                debug:            false,
-                quiet:            false,
-                color:            false,
+            input:      "clamav/clamav:1.5.1-11_base".into(),
+            strat:      vec![Strategy::Latest],
+            candidates: None,
+            json:       false,
+            common:     CommonOptions {
+                arch:                None,
+                tag_search_limit:    Some(1000),
+                prefer_suffix:       None,
+                variant_pin:         Vec::new(),
+                update_eol_variants: false,
+                same_precision:      false,
+                full_precision:      false,
+                allow_prerelease:    false,
+                max_risk:            None,
+                official_only:       false,
+                min_age:             7,
+                max_requests:        None,
+                include_latest:      false,
+                verify:              false,
+                fast:                false,
+                verbose:             0,
+                log_filter:          None,
+                trace_http:          false,
+                quiet:               false,
+                color:               false,
+                record:              None,
+                replay:              None,
+                candidate_cmd:       None,
+                config:              None,
+                stats:               false,
+                show_skipped:        false,
             },
         };
         handle_input(&i);
@@ -380,32 +2266,102 @@ mod tests {
         handle_input(&i);
         i.input = "clamav/clamav:1.5.1-99_base".into();
         handle_input(&i);
+        i.candidates = Some(3);
+        handle_input(&i);
+        i.json = true;
+        i.input = r#"{"image": "clamav/clamav:1.5.1-11_base"}"#.into();
+        assert!(handle_input(&i));
+        i.input = r#"{"tag": "1.5.1-11_base"}"#.into();
+        assert!(!handle_input(&i));
 
         let mut f = SingleFileArguments {
-            file:    "./tests/testfiles/DockerfileExample1".to_owned().into(),
-            strat:   Strategy::Latest,
-            dry_run: true,
-            common:  CommonOptions {
-                arch:             None,
-                tag_search_limit: Some(1000),
-                debug:            false,
-                quiet:            false,
-                color:            false,
+            file:             "./tests/testfiles/DockerfileExample1".to_owned().into(),
+            strat:            vec![Strategy::Latest],
+            dry_run:          true,
+            write_patch:      None,
+            apply:            Vec::new(),
+            pin:              Vec::new(),
+            pin_digest:       false,
+            stage:            None,
+            stages:           StageOptions::default(),
+            backup:           BackupOptions::default(),
+            hooks:            UpdateHooks::default(),
+            resolve_includes: false,
+            output:           ReportFormat::Text,
+            common:           CommonOptions {
+                arch:                None,
+                tag_search_limit:    Some(1000),
+                prefer_suffix:       None,
+                variant_pin:         Vec::new(),
+                update_eol_variants: false,
+                same_precision:      false,
+                full_precision:      false,
+                allow_prerelease:    false,
+                max_risk:            None,
+                official_only:       false,
+                min_age:             7,
+                max_requests:        None,
+                include_latest:      false,
+                verify:              false,
+                fast:                false,
+                verbose:             0,
+                log_filter:          None,
+                trace_http:          false,
+                quiet:               false,
+                color:               false,
+                record:              None,
+                replay:              None,
+                candidate_cmd:       None,
+                config:              None,
+                stats:               false,
+                show_skipped:        false,
             },
         };
 
         let mut m = MultiFileArguments {
-            folder:          "./tests/testfiles".into(),
-            strat:           Strategy::Latest,
-            dry_run:         true,
-            exclude_file:    vec!["./tests/testfiles/DockerfileExample1".to_owned()],
-            ignore_versions: vec!["node:8.0-alpine".to_owned()],
-            common:          CommonOptions {
-                arch:             None,
-                tag_search_limit: Some(1000),
-                debug:            false,
-                quiet:            false,
-                color:            false,
+            folder:           "./tests/testfiles".into(),
+            strat:            vec![Strategy::Latest],
+            dry_run:          true,
+            write_patch:      None,
+            apply:            Vec::new(),
+            pin_digest:       false,
+            stages:           StageOptions::default(),
+            backup:           BackupOptions::default(),
+            hooks:            UpdateHooks::default(),
+            resolve_includes: false,
+            discovery:        DiscoveryOptions::default(),
+            exclude_file:     vec!["./tests/testfiles/DockerfileExample1".to_owned()],
+            ignore_versions:  vec!["node:8.0-alpine".to_owned()],
+            pin:              vec![],
+            jobs:             None,
+            output:           ReportFormat::Text,
+            common:           CommonOptions {
+                arch:                None,
+                tag_search_limit:    Some(1000),
+                prefer_suffix:       None,
+                variant_pin:         Vec::new(),
+                update_eol_variants: false,
+                same_precision:      false,
+                full_precision:      false,
+                allow_prerelease:    false,
+                max_risk:            None,
+                official_only:       false,
+                min_age:             7,
+                max_requests:        None,
+                include_latest:      false,
+                verify:              false,
+                fast:                false,
+                verbose:             0,
+                log_filter:          None,
+                trace_http:          false,
+                quiet:               false,
+                color:               false,
+                record:              None,
+                replay:              None,
+                candidate_cmd:       None,
+                config:              None,
+                stats:               false,
+                show_skipped:        false,
             },
         };
 
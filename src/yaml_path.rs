@@ -0,0 +1,180 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::{LazyLock, RwLock};
+
+use regex::Regex;
+use serde::Deserialize;
+
+/// The selectors YAML files are searched with, see `set_yaml_paths`.
+static YAML_PATHS: LazyLock<RwLock<Vec<YamlPath>>> = LazyLock::new(|| RwLock::new(Vec::new()));
+/// A key of a YAML mapping, e.g. `image:` or `"image":`.
+static YAML_KEY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"^["']?(?<key>[\w./-]+)["']?\s*:(?:\s|$)"#).expect("YAML key regex is valid."));
+
+/// The segment of a path that stands for the items of a list.
+const LIST_ITEM: &str = "[]";
+
+/// YAML path related errors.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum YamlPathError {
+    #[error("The YAML path `{0}` has an empty segment, expected e.g. `spec.values.image`.")]
+    EmptySegment(String),
+}
+
+/// A selector of the values of YAML files, the keys are separated by dots.
+/// `*` matches any key, `x-*` any key starting with `x-`, and `[*]` the items
+/// of a list, e.g. `spec.values.sidecars[*].image`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct YamlPath {
+    segments: Vec<String>,
+}
+
+impl YamlPath {
+    /// Checks if the path of keys is selected.
+    pub(crate) fn matches(&self, path: &[String]) -> bool {
+        self.segments.len() == path.len()
+            && self
+                .segments
+                .iter()
+                .zip(path)
+                .all(|(segment, key)| segment == key || segment.strip_suffix('*').is_some_and(|prefix| key.starts_with(prefix) && key != LIST_ITEM))
+    }
+}
+
+impl FromStr for YamlPath {
+    type Err = YamlPathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        for part in s.trim().split('.') {
+            let (key, items) = part.strip_suffix("[*]").map_or((part, false), |key| (key, true));
+            if key.is_empty() {
+                return Err(YamlPathError::EmptySegment(s.to_owned()));
+            }
+            segments.push(key.to_owned());
+            if items {
+                segments.push(LIST_ITEM.to_owned());
+            }
+        }
+        Ok(Self { segments })
+    }
+}
+
+impl TryFrom<String> for YamlPath {
+    type Error = YamlPathError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Display for YamlPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let path = self.segments.join(".").replace(&format!(".{LIST_ITEM}"), "[*]");
+        write!(f, "{path}")
+    }
+}
+
+/// Sets the global YAML paths, the values they select are updated in YAML
+/// files.
+pub fn set_yaml_paths(paths: Vec<YamlPath>) {
+    *YAML_PATHS.write().expect("YAML paths can be written.") = paths;
+}
+
+/// Returns the configured YAML paths.
+pub fn yaml_paths() -> Vec<YamlPath> {
+    YAML_PATHS.read().expect("YAML paths can be read.").clone()
+}
+
+/// Follows the keys of a YAML file line by line by their indentation. Lines of
+/// block scalars are read as keys as well, so that e.g. the `values: |` of an
+/// Argo CD application can be searched like a mapping.
+#[derive(Debug, Default)]
+pub struct KeyPath {
+    /// The indentation and key of every level, `[]` for list items.
+    levels: Vec<(usize, String)>,
+}
+
+impl KeyPath {
+    /// Reads the line, returns `true` if it holds a key, which is the last
+    /// segment of the path then.
+    pub(crate) fn advance(&mut self, line: &str) -> bool {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            return false;
+        }
+        if trimmed.starts_with("---") {
+            self.levels.clear();
+            return false;
+        }
+        let indent = line.len() - trimmed.len();
+        let item = trimmed
+            .strip_prefix('-')
+            .filter(|item| item.is_empty() || item.starts_with(char::is_whitespace));
+        let (indent, rest) = if let Some(item) = item {
+            // Lists may be indented as deep as their key, previous items end with the
+            // new one.
+            self.levels.retain(|(level, key)| *level < indent || (*level == indent && key != LIST_ITEM));
+            self.levels.push((indent, LIST_ITEM.to_owned()));
+            let content = item.trim_start();
+            (indent + trimmed.len() - content.len(), content)
+        } else {
+            (indent, trimmed)
+        };
+        let Some(captures) = YAML_KEY.captures(rest) else {
+            return false;
+        };
+        self.levels.retain(|(level, _)| *level < indent);
+        self.levels.push((indent, captures["key"].to_owned()));
+        true
+    }
+
+    /// Returns the keys of the current line, starting at the document root.
+    pub(crate) fn segments(&self) -> Vec<String> {
+        self.levels.iter().map(|(_, key)| key.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use pretty_assertions::assert_eq;
+
+    use crate::yaml_path::{KeyPath, YamlPath, YamlPathError};
+
+    #[test]
+    fn yaml_paths() {
+        let path: YamlPath = "spec.values.sidecars[*].image".parse().unwrap();
+        assert_eq!(path.to_string(), "spec.values.sidecars[*].image");
+        assert_eq!("spec..image".parse::<YamlPath>(), Err(YamlPathError::EmptySegment("spec..image".to_owned())));
+
+        let content = "spec:\n  values:\n    image: nginx:1.27.0\n    sidecars:\n    - name: proxy\n      image: envoy:v1.31.0\n    - image: \
+                       redis:7.4.0\n  other: 1\n";
+        let mut keys = KeyPath::default();
+        let mut paths = Vec::new();
+        for line in content.lines() {
+            if keys.advance(line) {
+                paths.push(keys.segments().join("."));
+            }
+        }
+        assert_eq!(
+            paths,
+            [
+                "spec",
+                "spec.values",
+                "spec.values.image",
+                "spec.values.sidecars",
+                "spec.values.sidecars.[].name",
+                "spec.values.sidecars.[].image",
+                "spec.values.sidecars.[].image",
+                "spec.other",
+            ]
+        );
+        let segments = |path: &str| path.split('.').map(ToOwned::to_owned).collect::<Vec<_>>();
+        assert!(path.matches(&segments("spec.values.sidecars.[].image")));
+        assert!("spec.*.image".parse::<YamlPath>().unwrap().matches(&segments("spec.values.image")));
+        assert!(!"spec.*.image".parse::<YamlPath>().unwrap().matches(&segments("spec.[].image")));
+        assert!("x-*.image".parse::<YamlPath>().unwrap().matches(&segments("x-base.image")));
+        assert!(!"x-*.image".parse::<YamlPath>().unwrap().matches(&segments("services.image")));
+    }
+}